@@ -63,19 +63,24 @@ fn agg(c: &mut Criterion) {
         };
         for_all_size(c, format!("sum({ty})"), |b, &size| {
             let a1 = make_array(size);
-            b.iter(|| a1.sum())
+            b.iter(|| a1.sum(None))
+        });
+        for_all_size(c, format!("sum({ty}, half selected)"), |b, &size| {
+            let a1 = make_array(size);
+            let selection: Vec<u32> = (0..size as u32).step_by(2).collect();
+            b.iter(|| a1.sum(Some(&selection)))
         });
         for_all_size(c, format!("max({ty})"), |b, &size| {
             let a1 = make_array(size);
-            b.iter(|| a1.max_())
+            b.iter(|| a1.max_(None))
         });
         for_all_size(c, format!("first({ty})"), |b, &size| {
             let a1 = make_array(size);
-            b.iter(|| a1.first())
+            b.iter(|| a1.first(None))
         });
         for_all_size(c, format!("count({ty})"), |b, &size| {
             let a1 = make_array(size);
-            b.iter(|| a1.count())
+            b.iter(|| a1.count(None))
         });
     }
 }
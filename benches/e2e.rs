@@ -1,13 +1,19 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::sync::Arc;
+
 use criterion::*;
-use risinglight::Database;
+use risinglight::{Database, Session};
+
+fn new_session() -> Session {
+    Session::new(Arc::new(Database::new_in_memory()))
+}
 
 fn create_table(c: &mut Criterion) {
     let runtime = tokio::runtime::Runtime::new().unwrap();
     c.bench_function("create table", |b| {
         b.to_async(&runtime).iter_batched(
-            Database::new_in_memory,
+            new_session,
             |db| async move {
                 db.run("create table t(v1 int, v2 int, v3 int)")
                     .await
@@ -31,7 +37,7 @@ fn insert(c: &mut Criterion) {
                 .collect::<String>();
             b.to_async(&runtime).iter_batched(
                 || async {
-                    let db = Database::new_in_memory();
+                    let db = new_session();
                     db.run("create table t(v1 int, v2 int, v3 int)")
                         .await
                         .unwrap();
@@ -60,7 +66,7 @@ fn select_add(c: &mut Criterion) {
                 .collect::<String>();
             b.to_async(&runtime).iter_batched(
                 || async {
-                    let db = Database::new_in_memory();
+                    let db = new_session();
                     db.run("create table t(v1 int, v2 int)").await.unwrap();
                     db.run(&insert_sql).await.unwrap();
                     db
@@ -1,10 +1,11 @@
 // Copyright 2023 RisingLight Project Authors. Licensed under Apache-2.0.
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use criterion::*;
 use risinglight::storage::SecondaryStorageOptions;
-use risinglight::Database;
+use risinglight::{Database, Session};
 
 criterion_group! {
     name = benches;
@@ -25,7 +26,7 @@ fn bench_tpch(c: &mut Criterion) {
             path: db_dir.into(),
             ..SecondaryStorageOptions::default_for_cli()
         };
-        let db = Database::new_on_disk(opt).await;
+        let db = Session::new(Arc::new(Database::new_on_disk(opt).await));
         if should_import {
             db.run(&create_sql).await.unwrap();
             db.run(&import_sql).await.unwrap();
@@ -0,0 +1,166 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// The activity of all sessions in a database and the log of slow queries.
+pub struct ActivityMonitor {
+    next_pid: AtomicI32,
+    sessions: Mutex<BTreeMap<i32, SessionActivity>>,
+    log: Mutex<VecDeque<LoggedQuery>>,
+    /// The maximum number of queries in the log.
+    log_capacity: usize,
+}
+
+/// The state of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, parse_display::Display)]
+pub enum ActivityState {
+    /// Running a query.
+    #[display("active")]
+    Active,
+    /// Waiting for a new query.
+    #[display("idle")]
+    Idle,
+    /// Waiting for a new query in a transaction block.
+    #[display("idle in transaction")]
+    IdleInTransaction,
+}
+
+/// The activity of a session.
+#[derive(Debug, Clone)]
+pub struct SessionActivity {
+    /// The ID of the session, which is the process ID in Postgres.
+    pub pid: i32,
+    pub user: Option<String>,
+    pub application_name: Option<String>,
+    pub state: ActivityState,
+    /// The running query, or the last query if the session is idle.
+    pub query: Option<String>,
+    /// When the session started.
+    pub backend_start: SystemTime,
+    /// When the running or last query started.
+    pub query_start: Option<SystemTime>,
+}
+
+/// A finished query in the log.
+#[derive(Debug, Clone)]
+pub struct LoggedQuery {
+    /// The ID of the session running the query.
+    pub pid: i32,
+    pub user: Option<String>,
+    pub query: String,
+    pub query_start: SystemTime,
+    pub duration: Duration,
+    /// The number of returned rows, or `None` if the query failed.
+    pub rows: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl Default for ActivityMonitor {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LOG_CAPACITY)
+    }
+}
+
+impl ActivityMonitor {
+    /// The default maximum number of queries in the log.
+    pub const DEFAULT_LOG_CAPACITY: usize = 1000;
+
+    /// Create a monitor whose log keeps at most `log_capacity` latest queries.
+    pub fn new(log_capacity: usize) -> Self {
+        ActivityMonitor {
+            next_pid: AtomicI32::new(1),
+            sessions: Mutex::default(),
+            log: Mutex::default(),
+            log_capacity,
+        }
+    }
+
+    /// Registers a new session and returns its ID.
+    pub fn register(&self) -> i32 {
+        let pid = self.next_pid.fetch_add(1, Ordering::Relaxed);
+        let activity = SessionActivity {
+            pid,
+            user: None,
+            application_name: None,
+            state: ActivityState::Idle,
+            query: None,
+            backend_start: SystemTime::now(),
+            query_start: None,
+        };
+        self.sessions.lock().unwrap().insert(pid, activity);
+        pid
+    }
+
+    /// Removes a closed session.
+    pub fn unregister(&self, pid: i32) {
+        self.sessions.lock().unwrap().remove(&pid);
+    }
+
+    /// Updates the activity of a session.
+    pub fn update(&self, pid: i32, f: impl FnOnce(&mut SessionActivity)) {
+        if let Some(activity) = self.sessions.lock().unwrap().get_mut(&pid) {
+            f(activity);
+        }
+    }
+
+    /// Returns the activity of all sessions.
+    pub fn sessions(&self) -> Vec<SessionActivity> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Appends a finished query to the log, dropping the oldest one if the log is full.
+    pub fn log(&self, query: LoggedQuery) {
+        let mut log = self.log.lock().unwrap();
+        if log.len() >= self.log_capacity {
+            log.pop_front();
+        }
+        if self.log_capacity > 0 {
+            log.push_back(query);
+        }
+    }
+
+    /// Returns the queries in the log from the oldest to the latest.
+    pub fn logged_queries(&self) -> Vec<LoggedQuery> {
+        self.log.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity() {
+        let monitor = ActivityMonitor::new(2);
+        let pid1 = monitor.register();
+        let pid2 = monitor.register();
+        assert_ne!(pid1, pid2);
+        monitor.update(pid2, |a| a.state = ActivityState::Active);
+        let states = monitor
+            .sessions()
+            .iter()
+            .map(|a| a.state)
+            .collect::<Vec<_>>();
+        assert_eq!(states, [ActivityState::Idle, ActivityState::Active]);
+        monitor.unregister(pid1);
+        assert_eq!(monitor.sessions().len(), 1);
+
+        for query in ["q1", "q2", "q3"] {
+            monitor.log(LoggedQuery {
+                pid: pid2,
+                user: None,
+                query: query.into(),
+                query_start: SystemTime::now(),
+                duration: Duration::ZERO,
+                rows: Some(0),
+                error: None,
+            });
+        }
+        let queries = monitor.logged_queries();
+        let queries = queries.iter().map(|q| q.query.as_str()).collect::<Vec<_>>();
+        assert_eq!(queries, ["q2", "q3"]);
+    }
+}
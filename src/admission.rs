@@ -0,0 +1,205 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+/// The configuration of admission control.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    /// The maximum number of queries running at the same time, or `None` if unlimited.
+    pub max_concurrent: Option<usize>,
+    /// The maximum number of queries waiting to run, or `None` if unlimited.
+    pub max_queued: Option<usize>,
+    /// Queries waiting longer than this fail, or `None` to wait forever.
+    pub queue_timeout: Option<Duration>,
+}
+
+/// The error type of admission control.
+#[derive(thiserror::Error, Debug)]
+pub enum AdmissionError {
+    #[error("too many queued queries: the queue is limited to {0}")]
+    QueueFull(usize),
+    #[error("query waited in the queue for more than {0:?}")]
+    Timeout(Duration),
+    #[error("canceling statement due to user request")]
+    Cancelled,
+}
+
+/// A queue in front of query execution that limits the number of concurrent queries.
+///
+/// Queries run in the order they are admitted.
+pub struct QueryQueue {
+    config: AdmissionConfig,
+    /// The permits of running queries, or `None` if unlimited.
+    semaphore: Option<Arc<Semaphore>>,
+    next_id: AtomicU64,
+    queries: Mutex<BTreeMap<u64, QueuedQuery>>,
+}
+
+/// The state of a query in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, parse_display::Display)]
+#[display(style = "snake_case")]
+pub enum QueryState {
+    Queued,
+    Running,
+}
+
+/// A query that is queued or running.
+#[derive(Debug, Clone)]
+pub struct QueuedQuery {
+    pub id: u64,
+    pub user: Option<String>,
+    pub query: String,
+    pub state: QueryState,
+    /// When the query entered the queue.
+    pub queued_at: Instant,
+    /// When the query started running.
+    pub started_at: Option<Instant>,
+}
+
+impl Default for QueryQueue {
+    fn default() -> Self {
+        Self::new(AdmissionConfig::default())
+    }
+}
+
+impl QueryQueue {
+    /// Create a queue with the configuration.
+    pub fn new(config: AdmissionConfig) -> Self {
+        QueryQueue {
+            config,
+            semaphore: (config.max_concurrent).map(|n| Arc::new(Semaphore::new(n))),
+            next_id: AtomicU64::new(1),
+            queries: Mutex::default(),
+        }
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> AdmissionConfig {
+        self.config
+    }
+
+    /// Waits until the query can run, or the query is cancelled by `cancel`.
+    ///
+    /// The query is running until the returned permit is dropped.
+    pub async fn admit(
+        self: &Arc<Self>,
+        user: Option<&str>,
+        query: &str,
+        cancel: &CancellationToken,
+    ) -> Result<AdmissionPermit, AdmissionError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut queries = self.queries.lock().unwrap();
+            let queued = (queries.values())
+                .filter(|q| q.state == QueryState::Queued)
+                .count();
+            if let Some(max_queued) = self.config.max_queued
+                && self.semaphore.as_ref().map_or(0, |s| s.available_permits()) == 0
+                && queued >= max_queued
+            {
+                return Err(AdmissionError::QueueFull(max_queued));
+            }
+            queries.insert(
+                id,
+                QueuedQuery {
+                    id,
+                    user: user.map(|s| s.to_string()),
+                    query: query.to_string(),
+                    state: QueryState::Queued,
+                    queued_at: Instant::now(),
+                    started_at: None,
+                },
+            );
+        }
+        // the query is removed from the queue if it fails to be admitted
+        let mut permit = AdmissionPermit {
+            queue: self.clone(),
+            id,
+            _permit: None,
+        };
+        if let Some(semaphore) = &self.semaphore {
+            let acquire = semaphore.clone().acquire_owned();
+            let timeout = async {
+                match self.config.queue_timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            permit._permit = Some(tokio::select! {
+                permit = acquire => permit.expect("semaphore closed"),
+                _ = timeout => {
+                    return Err(AdmissionError::Timeout(self.config.queue_timeout.unwrap()));
+                }
+                _ = cancel.cancelled() => return Err(AdmissionError::Cancelled),
+            });
+        }
+        let mut queries = self.queries.lock().unwrap();
+        let query = queries.get_mut(&id).unwrap();
+        query.state = QueryState::Running;
+        query.started_at = Some(Instant::now());
+        Ok(permit)
+    }
+
+    /// Returns all queued and running queries.
+    pub fn queries(&self) -> Vec<QueuedQuery> {
+        self.queries.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// A permit to run a query, which leaves the queue when dropped.
+pub struct AdmissionPermit {
+    queue: Arc<QueryQueue>,
+    id: u64,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.queue.queries.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admission() {
+        let queue = Arc::new(QueryQueue::new(AdmissionConfig {
+            max_concurrent: Some(1),
+            max_queued: Some(1),
+            queue_timeout: Some(Duration::from_millis(100)),
+        }));
+        let cancel = CancellationToken::new();
+        let permit = queue.admit(None, "q1", &cancel).await.unwrap();
+
+        // the second query waits and times out
+        let err = queue.admit(None, "q2", &cancel).await.err().unwrap();
+        assert!(matches!(err, AdmissionError::Timeout(_)));
+
+        // the third query is rejected while the second is queued
+        let q2 = queue.admit(Some("alice"), "q2", &cancel);
+        let q3 = async {
+            tokio::task::yield_now().await;
+            let states = queue.queries().iter().map(|q| q.state).collect::<Vec<_>>();
+            assert_eq!(states, [QueryState::Running, QueryState::Queued]);
+            let err = queue.admit(None, "q3", &cancel).await.err().unwrap();
+            assert!(matches!(err, AdmissionError::QueueFull(1)));
+            drop(permit);
+        };
+        let (q2, _) = tokio::join!(q2, q3);
+        let q2 = q2.unwrap();
+        let queries = queue.queries();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].user.as_deref(), Some("alice"));
+        assert_eq!(queries[0].state, QueryState::Running);
+        drop(q2);
+        assert!(queue.queries().is_empty());
+    }
+}
@@ -12,10 +12,17 @@ use crate::types::{DataValue, Row};
 /// A data chunk is a horizontal subset of a query result.
 ///
 /// Note: It's valid for a [`DataChunk`] to have 0 column, but non-zero cardinality.
+///
+/// A data chunk may carry a selection vector, which contains the indexes of valid rows in the
+/// arrays in ascending order. Rows not in the selection are logically removed from the chunk.
+/// Executors that don't respect the selection should [`compact`](DataChunk::compact) the chunk
+/// before accessing its arrays.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DataChunk {
     arrays: Arc<[ArrayImpl]>,
+    /// The number of rows in arrays.
     cardinality: usize,
+    selection: Option<Arc<[u32]>>,
 }
 
 impl FromIterator<ArrayImpl> for DataChunk {
@@ -29,6 +36,7 @@ impl FromIterator<ArrayImpl> for DataChunk {
         DataChunk {
             arrays,
             cardinality,
+            selection: None,
         }
     }
 }
@@ -47,6 +55,7 @@ impl DataChunk {
                 .into_iter()
                 .collect(),
             cardinality: 1,
+            selection: None,
         }
     }
 
@@ -55,29 +64,43 @@ impl DataChunk {
         DataChunk {
             arrays: Arc::new([]),
             cardinality,
+            selection: None,
         }
     }
 
     /// Return the number of rows in the chunk.
     pub fn cardinality(&self) -> usize {
-        self.cardinality
+        match &self.selection {
+            Some(selection) => selection.len(),
+            None => self.cardinality,
+        }
+    }
+
+    /// Returns the selection vector.
+    pub fn selection(&self) -> Option<&[u32]> {
+        self.selection.as_deref()
     }
 
     /// Get reference to a row.
     pub fn row(&self, idx: usize) -> RowRef<'_> {
-        debug_assert!(idx < self.cardinality, "index out of range");
+        debug_assert!(idx < self.cardinality(), "index out of range");
         RowRef {
             chunk: self,
-            row_idx: idx,
+            row_idx: match &self.selection {
+                Some(selection) => selection[idx] as usize,
+                None => idx,
+            },
         }
     }
 
     /// Get an iterator over the rows.
     pub fn rows(&self) -> impl Iterator<Item = RowRef<'_>> {
-        (0..self.cardinality).map(|idx| self.row(idx))
+        (0..self.cardinality()).map(|idx| self.row(idx))
     }
 
     /// Get the reference of array by index.
+    ///
+    /// Note: the selection is not applied on the array.
     pub fn array_at(&self, idx: usize) -> &ArrayImpl {
         &self.arrays[idx]
     }
@@ -88,12 +111,53 @@ impl DataChunk {
     }
 
     /// Get all arrays.
+    ///
+    /// Note: the selection is not applied on the arrays.
     pub fn arrays(&self) -> &[ArrayImpl] {
         &self.arrays
     }
 
+    /// Select rows by visibility without copying arrays.
+    ///
+    /// The length of `visibility` should be the same as the cardinality.
+    pub fn select(self, visibility: &[bool]) -> Self {
+        assert_eq!(visibility.len(), self.cardinality());
+        if !visibility.contains(&false) {
+            return self;
+        }
+        let selection = match &self.selection {
+            Some(selection) => (selection.iter().zip(visibility))
+                .filter(|(_, v)| **v)
+                .map(|(i, _)| *i)
+                .collect(),
+            None => (visibility.iter().enumerate())
+                .filter(|(_, v)| **v)
+                .map(|(i, _)| i as u32)
+                .collect(),
+        };
+        DataChunk {
+            selection: Some(selection),
+            ..self
+        }
+    }
+
+    /// Apply the selection and return a chunk without selection.
+    pub fn compact(self) -> Self {
+        let Some(selection) = &self.selection else {
+            return self;
+        };
+        DataChunk {
+            arrays: self.arrays.iter().map(|a| a.gather(selection)).collect(),
+            cardinality: selection.len(),
+            selection: None,
+        }
+    }
+
     /// Filter elements and create a new chunk.
     pub fn filter(&self, visibility: &[bool]) -> Self {
+        if self.selection.is_some() {
+            return self.clone().select(visibility).compact();
+        }
         let arrays: Arc<[ArrayImpl]> = self.arrays.iter().map(|a| a.filter(visibility)).collect();
         DataChunk {
             cardinality: match arrays.first() {
@@ -101,6 +165,7 @@ impl DataChunk {
                 None => visibility.iter().filter(|b| **b).count(),
             },
             arrays,
+            selection: None,
         }
     }
 
@@ -111,6 +176,9 @@ impl DataChunk {
 
     /// Returns a slice of self that is equivalent to the given subset.
     pub fn slice(&self, range: impl RangeBounds<usize> + Clone) -> Self {
+        if self.selection.is_some() {
+            return self.clone().compact().slice(range);
+        }
         let begin = match range.start_bound() {
             Bound::Included(&n) => n,
             Bound::Excluded(&n) => n + 1,
@@ -126,6 +194,7 @@ impl DataChunk {
         DataChunk {
             arrays,
             cardinality: end - begin,
+            selection: None,
         }
     }
 
@@ -149,7 +218,8 @@ impl DataChunk {
     /// Concatenate two chunks in rows.
     pub fn row_concat(self, other: Self) -> Self {
         assert_eq!(self.cardinality(), other.cardinality());
-        self.arrays
+        let (this, other) = (self.compact(), other.compact());
+        this.arrays
             .iter()
             .chain(other.arrays.iter())
             .cloned()
@@ -163,8 +233,10 @@ impl fmt::Display for DataChunk {
         use comfy_table::Table;
         let mut table = Table::new();
         table.load_preset("||--+-++|    ++++++");
-        for i in 0..self.cardinality() {
-            let row: Vec<_> = self.arrays.iter().map(|a| a.get_to_string(i)).collect();
+        for row in self.rows() {
+            let row: Vec<_> = (self.arrays.iter())
+                .map(|a| a.get_to_string(row.row_idx))
+                .collect();
             table.add_row(row);
         }
         write!(f, "{}", table)
@@ -182,6 +254,8 @@ impl fmt::Debug for DataChunk {
 pub struct Chunk {
     data_chunks: Vec<DataChunk>,
     header: Option<Vec<String>>,
+    /// Types of columns, if the chunk is the result of a query.
+    types: Option<Vec<DataType>>,
 }
 
 impl Chunk {
@@ -190,6 +264,7 @@ impl Chunk {
         Chunk {
             data_chunks,
             header: None,
+            types: None,
         }
     }
 
@@ -212,6 +287,16 @@ impl Chunk {
     pub fn set_header(&mut self, header: Vec<String>) {
         self.header = Some(header);
     }
+
+    /// Get types of columns. Returns `None` if the chunk is not the result of a query.
+    pub fn types(&self) -> Option<&[DataType]> {
+        self.types.as_deref()
+    }
+
+    /// Set types of columns for current chunk
+    pub fn set_types(&mut self, types: Vec<DataType>) {
+        self.types = Some(types);
+    }
 }
 
 /// Print the chunk as a pretty table.
@@ -224,11 +309,11 @@ impl fmt::Display for Chunk {
         }
         table.load_preset("||--+-++|    ++++++");
         for data_chunk in self.data_chunks() {
-            for i in 0..data_chunk.cardinality() {
+            for row in data_chunk.rows() {
                 let row: Vec<_> = data_chunk
                     .arrays
                     .iter()
-                    .map(|a| a.get_to_string(i))
+                    .map(|a| a.get_to_string(row.row_idx))
                     .collect();
                 table.add_row(row);
             }
@@ -247,10 +332,10 @@ impl fmt::Debug for Chunk {
 pub fn datachunk_to_sqllogictest_string(chunk: &Chunk) -> Vec<Vec<String>> {
     let mut output = vec![];
     for data_chunk in chunk.data_chunks() {
-        for row in 0..data_chunk.cardinality() {
+        for row in data_chunk.rows() {
             let mut row_vec = vec![];
-            for array in data_chunk.arrays() {
-                let s = match array.get(row) {
+            for value in row.values() {
+                let s = match value {
                     DataValue::Null => "NULL".to_string(),
                     DataValue::Bool(v) => v.to_string(),
                     DataValue::Int16(v) => v.to_string(),
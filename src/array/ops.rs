@@ -4,6 +4,7 @@
 
 use std::borrow::Borrow;
 
+use itertools::Either;
 use num_traits::ToPrimitive;
 use regex::Regex;
 use rust_decimal::prelude::FromStr;
@@ -136,6 +137,8 @@ macro_rules! cmp {
             (A::String(a), A::String(b)) => binary_op(a.as_ref(), b.as_ref(), |a, b| a $op b),
 
             (A::Date(a), A::Date(b)) => binary_op(a.as_ref(), b.as_ref(), |a, b| a $op b),
+            (A::Timestamp(a), A::Timestamp(b)) => binary_op(a.as_ref(), b.as_ref(), |a, b| a $op b),
+            (A::TimestampTz(a), A::TimestampTz(b)) => binary_op(a.as_ref(), b.as_ref(), |a, b| a $op b),
 
             _ => return Err(ConvertError::NoBinaryOp(stringify!($name).into(), self.type_string(), other.type_string())),
         })))
@@ -607,22 +610,28 @@ impl ArrayImpl {
         })
     }
 
-    /// Returns the sum of values.
-    pub fn sum(&self) -> DataValue {
+    /// Returns the sum of selected values.
+    pub fn sum(&self, selection: Option<&[u32]>) -> DataValue {
         match self {
-            Self::Int16(a) => DataValue::Int16(a.raw_iter().sum()),
-            Self::Int32(a) => DataValue::Int32(a.raw_iter().sum()),
-            Self::Int64(a) => DataValue::Int64(a.raw_iter().sum()),
-            Self::Float64(a) => DataValue::Float64(a.raw_iter().sum()),
-            Self::Decimal(a) => DataValue::Decimal(a.raw_iter().sum()),
-            Self::Interval(a) => DataValue::Interval(a.raw_iter().sum()),
+            Self::Int16(a) => DataValue::Int16(selected_raw_iter(a.as_ref(), selection).sum()),
+            Self::Int32(a) => DataValue::Int32(selected_raw_iter(a.as_ref(), selection).sum()),
+            Self::Int64(a) => DataValue::Int64(selected_raw_iter(a.as_ref(), selection).sum()),
+            Self::Float64(a) => DataValue::Float64(selected_raw_iter(a.as_ref(), selection).sum()),
+            Self::Decimal(a) => DataValue::Decimal(selected_raw_iter(a.as_ref(), selection).sum()),
+            Self::Interval(a) => {
+                DataValue::Interval(selected_raw_iter(a.as_ref(), selection).sum())
+            }
             _ => panic!("can not sum array"),
         }
     }
 
-    /// Returns the number of non-null values.
-    pub fn count(&self) -> usize {
-        self.get_valid_bitmap().count_ones()
+    /// Returns the number of selected non-null values.
+    pub fn count(&self, selection: Option<&[u32]>) -> usize {
+        let valid = self.get_valid_bitmap();
+        match selection {
+            Some(selection) => selection.iter().filter(|i| valid[**i as usize]).count(),
+            None => valid.count_ones(),
+        }
     }
 
     pub fn replace(&self, from: &str, to: &str) -> Result {
@@ -723,31 +732,44 @@ impl ArrayImpl {
 macro_rules! impl_agg {
     ([], $( { $Abc:ident, $Type:ty, $abc:ident, $AbcArray:ty, $AbcArrayBuilder:ty, $Value:ident, $Pattern:pat } ),*) => {
         impl ArrayImpl {
-            /// Returns the minimum of values.
-            pub fn min_(&self) -> DataValue {
+            /// Returns the minimum of selected values.
+            pub fn min_(&self, selection: Option<&[u32]>) -> DataValue {
                 match self {
-                    $(Self::$Abc(a) => a.nonnull_iter().min().into(),)*
+                    $(Self::$Abc(a) => selected_iter(a.as_ref(), selection).flatten().min().into(),)*
                 }
             }
 
-            /// Returns the maximum of values.
-            pub fn max_(&self) -> DataValue {
+            /// Returns the maximum of selected values.
+            pub fn max_(&self, selection: Option<&[u32]>) -> DataValue {
                 match self {
-                    $(Self::$Abc(a) => a.nonnull_iter().max().into(),)*
+                    $(Self::$Abc(a) => selected_iter(a.as_ref(), selection).flatten().max().into(),)*
                 }
             }
 
-            /// Returns the first non-null value.
-            pub fn first(&self) -> DataValue {
+            /// Returns the first selected value.
+            pub fn first(&self, selection: Option<&[u32]>) -> DataValue {
                 match self {
-                    $(Self::$Abc(a) => a.iter().next().flatten().into(),)*
+                    $(Self::$Abc(a) => selected_iter(a.as_ref(), selection).next().flatten().into(),)*
                 }
             }
 
-            /// Returns the last non-null value.
-            pub fn last(&self) -> DataValue {
+            /// Returns the last selected value.
+            pub fn last(&self, selection: Option<&[u32]>) -> DataValue {
                 match self {
-                    $(Self::$Abc(a) => a.iter().rev().next().flatten().into(),)*
+                    $(Self::$Abc(a) => selected_iter(a.as_ref(), selection).next_back().flatten().into(),)*
+                }
+            }
+
+            /// Returns a new array of values at the given indexes.
+            pub fn gather(&self, indexes: &[u32]) -> Self {
+                match self {
+                    $(Self::$Abc(a) => {
+                        let mut builder = <$AbcArrayBuilder>::with_capacity(indexes.len());
+                        for i in indexes {
+                            builder.push(a.get(*i as usize));
+                        }
+                        Self::$Abc(builder.finish().into())
+                    })*
                 }
             }
         }
@@ -756,6 +778,33 @@ macro_rules! impl_agg {
 
 for_all_variants! { impl_agg }
 
+/// Returns an iterator over the indexes of selected rows.
+pub fn selected_rows(
+    selection: Option<&[u32]>,
+    len: usize,
+) -> impl DoubleEndedIterator<Item = usize> + '_ {
+    match selection {
+        Some(selection) => Either::Left(selection.iter().map(|i| *i as usize)),
+        None => Either::Right(0..len),
+    }
+}
+
+/// Returns an iterator over the selected values.
+fn selected_iter<'a, A: Array>(
+    a: &'a A,
+    selection: Option<&'a [u32]>,
+) -> impl DoubleEndedIterator<Item = Option<&'a A::Item>> {
+    selected_rows(selection, a.len()).map(|i| a.get(i))
+}
+
+/// Returns an iterator over the selected raw values.
+fn selected_raw_iter<'a, A: Array>(
+    a: &'a A,
+    selection: Option<&'a [u32]>,
+) -> impl DoubleEndedIterator<Item = &'a A::Item> {
+    selected_rows(selection, a.len()).map(|i| a.get_raw(i))
+}
+
 fn safen_dividend(array: &ArrayImpl, valid: &BitVec) -> Option<ArrayImpl> {
     fn f<T, N>(array: &PrimitiveArray<N>, valid: &BitVec, value: N) -> T
     where
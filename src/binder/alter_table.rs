@@ -0,0 +1,62 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use super::*;
+
+impl Binder {
+    /// Binds `ALTER TABLE t DROP PARTITION p0, p1`.
+    ///
+    /// # Example
+    /// - `alter table t drop partition p0` => `(drop_partition $1 (list 'p0'))`
+    pub(super) fn bind_alter_table(
+        &mut self,
+        name: ObjectName,
+        operations: Vec<AlterTableOperation>,
+    ) -> Result {
+        let [AlterTableOperation::DropPartitions {
+            partitions,
+            if_exists,
+        }] = &operations[..]
+        else {
+            return Err(
+                ErrorKind::Todo("ALTER TABLE other than DROP PARTITION".into()).with_spanned(&name),
+            );
+        };
+        // dropping a partition deletes its rows
+        let (table_id, is_system, is_view) = self.bind_table_id(&name, Privilege::Delete)?;
+        if is_system || is_view {
+            return Err(ErrorKind::CanNotDelete.with_spanned(&name));
+        }
+        let table = (self.catalog)
+            .get_table(&self.node(table_id).as_table())
+            .unwrap();
+        let Some(spec) = table.partition() else {
+            return Err(ErrorKind::InvalidPartition(format!(
+                "table {} is not partitioned",
+                table.name()
+            ))
+            .with_spanned(&name));
+        };
+        let mut names = vec![];
+        for partition in partitions {
+            let Expr::Identifier(ident) = partition else {
+                return Err(
+                    ErrorKind::InvalidPartition(format!("invalid name {partition}"))
+                        .with_spanned(partition),
+                );
+            };
+            let name = ident.value.to_lowercase();
+            if spec.get_partition_by_name(&name).is_none() {
+                if *if_exists {
+                    continue;
+                }
+                return Err(ErrorKind::InvalidPartition(format!(
+                    "partition {name} does not exist"
+                ))
+                .with_span(ident.span));
+            }
+            names.push(self.egraph.add(Node::Constant(name.into())));
+        }
+        let names = self.egraph.add(Node::List(names.into()));
+        Ok(self.egraph.add(Node::DropPartition([table_id, names])))
+    }
+}
@@ -70,7 +70,7 @@ impl Binder {
                     table_name,
                     columns,
                 } => {
-                    let (table, _, _) = self.bind_table_id(&table_name)?;
+                    let (table, _, _) = self.bind_table_id(&table_name, Privilege::Select)?;
                     let cols = self.bind_table_columns(&table_name, &columns)?;
                     let true_ = self.egraph.add(Node::true_());
                     self.egraph.add(Node::Scan([table, cols, true_]))
@@ -85,7 +85,8 @@ impl Binder {
                     table_name,
                     columns,
                 } => {
-                    let (table, is_system, is_view) = self.bind_table_id(&table_name)?;
+                    let (table, is_system, is_view) =
+                        self.bind_table_id(&table_name, Privilege::Insert)?;
                     if is_system {
                         return Err(
                             ErrorKind::CopyTo("system table".into()).with_spanned(&table_name)
@@ -57,14 +57,16 @@ impl Binder {
             ..
         }: crate::parser::CreateFunction,
     ) -> Result {
-        let Ok((schema_name, function_name)) = split_name(&name) else {
+        let Ok((schema_name, function_name)) = self.split_name(&name) else {
             return Err(ErrorKind::BindFunctionError(
                 "failed to parse the input function name".to_string(),
             )
             .with_spanned(&name));
         };
 
-        let schema_name = schema_name.to_string();
+        if let Some(schema) = self.catalog.get_schema_id_by_name(&schema_name) {
+            self.check_schema_privilege(Privilege::Create, schema, &schema_name)?;
+        }
         let name = function_name.to_string();
 
         let Some(return_type) = return_type else {
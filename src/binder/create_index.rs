@@ -168,14 +168,17 @@ impl Binder {
             ..
         } = stat;
         let index_name = lower_case_name(name);
-        let (_, index_name) = split_name(&index_name)?;
+        let (_, index_name) = self.split_name(&index_name)?;
         let table_obj: ObjectName = table_name.clone();
         let table_name = lower_case_name(&table_name);
-        let (schema_name, table_name) = split_name(&table_name)?;
+        let (schema_name, table_name) = self.split_name(&table_name)?;
         let schema = self
             .catalog
-            .get_schema_by_name(schema_name)
-            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name.into()).with_spanned(&table_obj))?;
+            .get_schema_by_name(&schema_name)
+            .ok_or_else(|| {
+                ErrorKind::InvalidSchema(schema_name.clone()).with_spanned(&table_obj)
+            })?;
+        self.check_schema_privilege(Privilege::Create, schema.id(), &schema_name)?;
         let Some(table) = schema.get_table_by_name(table_name) else {
             return Err(ErrorKind::InvalidTable(table_name.into()).with_spanned(&table_obj));
         };
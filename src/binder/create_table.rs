@@ -9,7 +9,7 @@ use pretty_xmlish::Pretty;
 use serde::{Deserialize, Serialize};
 
 use super::*;
-use crate::catalog::{ColumnCatalog, ColumnDesc, ColumnId, SchemaId};
+use crate::catalog::{ColumnCatalog, ColumnDesc, ColumnId, Partition, PartitionSpec, SchemaId};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
 pub struct CreateTable {
@@ -17,6 +17,7 @@ pub struct CreateTable {
     pub table_name: String,
     pub columns: Vec<ColumnCatalog>,
     pub ordered_pk_ids: Vec<ColumnId>,
+    pub partition: Option<PartitionSpec>,
 }
 
 impl fmt::Display for CreateTable {
@@ -30,12 +31,16 @@ impl CreateTable {
     pub fn pretty_table<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
         let cols = Pretty::Array(self.columns.iter().map(|c| c.desc().pretty()).collect());
         let ids = Pretty::Array(self.ordered_pk_ids.iter().map(Pretty::display).collect());
-        vec![
+        let mut fields = vec![
             ("schema_id", Pretty::display(&self.schema_id)),
             ("name", Pretty::display(&self.table_name)),
             ("columns", cols),
             ("ordered_ids", ids),
-        ]
+        ];
+        if let Some(partition) = &self.partition {
+            fields.push(("partition", Pretty::display(partition)));
+        }
+        fields
     }
 }
 
@@ -54,15 +59,17 @@ impl Binder {
             name,
             columns,
             constraints,
+            partition_by,
             ..
         }: crate::parser::CreateTable,
     ) -> Result {
         let name = lower_case_name(&name);
-        let (schema_name, table_name) = split_name(&name)?;
+        let (schema_name, table_name) = self.split_name(&name)?;
         let schema = self
             .catalog
-            .get_schema_by_name(schema_name)
-            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name.into()).with_spanned(&name))?;
+            .get_schema_by_name(&schema_name)
+            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name.clone()).with_spanned(&name))?;
+        self.check_schema_privilege(Privilege::Create, schema.id(), &schema_name)?;
         if schema.get_table_by_name(table_name).is_some() {
             return Err(ErrorKind::TableExists(table_name.into()).with_spanned(&name));
         }
@@ -126,15 +133,132 @@ impl Binder {
             columns[index as usize].set_nullable(false);
         }
 
+        let partition = match partition_by {
+            Some(expr) => Some(self.bind_partition_by(*expr, &columns)?),
+            None => None,
+        };
+
         let create = self.egraph.add(Node::CreateTable(Box::new(CreateTable {
             schema_id: schema.id(),
             table_name: table_name.into(),
             columns,
             ordered_pk_ids,
+            partition,
         })));
         Ok(create)
     }
 
+    /// Binds `PARTITION BY RANGE(col, p0 => (<bound>), ...)`, which is rewritten by the parser from
+    /// `PARTITION BY RANGE (col) (PARTITION p0 VALUES LESS THAN (<bound>), ...)`.
+    fn bind_partition_by(
+        &mut self,
+        expr: Expr,
+        columns: &[ColumnCatalog],
+    ) -> Result<PartitionSpec> {
+        let invalid = |message: String| ErrorKind::InvalidPartition(message).with_spanned(&expr);
+        let Expr::Function(Function {
+            name,
+            args: FunctionArguments::List(list),
+            ..
+        }) = &expr
+        else {
+            return Err(ErrorKind::Todo(format!("PARTITION BY {expr}")).with_spanned(&expr));
+        };
+        if !name.to_string().eq_ignore_ascii_case("range") {
+            return Err(ErrorKind::Todo(format!("PARTITION BY {name}")).with_spanned(&expr));
+        }
+        let [FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Identifier(column))), partitions @ ..] =
+            &list.args[..]
+        else {
+            return Err(invalid("expected a partition column".into()));
+        };
+        let column_name = column.value.to_lowercase();
+        let column = (columns.iter())
+            .find(|c| c.name() == column_name)
+            .ok_or_else(|| ErrorKind::InvalidColumn(column_name).with_span(column.span))?;
+        if partitions.is_empty() {
+            return Err(invalid("no partition is defined".into()));
+        }
+
+        let mut spec = PartitionSpec {
+            column: column.id(),
+            partitions: vec![],
+        };
+        for (id, arg) in partitions.iter().enumerate() {
+            let (name, bound) = match arg {
+                FunctionArg::Named {
+                    name,
+                    arg: FunctionArgExpr::Expr(bound),
+                    ..
+                }
+                | FunctionArg::ExprNamed {
+                    name: Expr::Identifier(name),
+                    arg: FunctionArgExpr::Expr(bound),
+                    ..
+                } => (name.value.to_lowercase(), bound),
+                _ => return Err(invalid(format!("invalid partition {arg}"))),
+            };
+            if spec.get_partition_by_name(&name).is_some() {
+                return Err(invalid(format!("duplicated partition {name}")));
+            }
+            let mut bound = bound;
+            while let Expr::Nested(inner) = bound {
+                bound = inner;
+            }
+            let upper_bound = match bound {
+                Expr::Identifier(ident) if ident.value.eq_ignore_ascii_case("maxvalue") => None,
+                // negative numbers are not constants before folding
+                Expr::UnaryOp {
+                    op: UnaryOperator::Minus,
+                    expr,
+                } if matches!(**expr, Expr::Value(Value::Number(..))) => {
+                    let value = Expr::Value(Value::Number(format!("-{expr}"), false));
+                    Some(self.bind_partition_bound(value, column)?)
+                }
+                _ => Some(self.bind_partition_bound(bound.clone(), column)?),
+            };
+            let last = spec.partitions.last();
+            if last.is_some_and(|p| p.upper_bound.is_none()) {
+                return Err(invalid(
+                    "MAXVALUE must be the bound of the last partition".into(),
+                ));
+            }
+            if let (Some(last), Some(bound)) =
+                (last.and_then(|p| p.upper_bound.as_ref()), &upper_bound)
+                && last >= bound
+            {
+                return Err(invalid(format!(
+                    "bounds must be strictly increasing, but {bound} follows {last}"
+                )));
+            }
+            spec.partitions.push(Partition {
+                id: id as u32,
+                name,
+                upper_bound,
+            });
+        }
+        Ok(spec)
+    }
+
+    /// Binds the upper bound of a partition, which must be a constant of the column type.
+    fn bind_partition_bound(&mut self, bound: Expr, column: &ColumnCatalog) -> Result<DataValue> {
+        let id = self.bind_expr(bound.clone())?;
+        let Node::Constant(value) = self.node(id) else {
+            return Err(
+                ErrorKind::InvalidPartition(format!("bound must be a constant: {bound}"))
+                    .with_spanned(&bound),
+            );
+        };
+        if value.is_null() {
+            return Err(
+                ErrorKind::InvalidPartition("bound can not be null".into()).with_spanned(&bound)
+            );
+        }
+        value.cast(&column.data_type()).map_err(|_| {
+            ErrorKind::CastError(value.clone(), column.data_type()).with_spanned(&bound)
+        })
+    }
+
     /// get primary keys' id in declared order。
     /// we use index in columns vector as column id
     fn ordered_pks_from_columns(columns: &[ColumnDef]) -> Vec<ColumnId> {
@@ -12,11 +12,12 @@ impl Binder {
         query: Query,
     ) -> Result {
         let name = lower_case_name(&name);
-        let (schema_name, table_name) = split_name(&name)?;
+        let (schema_name, table_name) = self.split_name(&name)?;
         let schema = self
             .catalog
-            .get_schema_by_name(schema_name)
-            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name.into()).with_spanned(&name))?;
+            .get_schema_by_name(&schema_name)
+            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name.clone()).with_spanned(&name))?;
+        self.check_schema_privilege(Privilege::Create, schema.id(), &schema_name)?;
         if schema.get_table_by_name(table_name).is_some() {
             return Err(ErrorKind::TableExists(table_name.into()).with_spanned(&name));
         }
@@ -57,6 +58,7 @@ impl Binder {
             table_name: table_name.into(),
             columns,
             ordered_pk_ids: vec![],
+            partition: None,
         })));
         let create_view = self.egraph.add(Node::CreateView([table, query]));
         Ok(create_view)
@@ -16,13 +16,31 @@ impl Binder {
                 ErrorKind::Todo(format!("delete from {from:?}")).with_spanned(&from[0].relation)
             );
         };
-        let (table_id, is_system, is_view) = self.bind_table_id(name)?;
+        let (table_id, is_system, is_view) = self.bind_table_id(name, Privilege::Delete)?;
         if is_system || is_view {
             return Err(ErrorKind::CanNotDelete.with_spanned(name));
         }
+        if delete.selection.is_some() {
+            // the condition reads the table
+            self.bind_table_id(name, Privilege::Select)?;
+        }
         let scan = self.bind_table_def(name, alias.clone(), true)?;
         let cond = self.bind_where(delete.selection)?;
         let filter = self.egraph.add(Node::Filter([cond, scan]));
         Ok(self.egraph.add(Node::Delete([table_id, filter])))
     }
+
+    /// Binds `TRUNCATE TABLE` as a deletion of all rows, which drops all RowSets of the table.
+    pub(super) fn bind_truncate(&mut self, table_names: Vec<TruncateTableTarget>) -> Result {
+        let [target] = table_names.as_slice() else {
+            return Err(ErrorKind::Todo("truncate multiple tables".into()).into());
+        };
+        let name = &target.name;
+        let (table_id, is_system, is_view) = self.bind_table_id(name, Privilege::Delete)?;
+        if is_system || is_view {
+            return Err(ErrorKind::CanNotDelete.with_spanned(name));
+        }
+        let scan = self.bind_table_def(name, None, true)?;
+        Ok(self.egraph.add(Node::Delete([table_id, scan])))
+    }
 }
@@ -19,13 +19,14 @@ impl Binder {
         let mut table_ids = Vec::with_capacity(names.len());
         for name in names {
             let name = lower_case_name(&name);
-            let (schema_name, table_name) = split_name(&name)?;
-            let result = self.catalog.get_table_id_by_name(schema_name, table_name);
+            let (schema_name, table_name) = self.split_name(&name)?;
+            let result = self.catalog.get_table_id_by_name(&schema_name, table_name);
             if if_exists && result.is_none() {
                 continue;
             }
             let table_id = result
                 .ok_or_else(|| ErrorKind::InvalidTable(table_name.into()).with_spanned(&name))?;
+            self.check_table_privilege(Privilege::Drop, table_id, table_name)?;
             let id = self.egraph.add(Node::Table(table_id));
             table_ids.push(id);
         }
@@ -11,6 +11,7 @@
 use sqlparser::ast::{Ident, ObjectType, Spanned};
 use sqlparser::tokenizer::Span;
 
+use crate::catalog::Privilege;
 use crate::planner::TypeError;
 
 /// The error type of bind operations.
@@ -113,10 +114,20 @@ pub enum ErrorKind {
     CanNotInsert,
     #[error("can only delete from table")]
     CanNotDelete,
+    #[error("can only vacuum or compact a table")]
+    CanNotCompact,
+    #[error("invalid partition: {0}")]
+    InvalidPartition(String),
     #[error("VIEW aliases mismatch query result")]
     ViewAliasesMismatch,
     #[error("pragma does not exist: {0}")]
     NoPragma(String),
+    #[error("permission denied for {0} {1}")]
+    PermissionDenied(&'static str, String),
+    #[error("must be superuser to {0}")]
+    MustBeSuperuser(&'static str),
+    #[error("invalid privilege type {0} for {1}")]
+    InvalidPrivilege(Privilege, &'static str),
 }
 
 impl ErrorKind {
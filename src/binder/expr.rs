@@ -17,15 +17,15 @@ impl Binder {
     pub fn bind_expr(&mut self, expr: Expr) -> Result {
         let id = match expr {
             Expr::Value(v) => {
-                // This is okay since only sql udf relies on
-                // parameter-like (i.e., `$1`) values at present
-                // TODO: consider formally `bind_parameter` in the future
-                // e.g., lambda function support, etc.
+                // parameter-like (i.e., `$1`) values refer to the arguments in sql udf,
+                // or the parameters of a prepared statement otherwise
                 if let Value::Placeholder(key) = &v {
-                    self.udf_context
-                        .get_expr(key)
-                        .cloned()
-                        .ok_or_else(|| ErrorKind::InvalidSQL.with_spanned(&v))
+                    match self.udf_context.get_expr(key) {
+                        Some(id) => Ok(*id),
+                        None => self
+                            .bind_param(key)
+                            .ok_or_else(|| ErrorKind::InvalidSQL.with_spanned(&v)),
+                    }
                 } else {
                     Ok(self.egraph.add(Node::Constant(v.into())))
                 }
@@ -124,8 +124,9 @@ impl Binder {
     fn bind_binary_op(&mut self, left: Expr, op: BinaryOperator, right: Expr) -> Result {
         use BinaryOperator::*;
 
-        let l = self.bind_expr(left)?;
-        let r = self.bind_expr(right)?;
+        let mut operands = [self.bind_expr(left)?, self.bind_expr(right)?];
+        self.infer_param_types(&mut operands);
+        let [l, r] = operands;
         let node = match op {
             Plus => Node::Add([l, r]),
             Minus => Node::Sub([l, r]),
@@ -176,6 +177,60 @@ impl Binder {
         Ok(self.egraph.add(Node::Cast([ty, expr])))
     }
 
+    /// Binds a parameter placeholder like `$1`. Returns `None` if the placeholder is invalid.
+    ///
+    /// If the type of the parameter is known, the parameter is casted to the type.
+    fn bind_param(&mut self, key: &str) -> Option<Id> {
+        let index: usize = key.strip_prefix('$')?.parse().ok()?;
+        if index == 0 {
+            return None;
+        }
+        if self.params.len() < index {
+            self.params.resize(index, None);
+        }
+        let index_id = self
+            .egraph
+            .add(Node::Constant(DataValue::Int32(index as i32)));
+        let param = self.egraph.add(Node::Param(index_id));
+        Some(match self.params[index - 1].clone() {
+            Some(ty) => self.cast_param(param, ty),
+            None => param,
+        })
+    }
+
+    /// Infers the types of parameters with unknown types from other operands,
+    /// and casts them to the inferred types.
+    fn infer_param_types(&mut self, operands: &mut [Id]) {
+        if operands.iter().all(|id| self.param_index(*id).is_none()) {
+            return;
+        }
+        let ty = (operands.iter())
+            .filter(|id| self.param_index(**id).is_none())
+            .find_map(|id| self.type_(*id).ok().filter(|ty| !ty.is_null()));
+        for id in operands {
+            let Some(index) = self.param_index(*id) else {
+                continue;
+            };
+            if let Some(ty) = self.params[index - 1].clone().or_else(|| ty.clone()) {
+                self.params[index - 1] = Some(ty.clone());
+                *id = self.cast_param(*id, ty);
+            }
+        }
+    }
+
+    /// Returns the index of the parameter if the node is an uncasted parameter.
+    pub(super) fn param_index(&self, id: Id) -> Option<usize> {
+        let Node::Param(index) = self.node(id) else {
+            return None;
+        };
+        self.node(*index).as_const().as_usize().ok()?
+    }
+
+    fn cast_param(&mut self, param: Id, ty: crate::types::DataType) -> Id {
+        let ty = self.egraph.add(Node::Type(ty));
+        self.egraph.add(Node::Cast([ty, param]))
+    }
+
     fn bind_is_null(&mut self, expr: Expr) -> Result {
         let expr = self.bind_expr(expr)?;
         Ok(self.egraph.add(Node::IsNull(expr)))
@@ -219,9 +274,13 @@ impl Binder {
     }
 
     fn bind_between(&mut self, expr: Expr, negated: bool, low: Expr, high: Expr) -> Result {
-        let expr = self.bind_expr(expr)?;
-        let low = self.bind_expr(low)?;
-        let high = self.bind_expr(high)?;
+        let mut operands = [
+            self.bind_expr(expr)?,
+            self.bind_expr(low)?,
+            self.bind_expr(high)?,
+        ];
+        self.infer_param_types(&mut operands);
+        let [expr, low, high] = operands;
         let left = self.egraph.add(Node::GtEq([expr, low]));
         let right = self.egraph.add(Node::LtEq([expr, high]));
         let between = self.egraph.add(Node::And([left, right]));
@@ -278,8 +337,13 @@ impl Binder {
     }
 
     fn bind_in_list(&mut self, expr: Expr, list: Vec<Expr>, negated: bool) -> Result {
-        let expr = self.bind_expr(expr)?;
-        let list = self.bind_exprs(list)?;
+        let mut operands = vec![self.bind_expr(expr)?];
+        for e in list {
+            operands.push(self.bind_expr(e)?);
+        }
+        self.infer_param_types(&mut operands);
+        let expr = operands[0];
+        let list = self.egraph.add(Node::List(operands[1..].into()));
         let in_list = self.egraph.add(Node::In([expr, list]));
         if negated {
             Ok(self.egraph.add(Node::Not(in_list)))
@@ -366,7 +430,7 @@ impl Binder {
         }
 
         let catalog = self.catalog();
-        let Ok((schema_name, function_name)) = split_name(&func.name) else {
+        let Ok((schema_name, function_name)) = self.split_name(&func.name) else {
             return Err(ErrorKind::BindFunctionError(format!(
                 "failed to parse the function name {}",
                 func.name
@@ -375,7 +439,8 @@ impl Binder {
         };
 
         // See if the input function is sql udf
-        if let Some(ref function_catalog) = catalog.get_function_by_name(schema_name, function_name)
+        if let Some(ref function_catalog) =
+            catalog.get_function_by_name(&schema_name, function_name)
         {
             // Create the brand new `udf_context`
             let Ok(context) = UdfContext::create_udf_context(function_args, function_catalog)
@@ -7,13 +7,35 @@ impl Binder {
         let Some(source) = insert.source else {
             return Err(ErrorKind::InvalidSQL.with_spanned(&insert));
         };
-        let (table, is_internal, is_view) = self.bind_table_id(&insert.table_name)?;
+        let (table, is_internal, is_view) =
+            self.bind_table_id(&insert.table_name, Privilege::Insert)?;
         if is_internal || is_view {
             return Err(ErrorKind::CanNotInsert.with_spanned(&insert.table_name));
         }
         let cols = self.bind_table_columns(&insert.table_name, &insert.columns)?;
         let source = self.bind_query(*source)?.0;
+        self.infer_insert_param_types(cols, source);
         let id = self.egraph.add(Node::Insert([table, cols, source]));
         Ok(id)
     }
+
+    /// Infers the types of parameters in `VALUES` from the columns to insert.
+    fn infer_insert_param_types(&mut self, cols: Id, source: Id) {
+        let Node::Limit([_, _, values]) = self.node(source) else {
+            return;
+        };
+        let Node::Values(rows) = self.node(*values) else {
+            return;
+        };
+        let types = (self.node(cols).as_list().iter())
+            .map(|id| self.type_(*id).ok())
+            .collect_vec();
+        let indexes = (rows.iter())
+            .flat_map(|row| self.node(*row).as_list().iter().zip(&types))
+            .filter_map(|(id, ty)| Some((self.param_index(*id)?, ty.clone()?)))
+            .collect_vec();
+        for (index, ty) in indexes {
+            self.params[index - 1].get_or_insert(ty);
+        }
+    }
 }
@@ -10,11 +10,12 @@ use itertools::Itertools;
 
 use crate::array;
 use crate::catalog::function::FunctionCatalog;
-use crate::catalog::{RootCatalog, RootCatalogRef, TableRefId};
+use crate::catalog::{Privilege, RootCatalog, RootCatalogRef, TableRefId};
 use crate::parser::*;
 use crate::planner::{Expr as Node, RecExpr, TypeSchemaAnalysis};
 use crate::types::DataValue;
 
+mod alter_table;
 pub mod copy;
 mod create_function;
 mod create_index;
@@ -25,14 +26,18 @@ mod drop;
 mod error;
 mod expr;
 mod insert;
+mod privilege;
 mod select;
 mod table;
+mod user;
 
 pub use self::create_function::CreateFunction;
 pub use self::create_index::{CreateIndex, IndexType, VectorDistance};
 pub use self::create_table::CreateTable;
 pub use self::error::BindError;
 use self::error::ErrorKind;
+pub use self::privilege::{Grant, GrantRole};
+pub use self::user::{AlterUser, CreateUser, DropUser};
 
 pub type Result<T = Id> = std::result::Result<T, BindError>;
 
@@ -46,6 +51,14 @@ pub struct Binder {
     table_occurrences: HashMap<TableRefId, u32>,
     /// The context used in sql udf binding
     udf_context: UdfContext,
+    /// The types of parameters `$1`, `$2`, ..., or `None` if not yet known.
+    params: Vec<Option<crate::types::DataType>>,
+    /// The output column names of the last bound query.
+    output_names: Vec<String>,
+    /// The user whose privileges are checked, or `None` to skip the checks.
+    user: Option<String>,
+    /// The schema of unqualified names.
+    schema: String,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -173,10 +186,46 @@ impl UdfContext {
 pub fn bind_header(mut chunk: array::Chunk, stmt: &Statement) -> array::Chunk {
     let header_values = match stmt {
         Statement::CreateTable { .. } => vec!["$create".to_string()],
+        Statement::CreateView { .. } => vec!["$create_view".to_string()],
+        Statement::CreateIndex { .. } => vec!["$create_index".to_string()],
+        Statement::CreateFunction { .. } => vec!["$create_function".to_string()],
+        Statement::CreateRole { .. } => vec!["$create_role".to_string()],
+        Statement::AlterRole {
+            operation: AlterRoleOperation::AddMember { .. },
+            ..
+        } => vec!["$grant_role".to_string()],
+        Statement::AlterRole {
+            operation: AlterRoleOperation::DropMember { .. },
+            ..
+        } => vec!["$revoke_role".to_string()],
+        Statement::AlterRole { .. } => vec!["$alter_role".to_string()],
+        Statement::Grant { .. } => vec!["$grant".to_string()],
+        Statement::Revoke { .. } => vec!["$revoke".to_string()],
+        Statement::Drop {
+            object_type: ObjectType::Role,
+            ..
+        } => vec!["$drop_role".to_string()],
+        Statement::Drop {
+            object_type: ObjectType::View,
+            ..
+        } => vec!["$drop_view".to_string()],
         Statement::Drop { .. } => vec!["$drop".to_string()],
         Statement::Insert { .. } => vec!["$insert.row_counts".to_string()],
         Statement::Explain { .. } => vec!["$explain".to_string()],
         Statement::Delete { .. } => vec!["$delete.row_counts".to_string()],
+        Statement::Copy { to: false, .. } => vec!["$copy.row_counts".to_string()],
+        Statement::Truncate { .. } => vec!["$truncate".to_string()],
+        Statement::AlterTable { .. } => vec!["$alter_table".to_string()],
+        Statement::Pragma { name, .. } if name.to_string().eq_ignore_ascii_case("backup") => {
+            vec!["$backup".to_string()]
+        }
+        Statement::Pragma { name, .. } if name.0.len() > 1 => {
+            match name.0[0].value.to_lowercase().as_str() {
+                "compact" => vec!["$compact".to_string()],
+                "vacuum" | "vacuum_full" => vec!["$vacuum".to_string()],
+                _ => Vec::new(),
+            }
+        }
         _ => Vec::new(),
     };
 
@@ -215,9 +264,38 @@ impl Binder {
             contexts: vec![Context::default()],
             table_occurrences: HashMap::new(),
             udf_context: UdfContext::new(),
+            params: vec![],
+            output_names: vec![],
+            user: None,
+            schema: RootCatalog::DEFAULT_SCHEMA_NAME.into(),
         }
     }
 
+    /// Set the types of parameters declared by the client.
+    /// `None` means the type should be inferred from the statement.
+    pub fn set_param_types(&mut self, types: Vec<Option<crate::types::DataType>>) {
+        self.params = types;
+    }
+
+    /// Returns the types of parameters after binding.
+    ///
+    /// Parameters whose type can not be inferred are strings.
+    pub fn param_types(&self) -> Vec<crate::types::DataType> {
+        (self.params.iter())
+            .map(|ty| ty.clone().unwrap_or(crate::types::DataType::String))
+            .collect()
+    }
+
+    /// Set the schema of unqualified names.
+    pub fn set_schema(&mut self, schema: &str) {
+        self.schema = schema.into();
+    }
+
+    /// Returns the output column names of the bound query.
+    pub fn output_names(&self) -> &[String] {
+        &self.output_names
+    }
+
     /// Bind a statement.
     pub fn bind(&mut self, stmt: Statement) -> Result<RecExpr> {
         let id = self.bind_stmt(stmt)?;
@@ -239,6 +317,53 @@ impl Binder {
             Statement::CreateFunction(create_function) => {
                 self.bind_create_function(create_function)
             }
+            Statement::CreateRole {
+                names,
+                if_not_exists,
+                login,
+                superuser,
+                password,
+                ..
+            } => self.bind_create_user(names, if_not_exists, login, superuser, password),
+            Statement::AlterRole {
+                name,
+                operation: AlterRoleOperation::AddMember { member_name },
+            } => self.bind_grant_role(name, member_name, false),
+            Statement::AlterRole {
+                name,
+                operation: AlterRoleOperation::DropMember { member_name },
+            } => self.bind_grant_role(name, member_name, true),
+            Statement::AlterRole { name, operation } => self.bind_alter_user(name, operation),
+            Statement::Grant {
+                with_grant_option: true,
+                ..
+            } => Err(ErrorKind::Todo("grant option".into()).into()),
+            Statement::Grant {
+                granted_by: Some(_),
+                ..
+            }
+            | Statement::Revoke {
+                granted_by: Some(_),
+                ..
+            } => Err(ErrorKind::Todo("granted by".into()).into()),
+            Statement::Grant {
+                privileges,
+                objects,
+                grantees,
+                ..
+            } => self.bind_grant(privileges, objects, grantees, false),
+            Statement::Revoke {
+                privileges,
+                objects,
+                grantees,
+                ..
+            } => self.bind_grant(privileges, objects, grantees, true),
+            Statement::Drop {
+                object_type: ObjectType::Role,
+                if_exists,
+                names,
+                ..
+            } => self.bind_drop_user(names, if_exists),
             Statement::Drop {
                 object_type,
                 if_exists,
@@ -248,6 +373,10 @@ impl Binder {
             } => self.bind_drop(object_type, if_exists, names, cascade),
             Statement::Insert(insert) => self.bind_insert(insert),
             Statement::Delete(delete) => self.bind_delete(delete),
+            Statement::Truncate { table_names, .. } => self.bind_truncate(table_names),
+            Statement::AlterTable {
+                name, operations, ..
+            } => self.bind_alter_table(name, operations),
             Statement::Copy {
                 source,
                 to,
@@ -366,7 +495,6 @@ impl Binder {
         &self.egraph[id].nodes[0]
     }
 
-    #[allow(dead_code)]
     fn recexpr(&self, id: Id) -> RecExpr {
         self.node(id).build_recexpr(|id| self.node(id).clone())
     }
@@ -397,9 +525,15 @@ impl Binder {
     }
 
     pub fn bind_pragma(&mut self, name: ObjectName, value: Option<Value>) -> Result {
+        if let [command, table @ ..] = name.0.as_slice() {
+            if !table.is_empty() {
+                return self.bind_compact(command, ObjectName(table.to_vec()));
+            }
+        }
         let name_string = name.to_string().to_lowercase();
         match name_string.as_str() {
             "enable_optimizer" | "disable_optimizer" => {}
+            "backup" => return self.bind_backup(value),
             name_str => return Err(ErrorKind::NoPragma(name_str.into()).with_spanned(&name)),
         }
         let name_id = self.egraph.add(Node::Constant(name_string.into()));
@@ -410,28 +544,102 @@ impl Binder {
         Ok(id)
     }
 
+    /// Binds `BACKUP TO '<dir>'`, which is parsed as `PRAGMA backup('<dir>')`.
+    fn bind_backup(&mut self, value: Option<Value>) -> Result {
+        self.check_superuser("back up the database")?;
+        let Some(Value::SingleQuotedString(dir)) = value else {
+            return Err(
+                ErrorKind::InvalidExpression("backup directory must be a string".into()).into(),
+            );
+        };
+        let dir = self
+            .egraph
+            .add(Node::Constant(DataValue::String(dir.into())));
+        Ok(self.egraph.add(Node::Backup(dir)))
+    }
+
+    /// Binds `VACUUM [FULL] t` and `COMPACT t`, which are parsed as `PRAGMA vacuum.t`,
+    /// `PRAGMA vacuum_full.t` and `PRAGMA compact.t`.
+    fn bind_compact(&mut self, command: &Ident, table: ObjectName) -> Result {
+        let mode = match command.value.to_lowercase().as_str() {
+            "vacuum" => Node::Vacuum,
+            "vacuum_full" => Node::VacuumFull,
+            "compact" => Node::ByPolicies,
+            mode => return Err(ErrorKind::NoPragma(mode.into()).with_span(command.span)),
+        };
+        // compactions purge deleted rows
+        let (table_id, is_system, is_view) = self.bind_table_id(&table, Privilege::Delete)?;
+        if is_system || is_view {
+            return Err(ErrorKind::CanNotCompact.with_spanned(&table));
+        }
+        let mode = self.egraph.add(mode);
+        Ok(self.egraph.add(Node::Compact([table_id, mode])))
+    }
+
+    /// Split an object name into `(schema name, table name)`.
+    ///
+    /// Unqualified names refer to objects in the current schema.
+    fn split_name<'a>(&self, name: &'a ObjectName) -> Result<(String, &'a str)> {
+        Ok(match name.0.as_slice() {
+            [table] => (self.schema.clone(), &table.value),
+            [schema, table] => (schema.value.clone(), &table.value),
+            _ => return Err(ErrorKind::InvalidTableName(name.0.clone()).with_spanned(name)),
+        })
+    }
+
+    /// Binds `SET name = value`.
+    ///
+    /// The value must be a constant or a parameter. Identifiers are taken as strings,
+    /// and a list of values like `SET search_path = a, b` is joined into one string.
     pub fn bind_set(&mut self, variables: &[ObjectName], values: Vec<Expr>) -> Result {
-        if variables.len() != 1 || values.len() != 1 {
+        let [variable] = variables else {
             return Err(ErrorKind::InvalidSQL.into());
-        }
-        let name_id = self
-            .egraph
-            .add(Node::Constant(variables[0].to_string().into()));
-        let value_id = self.bind_expr(values.into_iter().next().unwrap())?;
+        };
+        let name = variable.to_string().to_lowercase();
+        let value_id = match values.as_slice() {
+            [] => return Err(ErrorKind::InvalidSQL.into()),
+            [Expr::Identifier(_)] | [_, _, ..] => {
+                let strings: Vec<_> = (values.iter())
+                    .map(|value| match value {
+                        Expr::Identifier(ident) if ident.quote_style.is_none() => {
+                            Ok(ident.value.to_lowercase())
+                        }
+                        Expr::Identifier(ident) => Ok(ident.value.clone()),
+                        Expr::Value(Value::SingleQuotedString(s)) => Ok(s.clone()),
+                        _ => Err(
+                            ErrorKind::InvalidExpression(format!("invalid value {value}"))
+                                .with_spanned(value),
+                        ),
+                    })
+                    .try_collect()?;
+                let value = DataValue::String(strings.join(", ").into());
+                self.egraph.add(Node::Constant(value))
+            }
+            // negative numbers like `SET log_min_duration_statement = -1`
+            [Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            }] if matches!(**expr, Expr::Value(Value::Number(..))) => {
+                let value = Expr::Value(Value::Number(format!("-{expr}"), false));
+                self.bind_expr(value)?
+            }
+            [value] => {
+                let id = self.bind_expr(value.clone())?;
+                if !matches!(self.node(id), Node::Constant(_) | Node::Param(_)) {
+                    return Err(ErrorKind::InvalidExpression(format!(
+                        "SET value must be a constant: {value}"
+                    ))
+                    .with_spanned(value));
+                }
+                id
+            }
+        };
+        let name_id = self.egraph.add(Node::Constant(name.into()));
         let id = self.egraph.add(Node::Set([name_id, value_id]));
         Ok(id)
     }
 }
 
-/// Split an object name into `(schema name, table name)`.
-fn split_name(name: &ObjectName) -> Result<(&str, &str)> {
-    Ok(match name.0.as_slice() {
-        [table] => (RootCatalog::DEFAULT_SCHEMA_NAME, &table.value),
-        [schema, table] => (&schema.value, &table.value),
-        _ => return Err(ErrorKind::InvalidTableName(name.0.clone()).with_spanned(name)),
-    })
-}
-
 /// Convert an object name into lower case
 fn lower_case_name(name: &ObjectName) -> ObjectName {
     ObjectName(
@@ -0,0 +1,260 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::fmt;
+use std::str::FromStr;
+
+use pretty_xmlish::helper::delegate_fmt;
+use pretty_xmlish::Pretty;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::catalog::{PrivilegeObject, SchemaId};
+
+/// `GRANT privileges ON objects TO users`, or `REVOKE` with the same fields.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct Grant {
+    pub privileges: Vec<Privilege>,
+    pub objects: Vec<PrivilegeObject>,
+    pub users: Vec<String>,
+}
+
+/// `GRANT role TO member`, or `REVOKE` with the same fields.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct GrantRole {
+    pub role: String,
+    pub member: String,
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explainer = Pretty::childless_record("Grant", self.pretty_grant());
+        delegate_fmt(&explainer, f, String::with_capacity(1000))
+    }
+}
+
+impl fmt::Display for GrantRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explainer = Pretty::childless_record("GrantRole", self.pretty_grant());
+        delegate_fmt(&explainer, f, String::with_capacity(1000))
+    }
+}
+
+impl FromStr for Grant {
+    type Err = ();
+
+    fn from_str(_s: &str) -> std::result::Result<Self, Self::Err> {
+        Err(())
+    }
+}
+
+impl FromStr for GrantRole {
+    type Err = ();
+
+    fn from_str(_s: &str) -> std::result::Result<Self, Self::Err> {
+        Err(())
+    }
+}
+
+impl Grant {
+    pub fn pretty_grant<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
+        vec![
+            (
+                "privileges",
+                Pretty::display(&self.privileges.iter().join(", ")),
+            ),
+            ("objects", Pretty::display(&self.objects.iter().join(", "))),
+            ("users", Pretty::display(&self.users.join(", "))),
+        ]
+    }
+}
+
+impl GrantRole {
+    pub fn pretty_grant<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
+        vec![
+            ("role", Pretty::display(&self.role)),
+            ("member", Pretty::display(&self.member)),
+        ]
+    }
+}
+
+impl Binder {
+    /// Sets the user to check privileges for.
+    ///
+    /// Without a user, statements are bound with all privileges.
+    pub fn set_user(&mut self, user: &str) {
+        self.user = Some(user.into());
+    }
+
+    /// Returns an error if the user does not have the privilege on the table.
+    ///
+    /// Everyone can read system tables.
+    pub(super) fn check_table_privilege(
+        &self,
+        privilege: Privilege,
+        table: TableRefId,
+        name: &str,
+    ) -> Result<()> {
+        if table.schema_id == RootCatalog::SYSTEM_SCHEMA_ID && privilege == Privilege::Select {
+            return Ok(());
+        }
+        self.check_privilege(privilege, PrivilegeObject::Table(table), "table", name)
+    }
+
+    /// Returns an error if the user does not have the privilege on the schema.
+    pub(super) fn check_schema_privilege(
+        &self,
+        privilege: Privilege,
+        schema: SchemaId,
+        name: &str,
+    ) -> Result<()> {
+        self.check_privilege(privilege, PrivilegeObject::Schema(schema), "schema", name)
+    }
+
+    fn check_privilege(
+        &self,
+        privilege: Privilege,
+        object: PrivilegeObject,
+        kind: &'static str,
+        name: &str,
+    ) -> Result<()> {
+        match &self.user {
+            Some(user) if !self.catalog.has_privilege(user, privilege, object) => {
+                Err(ErrorKind::PermissionDenied(kind, name.into()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the user is not a superuser.
+    pub(super) fn check_superuser(&self, action: &'static str) -> Result<()> {
+        let Some(user) = &self.user else {
+            return Ok(());
+        };
+        match self.catalog.get_user_by_name(user) {
+            Some(user) if user.is_superuser() => Ok(()),
+            _ => Err(ErrorKind::MustBeSuperuser(action).into()),
+        }
+    }
+
+    /// Binds `GRANT` and `REVOKE` of privileges.
+    ///
+    /// `ALL` grants all privileges that apply to the objects,
+    /// and `ALL TABLES IN SCHEMA` refers to the tables that exist now.
+    pub(super) fn bind_grant(
+        &mut self,
+        privileges: Privileges,
+        objects: GrantObjects,
+        grantees: Vec<Ident>,
+        revoke: bool,
+    ) -> Result {
+        self.check_superuser(if revoke {
+            "revoke privileges"
+        } else {
+            "grant privileges"
+        })?;
+        let (objects, kind) = match objects {
+            GrantObjects::Tables(names) => {
+                let objects = (names.iter())
+                    .map(|name| self.bind_privilege_table(name))
+                    .try_collect()?;
+                (objects, "table")
+            }
+            GrantObjects::AllTablesInSchema { schemas } => {
+                let mut objects = vec![];
+                for name in &schemas {
+                    let schema = self.bind_privilege_schema(name)?;
+                    let tables = self.catalog.get_schema_by_id(schema).unwrap().all_tables();
+                    objects.extend(
+                        (tables.keys())
+                            .map(|id| PrivilegeObject::Table(TableRefId::new(schema, *id))),
+                    );
+                }
+                objects.sort();
+                (objects, "table")
+            }
+            GrantObjects::Schemas(names) => {
+                let objects = (names.iter())
+                    .map(|name| Ok(PrivilegeObject::Schema(self.bind_privilege_schema(name)?)))
+                    .try_collect::<_, _, BindError>()?;
+                (objects, "schema")
+            }
+            objects => return Err(ErrorKind::Todo(format!("grant on {objects}")).into()),
+        };
+        let grantable = match kind {
+            "schema" => Privilege::SCHEMA,
+            _ => Privilege::TABLE,
+        };
+        let privileges = match privileges {
+            Privileges::All { .. } => grantable.to_vec(),
+            Privileges::Actions(actions) => {
+                let mut privileges = vec![];
+                for action in actions {
+                    let privilege = match action {
+                        Action::Select { columns: None } => Privilege::Select,
+                        Action::Insert { columns: None } => Privilege::Insert,
+                        Action::Delete => Privilege::Delete,
+                        Action::Update { columns: None } => Privilege::Update,
+                        Action::Create => Privilege::Create,
+                        action => return Err(ErrorKind::Todo(format!("privilege {action}")).into()),
+                    };
+                    if !grantable.contains(&privilege) {
+                        return Err(ErrorKind::InvalidPrivilege(privilege, kind).into());
+                    }
+                    privileges.push(privilege);
+                }
+                privileges
+            }
+        };
+        let users = grantees
+            .iter()
+            .map(|ident| ident.value.to_lowercase())
+            .collect();
+        let grant = Grant {
+            privileges,
+            objects,
+            users,
+        };
+        let id = match revoke {
+            false => self.egraph.add(Node::Grant(grant)),
+            true => self.egraph.add(Node::Revoke(grant)),
+        };
+        Ok(id)
+    }
+
+    /// Binds `ALTER ROLE role ADD MEMBER member` and `ALTER ROLE role DROP MEMBER member`,
+    /// which are parsed from `GRANT role TO member` and `REVOKE role FROM member`.
+    pub(super) fn bind_grant_role(&mut self, role: Ident, member: Ident, revoke: bool) -> Result {
+        self.check_superuser(if revoke {
+            "revoke roles"
+        } else {
+            "grant roles"
+        })?;
+        let grant = GrantRole {
+            role: role.value.to_lowercase(),
+            member: member.value.to_lowercase(),
+        };
+        let id = match revoke {
+            false => self.egraph.add(Node::GrantRole(grant)),
+            true => self.egraph.add(Node::RevokeRole(grant)),
+        };
+        Ok(id)
+    }
+
+    fn bind_privilege_table(&self, name: &ObjectName) -> Result<PrivilegeObject> {
+        let name = lower_case_name(name);
+        let (schema_name, table_name) = self.split_name(&name)?;
+        let table = self
+            .catalog
+            .get_table_id_by_name(&schema_name, table_name)
+            .ok_or_else(|| ErrorKind::InvalidTable(table_name.into()).with_spanned(&name))?;
+        Ok(PrivilegeObject::Table(table))
+    }
+
+    fn bind_privilege_schema(&self, name: &ObjectName) -> Result<SchemaId> {
+        let name = lower_case_name(name);
+        let schema_name = name.to_string();
+        self.catalog
+            .get_schema_id_by_name(&schema_name)
+            .ok_or_else(|| ErrorKind::InvalidSchema(schema_name).with_spanned(&name))
+    }
+}
@@ -30,16 +30,30 @@ impl Binder {
             body => return Err(ErrorKind::Todo("unknown set expr".into()).with_spanned(&body)),
         };
         let limit = match query.limit {
-            Some(expr) => self.bind_expr(expr)?,
+            Some(expr) => self.bind_limit(expr)?,
             None => self.egraph.add(Node::null()),
         };
         let offset = match query.offset {
-            Some(offset) => self.bind_expr(offset.value)?,
+            Some(offset) => self.bind_limit(offset.value)?,
             None => self.egraph.add(Node::zero()),
         };
         Ok(self.egraph.add(Node::Limit([limit, offset, child])))
     }
 
+    /// Binds the expression of LIMIT or OFFSET.
+    fn bind_limit(&mut self, expr: Expr) -> Result {
+        let id = self.bind_expr(expr.clone())?;
+        if self
+            .recexpr(id)
+            .as_ref()
+            .iter()
+            .any(|e| matches!(e, Node::Param(_)))
+        {
+            return Err(ErrorKind::Todo("parameters in LIMIT or OFFSET".into()).with_spanned(&expr));
+        }
+        Ok(id)
+    }
+
     /// Binds a CTE definition: `alias AS query`.
     ///
     /// Returns a node of query and adds the CTE to the context.
@@ -79,7 +93,7 @@ impl Binder {
 
     fn bind_select(&mut self, select: Select, order_by: Option<OrderBy>) -> Result {
         let from = self.bind_from(select.from)?;
-        let projection = self.bind_projection(select.projection, from)?;
+        let (projection, output_names) = self.bind_projection(select.projection, from)?;
         let mut where_ = self.bind_where(select.selection)?;
         let groupby = match select.group_by {
             GroupByExpr::All(_) => {
@@ -111,12 +125,18 @@ impl Binder {
         plan = self.plan_distinct(distinct, orderby, &mut projection, plan)?;
         plan = self.egraph.add(Node::Order([orderby, plan]));
         plan = self.egraph.add(Node::Proj([projection, plan]));
+        self.output_names = output_names;
         Ok(plan)
     }
 
-    /// Binds the select list. Returns a list of expressions.
-    fn bind_projection(&mut self, projection: Vec<SelectItem>, from: Id) -> Result {
+    /// Binds the select list. Returns a list of expressions and their output names.
+    fn bind_projection(
+        &mut self,
+        projection: Vec<SelectItem>,
+        from: Id,
+    ) -> Result<(Id, Vec<String>)> {
         let mut select_list = vec![];
+        let mut names = vec![];
         for item in projection {
             match item {
                 SelectItem::UnnamedExpr(expr) => {
@@ -126,6 +146,7 @@ impl Binder {
                         None
                     };
                     let id = self.bind_expr(expr)?;
+                    names.push(ident.clone().unwrap_or_else(|| self.output_name(id)));
                     if let Some(ident) = ident {
                         self.add_output_alias(ident, id);
                     }
@@ -135,16 +156,38 @@ impl Binder {
                     let id = self.bind_expr(expr)?;
                     let name = alias.value.to_lowercase();
                     self.add_alias(name.clone(), "".into(), id);
-                    self.add_output_alias(name, id);
+                    self.add_output_alias(name.clone(), id);
                     select_list.push(id);
+                    names.push(name);
                 }
                 SelectItem::Wildcard(_) => {
-                    select_list.append(&mut self.schema(from));
+                    let mut schema = self.schema(from);
+                    names.extend(schema.iter().map(|id| self.output_name(*id)));
+                    select_list.append(&mut schema);
                 }
                 _ => todo!("bind select list"),
             }
         }
-        Ok(self.egraph.add(Node::List(select_list.into())))
+        Ok((self.egraph.add(Node::List(select_list.into())), names))
+    }
+
+    /// Returns the default output name of an expression in the select list.
+    fn output_name(&self, id: Id) -> String {
+        match self.node(id) {
+            Node::Column(column) => match self.catalog.get_column(column) {
+                Some(column) => column.name().to_string(),
+                None => "?column?".into(),
+            },
+            Node::Ref(e) | Node::Cast([_, e]) => self.output_name(*e),
+            Node::RowCount | Node::Count(_) | Node::CountDistinct(_) => "count".into(),
+            e @ (Node::Max(_)
+            | Node::Min(_)
+            | Node::Sum(_)
+            | Node::Avg(_)
+            | Node::First(_)
+            | Node::Last(_)) => e.to_string(),
+            _ => "?column?".into(),
+        }
     }
 
     /// Binds the WHERE clause. Returns an expression for condition.
@@ -213,6 +256,7 @@ impl Binder {
         }
 
         let column_len = values[0].len();
+        self.output_names = (1..=column_len).map(|i| format!("column{i}")).collect();
         for row in values {
             if row.len() != column_len {
                 let span = Span::union_iter(row.iter().map(|e| e.span()));
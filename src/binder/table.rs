@@ -59,6 +59,12 @@ impl Binder {
     /// - `bind_table_factor(select 1)` => `(values (1))`
     fn bind_table_factor(&mut self, table: TableFactor) -> Result {
         match table {
+            TableFactor::Table {
+                name,
+                alias,
+                args: Some(args),
+                ..
+            } => self.bind_table_as_of(&name, alias, args),
             TableFactor::Table { name, alias, .. } => self.bind_table_def(&name, alias, false),
             TableFactor::Derived {
                 subquery, alias, ..
@@ -150,7 +156,7 @@ impl Binder {
         with_rowid: bool,
     ) -> Result {
         let name = lower_case_name(name);
-        let (schema_name, table_name) = split_name(&name)?;
+        let (schema_name, table_name) = self.split_name(&name)?;
 
         // check duplicated alias
         let table_alias = match &alias {
@@ -171,8 +177,12 @@ impl Binder {
         // find table in catalog
         let ref_id = self
             .catalog
-            .get_table_id_by_name(schema_name, table_name)
+            .get_table_id_by_name(&schema_name, table_name)
             .ok_or_else(|| ErrorKind::InvalidTable(table_name.into()))?;
+        // privileges of `DELETE` are checked by the caller
+        if !with_rowid {
+            self.check_table_privilege(Privilege::Select, ref_id, table_name)?;
+        }
 
         let table = self.catalog.get_table(&ref_id).unwrap();
         let table_occurence = {
@@ -200,6 +210,63 @@ impl Binder {
         Ok(scan)
     }
 
+    /// Returns a `ScanAsOf` plan that reads a base table at a historical version.
+    ///
+    /// # Example
+    /// - `bind_table_as_of(t, epoch => 5)` => `(scan_as_of $1 (list $1.1 $1.2) 5)`
+    fn bind_table_as_of(
+        &mut self,
+        name: &ObjectName,
+        alias: Option<TableAlias>,
+        args: TableFunctionArgs,
+    ) -> Result {
+        let version = match &args.args[..] {
+            [FunctionArg::Named {
+                name: kind,
+                arg: FunctionArgExpr::Expr(Expr::Value(value)),
+                ..
+            }
+            | FunctionArg::ExprNamed {
+                name: Expr::Identifier(kind),
+                arg: FunctionArgExpr::Expr(Expr::Value(value)),
+                ..
+            }] => match (kind.value.to_lowercase().as_str(), value) {
+                ("epoch", Value::Number(n, _)) => DataValue::Int64(n.parse().map_err(|_| {
+                    ErrorKind::InvalidExpression(format!("invalid epoch: {n}")).with_span(kind.span)
+                })?),
+                ("timestamp", Value::SingleQuotedString(s)) => {
+                    DataValue::Timestamp(s.parse().map_err(|_| {
+                        ErrorKind::CastError(
+                            DataValue::String(s.as_str().into()),
+                            crate::types::DataType::Timestamp,
+                        )
+                        .with_span(kind.span)
+                    })?)
+                }
+                _ => {
+                    return Err(
+                        ErrorKind::Todo(format!("AS OF {kind} {value}")).with_span(kind.span)
+                    )
+                }
+            },
+            _ => return Err(ErrorKind::Todo("table arguments".into()).with_spanned(name)),
+        };
+        let scan = self.bind_table_def(name, alias, false)?;
+        let Node::Scan([table, cols, _]) = self.node(scan).clone() else {
+            return Err(ErrorKind::Todo("AS OF on CTE".into()).with_spanned(name));
+        };
+        let table_id = self.node(table).as_table();
+        if table_id.schema_id == RootCatalog::SYSTEM_SCHEMA_ID
+            || self.catalog.get_table(&table_id).unwrap().is_view()
+        {
+            return Err(
+                ErrorKind::Todo("AS OF on views and system tables".into()).with_spanned(name)
+            );
+        }
+        let version = self.egraph.add(Node::Constant(version));
+        Ok(self.egraph.add(Node::ScanAsOf([table, cols, version])))
+    }
+
     /// Returns a list of given columns in the table.
     ///
     /// If `columns` is empty, returns all columns in the table.
@@ -215,11 +282,11 @@ impl Binder {
         columns: &[Ident],
     ) -> Result {
         let name = lower_case_name(table_name);
-        let (schema_name, table_name) = split_name(&name)?;
+        let (schema_name, table_name) = self.split_name(&name)?;
 
         let table_ref_id = self
             .catalog
-            .get_table_id_by_name(schema_name, table_name)
+            .get_table_id_by_name(&schema_name, table_name)
             .ok_or_else(|| ErrorKind::InvalidTable(table_name.into()).with_spanned(&name))?;
 
         let table = self.catalog.get_table(&table_ref_id).unwrap();
@@ -250,16 +317,23 @@ impl Binder {
 
     /// Returns a [`Table`](Node::Table) node, `is_system` flag, and `is_view` flag.
     ///
+    /// The user must have the privilege on the table.
+    ///
     /// # Example
     /// - `bind_table_id(t)` => `$1`
-    pub(super) fn bind_table_id(&mut self, table_name: &ObjectName) -> Result<(Id, bool, bool)> {
+    pub(super) fn bind_table_id(
+        &mut self,
+        table_name: &ObjectName,
+        privilege: Privilege,
+    ) -> Result<(Id, bool, bool)> {
         let name = lower_case_name(table_name);
-        let (schema_name, table_name) = split_name(&name)?;
+        let (schema_name, table_name) = self.split_name(&name)?;
 
         let table_ref_id = self
             .catalog
-            .get_table_id_by_name(schema_name, table_name)
+            .get_table_id_by_name(&schema_name, table_name)
             .ok_or_else(|| ErrorKind::InvalidTable(table_name.into()).with_spanned(&name))?;
+        self.check_table_privilege(privilege, table_ref_id, table_name)?;
         let table = self.catalog.get_table(&table_ref_id).unwrap();
         let id = self.egraph.add(Node::Table(table_ref_id));
         Ok((
@@ -0,0 +1,213 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::fmt;
+use std::str::FromStr;
+
+use pretty_xmlish::helper::delegate_fmt;
+use pretty_xmlish::Pretty;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::catalog::{PasswordVerifier, UserOptions};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct CreateUser {
+    pub name: String,
+    pub if_not_exists: bool,
+    pub options: UserOptions,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct AlterUser {
+    pub name: String,
+    pub options: UserOptions,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct DropUser {
+    pub names: Vec<String>,
+    pub if_exists: bool,
+}
+
+impl fmt::Display for CreateUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explainer = Pretty::childless_record("CreateUser", self.pretty_user());
+        delegate_fmt(&explainer, f, String::with_capacity(1000))
+    }
+}
+
+impl fmt::Display for AlterUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explainer = Pretty::childless_record("AlterUser", self.pretty_user());
+        delegate_fmt(&explainer, f, String::with_capacity(1000))
+    }
+}
+
+impl fmt::Display for DropUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explainer = Pretty::childless_record("DropUser", self.pretty_user());
+        delegate_fmt(&explainer, f, String::with_capacity(1000))
+    }
+}
+
+impl FromStr for CreateUser {
+    type Err = ();
+
+    fn from_str(_s: &str) -> std::result::Result<Self, Self::Err> {
+        Err(())
+    }
+}
+
+impl FromStr for AlterUser {
+    type Err = ();
+
+    fn from_str(_s: &str) -> std::result::Result<Self, Self::Err> {
+        Err(())
+    }
+}
+
+impl FromStr for DropUser {
+    type Err = ();
+
+    fn from_str(_s: &str) -> std::result::Result<Self, Self::Err> {
+        Err(())
+    }
+}
+
+/// Returns the explain fields of user options. The password is never shown.
+fn pretty_options<'a>(options: &UserOptions) -> Vec<(&'a str, Pretty<'a>)> {
+    let mut fields = vec![];
+    if let Some(superuser) = options.superuser {
+        fields.push(("superuser", Pretty::display(&superuser)));
+    }
+    if let Some(login) = options.login {
+        fields.push(("login", Pretty::display(&login)));
+    }
+    if let Some(password) = &options.password {
+        fields.push(("password", Pretty::display(&password.is_some())));
+    }
+    fields
+}
+
+impl CreateUser {
+    pub fn pretty_user<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
+        let mut fields = vec![("name", Pretty::display(&self.name))];
+        fields.extend(pretty_options(&self.options));
+        fields
+    }
+}
+
+impl AlterUser {
+    pub fn pretty_user<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
+        let mut fields = vec![("name", Pretty::display(&self.name))];
+        fields.extend(pretty_options(&self.options));
+        fields
+    }
+}
+
+impl DropUser {
+    pub fn pretty_user<'a>(&self) -> Vec<(&'a str, Pretty<'a>)> {
+        vec![("names", Pretty::display(&self.names.join(", ")))]
+    }
+}
+
+impl Binder {
+    /// Binds `CREATE ROLE` and `CREATE USER`.
+    ///
+    /// Roles can not log in unless `LOGIN` is specified. The parser sets `LOGIN` for users.
+    pub(super) fn bind_create_user(
+        &mut self,
+        names: Vec<ObjectName>,
+        if_not_exists: bool,
+        login: Option<bool>,
+        superuser: Option<bool>,
+        password: Option<Password>,
+    ) -> Result {
+        self.check_superuser("create roles")?;
+        let [name] = names.as_slice() else {
+            return Err(ErrorKind::Todo("create multiple users".into()).into());
+        };
+        let name = user_name(name)?;
+        let password = match password {
+            Some(password) => Some(bind_password(&name, password)?),
+            None => None,
+        };
+        let options = UserOptions {
+            superuser,
+            login: Some(login.unwrap_or(false)),
+            password,
+        };
+        let id = self.egraph.add(Node::CreateUser(CreateUser {
+            name,
+            if_not_exists,
+            options,
+        }));
+        Ok(id)
+    }
+
+    /// Binds `ALTER ROLE` and `ALTER USER`.
+    ///
+    /// Users can change their own password. Other options require a superuser.
+    pub(super) fn bind_alter_user(&mut self, name: Ident, operation: AlterRoleOperation) -> Result {
+        let AlterRoleOperation::WithOptions {
+            options: role_options,
+        } = operation
+        else {
+            return Err(ErrorKind::Todo(format!("alter user {operation}")).into());
+        };
+        let name = name.value.to_lowercase();
+        let mut options = UserOptions::default();
+        for option in role_options {
+            match option {
+                RoleOption::SuperUser(v) => options.superuser = Some(v),
+                RoleOption::Login(v) => options.login = Some(v),
+                RoleOption::Password(password) => {
+                    options.password = Some(bind_password(&name, password)?)
+                }
+                option => return Err(ErrorKind::Todo(format!("user option {option}")).into()),
+            }
+        }
+        let own_password = UserOptions {
+            password: options.password.clone(),
+            ..Default::default()
+        };
+        if self.user.as_ref() != Some(&name) || options != own_password {
+            self.check_superuser("alter roles")?;
+        }
+        let id = self
+            .egraph
+            .add(Node::AlterUser(AlterUser { name, options }));
+        Ok(id)
+    }
+
+    /// Binds `DROP ROLE` and `DROP USER`.
+    pub(super) fn bind_drop_user(&mut self, names: Vec<ObjectName>, if_exists: bool) -> Result {
+        self.check_superuser("drop roles")?;
+        let names = names.iter().map(user_name).try_collect()?;
+        let id = self
+            .egraph
+            .add(Node::DropUser(DropUser { names, if_exists }));
+        Ok(id)
+    }
+}
+
+fn user_name(name: &ObjectName) -> Result<String> {
+    match name.0.as_slice() {
+        [ident] => Ok(ident.value.to_lowercase()),
+        _ => Err(ErrorKind::InvalidExpression(format!("invalid user name {name}")).into()),
+    }
+}
+
+/// Hashes the password. `PASSWORD NULL` removes the password.
+fn bind_password(user: &str, password: Password) -> Result<Option<PasswordVerifier>> {
+    match password {
+        Password::NullPassword => Ok(None),
+        Password::Password(Expr::Value(Value::SingleQuotedString(s))) => {
+            Ok(Some(PasswordVerifier::new(user, &s)))
+        }
+        Password::Password(expr) => Err(ErrorKind::InvalidExpression(
+            "password must be a string".into(),
+        )
+        .with_spanned(&expr)),
+    }
+}
@@ -7,22 +7,29 @@ use serde::{Deserialize, Serialize};
 
 pub use self::column::*;
 pub use self::index::*;
+pub use self::partition::*;
+pub use self::privilege::*;
 pub use self::root::*;
 pub use self::schema::*;
 pub use self::table::*;
+pub use self::user::*;
 use crate::types::*;
 
 mod column;
 pub mod function;
 mod index;
+mod partition;
+mod privilege;
 mod root;
 mod schema;
 mod table;
+mod user;
 
 pub type SchemaId = u32;
 pub type TableId = u32;
 pub type IndexId = u32;
 pub type ColumnId = u32;
+pub type UserId = u32;
 
 pub type RootCatalogRef = Arc<RootCatalog>;
 
@@ -179,4 +186,8 @@ pub enum CatalogError {
     NotFound(&'static str, String),
     #[error("duplicated {0}: {1}")]
     Duplicated(&'static str, String),
+    #[error("{0} is required by the database system: {1}")]
+    Required(&'static str, String),
+    #[error("role \"{0}\" is a member of role \"{1}\"")]
+    CircularMembership(String, String),
 }
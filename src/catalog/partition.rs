@@ -0,0 +1,139 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::ops::{Bound, RangeBounds};
+
+use super::*;
+
+/// The range partitioning of a table, defined by `PARTITION BY RANGE (column)`.
+///
+/// Each partition holds the rows whose partition column is less than its upper bound and not
+/// less than the bound of the previous partition. After a partition is dropped, its range is
+/// taken over by the next partition.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PartitionSpec {
+    /// The partition column.
+    pub column: ColumnId,
+    /// Partitions ordered by their upper bounds.
+    pub partitions: Vec<Partition>,
+}
+
+/// A partition of a table.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Partition {
+    /// The id of the partition, which is never reused in the table.
+    pub id: u32,
+    pub name: String,
+    /// The exclusive upper bound, or `None` for `MAXVALUE`.
+    pub upper_bound: Option<DataValue>,
+}
+
+impl std::fmt::Display for PartitionSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "range(#{}", self.column)?;
+        for p in &self.partitions {
+            match &p.upper_bound {
+                Some(bound) => write!(f, ", {} < {bound}", p.name)?,
+                None => write!(f, ", {} < MAXVALUE", p.name)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+impl PartitionSpec {
+    pub fn get_partition_by_name(&self, name: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.partitions.iter().any(|p| p.id == id)
+    }
+
+    /// Returns the partition of a row, or `None` if no partition holds the value.
+    pub fn partition_of(&self, value: &DataValue) -> Option<u32> {
+        if value.is_null() {
+            return None;
+        }
+        (self.partitions.iter())
+            .find(|p| p.upper_bound.as_ref().is_none_or(|bound| value < bound))
+            .map(|p| p.id)
+    }
+
+    /// Returns the partitions which may hold values in the range.
+    pub fn prune(&self, range: &impl RangeBounds<DataValue>) -> Vec<u32> {
+        let mut lower_bound = None;
+        let mut partitions = vec![];
+        for p in &self.partitions {
+            // the partition holds values in `[lower_bound, upper_bound)`
+            let after_start = match (range.start_bound(), &p.upper_bound) {
+                (_, None) | (Bound::Unbounded, _) => true,
+                (Bound::Included(k) | Bound::Excluded(k), Some(upper)) => k < upper,
+            };
+            let before_end = match (range.end_bound(), lower_bound) {
+                (_, None) | (Bound::Unbounded, _) => true,
+                (Bound::Included(k), Some(lower)) => k >= lower,
+                (Bound::Excluded(k), Some(lower)) => k > lower,
+            };
+            if after_start && before_end {
+                partitions.push(p.id);
+            }
+            lower_bound = p.upper_bound.as_ref();
+        }
+        partitions
+    }
+
+    /// Removes the partitions, whose ranges are taken over by the next partitions.
+    pub fn drop_partitions(&mut self, ids: &[u32]) {
+        self.partitions.retain(|p| !ids.contains(&p.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> PartitionSpec {
+        let partition = |id, upper_bound: Option<i32>| Partition {
+            id,
+            name: format!("p{id}"),
+            upper_bound: upper_bound.map(DataValue::Int32),
+        };
+        PartitionSpec {
+            column: 0,
+            partitions: vec![
+                partition(0, Some(10)),
+                partition(1, Some(20)),
+                partition(2, None),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_partition_of() {
+        let mut spec = spec();
+        assert_eq!(spec.partition_of(&DataValue::Int32(9)), Some(0));
+        assert_eq!(spec.partition_of(&DataValue::Int32(10)), Some(1));
+        assert_eq!(spec.partition_of(&DataValue::Int32(100)), Some(2));
+        assert_eq!(spec.partition_of(&DataValue::Null), None);
+
+        spec.drop_partitions(&[0, 2]);
+        assert_eq!(spec.partition_of(&DataValue::Int32(9)), Some(1));
+        assert_eq!(spec.partition_of(&DataValue::Int32(20)), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let spec = spec();
+        let v = DataValue::Int32;
+        assert_eq!(spec.prune(&(..)), [0, 1, 2]);
+        assert_eq!(spec.prune(&(v(10)..v(20))), [1]);
+        assert_eq!(spec.prune(&(v(10)..=v(20))), [1, 2]);
+        assert_eq!(spec.prune(&(..v(10))), [0]);
+        assert_eq!(spec.prune(&(v(5)..)), [0, 1, 2]);
+        assert_eq!(spec.prune(&(v(25)..=v(25))), [2]);
+        assert_eq!(
+            spec.prune(&(Bound::Excluded(v(9)), Bound::Excluded(v(10)))),
+            [0]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use parse_display::Display;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// A privilege that can be granted to users and roles.
+///
+/// `DROP` can only be granted with `ALL` since the parser does not accept it as a privilege.
+#[derive(
+    Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[display(style = "UPPERCASE")]
+pub enum Privilege {
+    Select,
+    Insert,
+    Delete,
+    Update,
+    /// Create tables, views, indexes and functions in a schema.
+    Create,
+    Drop,
+}
+
+impl Privilege {
+    /// Privileges that can be granted on tables.
+    pub const TABLE: &'static [Privilege] = &[
+        Privilege::Select,
+        Privilege::Insert,
+        Privilege::Delete,
+        Privilege::Update,
+        Privilege::Drop,
+    ];
+
+    /// Privileges that can be granted on schemas.
+    ///
+    /// Except `CREATE`, they apply to all tables in the schema, including those created later.
+    pub const SCHEMA: &'static [Privilege] = &[
+        Privilege::Select,
+        Privilege::Insert,
+        Privilege::Delete,
+        Privilege::Update,
+        Privilege::Create,
+        Privilege::Drop,
+    ];
+}
+
+/// An object that privileges are granted on.
+#[derive(
+    Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum PrivilegeObject {
+    #[display("schema {0}")]
+    Schema(SchemaId),
+    #[display("table {0}")]
+    Table(TableRefId),
+}
@@ -1,6 +1,6 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 
 use super::function::FunctionCatalog;
@@ -19,6 +19,8 @@ struct Inner {
     schema_idxs: HashMap<String, SchemaId>,
     schemas: HashMap<SchemaId, SchemaCatalog>,
     next_schema_id: SchemaId,
+    users: BTreeMap<String, UserCatalog>,
+    next_user_id: UserId,
 }
 
 impl Default for RootCatalog {
@@ -32,6 +34,13 @@ impl RootCatalog {
         let mut inner = Inner::default();
         inner.add_system_schema();
         inner.add_schema(Self::DEFAULT_SCHEMA_NAME.into()).unwrap();
+        let superuser = UserOptions {
+            superuser: Some(true),
+            ..Default::default()
+        };
+        inner
+            .add_user(Self::BOOTSTRAP_USER_NAME.into(), &superuser)
+            .unwrap();
         RootCatalog {
             inner: Mutex::new(inner),
         }
@@ -87,6 +96,28 @@ impl RootCatalog {
         schema.add_table(name, columns, ordered_pk_ids)
     }
 
+    /// Add a table with an id assigned before, e.g. by the storage on restart.
+    pub fn add_table_with_id(
+        &self,
+        schema_id: SchemaId,
+        table_id: TableId,
+        name: String,
+        columns: Vec<ColumnCatalog>,
+        ordered_pk_ids: Vec<ColumnId>,
+    ) -> Result<TableId, CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        let schema = inner.schemas.get_mut(&schema_id).unwrap();
+        schema.add_table_with_id(table_id, name, columns, ordered_pk_ids)
+    }
+
+    /// Make sure ids below `next_id` won't be assigned to new objects in the schema.
+    pub fn reserve_ids(&self, schema_id: SchemaId, next_id: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(schema) = inner.schemas.get_mut(&schema_id) {
+            schema.reserve_ids(next_id);
+        }
+    }
+
     pub fn add_view(
         &self,
         schema_id: SchemaId,
@@ -128,10 +159,22 @@ impl RootCatalog {
         schema.get_index_by_id(index_id)
     }
 
+    /// Set the range partitioning of a table, e.g. after creating it or dropping partitions.
+    pub fn set_partition(&self, table_ref_id: TableRefId, partition: Option<PartitionSpec>) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(schema) = inner.schemas.get_mut(&table_ref_id.schema_id) {
+            schema.set_partition(table_ref_id.table_id, partition);
+        }
+    }
+
     pub fn drop_table(&self, table_ref_id: TableRefId) {
         let mut inner = self.inner.lock().unwrap();
         let schema = inner.schemas.get_mut(&table_ref_id.schema_id).unwrap();
         schema.delete_table(table_ref_id.table_id);
+        let object = PrivilegeObject::Table(table_ref_id);
+        for user in inner.users.values_mut() {
+            user.revoke_all(&object);
+        }
     }
 
     pub fn get_table_id_by_name(&self, schema_name: &str, table_name: &str) -> Option<TableRefId> {
@@ -170,12 +213,152 @@ impl RootCatalog {
         schema.create_function(name, arg_types, arg_names, return_type, language, body);
     }
 
+    pub fn add_user(&self, name: String, options: &UserOptions) -> Result<UserId, CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.add_user(name, options)
+    }
+
+    pub fn alter_user(&self, name: &str, options: &UserOptions) -> Result<(), CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        let user = (inner.users.get_mut(name))
+            .ok_or_else(|| CatalogError::NotFound("user", name.into()))?;
+        user.alter(options);
+        Ok(())
+    }
+
+    pub fn drop_user(&self, name: &str) -> Result<(), CatalogError> {
+        if name == Self::BOOTSTRAP_USER_NAME {
+            return Err(CatalogError::Required("user", name.into()));
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.users.remove(name).is_none() {
+            return Err(CatalogError::NotFound("user", name.into()));
+        }
+        for user in inner.users.values_mut() {
+            user.roles_mut().remove(name);
+        }
+        Ok(())
+    }
+
+    /// Add or replace a user with an id assigned before, e.g. by the storage on restart.
+    pub fn put_user(&self, user: UserCatalog) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_user_id = inner.next_user_id.max(user.id() + 1);
+        inner.users.insert(user.name().into(), user);
+    }
+
+    pub fn get_user_by_name(&self, name: &str) -> Option<UserCatalog> {
+        let inner = self.inner.lock().unwrap();
+        inner.users.get(name).cloned()
+    }
+
+    /// Grants the privileges on the object to the user.
+    pub fn grant_privileges(
+        &self,
+        user: &str,
+        object: PrivilegeObject,
+        privileges: &[Privilege],
+    ) -> Result<(), CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        let user = (inner.users.get_mut(user))
+            .ok_or_else(|| CatalogError::NotFound("user", user.into()))?;
+        user.grant(object, privileges);
+        Ok(())
+    }
+
+    /// Revokes the privileges on the object from the user.
+    pub fn revoke_privileges(
+        &self,
+        user: &str,
+        object: PrivilegeObject,
+        privileges: &[Privilege],
+    ) -> Result<(), CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        let user = (inner.users.get_mut(user))
+            .ok_or_else(|| CatalogError::NotFound("user", user.into()))?;
+        user.revoke(&object, privileges);
+        Ok(())
+    }
+
+    /// Makes `member` a member of `role`, so that it inherits the privileges of the role.
+    pub fn grant_role(&self, role: &str, member: &str) -> Result<(), CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.users.contains_key(role) {
+            return Err(CatalogError::NotFound("role", role.into()));
+        }
+        if !inner.users.contains_key(member) {
+            return Err(CatalogError::NotFound("role", member.into()));
+        }
+        if inner.member_of(role).contains(member) {
+            return Err(CatalogError::CircularMembership(role.into(), member.into()));
+        }
+        let member = inner.users.get_mut(member).unwrap();
+        member.roles_mut().insert(role.into());
+        Ok(())
+    }
+
+    /// Removes `member` from `role`.
+    pub fn revoke_role(&self, role: &str, member: &str) -> Result<(), CatalogError> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.users.contains_key(role) {
+            return Err(CatalogError::NotFound("role", role.into()));
+        }
+        let member = (inner.users.get_mut(member))
+            .ok_or_else(|| CatalogError::NotFound("role", member.into()))?;
+        member.roles_mut().remove(role);
+        Ok(())
+    }
+
+    /// Returns true if the user has the privilege on the object.
+    ///
+    /// Superusers have all privileges. Otherwise the privilege must be granted to the user
+    /// or any role it is a member of, either on the object or on the schema of the table.
+    pub fn has_privilege(&self, user: &str, privilege: Privilege, object: PrivilegeObject) -> bool {
+        let inner = self.inner.lock().unwrap();
+        if inner.users.get(user).is_some_and(|u| u.is_superuser()) {
+            return true;
+        }
+        let schema = match object {
+            PrivilegeObject::Table(table) if privilege != Privilege::Create => {
+                Some(PrivilegeObject::Schema(table.schema_id))
+            }
+            _ => None,
+        };
+        inner.member_of(user).iter().any(|name| {
+            let user = &inner.users[name];
+            user.is_granted(privilege, &object)
+                || schema.is_some_and(|schema| user.is_granted(privilege, &schema))
+        })
+    }
+
+    /// Returns all users ordered by name.
+    pub fn all_users(&self) -> Vec<UserCatalog> {
+        let inner = self.inner.lock().unwrap();
+        inner.users.values().cloned().collect()
+    }
+
     pub const DEFAULT_SCHEMA_NAME: &'static str = "postgres";
+    /// The superuser created with the database.
+    pub const BOOTSTRAP_USER_NAME: &'static str = "postgres";
     pub const SYSTEM_SCHEMA_NAME: &'static str = "pg_catalog";
     pub const SYSTEM_SCHEMA_ID: TableId = 0;
 }
 
 impl Inner {
+    /// Returns the user and all roles it is a member of, directly or indirectly.
+    fn member_of(&self, user: &str) -> BTreeSet<String> {
+        let mut roles = BTreeSet::new();
+        let mut stack = vec![user.to_string()];
+        while let Some(name) = stack.pop() {
+            let Some(user) = self.users.get(&name) else {
+                continue;
+            };
+            stack.extend(user.roles().iter().cloned());
+            roles.insert(name);
+        }
+        roles
+    }
+
     fn add_schema(&mut self, name: String) -> Result<SchemaId, CatalogError> {
         if self.schema_idxs.contains_key(&name) {
             return Err(CatalogError::Duplicated("schema", name));
@@ -188,6 +371,17 @@ impl Inner {
         Ok(schema_id)
     }
 
+    fn add_user(&mut self, name: String, options: &UserOptions) -> Result<UserId, CatalogError> {
+        if self.users.contains_key(&name) {
+            return Err(CatalogError::Duplicated("user", name));
+        }
+        let user_id = self.next_user_id;
+        self.next_user_id += 1;
+        let user = UserCatalog::new(user_id, name.clone(), options);
+        self.users.insert(name, user);
+        Ok(user_id)
+    }
+
     fn add_system_schema(&mut self) {
         let schema_id = self
             .add_schema(RootCatalog::SYSTEM_SCHEMA_NAME.into())
@@ -262,6 +456,91 @@ const CREATE_SYSTEM_TABLE_SQL: &str = "
         n_row int,
         n_distinct int
     );
+    create table pg_user (
+        user_id int not null,
+        user_name string not null,
+        superuser boolean not null,
+        login boolean not null,
+        has_password boolean not null
+    );
+    create table pg_query_queue (
+        query_id bigint not null,
+        user_name string,
+        state string not null,
+        query string not null,
+        wait_ms bigint not null,
+        run_ms bigint
+    );
+    create table pg_stat_activity (
+        pid int not null,
+        usename string,
+        application_name string,
+        state string not null,
+        query string,
+        backend_start timestamp not null,
+        query_start timestamp,
+        elapsed_ms bigint
+    );
+    create table pg_query_log (
+        pid int not null,
+        user_name string,
+        query string not null,
+        query_start timestamp not null,
+        duration_ms double not null,
+        rows bigint,
+        error string
+    );
+    create table rw_rowsets (
+        schema_name string not null,
+        table_name string not null,
+        rowset_id int not null,
+        rows bigint not null,
+        deleted_rows bigint not null,
+        on_disk_size bigint not null
+    );
+    create table rw_rowset_columns (
+        schema_name string not null,
+        table_name string not null,
+        rowset_id int not null,
+        column_name string not null,
+        on_disk_size bigint not null,
+        blocks int not null,
+        encoding string
+    );
+    create table rw_delete_vectors (
+        schema_name string not null,
+        table_name string not null,
+        rowset_id int not null,
+        dv_id bigint not null,
+        deleted_rows bigint not null
+    );
+    create table rw_compactions (
+        schema_name string,
+        table_name string,
+        input_rowsets string not null,
+        output_rowset int,
+        input_size bigint not null,
+        output_size bigint not null,
+        output_rows bigint not null,
+        start_time timestamp not null,
+        duration_ms double not null,
+        policy string not null,
+        manual boolean not null,
+        input_rows bigint not null,
+        purged_dvs int not null
+    );
+    create table rw_block_cache (
+        capacity bigint not null,
+        entries bigint not null,
+        hits bigint not null,
+        misses bigint not null,
+        hit_ratio double
+    );
+    create table rw_epochs (
+        epoch bigint not null,
+        commit_time timestamp not null,
+        pinned int not null
+    );
 ";
 
 #[cfg(test)]
@@ -289,4 +568,41 @@ mod tests {
         let table_id = catalog.add_table(1, "t".into(), vec![col], vec![]).unwrap();
         assert_eq!(table_id, 0);
     }
+    #[test]
+    fn test_privileges() {
+        let catalog = RootCatalog::new();
+        let col = ColumnCatalog::new(0, ColumnDesc::new("a", DataType::Int32, false));
+        let table_id = catalog.add_table(1, "t".into(), vec![col], vec![]).unwrap();
+        let table = PrivilegeObject::Table(TableRefId::new(1, table_id));
+        let schema = PrivilegeObject::Schema(1);
+        for name in ["alice", "reader"] {
+            catalog
+                .add_user(name.into(), &UserOptions::default())
+                .unwrap();
+        }
+        assert!(catalog.has_privilege("postgres", Privilege::Drop, table));
+        assert!(!catalog.has_privilege("alice", Privilege::Select, table));
+
+        // granted through a role
+        catalog.grant_role("reader", "alice").unwrap();
+        catalog
+            .grant_privileges("reader", table, &[Privilege::Select])
+            .unwrap();
+        assert!(catalog.has_privilege("alice", Privilege::Select, table));
+        assert!(!catalog.has_privilege("alice", Privilege::Insert, table));
+        assert!(catalog.grant_role("alice", "reader").is_err());
+
+        // granted on the schema
+        catalog
+            .grant_privileges("alice", schema, &[Privilege::Insert])
+            .unwrap();
+        assert!(catalog.has_privilege("alice", Privilege::Insert, table));
+
+        catalog.revoke_role("reader", "alice").unwrap();
+        assert!(!catalog.has_privilege("alice", Privilege::Select, table));
+        catalog
+            .revoke_privileges("alice", schema, &[Privilege::Insert])
+            .unwrap();
+        assert!(!catalog.has_privilege("alice", Privilege::Insert, table));
+    }
 }
@@ -41,12 +41,25 @@ impl SchemaCatalog {
         name: String,
         columns: Vec<ColumnCatalog>,
         ordered_pk_ids: Vec<ColumnId>,
+    ) -> Result<TableId, CatalogError> {
+        self.add_table_with_id(self.next_id, name, columns, ordered_pk_ids)
+    }
+
+    /// Add a table with the given id. Ids below it won't be assigned to new objects.
+    pub(super) fn add_table_with_id(
+        &mut self,
+        table_id: TableId,
+        name: String,
+        columns: Vec<ColumnCatalog>,
+        ordered_pk_ids: Vec<ColumnId>,
     ) -> Result<TableId, CatalogError> {
         if self.table_idxs.contains_key(&name) {
             return Err(CatalogError::Duplicated("table", name));
         }
-        let table_id = self.next_id;
-        self.next_id += 1;
+        if self.tables.contains_key(&table_id) {
+            return Err(CatalogError::Duplicated("table", table_id.to_string()));
+        }
+        self.reserve_ids(table_id + 1);
         let table_catalog = Arc::new(TableCatalog::new(
             table_id,
             name.clone(),
@@ -58,6 +71,11 @@ impl SchemaCatalog {
         Ok(table_id)
     }
 
+    /// Make sure ids below `next_id` won't be assigned to new objects.
+    pub(super) fn reserve_ids(&mut self, next_id: u32) {
+        self.next_id = self.next_id.max(next_id);
+    }
+
     pub(super) fn add_index(
         &mut self,
         name: String,
@@ -104,6 +122,14 @@ impl SchemaCatalog {
         Ok(table_id)
     }
 
+    pub(super) fn set_partition(&mut self, id: TableId, partition: Option<PartitionSpec>) {
+        if let Some(table) = self.tables.get_mut(&id) {
+            let mut catalog = table.as_ref().clone();
+            catalog.set_partition(partition);
+            *table = Arc::new(catalog);
+        }
+    }
+
     pub(super) fn delete_table(&mut self, id: TableId) {
         let catalog = self.tables.remove(&id).unwrap();
         self.table_idxs.remove(catalog.name()).unwrap();
@@ -6,6 +6,7 @@ use super::*;
 use crate::planner::RecExpr;
 
 /// The catalog of a table.
+#[derive(Clone)]
 pub struct TableCatalog {
     id: TableId,
     name: String,
@@ -16,6 +17,7 @@ pub struct TableCatalog {
     kind: TableKind,
     next_column_id: ColumnId,
     primary_key: Vec<ColumnId>,
+    partition: Option<PartitionSpec>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -58,6 +60,7 @@ impl TableCatalog {
             kind,
             next_column_id: 0,
             primary_key,
+            partition: None,
         };
         table_catalog
             .add_column(ColumnCatalog::new(
@@ -128,6 +131,15 @@ impl TableCatalog {
         self.primary_key.clone()
     }
 
+    pub(super) fn set_partition(&mut self, partition: Option<PartitionSpec>) {
+        self.partition = partition;
+    }
+
+    /// Returns the range partitioning of the table.
+    pub fn partition(&self) -> Option<&PartitionSpec> {
+        self.partition.as_ref()
+    }
+
     pub fn is_view(&self) -> bool {
         matches!(self.kind, TableKind::View(_))
     }
@@ -0,0 +1,214 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use pgwire::api::auth::scram::gen_salted_password;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// The catalog of a user.
+///
+/// Like Postgres, users and roles are the same thing, except that users can log in by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserCatalog {
+    id: UserId,
+    name: String,
+    superuser: bool,
+    login: bool,
+    password: Option<PasswordVerifier>,
+    /// Roles that the user is a member of.
+    roles: BTreeSet<String>,
+    privileges: BTreeMap<PrivilegeObject, BTreeSet<Privilege>>,
+}
+
+impl UserCatalog {
+    pub fn new(id: UserId, name: String, options: &UserOptions) -> Self {
+        let mut user = UserCatalog {
+            id,
+            name,
+            superuser: false,
+            login: true,
+            password: None,
+            roles: BTreeSet::new(),
+            privileges: BTreeMap::new(),
+        };
+        user.alter(options);
+        user
+    }
+
+    /// Restores the roles and privileges of the user, e.g. by the storage on restart.
+    pub fn with_grants(
+        mut self,
+        roles: BTreeSet<String>,
+        privileges: BTreeMap<PrivilegeObject, BTreeSet<Privilege>>,
+    ) -> Self {
+        self.roles = roles;
+        self.privileges = privileges;
+        self
+    }
+
+    /// Applies the options to the user.
+    pub fn alter(&mut self, options: &UserOptions) {
+        if let Some(superuser) = options.superuser {
+            self.superuser = superuser;
+        }
+        if let Some(login) = options.login {
+            self.login = login;
+        }
+        if let Some(password) = &options.password {
+            self.password = password.clone();
+        }
+    }
+
+    pub fn id(&self) -> UserId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_superuser(&self) -> bool {
+        self.superuser
+    }
+
+    pub fn can_login(&self) -> bool {
+        self.login
+    }
+
+    /// Returns the password verifier, or `None` if the user has no password.
+    pub fn password(&self) -> Option<&PasswordVerifier> {
+        self.password.as_ref()
+    }
+
+    /// Returns all options of the user, which create the same user.
+    pub fn options(&self) -> UserOptions {
+        UserOptions {
+            superuser: Some(self.superuser),
+            login: Some(self.login),
+            password: Some(self.password.clone()),
+        }
+    }
+
+    /// Returns the roles that the user is a direct member of.
+    pub fn roles(&self) -> &BTreeSet<String> {
+        &self.roles
+    }
+
+    pub(super) fn roles_mut(&mut self) -> &mut BTreeSet<String> {
+        &mut self.roles
+    }
+
+    /// Returns the privileges directly granted to the user.
+    pub fn privileges(&self) -> &BTreeMap<PrivilegeObject, BTreeSet<Privilege>> {
+        &self.privileges
+    }
+
+    /// Returns true if the privilege on the object is directly granted to the user.
+    pub fn is_granted(&self, privilege: Privilege, object: &PrivilegeObject) -> bool {
+        self.privileges
+            .get(object)
+            .is_some_and(|set| set.contains(&privilege))
+    }
+
+    pub(super) fn grant(&mut self, object: PrivilegeObject, privileges: &[Privilege]) {
+        let set = self.privileges.entry(object).or_default();
+        set.extend(privileges);
+    }
+
+    pub(super) fn revoke(&mut self, object: &PrivilegeObject, privileges: &[Privilege]) {
+        if let Some(set) = self.privileges.get_mut(object) {
+            for privilege in privileges {
+                set.remove(privilege);
+            }
+            if set.is_empty() {
+                self.privileges.remove(object);
+            }
+        }
+    }
+
+    /// Removes all privileges on the object.
+    pub(super) fn revoke_all(&mut self, object: &PrivilegeObject) {
+        self.privileges.remove(object);
+    }
+}
+
+/// Options of `CREATE USER` and `ALTER USER`. `None` means unchanged or default.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct UserOptions {
+    pub superuser: Option<bool>,
+    pub login: Option<bool>,
+    /// `Some(None)` means `PASSWORD NULL`.
+    pub password: Option<Option<PasswordVerifier>>,
+}
+
+/// Verifiers of a password for each authentication method.
+///
+/// The password itself is never stored.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PasswordVerifier {
+    /// `md5(password || user name)` in hex, which is what MD5 authentication challenges.
+    md5: String,
+    /// The salt of SCRAM-SHA-256.
+    salt: Vec<u8>,
+    /// `SaltedPassword` of SCRAM-SHA-256 with [`PasswordVerifier::SCRAM_ITERATIONS`].
+    salted_password: Vec<u8>,
+}
+
+impl PasswordVerifier {
+    /// The iteration count of SCRAM-SHA-256.
+    pub const SCRAM_ITERATIONS: usize = 4096;
+
+    /// Creates verifiers of the user's password.
+    pub fn new(user: &str, password: &str) -> Self {
+        let salt: [u8; 16] = rand::random();
+        PasswordVerifier {
+            md5: format!("{:x}", md5::compute(format!("{password}{user}"))),
+            salted_password: gen_salted_password(password, &salt, Self::SCRAM_ITERATIONS),
+            salt: salt.to_vec(),
+        }
+    }
+
+    /// Returns true if the password is correct.
+    pub fn verify(&self, password: &str) -> bool {
+        gen_salted_password(password, &self.salt, Self::SCRAM_ITERATIONS) == self.salted_password
+    }
+
+    /// Returns the expected response of MD5 authentication with the given salt.
+    pub fn md5_response(&self, salt: &[u8]) -> String {
+        let mut bytes = self.md5.as_bytes().to_vec();
+        bytes.extend_from_slice(salt);
+        format!("md5{:x}", md5::compute(bytes))
+    }
+
+    /// Returns the salt and `SaltedPassword` for SCRAM-SHA-256 authentication.
+    pub fn scram_salted_password(&self) -> (&[u8], &[u8]) {
+        (&self.salt, &self.salted_password)
+    }
+}
+
+/// Hide the password in debug output.
+impl std::fmt::Debug for PasswordVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PasswordVerifier")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pgwire::api::auth::md5pass::hash_md5_password;
+
+    use super::*;
+
+    #[test]
+    fn verify_password() {
+        let verifier = PasswordVerifier::new("alice", "secret");
+        assert!(verifier.verify("secret"));
+        assert!(!verifier.verify("Secret"));
+
+        let salt = [1, 2, 3, 4];
+        let expected = hash_md5_password("alice", "secret", &salt);
+        assert_eq!(verifier.md5_response(&salt), expected);
+    }
+}
@@ -3,32 +3,33 @@
 use std::sync::{Arc, Mutex};
 
 use futures::TryStreamExt;
-use minitrace::collector::SpanContext;
-use minitrace::Span;
+use itertools::Itertools;
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 
+use crate::activity::ActivityMonitor;
+use crate::admission::{AdmissionConfig, AdmissionError, QueryQueue};
 use crate::array::Chunk;
-use crate::binder::bind_header;
-use crate::catalog::{RootCatalog, RootCatalogRef, TableRefId};
-use crate::parser::{parse, ParserError};
-use crate::planner::{Expr, RecExpr, Statistics};
+use crate::binder::Binder;
+use crate::catalog::{ColumnRefId, RootCatalog, RootCatalogRef, TableRefId};
+use crate::executor::QueryContext;
+use crate::parser::ParserError;
+use crate::planner::{Expr, Optimizer, RecExpr, Statistics, TypeSchemaAnalysis};
 use crate::storage::{
     InMemoryStorage, SecondaryStorage, SecondaryStorageOptions, Storage, StorageColumnRef,
-    StorageImpl, Table,
+    StorageError, StorageImpl, Table,
 };
+use crate::types::{DataType, DataValue};
 
 /// The database instance.
+///
+/// A database is shared by all [`Session`](crate::Session)s connected to it.
 pub struct Database {
     catalog: RootCatalogRef,
     storage: StorageImpl,
-    config: Mutex<Config>,
-}
-
-/// The configuration of the database.
-#[derive(Debug, Default)]
-struct Config {
-    disable_optimizer: bool,
-    mock_stat: Option<Statistics>,
+    queue: Arc<QueryQueue>,
+    activity: Arc<ActivityMonitor>,
+    /// Statistics of storage, and the version of data they are collected from.
+    statistics: Mutex<Option<((u64, u64), Statistics)>>,
 }
 
 impl Database {
@@ -38,7 +39,9 @@ impl Database {
         Database {
             catalog: storage.catalog().clone(),
             storage: StorageImpl::InMemoryStorage(Arc::new(storage)),
-            config: Default::default(),
+            queue: Default::default(),
+            activity: Default::default(),
+            statistics: Default::default(),
         }
     }
 
@@ -49,10 +52,32 @@ impl Database {
         Database {
             catalog: storage.catalog().clone(),
             storage: StorageImpl::SecondaryStorage(storage),
-            config: Default::default(),
+            queue: Default::default(),
+            activity: Default::default(),
+            statistics: Default::default(),
         }
     }
 
+    /// Returns the catalog of the database.
+    pub fn catalog(&self) -> &RootCatalogRef {
+        &self.catalog
+    }
+
+    /// Limit the number of concurrent queries from all sessions.
+    pub fn set_admission_config(&mut self, config: AdmissionConfig) {
+        self.queue = Arc::new(QueryQueue::new(config));
+    }
+
+    /// Returns the queue of queries waiting to run.
+    pub fn queue(&self) -> &Arc<QueryQueue> {
+        &self.queue
+    }
+
+    /// Returns the activity of sessions and the log of slow queries.
+    pub fn activity(&self) -> &Arc<ActivityMonitor> {
+        &self.activity
+    }
+
     pub async fn shutdown(&self) -> Result<(), Error> {
         if let StorageImpl::SecondaryStorage(storage) = &self.storage {
             storage.shutdown().await?;
@@ -60,139 +85,148 @@ impl Database {
         Ok(())
     }
 
-    /// Convert a command to SQL.
-    fn command_to_sql(&self, cmd: &str) -> Result<String, Error> {
-        let tokens = cmd.split_whitespace().collect::<Vec<_>>();
-        Ok(match tokens.as_slice() {
-            ["dt"] => "SELECT * FROM pg_catalog.pg_tables".to_string(),
-            ["di"] => "SELECT * FROM pg_catalog.pg_indexes".to_string(),
-            ["d", table] => format!(
-                "SELECT * FROM pg_catalog.pg_attribute WHERE table_name = '{table}'",
-            ),
-            ["stat"] => "SELECT * FROM pg_catalog.pg_stat".to_string(),
-            ["stat", table] => format!("SELECT * FROM pg_catalog.pg_stat WHERE table_name = '{table}'"),
-            ["stat", table, column] => format!(
-                "SELECT * FROM pg_catalog.pg_stat WHERE table_name = '{table}' AND column_name = '{column}'",
-            ),
-            _ => return Err(Error::Internal("invalid command".into())),
-        })
+    /// Returns the names and types of output columns, or an empty vector if the plan doesn't
+    /// return rows.
+    pub(crate) fn output_schema(&self, binder: &Binder, plan: &RecExpr) -> Vec<(String, DataType)> {
+        if matches!(
+            plan.as_ref().last(),
+            Some(Expr::Explain(_) | Expr::Analyze(_))
+        ) {
+            return vec![("QUERY PLAN".to_string(), DataType::String)];
+        }
+        let mut egraph = egg::EGraph::new(TypeSchemaAnalysis {
+            catalog: self.catalog.clone(),
+        });
+        let root = egraph.add_expr(plan);
+        let Ok(DataType::Struct(types)) = &egraph[root].data.type_ else {
+            return vec![];
+        };
+        let names = binder.output_names();
+        (types.iter().enumerate())
+            .map(|(i, ty)| {
+                let name = names.get(i).map_or("?column?", |s| s.as_str());
+                (name.to_string(), ty.clone())
+            })
+            .collect()
     }
 
-    /// Run SQL queries and return the outputs.
-    pub async fn run(&self, sql: &str) -> Result<Vec<Chunk>, Error> {
-        let _root = Span::root("run_sql", SpanContext::random());
-
-        let sql = if let Some(cmd) = sql.trim().strip_prefix('\\') {
-            self.command_to_sql(cmd)?
-        } else {
-            sql.to_string()
+    /// Returns an optimizer with the statistics of storage, or the mocked statistics if given.
+    pub(crate) async fn optimizer(
+        &self,
+        mock_stat: Option<Statistics>,
+    ) -> Result<Optimizer, Error> {
+        let stat = match mock_stat {
+            Some(stat) => stat,
+            None => self.get_storage_statistics().await?,
         };
-
-        let optimizer = crate::planner::Optimizer::new(
+        Ok(Optimizer::new(
             self.catalog.clone(),
-            self.get_storage_statistics().await?,
+            stat,
             crate::planner::Config {
                 enable_range_filter_scan: self.storage.support_range_filter_scan(),
                 table_is_sorted_by_primary_key: self.storage.table_is_sorted_by_primary_key(),
+                enable_statistics_agg: self.storage.support_statistics_agg(),
             },
-        );
-
-        let stmts = parse(&sql)?;
-        let mut outputs: Vec<Chunk> = vec![];
-        for stmt in stmts {
-            let mut binder = crate::binder::Binder::new(self.catalog.clone());
-            let mut plan = binder.bind(stmt.clone()).map_err(|e| e.with_sql(&sql))?;
-            if self.handle_set(&plan)? {
-                continue;
-            }
-            if !self.config.lock().unwrap().disable_optimizer {
-                plan = optimizer.optimize(plan);
-            }
-            let executor = match self.storage.clone() {
-                StorageImpl::InMemoryStorage(s) => {
-                    crate::executor::build(optimizer.clone(), s, &plan)
-                }
-                StorageImpl::SecondaryStorage(s) => {
-                    crate::executor::build(optimizer.clone(), s, &plan)
-                }
-            };
-            let output = executor.try_collect().await?;
-            let mut chunk = Chunk::new(output);
-            chunk = bind_header(chunk, &stmt);
-            outputs.push(chunk);
-        }
-        Ok(outputs)
+        ))
     }
 
+    /// Execute a plan in the context of a query and collect the outputs.
+    pub(crate) async fn execute(
+        &self,
+        optimizer: &Optimizer,
+        plan: &RecExpr,
+        context: QueryContext,
+    ) -> Result<Chunk, Error> {
+        let optimizer = optimizer.clone();
+        let executor = match self.storage.clone() {
+            StorageImpl::InMemoryStorage(s) => crate::executor::build(optimizer, s, plan, context),
+            StorageImpl::SecondaryStorage(s) => crate::executor::build(optimizer, s, plan, context),
+        };
+        let output = executor.try_collect().await?;
+        Ok(Chunk::new(output))
+    }
+
+    /// Returns the statistics of storage, which are collected again only if the data changes.
     async fn get_storage_statistics(&self) -> Result<Statistics, Error> {
-        if let Some(mock) = &self.config.lock().unwrap().mock_stat {
-            return Ok(mock.clone());
-        }
-        let mut stat = Statistics::default();
         // only secondary storage supports statistics
         let StorageImpl::SecondaryStorage(storage) = self.storage.clone() else {
-            return Ok(stat);
+            return Ok(Statistics::default());
+        };
+        let version = storage.data_version();
+        if let Some((cached_version, stat)) = &*self.statistics.lock().unwrap() {
+            if *cached_version == version {
+                return Ok(stat.clone());
+            }
+        }
+        let stat = self.collect_storage_statistics(&storage).await?;
+        *self.statistics.lock().unwrap() = Some((version, stat.clone()));
+        Ok(stat)
+    }
+
+    async fn collect_storage_statistics(
+        &self,
+        storage: &SecondaryStorage,
+    ) -> Result<Statistics, Error> {
+        let mut stat = Statistics::default();
+        let as_i64 = |value: &DataValue| match *value {
+            DataValue::Int16(v) => Some(v as i64),
+            DataValue::Int32(v) => Some(v as i64),
+            DataValue::Int64(v) => Some(v),
+            _ => None,
         };
         for schema in self.catalog.all_schemas().values() {
             // skip internal schema
             if schema.name() == RootCatalog::SYSTEM_SCHEMA_NAME {
                 continue;
             }
-            for table in schema.all_tables().values() {
-                if table.is_view() {
+            for table_catalog in schema.all_tables().values() {
+                if table_catalog.is_view() {
                     continue;
                 }
-                let table_id = TableRefId::new(schema.id(), table.id());
+                let table_id = TableRefId::new(schema.id(), table_catalog.id());
                 let table = storage.get_table(table_id)?;
                 let txn = table.read().await?;
-                let values = txn.aggreagate_block_stat(&[(
-                    BlockStatisticsType::RowCount,
-                    StorageColumnRef::Idx(0),
-                )]);
+                // row count of the table, the range of integer columns,
+                // and the number of distinct values of string columns
+                let mut stat_types =
+                    vec![(BlockStatisticsType::RowCount, StorageColumnRef::Idx(0))];
+                let mut columns = vec![];
+                for (column_id, column) in table_catalog.all_columns() {
+                    let types: &[_] = match column.data_type() {
+                        DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                            &[BlockStatisticsType::Min, BlockStatisticsType::Max]
+                        }
+                        DataType::String => &[BlockStatisticsType::DistinctValue],
+                        _ => continue,
+                    };
+                    for ty in types {
+                        stat_types.push((*ty, StorageColumnRef::Idx(column_id)));
+                    }
+                    columns.push((ColumnRefId::from_table(table_id, 0, column_id), types.len()));
+                }
+                let values = txn.aggreagate_block_stat(&stat_types);
                 stat.add_row_count(table_id, values[0].as_usize().unwrap().unwrap() as u32);
+                let mut values = values[1..].iter();
+                for (column_id, num_values) in columns {
+                    let values = values.by_ref().take(num_values).collect_vec();
+                    match values[..] {
+                        [min, max] => {
+                            if let (Some(min), Some(max)) = (as_i64(min), as_i64(max)) {
+                                stat.add_column_range(column_id, min, max);
+                            }
+                        }
+                        [DataValue::Int64(count)] => {
+                            let count = (*count).min(u32::MAX as i64) as u32;
+                            stat.add_distinct_values(column_id, count);
+                        }
+                        _ => {}
+                    }
+                }
             }
         }
         Ok(stat)
     }
 
-    /// Handle PRAGMA and SET statements.
-    fn handle_set(&self, plan: &RecExpr) -> Result<bool, Error> {
-        let root = &plan.as_ref()[plan.as_ref().len() - 1];
-        match root {
-            Expr::Pragma([name, _value]) => match plan[*name].as_const().as_str() {
-                "enable_optimizer" => {
-                    self.config.lock().unwrap().disable_optimizer = false;
-                    Ok(true)
-                }
-                "disable_optimizer" => {
-                    self.config.lock().unwrap().disable_optimizer = true;
-                    Ok(true)
-                }
-                name => Err(Error::Internal(format!("no such pragma: {name}"))),
-            },
-            Expr::Set([name, value]) => match plan[*name].as_const().as_str() {
-                // Mock the row count of a table for planner test.
-                name if name.starts_with("mock_rowcount_") => {
-                    let table_name = name.strip_prefix("mock_rowcount_").unwrap();
-                    let count = plan[*value].as_const().as_usize().unwrap().unwrap() as u32;
-                    let table_id = self
-                        .catalog
-                        .get_table_id_by_name("postgres", table_name)
-                        .ok_or_else(|| Error::Internal("table not found".into()))?;
-                    self.config
-                        .lock()
-                        .unwrap()
-                        .mock_stat
-                        .get_or_insert_with(Default::default)
-                        .add_row_count(table_id, count);
-                    Ok(true)
-                }
-                _ => Ok(false),
-            },
-            _ => Ok(false),
-        }
-    }
-
     /// Return all available pragma options.
     fn pragma_options() -> &'static [&'static str] {
         &["enable_optimizer", "disable_optimizer"]
@@ -227,10 +261,29 @@ pub enum Error {
         #[backtrace]
         crate::storage::TracedStorageError,
     ),
+    #[error("admission error: {0}")]
+    Admission(
+        #[source]
+        #[from]
+        AdmissionError,
+    ),
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+impl Error {
+    /// Returns true if the statement failed as it conflicts with a concurrent transaction, and
+    /// may succeed if retried.
+    pub fn is_write_conflict(&self) -> bool {
+        let storage_error = match self {
+            Error::Execute(e) => e.storage_error(),
+            Error::Storage(e) => Some(e),
+            _ => None,
+        };
+        storage_error.is_some_and(|e| matches!(e.inner(), StorageError::WriteConflict))
+    }
+}
+
 impl rustyline::Helper for &Database {}
 impl rustyline::validate::Validator for &Database {}
 impl rustyline::highlight::Highlighter for &Database {}
@@ -302,6 +355,30 @@ mod tests {
         assert_complete(&db, "pragma en", "pragma enable_optimizer");
     }
 
+    #[tokio::test]
+    async fn test_storage_statistics() {
+        let options = SecondaryStorageOptions::default_for_test();
+        let db = Arc::new(Database::new_on_disk(options).await);
+        let session = crate::Session::new(db.clone());
+        let schema = RootCatalog::DEFAULT_SCHEMA_NAME;
+
+        // rows appended to the memtable are counted without a new version
+        session.run("create table t (a int)").await.unwrap();
+        let t = db.catalog.get_table_id_by_name(schema, "t").unwrap();
+        for (sql, rows) in [
+            ("insert into t values (1), (2)", 2),
+            ("insert into t values (3)", 3),
+        ] {
+            session.run(sql).await.unwrap();
+            let stat = db.get_storage_statistics().await.unwrap();
+            assert_eq!(stat.get_row_count(t), Some(rows), "{sql}");
+        }
+        session.run("create table u (a int)").await.unwrap();
+        let u = db.catalog.get_table_id_by_name(schema, "u").unwrap();
+        let stat = db.get_storage_statistics().await.unwrap();
+        assert_eq!(stat.get_row_count(u), Some(0));
+    }
+
     /// Assert that if complete (e.g. press tab) the given `line`, the result will be
     /// `completed_line`.
     ///
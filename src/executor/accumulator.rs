@@ -0,0 +1,357 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Typed accumulators for batch-at-a-time aggregation.
+
+use std::ops::Add;
+
+use rust_decimal::Decimal;
+
+use super::*;
+use crate::array::ops::selected_rows;
+use crate::array::{
+    Array, ArrayBuilderImpl, ArrayImpl, ArrayImplValidExt, I32Array, PrimitiveArray,
+};
+use crate::types::{DataValue, Date, Interval, NativeType, Timestamp, TimestampTz, F64};
+
+/// The state of an aggregation over multiple groups.
+pub trait Accumulator: Send + Sync {
+    /// Updates the states with selected values of the array.
+    ///
+    /// The `i`-th selected value belongs to the group `group_ids[i]`.
+    /// `num_groups` is the number of groups after the update.
+    fn update(
+        &mut self,
+        array: &ArrayImpl,
+        selection: Option<&[u32]>,
+        group_ids: &[u32],
+        num_groups: usize,
+    );
+
+    /// Updates the state of a single group with selected values one by one,
+    /// and returns the result after each update.
+    fn update_running(&mut self, array: &ArrayImpl, selection: Option<&[u32]>) -> ArrayImpl;
+
+    /// Returns the results of all groups.
+    fn finish(self: Box<Self>) -> ArrayImpl;
+}
+
+/// Counts rows or non-null values.
+pub struct CountAccumulator {
+    counts: Vec<i32>,
+    /// Whether null values are counted.
+    count_null: bool,
+}
+
+impl CountAccumulator {
+    pub fn new(count_null: bool) -> Self {
+        Self {
+            counts: vec![],
+            count_null,
+        }
+    }
+}
+
+impl Accumulator for CountAccumulator {
+    fn update(
+        &mut self,
+        array: &ArrayImpl,
+        selection: Option<&[u32]>,
+        group_ids: &[u32],
+        num_groups: usize,
+    ) {
+        self.counts.resize(num_groups, 0);
+        let valid = array.get_valid_bitmap();
+        for (row, gid) in selected_rows(selection, array.len()).zip(group_ids) {
+            if self.count_null || valid[row] {
+                self.counts[*gid as usize] += 1;
+            }
+        }
+    }
+
+    fn update_running(&mut self, array: &ArrayImpl, selection: Option<&[u32]>) -> ArrayImpl {
+        self.counts.resize(1, 0);
+        let valid = array.get_valid_bitmap();
+        let results: I32Array = selected_rows(selection, array.len())
+            .map(|row| {
+                if self.count_null || valid[row] {
+                    self.counts[0] += 1;
+                }
+                self.counts[0]
+            })
+            .collect();
+        results.into()
+    }
+
+    fn finish(self: Box<Self>) -> ArrayImpl {
+        ArrayImpl::new_int32(self.counts.into_iter().collect())
+    }
+}
+
+/// Folds non-null primitive values of each group with a function.
+pub struct FoldAccumulator<T: NativeType> {
+    states: Vec<Option<T>>,
+    f: fn(T, T) -> T,
+}
+
+impl<T: NativeType> FoldAccumulator<T> {
+    pub fn new(f: fn(T, T) -> T) -> Self {
+        Self { states: vec![], f }
+    }
+
+    fn fold(&mut self, gid: usize, value: T) {
+        let state = &mut self.states[gid];
+        *state = Some(match *state {
+            Some(s) => (self.f)(s, value),
+            None => value,
+        });
+    }
+}
+
+impl<T: NativeType> Accumulator for FoldAccumulator<T>
+where
+    for<'a> &'a PrimitiveArray<T>: TryFrom<&'a ArrayImpl>,
+    ArrayImpl: From<PrimitiveArray<T>>,
+{
+    fn update(
+        &mut self,
+        array: &ArrayImpl,
+        selection: Option<&[u32]>,
+        group_ids: &[u32],
+        num_groups: usize,
+    ) {
+        self.states.resize(num_groups, None);
+        if let ArrayImpl::Null(_) = array {
+            return;
+        }
+        let Ok(array) = <&PrimitiveArray<T>>::try_from(array) else {
+            panic!("type mismatch: {}", array.type_string());
+        };
+        for (row, gid) in selected_rows(selection, array.len()).zip(group_ids) {
+            if let Some(value) = array.get(row) {
+                self.fold(*gid as usize, *value);
+            }
+        }
+    }
+
+    fn update_running(&mut self, array: &ArrayImpl, selection: Option<&[u32]>) -> ArrayImpl {
+        self.states.resize(1, None);
+        let results: PrimitiveArray<T> = match <&PrimitiveArray<T>>::try_from(array) {
+            Ok(array) => selected_rows(selection, array.len())
+                .map(|row| {
+                    if let Some(value) = array.get(row) {
+                        self.fold(0, *value);
+                    }
+                    self.states[0]
+                })
+                .collect(),
+            Err(_) if matches!(array, ArrayImpl::Null(_)) => {
+                let len = selected_rows(selection, array.len()).count();
+                std::iter::repeat(self.states[0]).take(len).collect()
+            }
+            Err(_) => panic!("type mismatch: {}", array.type_string()),
+        };
+        results.into()
+    }
+
+    fn finish(self: Box<Self>) -> ArrayImpl {
+        self.states
+            .into_iter()
+            .collect::<PrimitiveArray<T>>()
+            .into()
+    }
+}
+
+/// Aggregates values one by one with [`Evaluator::agg_append`].
+///
+/// This is the fallback for aggregations without a typed accumulator.
+pub struct GenericAccumulator {
+    /// The aggregation.
+    expr: RecExpr,
+    /// The result type.
+    ty: DataType,
+    states: Vec<AggState>,
+}
+
+impl GenericAccumulator {
+    pub fn new(expr: RecExpr, ty: DataType) -> Self {
+        Self {
+            expr,
+            ty,
+            states: vec![],
+        }
+    }
+
+    fn append(&mut self, gid: usize, value: DataValue) {
+        let state = std::mem::take(&mut self.states[gid]);
+        self.states[gid] = Evaluator::new(&self.expr).agg_append(state, value);
+    }
+
+    fn resize(&mut self, num_groups: usize) {
+        let evaluator = Evaluator::new(&self.expr);
+        self.states
+            .resize_with(num_groups, || evaluator.init_agg_state());
+    }
+}
+
+impl Accumulator for GenericAccumulator {
+    fn update(
+        &mut self,
+        array: &ArrayImpl,
+        selection: Option<&[u32]>,
+        group_ids: &[u32],
+        num_groups: usize,
+    ) {
+        self.resize(num_groups);
+        for (row, gid) in selected_rows(selection, array.len()).zip(group_ids) {
+            self.append(*gid as usize, array.get(row));
+        }
+    }
+
+    fn update_running(&mut self, array: &ArrayImpl, selection: Option<&[u32]>) -> ArrayImpl {
+        self.resize(1);
+        let mut builder = ArrayBuilderImpl::new(&self.ty);
+        for row in selected_rows(selection, array.len()) {
+            self.append(0, array.get(row));
+            builder.push(&self.states[0].result());
+        }
+        builder.finish()
+    }
+
+    fn finish(self: Box<Self>) -> ArrayImpl {
+        let mut builder = ArrayBuilderImpl::with_capacity(self.states.len(), &self.ty);
+        for state in self.states {
+            builder.push(&state.into_result());
+        }
+        builder.finish()
+    }
+}
+
+/// Returns a [`FoldAccumulator`] for values of the given type,
+/// or `None` if the type is not in the list.
+macro_rules! fold_accumulator {
+    ($ty:expr, $f:ident, [$($Pattern:pat => $T:ty),*]) => {
+        match $ty {
+            $($Pattern => Some(Box::new(FoldAccumulator::<$T>::new($f::<$T>)) as Box<dyn Accumulator>),)*
+            _ => None,
+        }
+    };
+}
+
+/// Returns a typed accumulator of `sum`.
+pub fn sum_accumulator(ty: &DataType) -> Option<Box<dyn Accumulator>> {
+    fold_accumulator!(ty, add, [
+        DataType::Int16 => i16,
+        DataType::Int32 => i32,
+        DataType::Int64 => i64,
+        DataType::Float64 => F64,
+        DataType::Decimal(_, _) => Decimal,
+        DataType::Interval => Interval
+    ])
+}
+
+/// Returns a typed accumulator of `min`, `max` or `first`.
+///
+/// Like `min` and `max`, `first` ignores NULLs: it returns the first non-null value of the
+/// group, the same as [`Evaluator::agg_append`].
+pub fn select_accumulator(ty: &DataType, agg: &Expr) -> Option<Box<dyn Accumulator>> {
+    macro_rules! select {
+        ($f:ident) => {
+            fold_accumulator!(ty, $f, [
+                DataType::Bool => bool,
+                DataType::Int16 => i16,
+                DataType::Int32 => i32,
+                DataType::Int64 => i64,
+                DataType::Float64 => F64,
+                DataType::Decimal(_, _) => Decimal,
+                DataType::Date => Date,
+                DataType::Timestamp => Timestamp,
+                DataType::TimestampTz => TimestampTz,
+                DataType::Interval => Interval
+            ])
+        };
+    }
+    match agg {
+        Expr::Min(_) => select!(min),
+        Expr::Max(_) => select!(max),
+        Expr::First(_) => select!(first),
+        _ => None,
+    }
+}
+
+fn add<T: Add<Output = T>>(a: T, b: T) -> T {
+    a + b
+}
+
+fn min<T: PartialOrd>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn max<T: PartialOrd>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+/// Keeps the first value. NULLs are skipped by [`FoldAccumulator`] before folding.
+fn first<T>(a: T, _: T) -> T {
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_accumulator() {
+        let mut acc = sum_accumulator(&DataType::Int32).unwrap();
+        let array = ArrayImpl::new_int32([Some(1), None, Some(2), Some(3)].into_iter().collect());
+        acc.update(&array, None, &[0, 1, 0, 1], 2);
+        acc.update(&array, Some(&[2, 3]), &[2, 2], 3);
+        assert_eq!(
+            acc.finish(),
+            ArrayImpl::new_int32([Some(3), Some(3), Some(5)].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_first_accumulator() {
+        let ty = DataType::Int32;
+        let expr: RecExpr = "(first $1.1)".parse().unwrap();
+        let first = &expr[Id::from(1)];
+        let array = ArrayImpl::new_int32([None, Some(1), Some(2), None].into_iter().collect());
+        // the first value of group 0 is NULL, and group 1 only has NULLs
+        let expected = ArrayImpl::new_int32([Some(1), None].into_iter().collect());
+
+        let mut acc = select_accumulator(&ty, first).unwrap();
+        acc.update(&array, None, &[0, 0, 0, 1], 2);
+        assert_eq!(acc.finish(), expected);
+
+        let mut acc = Box::new(GenericAccumulator::new(expr, ty));
+        acc.update(&array, None, &[0, 0, 0, 1], 2);
+        assert_eq!(acc.finish(), expected);
+    }
+
+    #[test]
+    fn test_count_accumulator() {
+        let array = ArrayImpl::new_int32([Some(1), None, Some(2)].into_iter().collect());
+        let mut acc = Box::new(CountAccumulator::new(false));
+        acc.update(&array, Some(&[1, 2]), &[1, 0], 2);
+        acc.update(&array, None, &[1, 1, 1], 2);
+        assert_eq!(
+            acc.finish(),
+            ArrayImpl::new_int32([1, 2].into_iter().collect())
+        );
+
+        let mut acc = Box::new(CountAccumulator::new(true));
+        assert_eq!(
+            acc.update_running(&array, Some(&[0, 1])),
+            ArrayImpl::new_int32([1, 2].into_iter().collect())
+        );
+    }
+}
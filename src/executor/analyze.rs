@@ -2,6 +2,7 @@
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use indicatif::HumanBytes;
 use pretty_xmlish::PrettyConfig;
 
 use super::*;
@@ -26,10 +27,14 @@ impl AnalyzeExecutor {
 
         // explain the plan
         let get_metadata = |id| {
-            vec![
+            let mut metadata = vec![
                 ("rows", self.metrics.get_rows(id).to_string()),
                 ("time", format!("{:?}", self.metrics.get_time(id))),
-            ]
+            ];
+            if let Some(memory) = self.metrics.get_memory(id) {
+                metadata.push(("memory", HumanBytes(memory).to_string()));
+            }
+            metadata
         };
         let explain_obj = Explain::of(&self.plan)
             .with_catalog(&self.catalog)
@@ -54,6 +59,8 @@ impl AnalyzeExecutor {
 pub struct Metrics {
     spans: HashMap<Id, TimeSpan>,
     rows: HashMap<Id, Counter>,
+    /// Peak memory of nodes that buffer their input.
+    memory: HashMap<Id, Counter>,
 }
 
 impl Metrics {
@@ -63,6 +70,11 @@ impl Metrics {
         self.rows.insert(id, rows);
     }
 
+    /// Register the peak memory counter for a node.
+    pub fn register_memory(&mut self, id: Id, peak: Counter) {
+        self.memory.insert(id, peak);
+    }
+
     /// Get the running time for a node.
     pub fn get_time(&self, id: Id) -> Duration {
         self.spans.get(&id).map(|span| span.busy_time()).unwrap()
@@ -72,6 +84,11 @@ impl Metrics {
     pub fn get_rows(&self, id: Id) -> u64 {
         self.rows.get(&id).map(|rows| rows.get()).unwrap()
     }
+
+    /// Get the peak memory in bytes used by a node, or `None` if it doesn't buffer its input.
+    pub fn get_memory(&self, id: Id) -> Option<u64> {
+        self.memory.get(&id).map(|peak| peak.get())
+    }
 }
 
 /// A counter.
@@ -86,6 +103,11 @@ impl Counter {
         self.count.fetch_add(value, Ordering::Relaxed);
     }
 
+    /// Sets the counter to `value` if it is greater.
+    pub fn max(&self, value: u64) {
+        self.count.fetch_max(value, Ordering::Relaxed);
+    }
+
     /// Gets the current value of the counter.
     pub fn get(&self) -> u64 {
         self.count.load(Ordering::Relaxed)
@@ -0,0 +1,26 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::*;
+use crate::storage::{Storage, TracedStorageError};
+
+/// The executor of `backup` statement.
+pub struct BackupExecutor<S: Storage> {
+    pub dir: PathBuf,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> BackupExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        let Some(storage) = self.storage.as_disk() else {
+            Err(TracedStorageError::not_supported(
+                "backup of an in-memory database",
+            ))?
+        };
+        storage.backup(&self.dir).await?;
+        yield DataChunk::single(1);
+    }
+}
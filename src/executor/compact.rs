@@ -0,0 +1,25 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::sync::Arc;
+
+use super::*;
+use crate::catalog::TableRefId;
+use crate::storage::CompactionMode;
+
+/// The executor of `vacuum` and `compact` statements.
+pub struct CompactExecutor<S: Storage> {
+    pub table_id: TableRefId,
+    pub mode: CompactionMode,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> CompactExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        // tables in memory are never compacted
+        if let Some(storage) = self.storage.as_disk() {
+            storage.compact(self.table_id, self.mode).await?;
+        }
+        yield DataChunk::single(1);
+    }
+}
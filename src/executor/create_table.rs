@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use super::*;
 use crate::binder::CreateTable;
-use crate::storage::Storage;
+use crate::storage::{Storage, TracedStorageError};
 
 /// The executor of `create table` statement.
 pub struct CreateTableExecutor<S: Storage> {
@@ -15,14 +15,31 @@ pub struct CreateTableExecutor<S: Storage> {
 impl<S: Storage> CreateTableExecutor<S> {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
     pub async fn execute(self) {
-        self.storage
-            .create_table(
-                self.table.schema_id,
-                &self.table.table_name,
-                &self.table.columns,
-                &self.table.ordered_pk_ids,
-            )
-            .await?;
+        if let Some(partition) = self.table.partition.clone() {
+            let Some(storage) = self.storage.as_disk() else {
+                Err(TracedStorageError::not_supported(
+                    "partitioned tables in an in-memory database",
+                ))?
+            };
+            storage
+                .create_partitioned_table(
+                    self.table.schema_id,
+                    &self.table.table_name,
+                    &self.table.columns,
+                    &self.table.ordered_pk_ids,
+                    partition,
+                )
+                .await?;
+        } else {
+            self.storage
+                .create_table(
+                    self.table.schema_id,
+                    &self.table.table_name,
+                    &self.table.columns,
+                    &self.table.ordered_pk_ids,
+                )
+                .await?;
+        }
 
         yield DataChunk::single(1);
     }
@@ -5,19 +5,28 @@ use std::sync::Arc;
 use super::*;
 use crate::array::DataChunk;
 use crate::catalog::TableRefId;
-use crate::storage::{RowHandler, Storage, Table, Transaction};
+use crate::storage::{KeyRange, RowHandler, Storage, Table, Transaction};
 
 /// The executor of `delete` statement.
 ///
 /// The last column of the input data chunk should be `_row_id_`.
 pub struct DeleteExecutor<S: Storage> {
     pub table_id: TableRefId,
+    /// The range of the first column if all rows in it are deleted, in which case the secondary
+    /// storage deletes the rows without scanning the input.
+    pub range: Option<KeyRange>,
     pub storage: Arc<S>,
 }
 
 impl<S: Storage> DeleteExecutor<S> {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
     pub async fn execute(self, child: BoxedExecutor) {
+        if let (Some(range), Some(storage)) = (&self.range, self.storage.as_disk()) {
+            if let Some(cnt) = storage.delete_range(self.table_id, range).await? {
+                yield DataChunk::single(cnt as i32);
+                return Ok(());
+            }
+        }
         let table = self.storage.get_table(self.table_id)?;
         let mut txn = table.update().await?;
         let mut cnt = 0;
@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use super::*;
 use crate::catalog::{RootCatalogRef, TableRefId};
-use crate::storage::Storage;
+use crate::storage::{Storage, TracedStorageError};
 
 /// The executor of `drop` statement.
 pub struct DropExecutor<S: Storage> {
@@ -26,3 +26,25 @@ impl<S: Storage> DropExecutor<S> {
         yield DataChunk::single(1);
     }
 }
+
+/// The executor of `alter table .. drop partition` statement.
+pub struct DropPartitionExecutor<S: Storage> {
+    pub table_id: TableRefId,
+    pub partitions: Vec<String>,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> DropPartitionExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        let Some(storage) = self.storage.as_disk() else {
+            Err(TracedStorageError::not_supported(
+                "partitioned tables in an in-memory database",
+            ))?
+        };
+        storage
+            .drop_partitions(self.table_id, &self.partitions)
+            .await?;
+        yield DataChunk::single(1);
+    }
+}
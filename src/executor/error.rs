@@ -40,6 +40,12 @@ enum Inner {
     NotNullable,
     #[error("abort")]
     Aborted,
+    #[error("canceling statement due to user request")]
+    Cancelled,
+    #[error("canceling statement due to statement timeout")]
+    Timeout,
+    #[error("out of memory: query exceeds query_memory_limit of {limit} bytes")]
+    OutOfMemory { limit: usize },
 }
 
 impl From<Inner> for Error {
@@ -91,4 +97,21 @@ impl Error {
     pub fn aborted() -> Self {
         Inner::Aborted.into()
     }
+    pub fn cancelled() -> Self {
+        Inner::Cancelled.into()
+    }
+    pub fn timeout() -> Self {
+        Inner::Timeout.into()
+    }
+    pub fn out_of_memory(limit: usize) -> Self {
+        Inner::OutOfMemory { limit }.into()
+    }
+
+    /// Returns the storage error causing the execution error.
+    pub fn storage_error(&self) -> Option<&TracedStorageError> {
+        match self.inner.as_ref() {
+            Inner::Storage(e) => Some(e),
+            _ => None,
+        }
+    }
 }
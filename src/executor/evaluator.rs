@@ -7,9 +7,11 @@ use std::fmt;
 
 use egg::{Id, Language};
 
+use super::accumulator::*;
+use crate::array::ops::selected_rows;
 use crate::array::*;
 use crate::planner::{Expr, RecExpr};
-use crate::types::{ConvertError, DataValue};
+use crate::types::{ConvertError, DataType, DataValue};
 
 /// A wrapper over [`RecExpr`] to evaluate it on [`DataChunk`]s.
 pub struct Evaluator<'a> {
@@ -54,10 +56,15 @@ impl<'a> Evaluator<'a> {
     }
 
     /// Evaluate the given expression as an array.
+    ///
+    /// The selection of the chunk is applied on the result.
     pub fn eval(&self, chunk: &DataChunk) -> Result<ArrayImpl, ConvertError> {
         use Expr::*;
         match self.node() {
-            ColumnIndex(idx) => Ok(chunk.array_at(idx.0 as _).clone()),
+            ColumnIndex(idx) => Ok(match chunk.selection() {
+                Some(selection) => chunk.array_at(idx.0 as _).gather(selection),
+                None => chunk.array_at(idx.0 as _).clone(),
+            }),
             Constant(v) => {
                 let mut builder =
                     ArrayBuilderImpl::with_capacity(chunk.cardinality(), &v.data_type());
@@ -112,7 +119,7 @@ impl<'a> Evaluator<'a> {
             }
             Desc(a) | Ref(a) => self.next(*a).eval(chunk),
             // for aggs, evaluate its children
-            RowCount => Ok(ArrayImpl::new_null(
+            RowCount | RowNumber => Ok(ArrayImpl::new_null(
                 (0..chunk.cardinality()).map(|_| ()).collect(),
             )),
             Count(a) | Sum(a) | Min(a) | Max(a) | First(a) | Last(a) | CountDistinct(a) => {
@@ -167,6 +174,26 @@ impl<'a> Evaluator<'a> {
         }
     }
 
+    /// Evaluate the given expression as an array, and return the selection on it.
+    ///
+    /// Unlike [`eval`](Self::eval), column references are returned without applying the
+    /// selection, so that aggregations can be computed without copying the column.
+    /// For aggregations, their argument is evaluated.
+    pub fn eval_selected<'c>(
+        &self,
+        chunk: &'c DataChunk,
+    ) -> Result<(ArrayImpl, Option<&'c [u32]>), ConvertError> {
+        use Expr::*;
+        match self.node() {
+            ColumnIndex(idx) => Ok((chunk.array_at(idx.0 as _).clone(), chunk.selection())),
+            Count(a) | Sum(a) | Min(a) | Max(a) | First(a) | Last(a) | CountDistinct(a) => {
+                self.next(*a).eval_selected(chunk)
+            }
+            Over([window, _, _]) => self.next(*window).eval_selected(chunk),
+            _ => Ok((self.eval(chunk)?, None)),
+        }
+    }
+
     /// Returns the initial aggregation states.
     pub fn init_agg_states<B: FromIterator<AggState>>(&self) -> B {
         (self.node().as_list().iter())
@@ -175,7 +202,7 @@ impl<'a> Evaluator<'a> {
     }
 
     /// Returns the initial aggregation state.
-    fn init_agg_state(&self) -> AggState {
+    pub fn init_agg_state(&self) -> AggState {
         use Expr::*;
         match self.node() {
             Over([window, _, _]) => self.next(*window).init_agg_state(),
@@ -221,33 +248,91 @@ impl<'a> Evaluator<'a> {
         states.into_iter().map(|s| s.into_result())
     }
 
-    /// Get the results of a list of agg states.
-    pub fn agg_list_get_result(
+    /// Returns typed accumulators for a list of aggregations, whose result types are `types`.
+    pub fn init_accumulators(&self, types: &[DataType]) -> Vec<Box<dyn Accumulator>> {
+        (self.node().as_list().iter())
+            .zip(types)
+            .map(|(id, ty)| self.next(*id).init_accumulator(ty))
+            .collect()
+    }
+
+    /// Returns a typed accumulator for the aggregation.
+    fn init_accumulator(&self, ty: &DataType) -> Box<dyn Accumulator> {
+        use Expr::*;
+        let acc = match self.node() {
+            Over([window, _, _]) => return self.next(*window).init_accumulator(ty),
+            RowCount | RowNumber => Some(Box::new(CountAccumulator::new(true)) as _),
+            Count(_) => Some(Box::new(CountAccumulator::new(false)) as _),
+            Sum(_) => sum_accumulator(ty),
+            agg => select_accumulator(ty, agg),
+        };
+        acc.unwrap_or_else(|| {
+            let expr = self.node().build_recexpr(|id| self.expr[id].clone());
+            Box::new(GenericAccumulator::new(expr, ty.clone()))
+        })
+    }
+
+    /// Update accumulators of a list of aggregations with a chunk.
+    ///
+    /// The `i`-th row of the chunk belongs to the group `group_ids[i]`.
+    pub fn update_accumulators(
+        &self,
+        accs: &mut [Box<dyn Accumulator>],
+        chunk: &DataChunk,
+        group_ids: &[u32],
+        num_groups: usize,
+    ) -> Result<(), ConvertError> {
+        for (acc, id) in accs.iter_mut().zip(self.node().as_list()) {
+            let (array, selection) = self.next(*id).eval_selected(chunk)?;
+            acc.update(&array, selection, group_ids, num_groups);
+        }
+        Ok(())
+    }
+
+    /// Update accumulators of a list of aggregations with a chunk row by row,
+    /// and return the results after each row.
+    pub fn update_accumulators_running(
         &self,
-        states: impl IntoIterator<Item = &'a AggState> + 'a,
-    ) -> impl Iterator<Item = DataValue> + 'a {
-        states.into_iter().map(|s| s.result())
+        accs: &mut [Box<dyn Accumulator>],
+        chunk: &DataChunk,
+    ) -> Result<DataChunk, ConvertError> {
+        let mut arrays = vec![];
+        for (acc, id) in accs.iter_mut().zip(self.node().as_list()) {
+            let (array, selection) = self.next(*id).eval_selected(chunk)?;
+            arrays.push(acc.update_running(&array, selection));
+        }
+        if arrays.is_empty() {
+            return Ok(DataChunk::no_column(chunk.cardinality()));
+        }
+        Ok(arrays.into_iter().collect())
     }
 
     /// Evaluate the aggregation.
     fn eval_agg(&self, state: AggState, chunk: &DataChunk) -> Result<AggState, ConvertError> {
         use Expr::*;
         Ok(match state {
-            AggState::Value(state) => AggState::Value(match self.node() {
-                RowCount => state.add(DataValue::Int32(chunk.cardinality() as _)),
-                Count(a) => state.add(DataValue::Int32(self.next(*a).eval(chunk)?.count() as _)),
-                Sum(a) => state.add(self.next(*a).eval(chunk)?.sum()),
-                Min(a) => state.min(self.next(*a).eval(chunk)?.min_()),
-                Max(a) => state.max(self.next(*a).eval(chunk)?.max_()),
-                First(a) => state.or(self.next(*a).eval(chunk)?.first()),
-                Last(a) => self.next(*a).eval(chunk)?.last().or(state),
-                t => panic!("not aggregation: {t}"),
-            }),
+            AggState::Value(state) => {
+                if let RowCount = self.node() {
+                    return Ok(AggState::Value(
+                        state.add(DataValue::Int32(chunk.cardinality() as _)),
+                    ));
+                }
+                let (array, sel) = self.eval_selected(chunk)?;
+                AggState::Value(match self.node() {
+                    Count(_) => state.add(DataValue::Int32(array.count(sel) as _)),
+                    Sum(_) => state.add(array.sum(sel)),
+                    Min(_) => state.min(array.min_(sel)),
+                    Max(_) => state.max(array.max_(sel)),
+                    First(_) => state.or(array.first(sel)),
+                    Last(_) => array.last(sel).or(state),
+                    t => panic!("not aggregation: {t}"),
+                })
+            }
             AggState::DistinctValue(mut values) => match self.node() {
-                CountDistinct(a) => {
-                    let array = self.next(*a).eval(chunk)?;
-                    for value in array.iter() {
-                        values.insert(value);
+                CountDistinct(_) => {
+                    let (array, sel) = self.eval_selected(chunk)?;
+                    for i in selected_rows(sel, array.len()) {
+                        values.insert(array.get(i));
                     }
                     AggState::DistinctValue(values)
                 }
@@ -257,7 +342,7 @@ impl<'a> Evaluator<'a> {
     }
 
     /// Append a value to agg state.
-    fn agg_append(&self, state: AggState, value: DataValue) -> AggState {
+    pub fn agg_append(&self, state: AggState, value: DataValue) -> AggState {
         use Expr::*;
         if let Over([window, _, _]) = self.node() {
             return self.next(*window).agg_append(state, value);
@@ -304,14 +389,14 @@ impl Default for AggState {
 }
 
 impl AggState {
-    fn into_result(self) -> DataValue {
+    pub fn into_result(self) -> DataValue {
         match self {
             AggState::Value(v) => v,
             AggState::DistinctValue(v) => DataValue::Int32(v.len() as _),
         }
     }
 
-    fn result(&self) -> DataValue {
+    pub fn result(&self) -> DataValue {
         match self {
             AggState::Value(v) => v.clone(),
             AggState::DistinctValue(v) => DataValue::Int32(v.len() as _),
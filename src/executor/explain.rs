@@ -17,11 +17,24 @@ impl ExplainExecutor {
     pub fn execute(self) -> BoxedExecutor {
         let costs = self.optimizer.costs(&self.plan);
         let rows = self.optimizer.rows(&self.plan);
+        let runtime_filters = self.optimizer.runtime_filters(&self.plan);
+        let catalog = self.optimizer.catalog();
         let get_metadata = |id| {
-            vec![
+            let mut meta = vec![
                 ("cost", costs[usize::from(id)].to_string()),
                 ("rows", rows[usize::from(id)].to_string()),
-            ]
+            ];
+            let filtered_columns = (runtime_filters.iter())
+                .filter(|f| f.scan.last() == Some(&id))
+                .map(|f| catalog.get_column(&f.column).unwrap().into_name())
+                .collect_vec();
+            if !filtered_columns.is_empty() {
+                meta.push((
+                    "runtime_filter",
+                    format!("[{}]", filtered_columns.join(", ")),
+                ));
+            }
+            meta
         };
         let explain_obj = Explain::of(&self.plan)
             .with_catalog(self.optimizer.catalog())
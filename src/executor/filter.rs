@@ -4,6 +4,8 @@ use super::*;
 use crate::array::{ArrayImpl, DataChunk};
 
 /// The executor of a filter operation.
+///
+/// The output chunks share arrays with the input, and carry a selection vector of passed rows.
 pub struct FilterExecutor {
     pub condition: RecExpr,
 }
@@ -18,7 +20,7 @@ impl FilterExecutor {
                 ArrayImpl::Bool(a) => a,
                 _ => panic!("filters can only accept bool array"),
             };
-            yield batch.filter(vis.true_array());
+            yield batch.select(vis.true_array());
         }
     }
 }
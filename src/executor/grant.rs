@@ -0,0 +1,63 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::sync::Arc;
+
+use super::*;
+use crate::binder::{Grant, GrantRole};
+use crate::storage::Storage;
+
+/// The executor of `grant` and `revoke` statements on privileges.
+pub struct GrantExecutor<S: Storage> {
+    pub grant: Grant,
+    pub revoke: bool,
+    pub catalog: RootCatalogRef,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> GrantExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        let Grant {
+            privileges,
+            objects,
+            users,
+        } = &self.grant;
+        for user in users {
+            for object in objects {
+                if self.revoke {
+                    self.catalog.revoke_privileges(user, *object, privileges)?;
+                } else {
+                    self.catalog.grant_privileges(user, *object, privileges)?;
+                }
+            }
+        }
+        if let Some(storage) = self.storage.as_disk() {
+            storage.persist_users(users.clone()).await?;
+        }
+        yield DataChunk::single(1);
+    }
+}
+
+/// The executor of `grant` and `revoke` statements on role membership.
+pub struct GrantRoleExecutor<S: Storage> {
+    pub grant: GrantRole,
+    pub revoke: bool,
+    pub catalog: RootCatalogRef,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> GrantRoleExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        let GrantRole { role, member } = &self.grant;
+        if self.revoke {
+            self.catalog.revoke_role(role, member)?;
+        } else {
+            self.catalog.grant_role(role, member)?;
+        }
+        if let Some(storage) = self.storage.as_disk() {
+            storage.persist_users(vec![member.clone()]).await?;
+        }
+        yield DataChunk::single(1);
+    }
+}
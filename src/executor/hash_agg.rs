@@ -1,55 +1,78 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
 use ahash::{HashMap, HashMapExt};
-use iter_chunks::IterChunks;
 use smallvec::SmallVec;
 
 use super::*;
-use crate::array::DataChunkBuilder;
+use crate::array::ArrayBuilderImpl;
 use crate::types::DataValue;
 
 /// The executor of hash aggregation.
+///
+/// Each input chunk is aggregated at a time: rows are mapped to group ids,
+/// and then the typed accumulators are updated with the group ids.
 pub struct HashAggExecutor {
     pub keys: RecExpr,
     pub aggs: RecExpr,
     pub types: Vec<DataType>,
+    /// The memory of group keys.
+    pub memory: MemoryConsumer,
 }
 
 pub type GroupKeys = SmallVec<[DataValue; 4]>;
-pub type AggValue = SmallVec<[AggState; 4]>;
 
 impl HashAggExecutor {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
     pub async fn execute(self, child: BoxedExecutor) {
-        let mut states = HashMap::<GroupKeys, AggValue>::new();
+        let num_keys = self.keys.as_ref().last().unwrap().as_list().len();
+        let (key_types, agg_types) = self.types.split_at(num_keys);
+
+        // group keys -> group id
+        let mut groups = HashMap::<GroupKeys, u32>::new();
+        let mut key_builders = key_types.iter().map(ArrayBuilderImpl::new).collect_vec();
+        let mut accs = Evaluator::new(&self.aggs).init_accumulators(agg_types);
+        let mut group_ids = vec![];
+        let mut memory = self.memory;
 
         #[for_await]
         for chunk in child {
             let chunk = chunk?;
             let keys_chunk = Evaluator::new(&self.keys).eval_list(&chunk)?;
-            let args_chunk = Evaluator::new(&self.aggs).eval_list(&chunk)?;
-
-            for i in 0..chunk.cardinality() {
-                let keys = keys_chunk.row(i).values().collect();
-                let states = states
-                    .entry(keys)
-                    .or_insert_with(|| Evaluator::new(&self.aggs).init_agg_states());
-                Evaluator::new(&self.aggs).agg_list_append(states, args_chunk.row(i).values());
-            }
-        }
 
-        let mut batches = IterChunks::chunks(states.into_iter(), PROCESSING_WINDOW_SIZE);
-        while let Some(batch) = batches.next() {
-            let mut builder = DataChunkBuilder::new(&self.types, PROCESSING_WINDOW_SIZE);
-            for (key, states) in batch {
-                let agg_results = Evaluator::new(&self.aggs).agg_list_take_result(states);
-                if let Some(chunk) = builder.push_row(key.into_iter().chain(agg_results)) {
-                    yield chunk;
-                }
+            group_ids.clear();
+            let num_groups = groups.len();
+            for row in keys_chunk.rows() {
+                let next_id = groups.len() as u32;
+                let id = *groups
+                    .entry(row.values().collect())
+                    .or_insert_with_key(|keys| {
+                        for (builder, key) in key_builders.iter_mut().zip(keys) {
+                            builder.push(key);
+                        }
+                        next_id
+                    });
+                group_ids.push(id);
             }
-            if let Some(chunk) = builder.take() {
-                yield chunk;
+            // charge the keys of new groups by their average size in the chunk
+            let new_groups = groups.len() - num_groups;
+            if new_groups > 0 {
+                let key_size = keys_chunk.estimated_size() / keys_chunk.cardinality();
+                memory.alloc(new_groups * (key_size + std::mem::size_of::<(GroupKeys, u32)>()))?;
             }
+            Evaluator::new(&self.aggs).update_accumulators(
+                &mut accs,
+                &chunk,
+                &group_ids,
+                groups.len(),
+            )?;
+        }
+
+        let chunk: DataChunk = (key_builders.into_iter().map(|b| b.finish()))
+            .chain(accs.into_iter().map(|acc| acc.finish()))
+            .collect();
+        for start in (0..chunk.cardinality()).step_by(PROCESSING_WINDOW_SIZE) {
+            let end = (start + PROCESSING_WINDOW_SIZE).min(chunk.cardinality());
+            yield chunk.slice(start..end);
         }
     }
 }
@@ -16,6 +16,11 @@ pub struct HashJoinExecutor<const T: JoinType> {
     pub right_keys: RecExpr,
     pub left_types: Vec<DataType>,
     pub right_types: Vec<DataType>,
+    /// Runtime filters to publish after the build side is finished.
+    /// Each filter is built from the key at the given index.
+    pub runtime_filters: Vec<(usize, RuntimeFilterSender)>,
+    /// The memory of the hash table.
+    pub memory: MemoryConsumer,
 }
 
 /// Join types for generating join code during the compilation.
@@ -39,16 +44,19 @@ impl<const T: JoinType> HashJoinExecutor<T> {
             matched: bool,
         }
         let mut hash_map: HashMap<JoinKeys, LeftKeyInfo> = HashMap::new();
+        let mut memory = self.memory;
         #[for_await]
         for chunk in left {
             let chunk = chunk?;
             let keys_chunk = Evaluator::new(&self.left_keys).eval_list(&chunk)?;
+            memory.alloc(chunk.estimated_size() + keys_chunk.estimated_size())?;
             for (row, keys) in chunk.rows().zip(keys_chunk.rows()) {
                 let keys = keys.values().collect();
                 hash_map.entry(keys).or_default().rows.push(row.to_owned());
             }
             tokio::task::consume_budget().await;
         }
+        publish_runtime_filters(&self.runtime_filters, hash_map.keys());
 
         let data_types = self.left_types.iter().chain(self.right_types.iter());
         let mut builder = DataChunkBuilder::new(data_types, PROCESSING_WINDOW_SIZE);
@@ -103,27 +111,48 @@ impl<const T: JoinType> HashJoinExecutor<T> {
     }
 }
 
+/// Builds runtime filters from the build keys and publishes them to the probe-side scans.
+fn publish_runtime_filters<'a>(
+    filters: &[(usize, RuntimeFilterSender)],
+    keys: impl Iterator<Item = &'a JoinKeys> + Clone,
+) {
+    for (key_index, tx) in filters {
+        let mut builder = RuntimeFilterBuilder::new(keys.clone().count());
+        for key in keys.clone() {
+            builder.insert(&key[*key_index]);
+        }
+        tx.send_replace(Some(Arc::new(builder.finish())));
+    }
+}
+
 /// The executor for hash semi/anti join
 pub struct HashSemiJoinExecutor {
     pub left_keys: RecExpr,
     pub right_keys: RecExpr,
     pub anti: bool,
+    /// Runtime filters to publish after the build side is finished.
+    pub runtime_filters: Vec<(usize, RuntimeFilterSender)>,
+    /// The memory of the hash set.
+    pub memory: MemoryConsumer,
 }
 
 impl HashSemiJoinExecutor {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
     pub async fn execute(self, left: BoxedExecutor, right: BoxedExecutor) {
         let mut key_set: HashSet<JoinKeys> = HashSet::new();
+        let mut memory = self.memory;
         // build
         #[for_await]
         for chunk in right {
             let chunk = chunk?;
             let keys_chunk = Evaluator::new(&self.right_keys).eval_list(&chunk)?;
+            memory.alloc(keys_chunk.estimated_size())?;
             for row in keys_chunk.rows() {
                 key_set.insert(row.values().collect());
             }
             tokio::task::consume_budget().await;
         }
+        publish_runtime_filters(&self.runtime_filters, key_set.iter());
         // probe
         #[for_await]
         for chunk in left {
@@ -146,17 +175,21 @@ pub struct HashSemiJoinExecutor2 {
     pub left_types: Vec<DataType>,
     pub right_types: Vec<DataType>,
     pub anti: bool,
+    /// The memory of the hash table.
+    pub memory: MemoryConsumer,
 }
 
 impl HashSemiJoinExecutor2 {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
-    pub async fn execute(self, left: BoxedExecutor, right: BoxedExecutor) {
+    pub async fn execute(mut self, left: BoxedExecutor, right: BoxedExecutor) {
         let mut key_set: HashMap<JoinKeys, DataChunkBuilder> = HashMap::new();
         // build
         #[for_await]
         for chunk in right {
             let chunk = chunk?;
             let keys_chunk = Evaluator::new(&self.right_keys).eval_list(&chunk)?;
+            self.memory
+                .alloc(chunk.estimated_size() + keys_chunk.estimated_size())?;
             for (key, row) in keys_chunk.rows().zip(chunk.rows()) {
                 let chunk = key_set
                     .entry(key.values().collect())
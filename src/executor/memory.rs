@@ -0,0 +1,101 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::*;
+
+/// Tracks the memory used by the buffers of all operators in a query.
+#[derive(Clone, Default)]
+pub struct MemoryTracker {
+    inner: Arc<TrackerInner>,
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    used: AtomicUsize,
+    /// The maximum memory in bytes, or `None` if unlimited.
+    limit: Option<usize>,
+}
+
+impl MemoryTracker {
+    /// Create a tracker with the memory limit in bytes.
+    pub fn new(limit: Option<usize>) -> Self {
+        MemoryTracker {
+            inner: Arc::new(TrackerInner {
+                used: AtomicUsize::new(0),
+                limit,
+            }),
+        }
+    }
+
+    /// Returns the memory in bytes used by the query.
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns a consumer for an operator, whose peak usage is recorded by `peak`.
+    pub fn consumer(&self, peak: Counter) -> MemoryConsumer {
+        MemoryConsumer {
+            tracker: self.clone(),
+            used: 0,
+            peak,
+        }
+    }
+}
+
+/// The memory used by an operator, which is released when dropped.
+pub struct MemoryConsumer {
+    tracker: MemoryTracker,
+    used: usize,
+    peak: Counter,
+}
+
+impl MemoryConsumer {
+    /// Charges the memory of a buffer.
+    ///
+    /// Returns an error if the query exceeds its memory limit.
+    pub fn alloc(&mut self, bytes: usize) -> Result<()> {
+        let inner = &self.tracker.inner;
+        let used = inner.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.used += bytes;
+        self.peak.max(self.used as u64);
+        match inner.limit {
+            Some(limit) if used > limit => Err(ExecutorError::out_of_memory(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Releases the memory of a buffer.
+    pub fn free(&mut self, bytes: usize) {
+        let bytes = bytes.min(self.used);
+        self.tracker.inner.used.fetch_sub(bytes, Ordering::Relaxed);
+        self.used -= bytes;
+    }
+}
+
+impl Drop for MemoryConsumer {
+    fn drop(&mut self) {
+        self.free(self.used);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_limit() {
+        let tracker = MemoryTracker::new(Some(100));
+        let peak = Counter::default();
+        let mut c1 = tracker.consumer(peak.clone());
+        let mut c2 = tracker.consumer(Counter::default());
+        c1.alloc(60).unwrap();
+        c1.free(20);
+        c2.alloc(50).unwrap();
+        assert_eq!(tracker.used(), 90);
+        assert!(c2.alloc(20).is_err());
+        drop(c2);
+        assert_eq!(tracker.used(), 40);
+        assert_eq!(peak.get(), 60);
+    }
+}
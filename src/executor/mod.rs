@@ -17,13 +17,17 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use egg::{Id, Language};
-use futures::stream::{BoxStream, StreamExt};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
 use futures_async_stream::try_stream;
 use itertools::Itertools;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 
 // use minitrace::prelude::*;
 use self::analyze::*;
+use self::backup::*;
+use self::compact::*;
 use self::copy_from_file::*;
 use self::copy_to_file::*;
 use self::create_function::*;
@@ -37,57 +41,75 @@ use self::error::*;
 use self::evaluator::*;
 use self::explain::*;
 use self::filter::*;
+use self::grant::*;
 use self::hash_agg::*;
 use self::hash_join::*;
 use self::insert::*;
 use self::limit::*;
+pub use self::memory::MemoryTracker;
+use self::memory::*;
 use self::merge_join::*;
 use self::nested_loop_join::*;
 use self::order::*;
-// #[allow(unused_imports)]
-// use self::perfect_hash_agg::*;
+use self::perfect_hash_agg::*;
 use self::projection::*;
+use self::runtime_filter::*;
 use self::simple_agg::*;
 use self::sort_agg::*;
+use self::statistics_agg::*;
 use self::system_table_scan::*;
 use self::table_scan::*;
 use self::top_n::TopNExecutor;
+use self::user::*;
 use self::values::*;
 use self::window::*;
+use crate::activity::ActivityMonitor;
+use crate::admission::QueryQueue;
 use crate::array::DataChunk;
-use crate::catalog::{RootCatalog, RootCatalogRef, TableRefId};
-use crate::planner::{Expr, ExprAnalysis, Optimizer, RecExpr, TypeSchemaAnalysis};
-use crate::storage::Storage;
+use crate::catalog::{ColumnRefId, RootCatalog, RootCatalogRef, TableRefId};
+use crate::planner::{
+    prune_partitions, Expr, ExprAnalysis, KeyDomain, Optimizer, RecExpr, RuntimeFilterDesc,
+    TypeSchemaAnalysis,
+};
+use crate::storage::{CompactionMode, StatisticsAgg, Storage};
 use crate::types::{ColumnIndex, DataType};
 use crate::utils::timed::{FutureExt as _, Span as TimeSpan};
 
+mod accumulator;
 mod analyze;
+mod backup;
+mod compact;
 mod copy_from_file;
 mod copy_to_file;
 mod create_function;
+mod create_index;
 mod create_table;
 mod create_view;
 mod delete;
 mod drop;
+mod error;
 mod evaluator;
 mod explain;
 mod filter;
+mod grant;
 mod hash_agg;
 mod hash_join;
 mod insert;
 mod limit;
+mod memory;
+mod merge_join;
 mod nested_loop_join;
 mod order;
-mod system_table_scan;
-// mod perfect_hash_agg;
-mod create_index;
-mod error;
-mod merge_join;
+mod perfect_hash_agg;
 mod projection;
+mod runtime_filter;
 mod simple_agg;
 mod sort_agg;
+mod statistics_agg;
+mod system_table_scan;
 mod table_scan;
 mod top_n;
+mod user;
 mod values;
 mod window;
 
@@ -102,8 +124,27 @@ const PROCESSING_WINDOW_SIZE: usize = 1024;
 /// and produces a stream to its parent.
 pub type BoxedExecutor = BoxStream<'static, Result<DataChunk>>;
 
-pub fn build(optimizer: Optimizer, storage: Arc<impl Storage>, plan: &RecExpr) -> BoxedExecutor {
-    Builder::new(optimizer, storage, plan).build()
+/// The context of a running query.
+#[derive(Clone, Default)]
+pub struct QueryContext {
+    /// All tasks of the executor stop and output a cancellation error once it is cancelled.
+    pub cancel: CancellationToken,
+    /// The tracker of memory used by operators.
+    pub memory: MemoryTracker,
+    /// The queue of queries in the database.
+    pub queue: Arc<QueryQueue>,
+    /// The activity of sessions in the database.
+    pub activity: Arc<ActivityMonitor>,
+}
+
+/// Builds the executor of a plan.
+pub fn build(
+    optimizer: Optimizer,
+    storage: Arc<impl Storage>,
+    plan: &RecExpr,
+    context: QueryContext,
+) -> BoxedExecutor {
+    Builder::new(optimizer, storage, plan, context).build()
 }
 
 /// The builder of executor.
@@ -115,16 +156,28 @@ struct Builder<S: Storage> {
     /// For scans on views, we prebuild their executors and store them here.
     /// Multiple scans on the same view will share the same executor.
     views: HashMap<TableRefId, StreamSubscriber>,
+    /// Filter conditions pushed down to scans.
+    scan_predicates: HashMap<Id, Arc<ScanPredicateExpr>>,
+    /// Scans that are started by their parent on demand.
+    deferred_scans: HashMap<Id, oneshot::Receiver<()>>,
+    /// Runtime filters that can be pushed from hash joins to scans.
+    runtime_filters: Vec<RuntimeFilterDesc>,
+    /// Runtime filters whose join has been built but scan has not.
+    pending_runtime_filters: Vec<(RuntimeFilterDesc, RuntimeFilterReceiver)>,
+    /// The plan nodes from the root to the node being built.
+    path: Vec<Id>,
     metrics: Metrics,
+    context: QueryContext,
 }
 
 impl<S: Storage> Builder<S> {
     /// Create a new executor builder.
-    fn new(optimizer: Optimizer, storage: Arc<S>, plan: &RecExpr) -> Self {
+    fn new(optimizer: Optimizer, storage: Arc<S>, plan: &RecExpr, context: QueryContext) -> Self {
         let mut egraph = egg::EGraph::new(TypeSchemaAnalysis {
             catalog: optimizer.catalog().clone(),
         });
         let root = egraph.add_expr(plan);
+        let runtime_filters = optimizer.runtime_filters(plan);
 
         // recursively build for all views
         let mut views = HashMap::new();
@@ -132,7 +185,7 @@ impl<S: Storage> Builder<S> {
             if let Expr::Table(tid) = node
                 && let Some(query) = optimizer.catalog().get_table(tid).unwrap().query()
             {
-                let builder = Self::new(optimizer.clone(), storage.clone(), query);
+                let builder = Self::new(optimizer.clone(), storage.clone(), query, context.clone());
                 let subscriber = builder.build_subscriber();
                 views.insert(*tid, subscriber);
             }
@@ -144,7 +197,13 @@ impl<S: Storage> Builder<S> {
             egraph,
             root,
             views,
+            scan_predicates: HashMap::new(),
+            deferred_scans: HashMap::new(),
+            runtime_filters,
+            pending_runtime_filters: vec![],
+            path: vec![],
             metrics: Metrics::default(),
+            context,
         }
     }
 
@@ -165,6 +224,19 @@ impl<S: Storage> Builder<S> {
         ty.as_struct()
     }
 
+    /// Returns the domain of a group key from statistics, like the planner does for the column in
+    /// its e-class. Returns `None` if the key is not a column with a known domain.
+    fn key_domain(&self, key: Id) -> Option<KeyDomain> {
+        match self.node(key) {
+            Expr::Column(column) => {
+                let ty = self.optimizer.catalog().get_column(column)?.data_type();
+                self.optimizer.statistics().get_key_domain(*column, &ty)
+            }
+            Expr::Ref(expr) => self.key_domain(*expr),
+            _ => None,
+        }
+    }
+
     /// Resolve the column index of `expr` in `plan`.
     fn resolve_column_index(&self, expr: Id, plan: Id) -> RecExpr {
         let schema = &self.egraph[plan].data.schema;
@@ -192,6 +264,51 @@ impl<S: Storage> Builder<S> {
         })
     }
 
+    /// Returns true if the plan is a scan on a base table.
+    fn is_table_scan(&self, id: Id) -> bool {
+        let Expr::Scan([table, ..]) = self.node(id) else {
+            return false;
+        };
+        let table_id = self.node(*table).as_table();
+        !self.views.contains_key(&table_id) && table_id.schema_id != RootCatalog::SYSTEM_SCHEMA_ID
+    }
+
+    /// Returns the range condition of a scan filter.
+    fn range_filter(&self, filter: Id) -> Option<(ColumnRefId, crate::storage::KeyRange)> {
+        let mut egraph = egg::EGraph::new(ExprAnalysis::default());
+        let root = egraph.add_expr(&self.recexpr(filter));
+        egraph[root].data.range.clone()
+    }
+
+    /// Returns the key range of the rows to delete, if the child of a deletion is a scan on all
+    /// rows, or on a range of the first column, so that the rows don't have to be located.
+    fn delete_range(&self, child: Id) -> Option<crate::storage::KeyRange> {
+        use std::ops::Bound;
+        let Expr::Scan([table, _, filter]) = self.node(child) else {
+            return None;
+        };
+        if !self.is_table_scan(child) {
+            return None;
+        }
+        if self.node(*filter) == &Expr::true_() {
+            return Some(crate::storage::KeyRange {
+                start: Bound::Unbounded,
+                end: Bound::Unbounded,
+            });
+        }
+        let (column, range) = self.range_filter(*filter)?;
+        let table = self.catalog().get_table(&self.node(*table).as_table())?;
+        let first_column = table.all_columns().into_keys().next();
+        (first_column == Some(column.column_id)).then_some(range)
+    }
+
+    /// Returns a memory consumer for the node, whose peak usage is shown in `EXPLAIN ANALYZE`.
+    fn memory(&mut self, id: Id) -> MemoryConsumer {
+        let peak = Counter::default();
+        self.metrics.register_memory(id, peak.clone());
+        self.context.memory.consumer(peak)
+    }
+
     /// Returns the catalog.
     fn catalog(&self) -> &RootCatalogRef {
         self.optimizer.catalog()
@@ -208,26 +325,41 @@ impl<S: Storage> Builder<S> {
     }
 
     /// Builds the executor for the given id.
+    ///
+    /// The selection of output chunks is applied, so the parent can access arrays directly.
     fn build_id(&mut self, id: Id) -> BoxedExecutor {
+        self.build_id_selected(id)
+            .map_ok(DataChunk::compact)
+            .boxed()
+    }
+
+    /// Builds the executor for the given id, whose output chunks may carry a selection vector.
+    ///
+    /// This should only be used if the parent respects the selection.
+    fn build_id_selected(&mut self, id: Id) -> BoxedExecutor {
         self.build_id_subscriber(id).subscribe()
     }
 
     /// Builds the executor for the given id and returns its subscriber.
     fn build_id_subscriber(&mut self, id: Id) -> StreamSubscriber {
         use Expr::*;
+        self.path.push(id);
         let stream = match self.node(id).clone() {
             Scan([table, list, filter]) => {
                 let table_id = self.node(table).as_table();
                 let columns = (self.node(list).as_list().iter())
                     .map(|id| self.node(*id).as_column())
                     .collect_vec();
-                // analyze range filter
+                // analyze range filter, which is applied by the storage only on primary keys
+                let range = self.range_filter(filter);
+                let partitions = prune_partitions(self.catalog(), &range).map(|(p, _)| p);
                 let filter = {
                     use std::ops::Bound;
-                    let mut egraph = egg::EGraph::new(ExprAnalysis::default());
-                    let root = egraph.add_expr(&self.recexpr(filter));
-                    let expr: Option<crate::storage::KeyRange> =
-                        egraph[root].data.range.clone().map(|(_, r)| r);
+                    let expr = range
+                        .filter(|(column, _)| {
+                            (self.catalog().get_column(column)).is_some_and(|c| c.is_primary())
+                        })
+                        .map(|(_, r)| r);
                     if matches!(
                         expr,
                         Some(crate::storage::KeyRange {
@@ -264,21 +396,57 @@ impl<S: Storage> Builder<S> {
                     SystemTableScan {
                         catalog: self.catalog().clone(),
                         storage: self.storage.clone(),
+                        queue: self.context.queue.clone(),
+                        activity: self.context.activity.clone(),
                         table_id,
                         columns,
                     }
                     .execute()
                 } else {
+                    let runtime_filters = self.take_runtime_filters(&columns);
                     TableScanExecutor {
                         table_id,
                         columns,
                         filter,
+                        predicate: self.scan_predicates.remove(&id),
                         storage: self.storage.clone(),
+                        runtime_filters,
+                        start: self.deferred_scans.remove(&id),
+                        as_of: None,
+                        partitions,
                     }
                     .execute()
                 }
             }
 
+            ScanAsOf([table, list, version]) => {
+                let columns = (self.node(list).as_list().iter())
+                    .map(|id| self.node(*id).as_column())
+                    .collect_vec();
+                let as_of = match self.node(version).as_const() {
+                    crate::types::DataValue::Int64(epoch) => {
+                        crate::storage::AsOf::Epoch(epoch as u64)
+                    }
+                    crate::types::DataValue::Timestamp(ts) => crate::storage::AsOf::Timestamp(
+                        std::time::UNIX_EPOCH
+                            + std::time::Duration::from_micros(ts.unix_micros() as u64),
+                    ),
+                    v => panic!("invalid version for time travel: {v}"),
+                };
+                TableScanExecutor {
+                    table_id: self.node(table).as_table(),
+                    columns,
+                    filter: None,
+                    predicate: None,
+                    storage: self.storage.clone(),
+                    runtime_filters: vec![],
+                    start: None,
+                    as_of: Some(as_of),
+                    partitions: None,
+                }
+                .execute()
+            }
+
             Values(rows) => ValuesExecutor {
                 column_types: self.plan_types(id).to_vec(),
                 values: {
@@ -296,16 +464,25 @@ impl<S: Storage> Builder<S> {
             Proj([projs, child]) => ProjectionExecutor {
                 projs: self.resolve_column_index(projs, child),
             }
-            .execute(self.build_id(child)),
-
-            Filter([cond, child]) => FilterExecutor {
-                condition: self.resolve_column_index(cond, child),
+            .execute(self.build_id_selected(child)),
+
+            Filter([cond, child]) => {
+                let condition = self.resolve_column_index(cond, child);
+                if self.is_table_scan(child)
+                    && let Some(predicate) = ScanPredicateExpr::new(&condition)
+                {
+                    // evaluate the condition in storage for late materialization
+                    self.scan_predicates.insert(child, Arc::new(predicate));
+                    self.build_id(child)
+                } else {
+                    FilterExecutor { condition }.execute(self.build_id_selected(child))
+                }
             }
-            .execute(self.build_id(child)),
 
             Order([order_keys, child]) => OrderExecutor {
                 order_keys: self.resolve_column_index(order_keys, child),
                 types: self.plan_types(id).to_vec(),
+                memory: self.memory(id),
             }
             .execute(self.build_id(child)),
 
@@ -329,24 +506,26 @@ impl<S: Storage> Builder<S> {
                     condition: self.resolve_column_index2(on, left, right),
                     left_types: self.plan_types(left).to_vec(),
                     right_types: self.plan_types(right).to_vec(),
+                    memory: self.memory(id),
                 }
                 .execute(self.build_id(left), self.build_id(right)),
                 op @ Semi | op @ Anti => NestedLoopSemiJoinExecutor {
                     anti: matches!(op, Anti),
                     condition: self.resolve_column_index2(on, left, right),
                     left_types: self.plan_types(left).to_vec(),
+                    memory: self.memory(id),
                 }
                 .execute(self.build_id(left), self.build_id(right)),
                 t => panic!("invalid join type: {t:?}"),
             },
 
             HashJoin(args @ [op, ..]) => match self.node(op) {
-                Inner => self.build_hashjoin::<{ JoinType::Inner }>(args),
-                LeftOuter => self.build_hashjoin::<{ JoinType::LeftOuter }>(args),
-                RightOuter => self.build_hashjoin::<{ JoinType::RightOuter }>(args),
-                FullOuter => self.build_hashjoin::<{ JoinType::FullOuter }>(args),
-                Semi => self.build_hashsemijoin(args, false),
-                Anti => self.build_hashsemijoin(args, true),
+                Inner => self.build_hashjoin::<{ JoinType::Inner }>(id, args),
+                LeftOuter => self.build_hashjoin::<{ JoinType::LeftOuter }>(id, args),
+                RightOuter => self.build_hashjoin::<{ JoinType::RightOuter }>(id, args),
+                FullOuter => self.build_hashjoin::<{ JoinType::FullOuter }>(id, args),
+                Semi => self.build_hashsemijoin(id, args, false),
+                Anti => self.build_hashsemijoin(id, args, true),
                 t => panic!("invalid join type: {t:?}"),
             },
 
@@ -366,14 +545,66 @@ impl<S: Storage> Builder<S> {
                 aggs: self.resolve_column_index(aggs, child),
                 types: self.plan_types(id).to_vec(),
             }
-            .execute(self.build_id(child)),
+            .execute(self.build_id_selected(child)),
+
+            StatAgg([aggs, child]) => {
+                let Scan([table, ..]) = self.node(child).clone() else {
+                    panic!("the child of statagg must be a scan");
+                };
+                // the scan is started only if statistics are not available
+                let (start_scan, start) = oneshot::channel();
+                self.deferred_scans.insert(child, start);
+                StatisticsAggExecutor {
+                    table_id: self.node(table).as_table(),
+                    aggs: (self.node(aggs).as_list().iter())
+                        .map(|id| match self.node(*id) {
+                            RowCount | Count(_) => StatisticsAgg::RowCount,
+                            Min(c) => StatisticsAgg::Min(self.node(*c).as_column().column_id),
+                            Max(c) => StatisticsAgg::Max(self.node(*c).as_column().column_id),
+                            agg => panic!("not a statistics aggregation: {agg}"),
+                        })
+                        .collect(),
+                    storage: self.storage.clone(),
+                    fallback: SimpleAggExecutor {
+                        aggs: self.resolve_column_index(aggs, child),
+                        types: self.plan_types(id).to_vec(),
+                    },
+                    start_scan,
+                }
+                .execute(self.build_id(child))
+            }
 
             HashAgg([keys, aggs, child]) => HashAggExecutor {
                 keys: self.resolve_column_index(keys, child),
                 aggs: self.resolve_column_index(aggs, child),
                 types: self.plan_types(id).to_vec(),
+                memory: self.memory(id),
+            }
+            .execute(self.build_id_selected(child)),
+
+            PerfectHashAgg([keys, aggs, child]) => {
+                let domains = (self.node(keys).as_list().iter())
+                    .map(|key| self.key_domain(*key))
+                    .collect::<Option<Vec<_>>>();
+                // the extracted keys may not be the columns whose domains were found by the planner
+                if let Some(domains) = domains {
+                    PerfectHashAggExecutor {
+                        keys: self.resolve_column_index(keys, child),
+                        aggs: self.resolve_column_index(aggs, child),
+                        types: self.plan_types(id).to_vec(),
+                        domains,
+                    }
+                    .execute(self.build_id_selected(child))
+                } else {
+                    HashAggExecutor {
+                        keys: self.resolve_column_index(keys, child),
+                        aggs: self.resolve_column_index(aggs, child),
+                        types: self.plan_types(id).to_vec(),
+                        memory: self.memory(id),
+                    }
+                    .execute(self.build_id_selected(child))
+                }
             }
-            .execute(self.build_id(child)),
 
             SortAgg([keys, aggs, child]) => SortAggExecutor {
                 keys: self.resolve_column_index(keys, child),
@@ -386,7 +617,7 @@ impl<S: Storage> Builder<S> {
                 exprs: self.resolve_column_index(exprs, child),
                 types: self.plan_types(exprs).to_vec(),
             }
-            .execute(self.build_id(child)),
+            .execute(self.build_id_selected(child)),
 
             CreateTable(table) => CreateTableExecutor {
                 table,
@@ -422,6 +653,86 @@ impl<S: Storage> Builder<S> {
             }
             .execute(),
 
+            DropPartition([table, partitions]) => DropPartitionExecutor {
+                table_id: self.node(table).as_table(),
+                partitions: (self.node(partitions).as_list().iter())
+                    .map(|id| self.node(*id).as_const().as_str().to_string())
+                    .collect(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            Backup(dir) => BackupExecutor {
+                dir: self.node(dir).as_const().as_str().into(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            Compact([table, mode]) => CompactExecutor {
+                table_id: self.node(table).as_table(),
+                mode: match self.node(mode) {
+                    Vacuum => CompactionMode::Vacuum,
+                    VacuumFull => CompactionMode::VacuumFull,
+                    ByPolicies => CompactionMode::Compact,
+                    m => panic!("invalid compaction mode: {m:?}"),
+                },
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            CreateUser(user) => CreateUserExecutor {
+                user,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            AlterUser(user) => AlterUserExecutor {
+                user,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            DropUser(user) => DropUserExecutor {
+                user,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            Grant(grant) => GrantExecutor {
+                grant,
+                revoke: false,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            Revoke(grant) => GrantExecutor {
+                grant,
+                revoke: true,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            GrantRole(grant) => GrantRoleExecutor {
+                grant,
+                revoke: false,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
+            RevokeRole(grant) => GrantRoleExecutor {
+                grant,
+                revoke: true,
+                catalog: self.catalog().clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+
             Insert([table, cols, child]) => InsertExecutor {
                 table_id: self.node(table).as_table(),
                 column_ids: (self.node(cols).as_list().iter())
@@ -433,6 +744,7 @@ impl<S: Storage> Builder<S> {
 
             Delete([table, child]) => DeleteExecutor {
                 table_id: self.node(table).as_table(),
+                range: self.delete_range(child),
                 storage: self.storage.clone(),
             }
             .execute(self.build_id(child)),
@@ -468,30 +780,45 @@ impl<S: Storage> Builder<S> {
 
             node => panic!("not a plan: {node:?}"),
         };
+        self.path.pop();
         self.spawn(id, stream)
     }
 
-    fn build_hashjoin<const T: JoinType>(&mut self, args: [Id; 6]) -> BoxedExecutor {
+    fn build_hashjoin<const T: JoinType>(&mut self, id: Id, args: [Id; 6]) -> BoxedExecutor {
         let [_, cond, lkeys, rkeys, left, right] = args;
         assert_eq!(self.node(cond), &Expr::true_());
+        // build side must be built before registering runtime filters for the probe side
+        let left_stream = self.build_id(left);
+        let runtime_filters = self.register_runtime_filters(id);
+        let right_stream = self.build_id(right);
+        self.unregister_runtime_filters(id);
         HashJoinExecutor::<T> {
             left_keys: self.resolve_column_index(lkeys, left),
             right_keys: self.resolve_column_index(rkeys, right),
             left_types: self.plan_types(left).to_vec(),
             right_types: self.plan_types(right).to_vec(),
+            runtime_filters,
+            memory: self.memory(id),
         }
-        .execute(self.build_id(left), self.build_id(right))
+        .execute(left_stream, right_stream)
     }
 
-    fn build_hashsemijoin(&mut self, args: [Id; 6], anti: bool) -> BoxedExecutor {
+    fn build_hashsemijoin(&mut self, id: Id, args: [Id; 6], anti: bool) -> BoxedExecutor {
         let [_, cond, lkeys, rkeys, left, right] = args;
         if self.node(cond) == &Expr::true_() {
+            // build side must be built before registering runtime filters for the probe side
+            let right_stream = self.build_id(right);
+            let runtime_filters = self.register_runtime_filters(id);
+            let left_stream = self.build_id(left);
+            self.unregister_runtime_filters(id);
             HashSemiJoinExecutor {
                 left_keys: self.resolve_column_index(lkeys, left),
                 right_keys: self.resolve_column_index(rkeys, right),
                 anti,
+                runtime_filters,
+                memory: self.memory(id),
             }
-            .execute(self.build_id(left), self.build_id(right))
+            .execute(left_stream, right_stream)
         } else {
             HashSemiJoinExecutor2 {
                 left_keys: self.resolve_column_index(lkeys, left),
@@ -500,6 +827,7 @@ impl<S: Storage> Builder<S> {
                 left_types: self.plan_types(left).to_vec(),
                 right_types: self.plan_types(right).to_vec(),
                 anti,
+                memory: self.memory(id),
             }
             .execute(self.build_id(left), self.build_id(right))
         }
@@ -517,11 +845,55 @@ impl<S: Storage> Builder<S> {
         .execute(self.build_id(left), self.build_id(right))
     }
 
+    /// Creates channels for the runtime filters built by the join `id`.
+    ///
+    /// Returns the senders for the join. The receivers will be taken by scans.
+    fn register_runtime_filters(&mut self, id: Id) -> Vec<(usize, RuntimeFilterSender)> {
+        let mut senders = vec![];
+        for desc in self.runtime_filters.iter().filter(|f| f.join == id) {
+            let (tx, rx) = runtime_filter_channel();
+            senders.push((desc.key_index, tx));
+            self.pending_runtime_filters.push((desc.clone(), rx));
+        }
+        senders
+    }
+
+    /// Removes the runtime filters built by the join `id` that are not taken by any scan.
+    fn unregister_runtime_filters(&mut self, id: Id) {
+        self.pending_runtime_filters.retain(|(f, _)| f.join != id);
+    }
+
+    /// Takes the runtime filters for the scan being built.
+    ///
+    /// A filter is taken only if the scan is at the planned position under its join, since
+    /// identical scans elsewhere in the plan share the same `Id`.
+    ///
+    /// Returns the index of the filtered column in `columns` and the receiver of each filter.
+    fn take_runtime_filters(
+        &mut self,
+        columns: &[ColumnRefId],
+    ) -> Vec<(usize, RuntimeFilterReceiver)> {
+        let path = &self.path;
+        let (taken, pending) = std::mem::take(&mut self.pending_runtime_filters)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(f, _)| {
+                path.ends_with(&f.scan)
+                    && path.len() > f.scan.len()
+                    && path[path.len() - f.scan.len() - 1] == f.join
+            });
+        self.pending_runtime_filters = pending;
+        taken
+            .into_iter()
+            .filter_map(|(f, rx)| Some((columns.iter().position(|c| *c == f.column)?, rx)))
+            .collect()
+    }
+
     /// Spawn a new task to execute the given stream.
     fn spawn(&mut self, id: Id, mut stream: BoxedExecutor) -> StreamSubscriber {
         let name = self.node(id).to_string();
         let span = TimeSpan::default();
         let output_row_counter = Counter::default();
+        let cancel = self.context.cancel.clone();
 
         self.metrics
             .register(id, span.clone(), output_row_counter.clone());
@@ -531,12 +903,21 @@ impl<S: Storage> Builder<S> {
             .name(&format!("{id}.{name}"))
             .spawn(
                 async move {
-                    while let Some(item) = stream.next().await {
+                    loop {
+                        let item = tokio::select! {
+                            biased;
+                            _ = cancel.cancelled() => Err(ExecutorError::cancelled()),
+                            item = stream.next() => match item {
+                                Some(item) => item,
+                                None => return,
+                            },
+                        };
+                        let cancelled = cancel.is_cancelled();
                         if let Ok(chunk) = &item {
                             output_row_counter.inc(chunk.cardinality() as _);
                         }
-                        if tx.broadcast(item).await.is_err() {
-                            // all receivers are dropped, stop the task.
+                        if tx.broadcast(item).await.is_err() || cancelled {
+                            // all receivers are dropped or the query is cancelled, stop the task.
                             return;
                         }
                     }
@@ -2,8 +2,6 @@
 
 use std::vec::Vec;
 
-use futures::TryStreamExt;
-
 use super::*;
 use crate::array::{
     Array, ArrayBuilder, ArrayBuilderImpl, ArrayImpl, BoolArrayBuilder, DataChunk,
@@ -17,6 +15,8 @@ pub struct NestedLoopJoinExecutor {
     pub condition: RecExpr,
     pub left_types: Vec<DataType>,
     pub right_types: Vec<DataType>,
+    /// The memory of buffered left rows.
+    pub memory: MemoryConsumer,
 }
 
 impl NestedLoopJoinExecutor {
@@ -25,7 +25,14 @@ impl NestedLoopJoinExecutor {
         if !matches!(self.op, Expr::Inner | Expr::LeftOuter) {
             todo!("unsupported join type: {:?}", self.op);
         }
-        let left_chunks = left_child.try_collect::<Vec<DataChunk>>().await?;
+        let mut memory = self.memory;
+        let mut left_chunks = vec![];
+        #[for_await]
+        for chunk in left_child {
+            let chunk = chunk?;
+            memory.alloc(chunk.estimated_size())?;
+            left_chunks.push(chunk);
+        }
 
         let left_rows = || left_chunks.iter().flat_map(|chunk| chunk.rows());
 
@@ -104,12 +111,20 @@ pub struct NestedLoopSemiJoinExecutor {
     pub anti: bool,
     pub condition: RecExpr,
     pub left_types: Vec<DataType>,
+    /// The memory of buffered right rows.
+    pub memory: MemoryConsumer,
 }
 
 impl NestedLoopSemiJoinExecutor {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
-    pub async fn execute(self, left_child: BoxedExecutor, right_child: BoxedExecutor) {
-        let right_chunks = right_child.try_collect::<Vec<DataChunk>>().await?;
+    pub async fn execute(mut self, left_child: BoxedExecutor, right_child: BoxedExecutor) {
+        let mut right_chunks = vec![];
+        #[for_await]
+        for chunk in right_child {
+            let chunk = chunk?;
+            self.memory.alloc(chunk.estimated_size())?;
+            right_chunks.push(chunk);
+        }
 
         let mut builder = DataChunkBuilder::new(&self.left_types, PROCESSING_WINDOW_SIZE);
 
@@ -13,6 +13,8 @@ pub struct OrderExecutor {
     /// e.g. `(list (+ #0 #1) (desc #0))`
     pub order_keys: RecExpr,
     pub types: Vec<DataType>,
+    /// The memory of buffered rows.
+    pub memory: MemoryConsumer,
 }
 
 impl OrderExecutor {
@@ -20,16 +22,20 @@ impl OrderExecutor {
     pub async fn execute(self, child: BoxedExecutor) {
         // evaluate order keys and append the original rows
         // chunks = keys || child
+        let mut memory = self.memory;
         let mut chunks = vec![];
         #[for_await]
         for chunk in child {
             let chunk = chunk?;
             let order_key_chunk = Evaluator::new(&self.order_keys).eval_list(&chunk)?;
-            chunks.push(order_key_chunk.row_concat(chunk));
+            let chunk = order_key_chunk.row_concat(chunk);
+            memory.alloc(chunk.estimated_size())?;
+            chunks.push(chunk);
         }
 
         // sort the rows by keys
         let mut rows = gen_row_array(&chunks);
+        memory.alloc(rows.len() * std::mem::size_of::<RowRef<'_>>())?;
         let orders = Evaluator::new(&self.order_keys).orders();
         rows.sort_unstable_by(|row1, row2| cmp(row1, row2, &orders));
 
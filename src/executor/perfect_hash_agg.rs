@@ -0,0 +1,279 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use ahash::{HashMap, HashMapExt};
+
+use super::*;
+use crate::array::ArrayBuilderImpl;
+use crate::planner::KeyDomain;
+use crate::types::DataValue;
+
+/// The executor of perfect hash aggregation.
+///
+/// Each key is encoded into a small integer by its domain, and the combination of codes is used as
+/// the group id directly, so no hash table is needed.
+///
+/// Since the domains are estimated from statistics, a key may turn out to be out of its domain.
+/// In this case, the executor falls back to hash aggregation for the rest of the input.
+pub struct PerfectHashAggExecutor {
+    pub keys: RecExpr,
+    pub aggs: RecExpr,
+    pub types: Vec<DataType>,
+    /// The domain of each key.
+    pub domains: Vec<KeyDomain>,
+}
+
+impl PerfectHashAggExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self, child: BoxedExecutor) {
+        let num_keys = self.keys.as_ref().last().unwrap().as_list().len();
+        let (key_types, agg_types) = self.types.split_at(num_keys);
+
+        let mut encoders = (self.domains.iter().zip(key_types))
+            .map(|(domain, ty)| KeyEncoder::new(*domain, ty.clone()))
+            .collect_vec();
+        // the slot of keys is `sum(code[i] * strides[i])`
+        let mut strides = vec![1; num_keys];
+        for i in (0..num_keys.saturating_sub(1)).rev() {
+            strides[i] = strides[i + 1] * encoders[i + 1].num_codes();
+        }
+        let num_slots = strides[0] * encoders[0].num_codes();
+        let decode = |encoders: &[KeyEncoder], slot: usize| -> GroupKeys {
+            (encoders.iter().zip(&strides))
+                .map(|(encoder, stride)| encoder.decode(slot / stride % encoder.num_codes()))
+                .collect()
+        };
+
+        let mut occupied = vec![false; num_slots];
+        // group keys -> group id, for keys out of domains
+        // if set, slots are no longer used and all keys are looked up here
+        let mut fallback: Option<HashMap<GroupKeys, u32>> = None;
+        let mut fallback_keys = vec![];
+        let mut accs = Evaluator::new(&self.aggs).init_accumulators(agg_types);
+        let mut group_ids = vec![];
+
+        #[for_await]
+        for chunk in child {
+            let chunk = chunk?;
+            let keys_chunk = Evaluator::new(&self.keys).eval_list(&chunk)?;
+
+            group_ids.clear();
+            for row in keys_chunk.rows() {
+                if fallback.is_none() {
+                    let slot = (encoders.iter_mut().zip(row.values()).zip(&strides))
+                        .map(|((encoder, key), stride)| Some(encoder.encode(&key)? * stride))
+                        .sum::<Option<usize>>();
+                    if let Some(slot) = slot {
+                        occupied[slot] = true;
+                        group_ids.push(slot as u32);
+                        continue;
+                    }
+                    // switch to hash aggregation, and keep the ids of existing groups
+                    let groups = (0..num_slots)
+                        .filter(|slot| occupied[*slot])
+                        .map(|slot| (decode(&encoders, slot), slot as u32))
+                        .collect();
+                    fallback = Some(groups);
+                }
+                let groups = fallback.as_mut().unwrap();
+                let next_id = (num_slots + fallback_keys.len()) as u32;
+                let id = *groups
+                    .entry(row.values().collect())
+                    .or_insert_with_key(|keys| {
+                        fallback_keys.push(keys.clone());
+                        next_id
+                    });
+                group_ids.push(id);
+            }
+            Evaluator::new(&self.aggs).update_accumulators(
+                &mut accs,
+                &chunk,
+                &group_ids,
+                num_slots + fallback_keys.len(),
+            )?;
+        }
+
+        let num_groups = num_slots + fallback_keys.len();
+        if !occupied.contains(&true) && fallback_keys.is_empty() {
+            return Ok(());
+        }
+        // output occupied slots, then groups out of domains
+        let mut key_builders = key_types.iter().map(ArrayBuilderImpl::new).collect_vec();
+        let slot_keys = (0..num_slots)
+            .filter(|slot| occupied[*slot])
+            .map(|slot| decode(&encoders, slot));
+        for keys in slot_keys.chain(fallback_keys) {
+            for (builder, key) in key_builders.iter_mut().zip(&keys) {
+                builder.push(key);
+            }
+        }
+        occupied.resize(num_groups, true);
+        let chunk: DataChunk = (key_builders.into_iter().map(|b| b.finish()))
+            .chain(accs.into_iter().map(|acc| acc.finish().filter(&occupied)))
+            .collect();
+        for start in (0..chunk.cardinality()).step_by(PROCESSING_WINDOW_SIZE) {
+            let end = (start + PROCESSING_WINDOW_SIZE).min(chunk.cardinality());
+            yield chunk.slice(start..end);
+        }
+    }
+}
+
+/// Encodes values of a key into codes in `0..num_codes`. The code of null is 0.
+enum KeyEncoder {
+    Range {
+        min: i64,
+        max: i64,
+        ty: DataType,
+    },
+    Dictionary {
+        codes: HashMap<DataValue, usize>,
+        values: Vec<DataValue>,
+        capacity: usize,
+    },
+}
+
+impl KeyEncoder {
+    fn new(domain: KeyDomain, ty: DataType) -> Self {
+        match domain {
+            KeyDomain::Range(min, max) => Self::Range { min, max, ty },
+            KeyDomain::Dictionary(capacity) => Self::Dictionary {
+                codes: HashMap::new(),
+                values: vec![],
+                capacity: capacity as usize,
+            },
+        }
+    }
+
+    /// Returns the number of codes, including null.
+    fn num_codes(&self) -> usize {
+        match self {
+            Self::Range { min, max, .. } => {
+                usize::try_from(*max as i128 - *min as i128 + 2).expect("key domain is too large")
+            }
+            Self::Dictionary { capacity, .. } => capacity + 1,
+        }
+    }
+
+    /// Returns the code of the value, or `None` if it is out of the domain.
+    fn encode(&mut self, value: &DataValue) -> Option<usize> {
+        if value.is_null() {
+            return Some(0);
+        }
+        match self {
+            Self::Range { min, max, .. } => {
+                let v = match *value {
+                    DataValue::Bool(v) => v as i64,
+                    DataValue::Int16(v) => v as i64,
+                    DataValue::Int32(v) => v as i64,
+                    DataValue::Int64(v) => v,
+                    _ => return None,
+                };
+                (*min..=*max)
+                    .contains(&v)
+                    .then(|| (v as i128 - *min as i128) as usize + 1)
+            }
+            Self::Dictionary {
+                codes,
+                values,
+                capacity,
+            } => {
+                if let Some(code) = codes.get(value) {
+                    return Some(*code);
+                }
+                if values.len() == *capacity {
+                    return None;
+                }
+                values.push(value.clone());
+                codes.insert(value.clone(), values.len());
+                Some(values.len())
+            }
+        }
+    }
+
+    /// Returns the value of the code.
+    fn decode(&self, code: usize) -> DataValue {
+        if code == 0 {
+            return DataValue::Null;
+        }
+        match self {
+            Self::Range { min, ty, .. } => {
+                let v = (*min as i128 + code as i128 - 1) as i64;
+                match ty {
+                    DataType::Bool => DataValue::Bool(v != 0),
+                    DataType::Int16 => DataValue::Int16(v as i16),
+                    DataType::Int32 => DataValue::Int32(v as i32),
+                    DataType::Int64 => DataValue::Int64(v),
+                    _ => panic!("unsupported range key type: {ty}"),
+                }
+            }
+            Self::Dictionary { values, .. } => values[code - 1].clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::array::ArrayImpl;
+
+    #[tokio::test]
+    async fn fallback_to_hash() {
+        let executor = PerfectHashAggExecutor {
+            keys: "(list #0 #1)".parse().unwrap(),
+            aggs: "(list (sum #2))".parse().unwrap(),
+            types: vec![DataType::Int32, DataType::String, DataType::Int32],
+            domains: vec![KeyDomain::Range(1, 2), KeyDomain::Dictionary(1)],
+        };
+        let chunk = |k1: &[Option<i32>], k2: &[Option<&str>], v: &[i32]| -> Result<DataChunk> {
+            Ok([
+                ArrayImpl::new_int32(k1.iter().cloned().collect()),
+                ArrayImpl::new_string(k2.iter().cloned().collect()),
+                ArrayImpl::new_int32(v.iter().cloned().collect()),
+            ]
+            .into_iter()
+            .collect())
+        };
+        let child = futures::stream::iter([
+            chunk(
+                &[Some(2), None, Some(2)],
+                &[Some("a"), Some("a"), None],
+                &[1, 2, 3],
+            ),
+            // both keys are out of domains
+            chunk(
+                &[Some(3), Some(2), Some(2)],
+                &[Some("a"), Some("b"), Some("a")],
+                &[4, 5, 6],
+            ),
+        ])
+        .boxed();
+        let output: Vec<DataChunk> = executor.execute(child).try_collect().await.unwrap();
+        let expected = chunk(
+            &[None, Some(2), Some(2), Some(3), Some(2)],
+            &[Some("a"), None, Some("a"), Some("a"), Some("b")],
+            &[2, 3, 7, 4, 5],
+        )
+        .unwrap();
+        assert_eq!(output, vec![expected]);
+    }
+
+    #[test]
+    fn full_range_domain() {
+        assert_eq!(KeyDomain::Range(i64::MIN, i64::MAX).size(), None);
+        assert_eq!(
+            KeyDomain::Range(i64::MIN, i64::MAX - 1).size(),
+            Some(u64::MAX)
+        );
+
+        // the codes of values at both ends of i64 don't overflow
+        for (min, max) in [(i64::MIN, i64::MIN + 1), (i64::MAX - 1, i64::MAX)] {
+            let mut encoder = KeyEncoder::new(KeyDomain::Range(min, max), DataType::Int64);
+            assert_eq!(encoder.num_codes(), 3);
+            for (value, code) in [(min, 1), (max, 2)] {
+                assert_eq!(encoder.encode(&DataValue::Int64(value)), Some(code));
+                assert_eq!(encoder.decode(code), DataValue::Int64(value));
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use ahash::RandomState;
+use smallvec::SmallVec;
+use tokio::sync::watch;
+
+use super::*;
+use crate::array::{Array, ArrayImpl};
+use crate::for_all_variants;
+use crate::types::DataValue;
+
+/// The maximum number of keys to build a bloom filter.
+///
+/// For larger build sides, the filter only keeps the min-max range of keys.
+const MAX_BLOOM_FILTER_KEYS: usize = 1 << 22;
+
+/// The number of hash functions of the bloom filter.
+const BLOOM_FILTER_HASHES: u64 = 3;
+
+/// A filter over the build keys of a hash join.
+///
+/// It never rejects a value that was inserted, but may accept values that were not.
+pub struct RuntimeFilter {
+    /// The minimum non-null key.
+    min: Option<DataValue>,
+    /// The maximum non-null key.
+    max: Option<DataValue>,
+    /// Whether there is a null key.
+    has_null: bool,
+    /// An optional bloom filter over all keys.
+    bloom: Option<BloomFilter>,
+}
+
+/// The sending half of a runtime filter, owned by the hash join.
+///
+/// Dropping the sender without publishing a filter lets the scan pass all rows.
+pub type RuntimeFilterSender = watch::Sender<Option<Arc<RuntimeFilter>>>;
+
+/// The receiving half of a runtime filter, owned by the table scan.
+pub type RuntimeFilterReceiver = watch::Receiver<Option<Arc<RuntimeFilter>>>;
+
+/// Creates a channel to publish a runtime filter.
+pub fn runtime_filter_channel() -> (RuntimeFilterSender, RuntimeFilterReceiver) {
+    watch::channel(None)
+}
+
+impl RuntimeFilter {
+    /// Returns true if the value may be one of the build keys.
+    pub fn may_contain(&self, value: &DataValue) -> bool {
+        if value.is_null() {
+            return self.has_null;
+        }
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) if min <= value && value <= max => {}
+            _ => return false,
+        }
+        match &self.bloom {
+            Some(bloom) => bloom.may_contain(value),
+            None => true,
+        }
+    }
+
+    /// Returns a visibility bitmap of the array, where `false` means the row can be dropped.
+    pub fn check_array(&self, array: &ArrayImpl) -> Vec<bool> {
+        macro_rules! check_array {
+            ([], $( { $Abc:ident, $Type:ty, $abc:ident, $AbcArray:ty, $AbcArrayBuilder:ty, $Value:ident, $Pattern:pat } ),*) => {
+                match array {
+                    $(
+                        ArrayImpl::$Abc(a) => (a.iter())
+                            .map(|v| self.may_contain(&DataValue::from(v)))
+                            .collect(),
+                    )*
+                }
+            }
+        }
+        for_all_variants! { check_array }
+    }
+}
+
+/// The builder of [`RuntimeFilter`].
+pub struct RuntimeFilterBuilder {
+    min: Option<DataValue>,
+    max: Option<DataValue>,
+    has_null: bool,
+    bloom: Option<BloomFilter>,
+}
+
+impl RuntimeFilterBuilder {
+    /// Creates a builder for the given number of keys.
+    pub fn new(num_keys: usize) -> Self {
+        Self {
+            min: None,
+            max: None,
+            has_null: false,
+            bloom: (num_keys <= MAX_BLOOM_FILTER_KEYS).then(|| BloomFilter::new(num_keys)),
+        }
+    }
+
+    /// Inserts a build key.
+    pub fn insert(&mut self, value: &DataValue) {
+        if value.is_null() {
+            self.has_null = true;
+            return;
+        }
+        if self.min.as_ref().map_or(true, |min| value < min) {
+            self.min = Some(value.clone());
+        }
+        if self.max.as_ref().map_or(true, |max| value > max) {
+            self.max = Some(value.clone());
+        }
+        if let Some(bloom) = &mut self.bloom {
+            bloom.insert(value);
+        }
+    }
+
+    /// Finishes building the filter.
+    pub fn finish(self) -> RuntimeFilter {
+        RuntimeFilter {
+            min: self.min,
+            max: self.max,
+            has_null: self.has_null,
+            bloom: self.bloom,
+        }
+    }
+}
+
+/// A simple bloom filter over [`DataValue`]s.
+struct BloomFilter {
+    bits: Vec<u64>,
+    /// `bits.len() * 64 - 1`
+    mask: u64,
+    hasher: RandomState,
+}
+
+impl BloomFilter {
+    /// Creates a bloom filter with about 8 bits per key.
+    fn new(num_keys: usize) -> Self {
+        let num_bits = (num_keys * 8).next_power_of_two().max(64);
+        Self {
+            bits: vec![0; num_bits / 64],
+            mask: num_bits as u64 - 1,
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Returns the bit positions of the value.
+    fn positions(&self, value: &DataValue) -> impl Iterator<Item = u64> {
+        let hash = self.hasher.hash_one(value);
+        let (h1, h2) = (hash, (hash >> 32) | 1);
+        let mask = self.mask;
+        (0..BLOOM_FILTER_HASHES).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) & mask)
+    }
+
+    fn insert(&mut self, value: &DataValue) {
+        for pos in self.positions(value).collect::<SmallVec<[u64; 4]>>() {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+    }
+
+    fn may_contain(&self, value: &DataValue) -> bool {
+        self.positions(value)
+            .all(|pos| self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0)
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::sync::Arc;
+
+use tokio::sync::oneshot;
+
+use super::*;
+use crate::array::DataChunkBuilder;
+use crate::storage::{StatisticsAgg, Table, Transaction};
+
+/// The executor of aggregations answered from statistics of a table.
+///
+/// If the storage can not answer them precisely, it falls back to aggregate over the child scan.
+pub struct StatisticsAggExecutor<S: Storage> {
+    pub table_id: TableRefId,
+    /// The aggregations to be answered from statistics.
+    pub aggs: Vec<StatisticsAgg>,
+    pub storage: Arc<S>,
+    /// The executor to aggregate over the child scan.
+    pub fallback: SimpleAggExecutor,
+    /// Starts the child scan. Dropping it without sending lets the scan finish without output.
+    pub start_scan: oneshot::Sender<()>,
+}
+
+impl<S: Storage> StatisticsAggExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self, child: BoxedExecutor) {
+        let table = self.storage.get_table(self.table_id)?;
+        let txn = table.read().await?;
+        if let Some(values) = txn.aggregate_statistics(&self.aggs) {
+            let types = &self.fallback.types;
+            let values: Vec<_> = (values.iter().zip(types))
+                .map(|(value, ty)| value.cast(ty))
+                .try_collect()?;
+            let mut builder = DataChunkBuilder::new(types, 1);
+            yield builder.push_row(values).unwrap();
+            return Ok(());
+        }
+        drop(txn);
+
+        // the receiver is dropped only if the scan is aborted
+        _ = self.start_scan.send(());
+        #[for_await]
+        for chunk in self.fallback.execute(child) {
+            yield chunk?;
+        }
+    }
+}
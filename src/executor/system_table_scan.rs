@@ -1,16 +1,23 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 
 use super::*;
+use crate::activity::{ActivityMonitor, ActivityState};
+use crate::admission::QueryQueue;
 use crate::array::*;
 use crate::catalog::{ColumnRefId, RootCatalogRef, TableRefId};
 use crate::storage::{Storage, StorageColumnRef, Table};
+use crate::types::Timestamp;
 
 /// Scan a system table.
 pub struct SystemTableScan<S: Storage> {
     pub catalog: RootCatalogRef,
     pub storage: Arc<S>,
+    pub queue: Arc<QueryQueue>,
+    pub activity: Arc<ActivityMonitor>,
     pub table_id: TableRefId,
     pub columns: Vec<ColumnRefId>,
 }
@@ -30,6 +37,16 @@ impl<S: Storage> SystemTableScan<S> {
             "pg_indexes" => pg_indexes(self.catalog),
             "pg_attribute" => pg_attribute(self.catalog),
             "pg_stat" => pg_stat(self.catalog, &*self.storage).await?,
+            "pg_user" => pg_user(self.catalog),
+            "pg_query_queue" => pg_query_queue(&self.queue),
+            "pg_stat_activity" => pg_stat_activity(&self.activity),
+            "pg_query_log" => pg_query_log(&self.activity),
+            "rw_rowsets" => rw_rowsets(self.catalog, &*self.storage).await?,
+            "rw_rowset_columns" => rw_rowset_columns(self.catalog, &*self.storage).await?,
+            "rw_delete_vectors" => rw_delete_vectors(self.catalog, &*self.storage),
+            "rw_compactions" => rw_compactions(self.catalog, &*self.storage),
+            "rw_block_cache" => rw_block_cache(&*self.storage).await,
+            "rw_epochs" => rw_epochs(&*self.storage),
             name => panic!("unknown system table: {:?}", name),
         };
     }
@@ -175,6 +192,357 @@ fn pg_tables(catalog: RootCatalogRef) -> DataChunk {
     .collect()
 }
 
+/// Returns `pg_user` table.
+fn pg_user(catalog: RootCatalogRef) -> DataChunk {
+    let mut user_id = I32ArrayBuilder::new();
+    let mut user_name = StringArrayBuilder::new();
+    let mut superuser = BoolArrayBuilder::new();
+    let mut login = BoolArrayBuilder::new();
+    let mut has_password = BoolArrayBuilder::new();
+
+    for user in catalog.all_users() {
+        user_id.push(Some(&(user.id() as i32)));
+        user_name.push(Some(user.name()));
+        superuser.push(Some(&user.is_superuser()));
+        login.push(Some(&user.can_login()));
+        has_password.push(Some(&user.password().is_some()));
+    }
+    [
+        ArrayBuilderImpl::from(user_id),
+        user_name.into(),
+        superuser.into(),
+        login.into(),
+        has_password.into(),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Returns `pg_query_queue` table.
+fn pg_query_queue(queue: &QueryQueue) -> DataChunk {
+    let mut query_id = I64ArrayBuilder::new();
+    let mut user_name = StringArrayBuilder::new();
+    let mut state = StringArrayBuilder::new();
+    let mut query = StringArrayBuilder::new();
+    let mut wait_ms = I64ArrayBuilder::new();
+    let mut run_ms = I64ArrayBuilder::new();
+
+    let now = Instant::now();
+    for q in queue.queries() {
+        let started_at = q.started_at.unwrap_or(now);
+        query_id.push(Some(&(q.id as i64)));
+        user_name.push(q.user.as_deref());
+        state.push(Some(&q.state.to_string()));
+        query.push(Some(&q.query));
+        wait_ms.push(Some(&((started_at - q.queued_at).as_millis() as i64)));
+        run_ms.push(q.started_at.map(|t| (now - t).as_millis() as i64).as_ref());
+    }
+    [
+        ArrayBuilderImpl::from(query_id),
+        user_name.into(),
+        state.into(),
+        query.into(),
+        wait_ms.into(),
+        run_ms.into(),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Returns `pg_stat_activity` table.
+fn pg_stat_activity(activity: &ActivityMonitor) -> DataChunk {
+    let mut pid = I32ArrayBuilder::new();
+    let mut usename = StringArrayBuilder::new();
+    let mut application_name = StringArrayBuilder::new();
+    let mut state = StringArrayBuilder::new();
+    let mut query = StringArrayBuilder::new();
+    let mut backend_start = TimestampArrayBuilder::new();
+    let mut query_start = TimestampArrayBuilder::new();
+    let mut elapsed_ms = I64ArrayBuilder::new();
+
+    let now = SystemTime::now();
+    for a in activity.sessions() {
+        pid.push(Some(&a.pid));
+        usename.push(a.user.as_deref());
+        application_name.push(a.application_name.as_deref());
+        state.push(Some(&a.state.to_string()));
+        query.push(a.query.as_deref());
+        backend_start.push(Some(&timestamp(a.backend_start)));
+        query_start.push(a.query_start.map(timestamp).as_ref());
+        let elapsed = (a.query_start)
+            .filter(|_| a.state == ActivityState::Active)
+            .map(|t| now.duration_since(t).unwrap_or_default().as_millis() as i64);
+        elapsed_ms.push(elapsed.as_ref());
+    }
+    [
+        ArrayBuilderImpl::from(pid),
+        usename.into(),
+        application_name.into(),
+        state.into(),
+        query.into(),
+        backend_start.into(),
+        query_start.into(),
+        elapsed_ms.into(),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Returns `pg_query_log` table.
+fn pg_query_log(activity: &ActivityMonitor) -> DataChunk {
+    let mut pid = I32ArrayBuilder::new();
+    let mut user_name = StringArrayBuilder::new();
+    let mut query = StringArrayBuilder::new();
+    let mut query_start = TimestampArrayBuilder::new();
+    let mut duration_ms = F64ArrayBuilder::new();
+    let mut rows = I64ArrayBuilder::new();
+    let mut error = StringArrayBuilder::new();
+
+    for q in activity.logged_queries() {
+        pid.push(Some(&q.pid));
+        user_name.push(q.user.as_deref());
+        query.push(Some(&q.query));
+        query_start.push(Some(&timestamp(q.query_start)));
+        duration_ms.push(Some(&(q.duration.as_secs_f64() * 1000.0).into()));
+        rows.push(q.rows.map(|r| r as i64).as_ref());
+        error.push(q.error.as_deref());
+    }
+    [
+        ArrayBuilderImpl::from(pid),
+        user_name.into(),
+        query.into(),
+        query_start.into(),
+        duration_ms.into(),
+        rows.into(),
+        error.into(),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Returns the schema name and table name of a table.
+fn names_of(catalog: &RootCatalog, table_id: TableRefId) -> Option<(String, String)> {
+    let schema = catalog.get_schema_by_id(table_id.schema_id)?;
+    let table = schema.get_table_by_id(table_id.table_id)?;
+    Some((schema.name(), table.name().to_string()))
+}
+
+/// Returns `rw_rowsets` table.
+async fn rw_rowsets(catalog: RootCatalogRef, storage: &impl Storage) -> Result<DataChunk> {
+    let mut schema_name = StringArrayBuilder::new();
+    let mut table_name = StringArrayBuilder::new();
+    let mut rowset_id = I32ArrayBuilder::new();
+    let mut rows = I64ArrayBuilder::new();
+    let mut deleted_rows = I64ArrayBuilder::new();
+    let mut on_disk_size = I64ArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        let dvs = storage.delete_vectors();
+        for rowset in storage.rowsets().await? {
+            let Some((schema, table)) = names_of(&catalog, rowset.table_id) else {
+                continue;
+            };
+            let deleted: usize = (dvs.iter())
+                .filter(|dv| dv.table_id == rowset.table_id && dv.rowset_id == rowset.rowset_id)
+                .map(|dv| dv.deleted_rows)
+                .sum();
+            schema_name.push(Some(&schema));
+            table_name.push(Some(&table));
+            rowset_id.push(Some(&(rowset.rowset_id as i32)));
+            rows.push(Some(&(rowset.rows as i64)));
+            deleted_rows.push(Some(&(deleted as i64)));
+            on_disk_size.push(Some(&(rowset.on_disk_size as i64)));
+        }
+    }
+    Ok(DataChunk::from_iter([
+        ArrayBuilderImpl::from(schema_name),
+        table_name.into(),
+        rowset_id.into(),
+        rows.into(),
+        deleted_rows.into(),
+        on_disk_size.into(),
+    ]))
+}
+
+/// Returns `rw_rowset_columns` table.
+async fn rw_rowset_columns(catalog: RootCatalogRef, storage: &impl Storage) -> Result<DataChunk> {
+    let mut schema_name = StringArrayBuilder::new();
+    let mut table_name = StringArrayBuilder::new();
+    let mut rowset_id = I32ArrayBuilder::new();
+    let mut column_name = StringArrayBuilder::new();
+    let mut on_disk_size = I64ArrayBuilder::new();
+    let mut blocks = I32ArrayBuilder::new();
+    let mut encoding = StringArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        for rowset in storage.rowsets().await? {
+            let Some(table) = catalog.get_table(&rowset.table_id) else {
+                continue;
+            };
+            let Some((schema, _)) = names_of(&catalog, rowset.table_id) else {
+                continue;
+            };
+            for column in &rowset.columns {
+                let Some(catalog) = table.get_column_by_id(column.column_id) else {
+                    continue;
+                };
+                schema_name.push(Some(&schema));
+                table_name.push(Some(table.name()));
+                rowset_id.push(Some(&(rowset.rowset_id as i32)));
+                column_name.push(Some(catalog.name()));
+                on_disk_size.push(Some(&(column.on_disk_size as i64)));
+                blocks.push(Some(&(column.blocks as i32)));
+                encoding.push(column.encoding.as_deref());
+            }
+        }
+    }
+    Ok(DataChunk::from_iter([
+        ArrayBuilderImpl::from(schema_name),
+        table_name.into(),
+        rowset_id.into(),
+        column_name.into(),
+        on_disk_size.into(),
+        blocks.into(),
+        encoding.into(),
+    ]))
+}
+
+/// Returns `rw_delete_vectors` table.
+fn rw_delete_vectors(catalog: RootCatalogRef, storage: &impl Storage) -> DataChunk {
+    let mut schema_name = StringArrayBuilder::new();
+    let mut table_name = StringArrayBuilder::new();
+    let mut rowset_id = I32ArrayBuilder::new();
+    let mut dv_id = I64ArrayBuilder::new();
+    let mut deleted_rows = I64ArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        for dv in storage.delete_vectors() {
+            let Some((schema, table)) = names_of(&catalog, dv.table_id) else {
+                continue;
+            };
+            schema_name.push(Some(&schema));
+            table_name.push(Some(&table));
+            rowset_id.push(Some(&(dv.rowset_id as i32)));
+            dv_id.push(Some(&(dv.dv_id as i64)));
+            deleted_rows.push(Some(&(dv.deleted_rows as i64)));
+        }
+    }
+    DataChunk::from_iter([
+        ArrayBuilderImpl::from(schema_name),
+        table_name.into(),
+        rowset_id.into(),
+        dv_id.into(),
+        deleted_rows.into(),
+    ])
+}
+
+/// Returns `rw_compactions` table.
+fn rw_compactions(catalog: RootCatalogRef, storage: &impl Storage) -> DataChunk {
+    let mut schema_name = StringArrayBuilder::new();
+    let mut table_name = StringArrayBuilder::new();
+    let mut input_rowsets = StringArrayBuilder::new();
+    let mut output_rowset = I32ArrayBuilder::new();
+    let mut input_size = I64ArrayBuilder::new();
+    let mut output_size = I64ArrayBuilder::new();
+    let mut output_rows = I64ArrayBuilder::new();
+    let mut start_time = TimestampArrayBuilder::new();
+    let mut duration_ms = F64ArrayBuilder::new();
+    let mut policy = StringArrayBuilder::new();
+    let mut manual = BoolArrayBuilder::new();
+    let mut input_rows = I64ArrayBuilder::new();
+    let mut purged_dvs = I32ArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        for c in storage.compactions() {
+            let (schema, table) = names_of(&catalog, c.table_id).unzip();
+            schema_name.push(schema.as_deref());
+            table_name.push(table.as_deref());
+            input_rowsets.push(Some(&c.input_rowsets.iter().join(",")));
+            output_rowset.push(c.output_rowset.map(|id| id as i32).as_ref());
+            input_size.push(Some(&(c.input_size as i64)));
+            output_size.push(Some(&(c.output_size as i64)));
+            output_rows.push(Some(&(c.output_rows as i64)));
+            start_time.push(Some(&timestamp(c.start_time)));
+            duration_ms.push(Some(&(c.duration.as_secs_f64() * 1000.0).into()));
+            policy.push(Some(&c.policy));
+            manual.push(Some(&c.manual));
+            input_rows.push(Some(&(c.input_rows as i64)));
+            purged_dvs.push(Some(&(c.purged_dvs as i32)));
+        }
+    }
+    DataChunk::from_iter([
+        ArrayBuilderImpl::from(schema_name),
+        table_name.into(),
+        input_rowsets.into(),
+        output_rowset.into(),
+        input_size.into(),
+        output_size.into(),
+        output_rows.into(),
+        start_time.into(),
+        duration_ms.into(),
+        policy.into(),
+        manual.into(),
+        input_rows.into(),
+        purged_dvs.into(),
+    ])
+}
+
+/// Returns `rw_block_cache` table.
+async fn rw_block_cache(storage: &impl Storage) -> DataChunk {
+    let mut capacity = I64ArrayBuilder::new();
+    let mut entries = I64ArrayBuilder::new();
+    let mut hits = I64ArrayBuilder::new();
+    let mut misses = I64ArrayBuilder::new();
+    let mut hit_ratio = F64ArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        let info = storage.block_cache_info().await;
+        let lookups = info.hits + info.misses;
+        capacity.push(Some(&(info.capacity as i64)));
+        entries.push(Some(&(info.entries as i64)));
+        hits.push(Some(&(info.hits as i64)));
+        misses.push(Some(&(info.misses as i64)));
+        let ratio = (lookups != 0).then(|| (info.hits as f64 / lookups as f64).into());
+        hit_ratio.push(ratio.as_ref());
+    }
+    DataChunk::from_iter([
+        ArrayBuilderImpl::from(capacity),
+        entries.into(),
+        hits.into(),
+        misses.into(),
+        hit_ratio.into(),
+    ])
+}
+
+/// Returns `rw_epochs` table.
+fn rw_epochs(storage: &impl Storage) -> DataChunk {
+    let mut epoch = I64ArrayBuilder::new();
+    let mut commit_time = TimestampArrayBuilder::new();
+    let mut pinned = I32ArrayBuilder::new();
+
+    if let Some(storage) = storage.as_disk() {
+        for e in storage.epochs() {
+            epoch.push(Some(&(e.epoch as i64)));
+            commit_time.push(Some(&timestamp(e.commit_time)));
+            pinned.push(Some(&(e.pinned as i32)));
+        }
+    }
+    DataChunk::from_iter([
+        ArrayBuilderImpl::from(epoch),
+        commit_time.into(),
+        pinned.into(),
+    ])
+}
+
+/// Converts a system time to a timestamp.
+fn timestamp(time: SystemTime) -> Timestamp {
+    let micros = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    Timestamp::from_unix_micros(micros as i64)
+}
+
 /// Returns `pg_attribute` table.
 fn pg_attribute(catalog: RootCatalogRef) -> DataChunk {
     // let mut schema_id = I32ArrayBuilder::new();
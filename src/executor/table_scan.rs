@@ -2,11 +2,14 @@
 
 use std::sync::Arc;
 
+use tokio::sync::oneshot;
+
 use super::*;
-use crate::array::DataChunk;
+use crate::array::{ArrayImpl, DataChunk};
 use crate::catalog::{ColumnRefId, TableRefId};
 use crate::storage::{
-    KeyRange, ScanOptions, Storage, StorageColumnRef, Table, Transaction, TxnIterator,
+    AsOf, KeyRange, ScanOptions, ScanPredicate, Storage, StorageColumnRef, StorageResult, Table,
+    Transaction, TxnIterator,
 };
 
 /// The executor of table scan operation.
@@ -14,12 +17,28 @@ pub struct TableScanExecutor<S: Storage> {
     pub table_id: TableRefId,
     pub columns: Vec<ColumnRefId>,
     pub filter: Option<KeyRange>,
+    /// A predicate pushed down to the storage.
+    pub predicate: Option<Arc<ScanPredicateExpr>>,
     pub storage: Arc<S>,
+    /// Runtime filters from hash joins, and the index of the column they apply to.
+    pub runtime_filters: Vec<(usize, RuntimeFilterReceiver)>,
+    /// If set, the scan waits for the signal before reading the table.
+    /// Dropping the sender lets the scan finish without output.
+    pub start: Option<oneshot::Receiver<()>>,
+    /// If set, read the table as of a historical version.
+    pub as_of: Option<AsOf>,
+    /// If set, read only these partitions of a partitioned table.
+    pub partitions: Option<Vec<u32>>,
 }
 
 impl<S: Storage> TableScanExecutor<S> {
     #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
     pub async fn execute(self) {
+        if let Some(start) = self.start {
+            if start.await.is_err() {
+                return Ok(());
+            }
+        }
         let table = self.storage.get_table(self.table_id)?;
 
         let mut col_idx = self
@@ -36,12 +55,25 @@ impl<S: Storage> TableScanExecutor<S> {
             col_idx.push(StorageColumnRef::RowHandler);
         }
 
+        // wait for the runtime filters to be built
+        let mut runtime_filters = vec![];
+        for (idx, mut rx) in self.runtime_filters {
+            // if the join is finished without publishing a filter, scan all rows
+            if let Ok(filter) = rx.wait_for(Option::is_some).await {
+                runtime_filters.push((idx, filter.clone().unwrap()));
+            }
+        }
+
         let txn = table.read().await?;
 
         let mut it = txn
             .scan(
                 &col_idx,
-                ScanOptions::default().with_filter_opt(self.filter),
+                ScanOptions::default()
+                    .with_filter_opt(self.filter)
+                    .with_predicate_opt(self.predicate.map(|p| p as _))
+                    .with_as_of_opt(self.as_of)
+                    .with_partitions_opt(self.partitions),
             )
             .await?;
 
@@ -49,7 +81,74 @@ impl<S: Storage> TableScanExecutor<S> {
             if self.columns.is_empty() {
                 x = DataChunk::no_column(x.cardinality());
             }
+            if !runtime_filters.is_empty() {
+                let mut visibility = vec![true; x.cardinality()];
+                for (idx, filter) in &runtime_filters {
+                    let bitmap = filter.check_array(x.array_at(*idx));
+                    for (v, b) in visibility.iter_mut().zip(bitmap) {
+                        *v &= b;
+                    }
+                }
+                if !visibility.contains(&true) {
+                    continue;
+                }
+                x = x.select(&visibility);
+            }
             yield x;
         }
     }
 }
+
+/// A filter condition pushed down to the storage.
+#[derive(Debug)]
+pub struct ScanPredicateExpr {
+    /// The indexes of columns in the scan output required by the condition.
+    columns: Vec<usize>,
+    /// The condition, where column `#i` refers to `columns[i]`.
+    condition: RecExpr,
+}
+
+impl ScanPredicateExpr {
+    /// Creates a predicate from a condition on the scan output.
+    ///
+    /// Returns `None` if the condition doesn't refer to any column.
+    pub fn new(condition: &RecExpr) -> Option<Self> {
+        let columns = (condition.as_ref().iter())
+            .filter_map(|e| match e {
+                Expr::ColumnIndex(i) => Some(i.0 as usize),
+                _ => None,
+            })
+            .sorted()
+            .dedup()
+            .collect_vec();
+        if columns.is_empty() {
+            return None;
+        }
+        let condition = condition
+            .as_ref()
+            .iter()
+            .map(|e| match e {
+                Expr::ColumnIndex(i) => {
+                    let idx = columns.iter().position(|c| *c == i.0 as usize).unwrap();
+                    Expr::ColumnIndex(ColumnIndex(idx as _))
+                }
+                e => e.clone(),
+            })
+            .collect_vec()
+            .into();
+        Some(Self { columns, condition })
+    }
+}
+
+impl ScanPredicate for ScanPredicateExpr {
+    fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    fn eval(&self, chunk: &DataChunk) -> StorageResult<Vec<bool>> {
+        match Evaluator::new(&self.condition).eval(chunk)? {
+            ArrayImpl::Bool(a) => Ok(a.true_array().to_vec()),
+            _ => panic!("filters can only accept bool array"),
+        }
+    }
+}
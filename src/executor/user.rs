@@ -0,0 +1,80 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::sync::Arc;
+
+use super::*;
+use crate::binder::{AlterUser, CreateUser, DropUser};
+use crate::storage::Storage;
+
+/// The executor of `create user` statement.
+pub struct CreateUserExecutor<S: Storage> {
+    pub user: CreateUser,
+    pub catalog: RootCatalogRef,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> CreateUserExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        let CreateUser {
+            name,
+            if_not_exists,
+            options,
+        } = self.user;
+        if !(if_not_exists && self.catalog.get_user_by_name(&name).is_some()) {
+            self.catalog.add_user(name.clone(), &options)?;
+            if let Some(storage) = self.storage.as_disk() {
+                storage.persist_users(vec![name]).await?;
+            }
+        }
+        yield DataChunk::single(1);
+    }
+}
+
+/// The executor of `alter user` statement.
+pub struct AlterUserExecutor<S: Storage> {
+    pub user: AlterUser,
+    pub catalog: RootCatalogRef,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> AlterUserExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        self.catalog
+            .alter_user(&self.user.name, &self.user.options)?;
+        if let Some(storage) = self.storage.as_disk() {
+            storage.persist_users(vec![self.user.name]).await?;
+        }
+        yield DataChunk::single(1);
+    }
+}
+
+/// The executor of `drop user` statement.
+pub struct DropUserExecutor<S: Storage> {
+    pub user: DropUser,
+    pub catalog: RootCatalogRef,
+    pub storage: Arc<S>,
+}
+
+impl<S: Storage> DropUserExecutor<S> {
+    #[try_stream(boxed, ok = DataChunk, error = ExecutorError)]
+    pub async fn execute(self) {
+        for name in &self.user.names {
+            if self.user.if_exists && self.catalog.get_user_by_name(name).is_none() {
+                continue;
+            }
+            // the user is removed from the roles of its members as well
+            let mut changed = (self.catalog.all_users().into_iter())
+                .filter(|user| user.roles().contains(name))
+                .map(|user| user.name().to_string())
+                .collect_vec();
+            changed.push(name.clone());
+            self.catalog.drop_user(name)?;
+            if let Some(storage) = self.storage.as_disk() {
+                storage.persist_users(changed).await?;
+            }
+        }
+        yield DataChunk::single(1);
+    }
+}
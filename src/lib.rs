@@ -24,6 +24,15 @@
 /// Top-level structure of the database.
 pub mod db;
 
+/// Per-connection state of clients.
+pub mod session;
+
+/// Admission control of concurrent queries.
+pub mod admission;
+
+/// Activity of sessions and the log of slow queries.
+pub mod activity;
+
 /// Parse the SQL string into an Abstract Syntax Tree (AST).
 pub mod parser;
 
@@ -56,6 +65,7 @@ pub mod utils;
 use tikv_jemallocator::Jemalloc;
 
 pub use self::db::{Database, Error};
+pub use self::session::{PreparedStatement, Session, TransactionState};
 
 /// Jemalloc can significantly improve performance compared to the default system allocator.
 #[cfg(feature = "jemalloc")]
@@ -3,20 +3,22 @@
 //! A simple interactive shell of the database.
 
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use humantime::format_duration;
 use itertools::Itertools;
+use risinglight::admission::AdmissionConfig;
 use risinglight::array::{datachunk_to_sqllogictest_string, Chunk};
-use risinglight::server::run_server;
-use risinglight::storage::SecondaryStorageOptions;
+use risinglight::catalog::{PasswordVerifier, RootCatalog, UserOptions};
+use risinglight::server::{run_server, AuthMethod};
+use risinglight::storage::{SecondaryStorage, SecondaryStorageOptions};
 use risinglight::utils::time::RoundingDuration;
-use risinglight::Database;
+use risinglight::{Database, Session};
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
 use rustyline::Editor;
@@ -27,8 +29,11 @@ use tracing_subscriber::prelude::*;
 
 /// RisingLight: an OLAP database system.
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// The name of an RisingLight database.
     /// A new database is created if the file does not previously exist.
     #[clap(default_value = ":memory:")]
@@ -65,6 +70,79 @@ struct Args {
     /// Ignored if `--server` is not specified.
     #[clap(long)]
     port: Option<u16>,
+    /// The method to authenticate clients of the server.
+    /// Unless it is `trust`, the password of the bootstrap user `postgres`
+    /// is read from the `RISINGLIGHT_PASSWORD` environment variable.
+    /// Ignored if `--server` is not specified.
+    #[clap(long, value_enum, default_value_t)]
+    auth: AuthMethod,
+    /// The maximum number of queries running at the same time.
+    /// Other queries wait in a queue. Unlimited if not specified.
+    /// Ignored if `--server` is not specified.
+    #[clap(long)]
+    max_concurrent_queries: Option<usize>,
+    /// The maximum number of queries waiting in the queue.
+    /// More queries are rejected. Unlimited if not specified.
+    /// Ignored if `--server` is not specified.
+    #[clap(long)]
+    max_queued_queries: Option<usize>,
+    /// The maximum time a query waits in the queue, e.g. `30s`.
+    /// Queries wait forever if not specified.
+    /// Ignored if `--server` is not specified.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    queue_timeout: Option<Duration>,
+
+    /// How long historical versions stay readable by `AS OF` queries, e.g. `1h`.
+    /// Time travel is disabled if not specified.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    time_travel_retention: Option<Duration>,
+
+    /// Restore the database from a directory written by `BACKUP TO`, and exit.
+    /// The checksums of all files are verified first.
+    /// The database directory must be empty or not exist.
+    #[clap(long)]
+    restore: Option<PathBuf>,
+}
+
+/// Offline tools for a database directory, which must not be in use.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Verify the checksums of all files of a database, and print statistics of each table.
+    /// Exits with an error if any problem is found.
+    Check { db: PathBuf },
+    /// Print the manifest and the block indexes of a database as JSON, one object per line.
+    Dump { db: PathBuf },
+}
+
+/// Run an offline tool.
+async fn run_command(command: Command) -> Result<()> {
+    match command {
+        Command::Check { db } => {
+            let report = SecondaryStorage::check(&db).await?;
+            for table in &report.tables {
+                println!(
+                    "{} (id {}.{}): {} rowsets, {} blocks, {} rows, {} deleted rows, {} bytes",
+                    table.table_name,
+                    table.table_id.schema_id,
+                    table.table_id.table_id,
+                    table.rowsets,
+                    table.blocks,
+                    table.rows,
+                    table.deleted_rows,
+                    table.on_disk_size
+                );
+            }
+            for problem in &report.problems {
+                println!("problem: {problem}");
+            }
+            if !report.problems.is_empty() {
+                return Err(anyhow!("{} problems found", report.problems.len()));
+            }
+            println!("no problems found");
+        }
+        Command::Dump { db } => SecondaryStorage::dump(&db, &mut std::io::stdout().lock()).await?,
+    }
+    Ok(())
 }
 
 // human-readable message
@@ -85,8 +163,27 @@ fn print_chunk(chunk: &Chunk, output_format: &Option<String>) {
                         chunk.get_first_data_chunk().array_at(0).get_to_string(0)
                     )
                 }
-                "$create" => println!("created"),
-                "$drop" => println!("dropped"),
+                "$copy.row_counts" => {
+                    println!(
+                        "{} rows copied",
+                        chunk.get_first_data_chunk().array_at(0).get_to_string(0)
+                    )
+                }
+                "$create" | "$create_view" | "$create_index" | "$create_function" => {
+                    println!("created")
+                }
+                "$drop" | "$drop_view" => println!("dropped"),
+                "$alter_table" => println!("altered"),
+                "$truncate" => println!("truncated"),
+                "$backup" => println!("backed up"),
+                "$vacuum" => println!("vacuumed"),
+                "$compact" => println!("compacted"),
+                "$set" | "$begin" | "$commit" | "$rollback" | "$prepare" | "$deallocate" => {}
+                "$create_role" => println!("role created"),
+                "$alter_role" => println!("role altered"),
+                "$drop_role" => println!("role dropped"),
+                "$grant" | "$grant_role" => println!("granted"),
+                "$revoke" | "$revoke_role" => println!("revoked"),
                 "$explain" => println!(
                     "{}",
                     chunk.get_first_data_chunk().array_at(0).get_to_string(0)
@@ -119,15 +216,20 @@ fn print_execution_time(start_time: Instant) {
     }
 }
 
-async fn run_query_in_background(db: Arc<Database>, sql: String, output_format: Option<String>) {
+async fn run_query_in_background(
+    session: Arc<Session>,
+    sql: String,
+    output_format: Option<String>,
+) {
     let start_time = Instant::now();
 
     select! {
         _ = signal::ctrl_c() => {
-            // we simply drop the future `task` to cancel the query.
+            // cancel the executor tasks of the query and drop the future.
+            session.cancel();
             println!("Interrupted");
         }
-        ret = db.run(&sql) => {
+        ret = session.run(&sql) => {
             match ret {
                 Ok(chunks) => {
                     for chunk in chunks {
@@ -188,6 +290,7 @@ async fn interactive(db: Database, output_format: Option<String>) -> Result<()>
     }
 
     let db = Arc::new(db);
+    let session = Arc::new(Session::new(db.clone()));
     rl.set_helper(Some(&db));
 
     loop {
@@ -196,7 +299,7 @@ async fn interactive(db: Database, output_format: Option<String>) -> Result<()>
             Ok(sql) => {
                 if !sql.trim().is_empty() {
                     rl.add_history_entry(sql.as_str())?;
-                    run_query_in_background(db.clone(), sql, output_format.clone()).await;
+                    run_query_in_background(session.clone(), sql, output_format.clone()).await;
                 }
             }
             Err(ReadlineError::Interrupted) => {
@@ -228,7 +331,8 @@ async fn run_sql(db: Database, path: &str, output_format: Option<String>) -> Res
 
     info!("{}", lines);
 
-    let chunks = db.run(&lines).await?;
+    let session = Session::new(Arc::new(db));
+    let chunks = session.run(&lines).await?;
 
     for chunk in chunks {
         print_chunk(&chunk, &output_format);
@@ -239,7 +343,7 @@ async fn run_sql(db: Database, path: &str, output_format: Option<String>) -> Res
 
 /// Wrapper for sqllogictest
 struct DatabaseWrapper {
-    db: Database,
+    session: Session,
     output_format: Option<String>,
 }
 
@@ -263,7 +367,7 @@ impl sqllogictest::AsyncDB for &DatabaseWrapper {
         };
 
         info!("{}", sql);
-        let chunks = self.db.run(sql).await?;
+        let chunks = self.session.run(sql).await?;
 
         for chunk in &chunks {
             print_chunk(chunk, &self.output_format);
@@ -290,7 +394,10 @@ impl sqllogictest::AsyncDB for &DatabaseWrapper {
 
 /// Run a sqllogictest file in RisingLight
 async fn run_sqllogictest(db: Database, path: &str, output_format: Option<String>) -> Result<()> {
-    let db = DatabaseWrapper { db, output_format };
+    let db = DatabaseWrapper {
+        session: Session::new(Arc::new(db)),
+        output_format,
+    };
     let mut tester = sqllogictest::Runner::new(|| async { Ok(&db) });
     let path = path.to_string();
 
@@ -323,12 +430,28 @@ async fn main() -> Result<()> {
         minitrace::set_reporter(ConsoleReporter, Config::default());
     }
 
-    let db = if args.filename == ":memory:" {
+    if let Some(command) = args.command {
+        return run_command(command).await;
+    }
+
+    if let Some(backup) = args.restore {
+        if args.filename == ":memory:" {
+            return Err(anyhow!("a database file is required to restore a backup"));
+        }
+        SecondaryStorage::restore(&backup, Path::new(&args.filename)).await?;
+        println!("restored {} from {}", args.filename, backup.display());
+        return Ok(());
+    }
+
+    let mut db = if args.filename == ":memory:" {
         info!("Connected to a transient in-memory database.");
         Database::new_in_memory()
     } else {
         let mut options = SecondaryStorageOptions::default_for_cli();
         options.path = PathBuf::new().join(args.filename);
+        if let Some(retention) = args.time_travel_retention {
+            options.time_travel_retention = retention;
+        }
         Database::new_on_disk(options).await
     };
 
@@ -342,7 +465,23 @@ async fn main() -> Result<()> {
             run_sql(db, &file, args.output_format).await?;
         }
     } else if args.server {
-        run_server(args.host, args.port, db).await;
+        if args.auth != AuthMethod::Trust {
+            let password = std::env::var("RISINGLIGHT_PASSWORD").map_err(|_| {
+                anyhow!("RISINGLIGHT_PASSWORD must be set unless the auth method is trust")
+            })?;
+            let user = RootCatalog::BOOTSTRAP_USER_NAME;
+            let options = UserOptions {
+                password: Some(Some(PasswordVerifier::new(user, &password))),
+                ..Default::default()
+            };
+            db.catalog().alter_user(user, &options)?;
+        }
+        db.set_admission_config(AdmissionConfig {
+            max_concurrent: args.max_concurrent_queries,
+            max_queued: args.max_queued_queries,
+            queue_timeout: args.queue_timeout,
+        });
+        run_server(args.host, args.port, db, args.auth).await;
     } else {
         interactive(db, args.output_format).await?;
     }
@@ -5,11 +5,315 @@
 
 pub use sqlparser::ast::*;
 use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::keywords::Keyword;
 use sqlparser::parser::Parser;
 pub use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Token, TokenWithSpan, Tokenizer, Whitespace};
 
 /// Parse the SQL string into a list of ASTs.
+///
+/// Besides the syntax supported by [`sqlparser`], `CREATE USER`, `ALTER USER` and `DROP USER`
+/// are parsed as their `ROLE` counterparts like Postgres, and `GRANT role TO user` and
+/// `REVOKE role FROM user` are parsed as `ALTER ROLE role ADD MEMBER user` and
+/// `ALTER ROLE role DROP MEMBER user` for each pair of role and user. `BACKUP TO '<dir>'` is
+/// parsed as `PRAGMA backup('<dir>')`, and `VACUUM [FULL] t` and `COMPACT t` are parsed as
+/// `PRAGMA vacuum.t`, `PRAGMA vacuum_full.t` and `PRAGMA compact.t`.
+///
+/// Time-travel clauses `t AS OF EPOCH <n>` and `t AS OF TIMESTAMP '<ts>'` are parsed as
+/// table arguments `t(epoch => <n>)` and `t(timestamp => '<ts>')`.
+///
+/// Range partitions `PARTITION BY RANGE (col) (PARTITION p0 VALUES LESS THAN (<v>), ...)` are
+/// parsed as `PARTITION BY RANGE(col, p0 => (<v>), ...)`, where the bound of the last partition
+/// may be `MAXVALUE`, and `ALTER TABLE t DROP PARTITION [IF EXISTS] p0, p1` is parsed as
+/// `ALTER TABLE t DROP [IF EXISTS] PARTITION (p0, p1)`.
 pub fn parse(sql: &str) -> Result<Vec<Statement>, ParserError> {
     let dialect = PostgreSqlDialect {};
-    Parser::parse_sql(&dialect, sql)
+    let tokens = Tokenizer::new(&dialect, sql).tokenize_with_location()?;
+    let tokens = rewrite_as_of(tokens);
+    let tokens = rewrite_partitions(tokens);
+    let mut parser = Parser::new(&dialect).with_tokens_with_locations(tokens);
+    let mut stmts = Vec::new();
+    let mut expecting_statement_delimiter = false;
+    loop {
+        // ignore empty statements (between successive statement delimiters)
+        while parser.consume_token(&Token::SemiColon) {
+            expecting_statement_delimiter = false;
+        }
+        if parser.peek_token().token == Token::EOF {
+            break;
+        }
+        if expecting_statement_delimiter {
+            return parser.expected("end of statement", parser.peek_token());
+        }
+        stmts.extend(parse_statement(&mut parser)?);
+        expecting_statement_delimiter = true;
+    }
+    Ok(stmts)
+}
+
+fn parse_statement(parser: &mut Parser<'_>) -> Result<Vec<Statement>, ParserError> {
+    if let Some(keyword) = parser.parse_one_of_keywords(&[Keyword::GRANT, Keyword::REVOKE]) {
+        if !is_privilege(parser) {
+            return parse_grant_role(parser, keyword);
+        }
+        return Ok(vec![match keyword {
+            Keyword::GRANT => parser.parse_grant()?,
+            _ => parser.parse_revoke()?,
+        }]);
+    }
+    parse_single_statement(parser).map(|stmt| vec![stmt])
+}
+
+fn parse_single_statement(parser: &mut Parser<'_>) -> Result<Statement, ParserError> {
+    if parser.parse_keywords(&[Keyword::CREATE, Keyword::USER]) {
+        let mut stmt = parser.parse_create_role()?;
+        if let Statement::CreateRole { login, .. } = &mut stmt {
+            login.get_or_insert(true);
+        }
+        Ok(stmt)
+    } else if parser.parse_keywords(&[Keyword::ALTER, Keyword::USER]) {
+        parser.parse_alter_role()
+    } else if parser.parse_keywords(&[Keyword::DROP, Keyword::USER]) {
+        let if_exists = parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+        let names = parser.parse_comma_separated(|p| p.parse_object_name(false))?;
+        Ok(Statement::Drop {
+            object_type: ObjectType::Role,
+            if_exists,
+            names,
+            cascade: false,
+            restrict: false,
+            purge: false,
+            temporary: false,
+        })
+    } else if parse_word(parser, "backup") {
+        parser.expect_keyword(Keyword::TO)?;
+        let dir = parser.parse_literal_string()?;
+        Ok(Statement::Pragma {
+            name: ObjectName(vec![Ident::new("backup")]),
+            value: Some(Value::SingleQuotedString(dir)),
+            is_eq: false,
+        })
+    } else if parser.parse_keyword(Keyword::VACUUM) {
+        let command = match parser.parse_keyword(Keyword::FULL) {
+            true => "vacuum_full",
+            false => "vacuum",
+        };
+        parse_table_command(parser, command)
+    } else if parse_word(parser, "compact") {
+        parse_table_command(parser, "compact")
+    } else {
+        parser.parse_statement()
+    }
+}
+
+/// Parses the table of a maintenance command as `PRAGMA <command>.<table>`.
+fn parse_table_command(parser: &mut Parser<'_>, command: &str) -> Result<Statement, ParserError> {
+    let table = parser.parse_object_name(false)?;
+    let mut name = vec![Ident::new(command)];
+    name.extend(table.0);
+    Ok(Statement::Pragma {
+        name: ObjectName(name),
+        value: None,
+        is_eq: false,
+    })
+}
+
+/// Consumes the next token if it is the given non-keyword word.
+fn parse_word(parser: &mut Parser<'_>, value: &str) -> bool {
+    match parser.peek_token().token {
+        Token::Word(word) if word.value.eq_ignore_ascii_case(value) => {
+            parser.next_token();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if the next token starts a list of privileges rather than roles.
+fn is_privilege(parser: &Parser<'_>) -> bool {
+    let Token::Word(word) = parser.peek_token().token else {
+        return false;
+    };
+    matches!(
+        word.keyword,
+        Keyword::ALL
+            | Keyword::CONNECT
+            | Keyword::CREATE
+            | Keyword::DELETE
+            | Keyword::EXECUTE
+            | Keyword::INSERT
+            | Keyword::REFERENCES
+            | Keyword::SELECT
+            | Keyword::TEMPORARY
+            | Keyword::TRIGGER
+            | Keyword::TRUNCATE
+            | Keyword::UPDATE
+            | Keyword::USAGE
+    )
+}
+
+/// Parses `GRANT roles TO users` or `REVOKE roles FROM users` after the first keyword.
+fn parse_grant_role(
+    parser: &mut Parser<'_>,
+    keyword: Keyword,
+) -> Result<Vec<Statement>, ParserError> {
+    let roles = parser.parse_comma_separated(|p| p.parse_identifier(false))?;
+    let grant = keyword == Keyword::GRANT;
+    parser.expect_keyword(if grant { Keyword::TO } else { Keyword::FROM })?;
+    let members = parser.parse_comma_separated(|p| p.parse_identifier(false))?;
+    let mut stmts = vec![];
+    for name in &roles {
+        for member_name in &members {
+            let member_name = member_name.clone();
+            let operation = match grant {
+                true => AlterRoleOperation::AddMember { member_name },
+                false => AlterRoleOperation::DropMember { member_name },
+            };
+            stmts.push(Statement::AlterRole {
+                name: name.clone(),
+                operation,
+            });
+        }
+    }
+    Ok(stmts)
+}
+
+/// Rewrites `AS OF EPOCH <n>` and `AS OF TIMESTAMP '<ts>'` into `(epoch => <n>)` and
+/// `(timestamp => '<ts>')`.
+fn rewrite_as_of(tokens: Vec<TokenWithSpan>) -> Vec<TokenWithSpan> {
+    let is_word = |token: &TokenWithSpan, value: &str| matches!(&token.token, Token::Word(w) if w.value.eq_ignore_ascii_case(value));
+    // indices of the next 3 non-whitespace tokens after `i`
+    let next3 = |i: usize| -> Vec<usize> {
+        (i + 1..tokens.len())
+            .filter(|&j| !matches!(tokens[j].token, Token::Whitespace(_)))
+            .take(3)
+            .collect()
+    };
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_word(&tokens[i], "as")
+            && let [of, kind, value] = next3(i)[..]
+            && is_word(&tokens[of], "of")
+            && (is_word(&tokens[kind], "epoch") || is_word(&tokens[kind], "timestamp"))
+            && matches!(
+                tokens[value].token,
+                Token::Number(..) | Token::SingleQuotedString(_)
+            )
+        {
+            let span = tokens[i].span;
+            output.push(TokenWithSpan::new(
+                Token::Whitespace(Whitespace::Space),
+                span,
+            ));
+            output.push(TokenWithSpan::new(Token::LParen, span));
+            output.push(tokens[kind].clone());
+            output.push(TokenWithSpan::new(Token::RArrow, span));
+            output.push(tokens[value].clone());
+            output.push(TokenWithSpan::new(Token::RParen, tokens[value].span));
+            i = value + 1;
+        } else {
+            output.push(tokens[i].clone());
+            i += 1;
+        }
+    }
+    output
+}
+
+/// Rewrites the partition list of `PARTITION BY RANGE (col)` into arguments of `RANGE`, and
+/// wraps the partitions of `DROP PARTITION [IF EXISTS]` in parentheses.
+fn rewrite_partitions(tokens: Vec<TokenWithSpan>) -> Vec<TokenWithSpan> {
+    let is_word = |i: usize, value: &str| matches!(tokens.get(i), Some(TokenWithSpan { token: Token::Word(w), .. }) if w.value.eq_ignore_ascii_case(value));
+    // the index of the next non-whitespace token after `i`
+    let next = |i: usize| {
+        (i + 1..tokens.len())
+            .find(|&j| !matches!(tokens[j].token, Token::Whitespace(_)))
+            .unwrap_or(tokens.len())
+    };
+    let is_token = |i: usize, token: &Token| tokens.get(i).is_some_and(|t| &t.token == token);
+    // the index of the parenthesis closing the one at `i`
+    let closing = |i: usize| {
+        let mut depth = 0;
+        for (j, token) in tokens.iter().enumerate().skip(i) {
+            match token.token {
+                Token::LParen => depth += 1,
+                Token::RParen if depth == 1 => return Some(j),
+                Token::RParen => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    };
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_word(i, "partition")
+            && let by = next(i)
+            && is_word(by, "by")
+            && let range = next(by)
+            && is_word(range, "range")
+            && let column = next(range)
+            && is_token(column, &Token::LParen)
+            && let Some(column_end) = closing(column)
+            && is_token(next(column_end), &Token::LParen)
+            && let list = next(column_end)
+            && let Some(list_end) = closing(list)
+        {
+            output.extend_from_slice(&tokens[i..column_end]);
+            output.push(TokenWithSpan::new(Token::Comma, tokens[list].span));
+            let mut j = list + 1;
+            while j < list_end {
+                if is_word(j, "partition") {
+                    j += 1;
+                } else if is_word(j, "values")
+                    && let less = next(j)
+                    && is_word(less, "less")
+                    && let than = next(less)
+                    && is_word(than, "than")
+                {
+                    output.push(TokenWithSpan::new(Token::RArrow, tokens[j].span));
+                    j = than + 1;
+                } else {
+                    output.push(tokens[j].clone());
+                    j += 1;
+                }
+            }
+            output.push(tokens[list_end].clone());
+            i = list_end + 1;
+        } else if is_word(i, "drop")
+            && let partition = next(i)
+            && is_word(partition, "partition")
+            && next(partition) < tokens.len()
+            && !is_token(next(partition), &Token::LParen)
+        {
+            // `IF EXISTS` is expected before `PARTITION`
+            let if_exists =
+                is_word(next(partition), "if") && is_word(next(next(partition)), "exists");
+            let start = match if_exists {
+                true => next(next(partition)) + 1,
+                false => partition + 1,
+            };
+            // the partitions end at the end of the statement
+            let end = (start..tokens.len())
+                .find(|&j| matches!(tokens[j].token, Token::SemiColon | Token::EOF))
+                .unwrap_or(tokens.len());
+            output.extend_from_slice(&tokens[i..partition]);
+            if if_exists {
+                output.extend_from_slice(&tokens[next(partition)..start]);
+                output.push(TokenWithSpan::new(
+                    Token::Whitespace(Whitespace::Space),
+                    tokens[partition].span,
+                ));
+            }
+            output.push(tokens[partition].clone());
+            output.push(TokenWithSpan::new(Token::LParen, tokens[partition].span));
+            output.extend_from_slice(&tokens[start..end]);
+            output.push(TokenWithSpan::new(Token::RParen, tokens[partition].span));
+            i = end;
+        } else {
+            output.push(tokens[i].clone());
+            i += 1;
+        }
+    }
+    output
 }
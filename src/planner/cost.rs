@@ -5,6 +5,7 @@
 use egg::Language;
 use tracing::debug;
 
+use super::rules::prune_partitions;
 use super::*;
 
 /// The main cost function.
@@ -31,15 +32,31 @@ impl egg::CostFunction<Expr> for CostFn<'_> {
 
         let c = match enode {
             // plan nodes
-            Scan(_) | Values(_) | IndexScan(_) => build(),
+            // scanning fewer partitions is cheaper, while the rows are estimated by the filter
+            Scan([_, _, filter]) => match prune_partitions(
+                &self.egraph.analysis.catalog,
+                &self.egraph[*filter].data.range,
+            ) {
+                Some((partitions, total)) => {
+                    build() * partitions.len() as f32 / total.max(1) as f32
+                }
+                None => build(),
+            },
+            ScanAsOf(_) | Values(_) | IndexScan(_) => build(),
             Order([_, c]) => nlogn(rows(c)) + build() + costs(c),
             Filter([exprs, c]) => costs(exprs) * rows(c) + build() + costs(c),
             Proj([exprs, c]) | Window([exprs, c]) => costs(exprs) * rows(c) + costs(c),
             Agg([exprs, c]) => costs(exprs) * rows(c) + build() + costs(c),
+            // the child is scanned only if statistics are not available
+            StatAgg([_, c]) => build() + costs(c) * 0.5,
             HashAgg([keys, aggs, c]) => {
                 (hash(rows(id)) + costs(keys) + costs(aggs)) * rows(c) + build() + costs(c)
             }
             SortAgg([keys, aggs, c]) => (costs(keys) + costs(aggs)) * rows(c) + build() + costs(c),
+            // direct indexing is cheaper than hashing, but not free as streaming aggregation
+            PerfectHashAgg([keys, aggs, c]) => {
+                (costs(keys) + costs(aggs) + 0.001) * rows(c) + build() + costs(c)
+            }
             Limit([_, _, c]) => build() + costs(c),
             TopN([_, _, _, c]) => (rows(id) + 1.0).log2() * rows(c) + build() + costs(c),
             Join([_, cond, l, r]) => {
@@ -129,6 +129,7 @@ impl<'a> Explain<'a> {
             ExtSource(src) => format!("path={:?}, format={}", src.path, src.format).into(),
             Symbol(s) => Pretty::display(s),
             Ref(e) => Pretty::fieldless_record("ref", vec![self.expr(e).pretty()]),
+            Param(i) => format!("${}", self.expr[*i].as_const()).into(),
             List(list) => Pretty::Array(list.iter().map(|e| self.expr(e).pretty()).collect()),
 
             // binary operations
@@ -255,6 +256,14 @@ impl<'a> Explain<'a> {
                     ("filter", self.expr(filter).pretty()),
                 ]),
             ),
+            ScanAsOf([table, list, version]) => Pretty::childless_record(
+                "ScanAsOf",
+                with_meta(vec![
+                    ("table", self.expr(table).pretty()),
+                    ("list", self.expr(list).pretty()),
+                    ("version", self.expr(version).pretty()),
+                ]),
+            ),
             IndexScan([table, columns, filter, key, vector]) => Pretty::childless_record(
                 "IndexScan",
                 with_meta(vec![
@@ -340,15 +349,23 @@ impl<'a> Explain<'a> {
                 vec![self.child(left).pretty(), self.child(right).pretty()],
             ),
             Inner | LeftOuter | RightOuter | FullOuter | Semi | Anti => Pretty::display(enode),
-            Agg([aggs, child]) => Pretty::simple_record(
-                "Agg",
+            Vacuum | VacuumFull | ByPolicies => Pretty::display(enode),
+            Agg([aggs, child]) | StatAgg([aggs, child]) => Pretty::simple_record(
+                match enode {
+                    Agg(_) => "Agg",
+                    StatAgg(_) => "StatAgg",
+                    _ => unreachable!(),
+                },
                 with_meta(vec![("aggs", self.expr(aggs).pretty())]),
                 vec![self.child(child).pretty()],
             ),
-            HashAgg([keys, aggs, child]) | SortAgg([keys, aggs, child]) => Pretty::simple_record(
+            HashAgg([keys, aggs, child])
+            | SortAgg([keys, aggs, child])
+            | PerfectHashAgg([keys, aggs, child]) => Pretty::simple_record(
                 match enode {
                     HashAgg(_) => "HashAgg",
                     SortAgg(_) => "SortAgg",
+                    PerfectHashAgg(_) => "PerfectHashAgg",
                     _ => unreachable!(),
                 },
                 with_meta(vec![
@@ -383,6 +400,20 @@ impl<'a> Explain<'a> {
                 let fields = with_meta(vec![("objects", self.expr(tables).pretty())]);
                 Pretty::childless_record("Drop", fields)
             }
+            DropPartition([table, names]) => {
+                let fields = with_meta(vec![
+                    ("table", self.expr(table).pretty()),
+                    ("partitions", self.expr(names).pretty()),
+                ]);
+                Pretty::childless_record("DropPartition", fields)
+            }
+            CreateUser(u) => Pretty::childless_record("CreateUser", u.pretty_user()),
+            AlterUser(u) => Pretty::childless_record("AlterUser", u.pretty_user()),
+            DropUser(u) => Pretty::childless_record("DropUser", u.pretty_user()),
+            Grant(g) => Pretty::childless_record("Grant", g.pretty_grant()),
+            Revoke(g) => Pretty::childless_record("Revoke", g.pretty_grant()),
+            GrantRole(g) => Pretty::childless_record("GrantRole", g.pretty_grant()),
+            RevokeRole(g) => Pretty::childless_record("RevokeRole", g.pretty_grant()),
             Insert([table, cols, child]) => Pretty::simple_record(
                 "Insert",
                 with_meta(vec![
@@ -422,6 +453,17 @@ impl<'a> Explain<'a> {
                     ("value", self.expr(value).pretty()),
                 ]),
             ),
+            Backup(dir) => Pretty::childless_record(
+                "Backup",
+                with_meta(vec![("dir", self.expr(dir).pretty())]),
+            ),
+            Compact([table, mode]) => Pretty::childless_record(
+                "Compact",
+                with_meta(vec![
+                    ("table", self.expr(table).pretty()),
+                    ("mode", self.expr(mode).pretty()),
+                ]),
+            ),
             Set([name, value]) => Pretty::childless_record(
                 "Set",
                 with_meta(vec![
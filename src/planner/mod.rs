@@ -3,7 +3,9 @@
 use egg::{define_language, Id, Symbol};
 
 use crate::binder::copy::ExtSource;
-use crate::binder::{CreateFunction, CreateIndex, CreateTable};
+use crate::binder::{
+    AlterUser, CreateFunction, CreateIndex, CreateTable, CreateUser, DropUser, Grant, GrantRole,
+};
 use crate::catalog::{ColumnRefId, TableRefId};
 use crate::parser::{BinaryOperator, UnaryOperator};
 use crate::types::{ColumnIndex, DataType, DataValue, DateTimeField};
@@ -12,10 +14,14 @@ mod cost;
 mod explain;
 mod optimizer;
 mod rules;
+mod runtime_filter;
 
 pub use explain::Explain;
 pub use optimizer::{Config, Optimizer};
-pub use rules::{ExprAnalysis, Statistics, TypeError, TypeSchemaAnalysis};
+pub use rules::{
+    prune_partitions, ExprAnalysis, KeyDomain, Statistics, TypeError, TypeSchemaAnalysis,
+};
+pub use runtime_filter::RuntimeFilterDesc;
 
 // Alias types for our language.
 type EGraph = egg::EGraph<Expr, ExprAnalysis>;
@@ -37,6 +43,9 @@ define_language! {
                                             // refer the expr as a column
                                             // it can also prevent optimization
         "list" = List(Box<[Id]>),       // (list ...)
+        "param" = Param(Id),            // (param index)
+                                            // a parameter placeholder like `$1`
+                                            // replaced by its value before execution
 
         // binary operations
         "+" = Add([Id; 2]),
@@ -100,6 +109,7 @@ define_language! {
         // plans
         "scan" = Scan([Id; 3]),                 // (scan table [column..] filter)
         "index_scan" = IndexScan([Id; 5]), // (index_scan table [column..] filter key value)
+        "scan_as_of" = ScanAsOf([Id; 3]),       // (scan_as_of table [column..] version)
         "values" = Values(Box<[Id]>),           // (values [expr..]..)
         "proj" = Proj([Id; 2]),                 // (proj [expr..] child)
         "filter" = Filter([Id; 2]),             // (filter expr child)
@@ -119,10 +129,15 @@ define_language! {
             "anti" = Anti,
         "agg" = Agg([Id; 2]),                   // (agg aggs=[expr..] child)
                                                     // expressions must be aggregate functions
+        "statagg" = StatAgg([Id; 2]),           // (statagg aggs=[expr..] child)
+                                                    // answer aggregations from statistics of the scan
+                                                    // child must be a table scan without filter
         "hashagg" = HashAgg([Id; 3]),           // (hashagg keys=[expr..] aggs=[expr..] child)
                                                     // output = keys || aggs
         "sortagg" = SortAgg([Id; 3]),           // (sortagg keys=[expr..] aggs=[expr..] child)
                                                     // child must be ordered by keys
+        "perfecthashagg" = PerfectHashAgg([Id; 3]), // (perfecthashagg keys=[expr..] aggs=[expr..] child)
+                                                    // keys must be columns with small domains
         "window" = Window([Id; 2]),             // (window [over..] child)
                                                     // output = child || exprs
         CreateTable(Box<CreateTable>),
@@ -130,6 +145,14 @@ define_language! {
         "create_view" = CreateView([Id; 2]),    // (create_view create_table child)
         CreateFunction(CreateFunction),
         "drop" = Drop(Id),                      // (drop [table..])
+        "drop_partition" = DropPartition([Id; 2]), // (drop_partition table [name..])
+        CreateUser(CreateUser),
+        AlterUser(AlterUser),
+        DropUser(DropUser),
+        Grant(Grant),
+        Revoke(Grant),
+        GrantRole(GrantRole),
+        RevokeRole(GrantRole),
         "insert" = Insert([Id; 3]),             // (insert table [column..] child)
         "delete" = Delete([Id; 2]),             // (delete table child)
         "copy_from" = CopyFrom([Id; 2]),        // (copy_from dest types)
@@ -138,6 +161,11 @@ define_language! {
         "explain" = Explain(Id),                // (explain child)
         "analyze" = Analyze(Id),                // (analyze child)
         "pragma" = Pragma([Id; 2]),             // (pragma name value)
+        "backup" = Backup(Id),                  // (backup dir)
+        "compact" = Compact([Id; 2]),           // (compact table mode)
+            "vacuum" = Vacuum,                      // rewrite RowSets with deleted rows
+            "vacuum_full" = VacuumFull,             // merge all RowSets into one
+            "by_policies" = ByPolicies,             // compact with the policies of the storage
         "set" = Set([Id; 2]),                   // (set name value)
 
         // internal functions
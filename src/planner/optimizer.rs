@@ -18,6 +18,7 @@ pub struct Optimizer {
 pub struct Config {
     pub enable_range_filter_scan: bool,
     pub table_is_sorted_by_primary_key: bool,
+    pub enable_statistics_agg: bool,
 }
 
 impl Optimizer {
@@ -41,6 +42,9 @@ impl Optimizer {
         if self.analysis.config.enable_range_filter_scan {
             extra_rules.append(&mut rules::range::filter_scan_rule());
         }
+        if self.analysis.config.enable_statistics_agg {
+            extra_rules.append(&mut rules::plan::statistics_agg_rules());
+        }
 
         // 1. pushdown apply
         self.optimize_stage(&mut expr, &mut cost, STAGE1_RULES.iter(), 2, 6);
@@ -104,10 +108,21 @@ impl Optimizer {
             .collect()
     }
 
+    /// Returns the runtime filters that can be pushed from hash joins to scans.
+    pub fn runtime_filters(&self, expr: &RecExpr) -> Vec<RuntimeFilterDesc> {
+        let root = Id::from(expr.as_ref().len() - 1);
+        runtime_filter::runtime_filters(root, |id| &expr[id], &self.analysis.catalog)
+    }
+
     /// Returns the catalog.
     pub fn catalog(&self) -> &RootCatalogRef {
         &self.analysis.catalog
     }
+
+    /// Returns the statistics used by the optimizer.
+    pub fn statistics(&self) -> &Statistics {
+        &self.analysis.stat
+    }
 }
 
 /// Stage1 rules in the optimizer.
@@ -143,5 +158,6 @@ static STAGE3_RULES: LazyLock<Vec<Rewrite>> = LazyLock::new(|| {
     rules.append(&mut rules::plan::predicate_pushdown_rules());
     rules.append(&mut rules::plan::projection_pushdown_rules());
     rules.append(&mut rules::order::order_rules());
+    rules.append(&mut rules::plan::perfect_hash_agg_rules());
     rules
 });
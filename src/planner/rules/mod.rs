@@ -46,7 +46,8 @@ pub mod rows;
 pub mod schema;
 pub mod type_;
 
-pub use rows::Statistics;
+pub use range::prune_partitions;
+pub use rows::{KeyDomain, Statistics};
 
 pub use self::type_::TypeError;
 
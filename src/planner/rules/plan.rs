@@ -7,8 +7,9 @@ use itertools::Itertools;
 use super::schema::schema_is_eq;
 use super::*;
 use crate::binder::{IndexType, VectorDistance};
+use crate::catalog::RootCatalog;
 use crate::planner::ExprExt;
-use crate::types::DataValue;
+use crate::types::{DataType, DataValue};
 
 /// Returns the rules that always improve the plan.
 pub fn always_better_rules() -> Vec<Rewrite> {
@@ -474,6 +475,105 @@ fn has_vector_index(
     }
 }
 
+/// Answer aggregations on a table from storage statistics.
+#[rustfmt::skip]
+pub fn statistics_agg_rules() -> Vec<Rewrite> { vec![
+    rw!("statistics-agg";
+        "(agg ?aggs (scan ?table ?columns true))" =>
+        "(statagg ?aggs (scan ?table ?columns true))"
+        if is_statistics_agg("?aggs", "?table")
+    ),
+]}
+
+/// Returns true if all aggregations can be answered from statistics of the table.
+/// i.e. `count(*)`, `count` on non-null columns, and `min` / `max` on columns with zone maps.
+fn is_statistics_agg(aggs: &str, table: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let aggs = var(aggs);
+    let table = var(table);
+    move |egraph, _, subst| {
+        let catalog = &egraph.analysis.catalog;
+        let table_id = egraph[subst[table]].nodes[0].as_table();
+        if table_id.schema_id == RootCatalog::SYSTEM_SCHEMA_ID {
+            return false;
+        }
+        match catalog.get_table(&table_id) {
+            Some(table) if !table.is_view() => {}
+            _ => return false,
+        }
+        let column = |id: &Id| {
+            egraph[*id].nodes.iter().find_map(|e| match e {
+                Expr::Column(c) => catalog.get_column(c),
+                _ => None,
+            })
+        };
+        egraph[subst[aggs]].as_list().iter().all(|id| {
+            egraph[*id].nodes.iter().any(|agg| match agg {
+                Expr::RowCount => true,
+                Expr::Count(c) => column(c).is_some_and(|c| !c.is_nullable()),
+                Expr::Min(c) | Expr::Max(c) => column(c).is_some_and(|c| {
+                    use DataType::*;
+                    matches!(
+                        c.data_type(),
+                        Bool | Int16
+                            | Int32
+                            | Int64
+                            | Float64
+                            | Decimal(_, _)
+                            | Date
+                            | Timestamp
+                            | TimestampTz
+                            | Interval
+                    )
+                }),
+                _ => false,
+            })
+        })
+    }
+}
+
+/// The maximum number of slots in a perfect hash aggregation.
+const MAX_PERFECT_HASH_SLOTS: u64 = 1 << 16;
+
+#[rustfmt::skip]
+pub fn perfect_hash_agg_rules() -> Vec<Rewrite> { vec![
+    rw!("perfect-hash-agg";
+        "(hashagg ?keys ?aggs ?child)" =>
+        "(perfecthashagg ?keys ?aggs ?child)"
+        if has_small_key_domain("?keys")
+    ),
+]}
+
+/// Returns true if all keys are columns with known domains,
+/// and the number of key combinations (including nulls) is small.
+fn has_small_key_domain(keys: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let keys = var(keys);
+    move |egraph, _, subst| {
+        let keys = egraph[subst[keys]].as_list();
+        if keys.is_empty() {
+            return false;
+        }
+        let mut slots = 1u64;
+        for id in keys {
+            let domain = egraph[*id].nodes.iter().find_map(|e| match e {
+                Expr::Column(c) => {
+                    let ty = egraph.analysis.catalog.get_column(c)?.data_type();
+                    egraph.analysis.stat.get_key_domain(*c, &ty)
+                }
+                _ => None,
+            });
+            let Some(size) = domain.and_then(|d| d.size()) else {
+                return false;
+            };
+            // reserve a slot for null
+            slots = slots.saturating_mul(size.saturating_add(1));
+            if slots > MAX_PERFECT_HASH_SLOTS {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 /// Returns true if the columns used in `expr` is disjoint from columns produced by `plan`.
 fn not_depend_on(expr: &str, plan: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let expr = var(expr);
@@ -5,7 +5,7 @@
 use std::ops::Bound;
 
 use super::*;
-use crate::catalog::ColumnRefId;
+use crate::catalog::{ColumnRefId, RootCatalog};
 use crate::storage::KeyRange;
 
 /// The data type of range analysis.
@@ -97,6 +97,17 @@ pub fn filter_scan_rule() -> Vec<Rewrite> { vec![
         "(filter ?cond2 (scan ?table ?columns ?cond1))"
         if is_primary_key_range("?cond1")
     ),
+    // a range of the partition column prunes partitions, while the filter is still needed
+    rw!("filter-scan-partition";
+        "(filter ?cond (scan ?table ?columns true))" =>
+        "(filter ?cond (scan ?table ?columns ?cond))"
+        if is_partition_range("?cond")
+    ),
+    rw!("filter-scan-partition-1";
+        "(filter (and ?cond1 ?cond2) (scan ?table ?columns true))" =>
+        "(filter (and ?cond1 ?cond2) (scan ?table ?columns ?cond1))"
+        if is_partition_range("?cond1")
+    ),
 ]}
 
 /// Returns true if the expression is a primary key range.
@@ -114,3 +125,31 @@ fn is_primary_key_range(expr: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool
         }
     }
 }
+
+/// Returns true if the expression is a range of the partition column, which is not a primary key.
+fn is_partition_range(expr: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let var = var(expr);
+    move |egraph, _, subst| {
+        let range = &egraph[subst[var]].data.range;
+        let catalog = &egraph.analysis.catalog;
+        let Some((column, _)) = range else {
+            return false;
+        };
+        catalog.get_column(column).is_some_and(|c| !c.is_primary())
+            && prune_partitions(catalog, range).is_some()
+    }
+}
+
+/// Returns the partitions which may hold rows in the range, and the number of all partitions,
+/// if the range is on the partition column of a table.
+pub fn prune_partitions(
+    catalog: &RootCatalog,
+    range: &RangeCondition,
+) -> Option<(Vec<u32>, usize)> {
+    let (column, range) = range.as_ref()?;
+    let table = catalog.get_table(&column.table())?;
+    let spec = table
+        .partition()
+        .filter(|spec| spec.column == column.column_id)?;
+    Some((spec.prune(range), spec.partitions.len()))
+}
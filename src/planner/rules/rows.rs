@@ -4,7 +4,7 @@ use std::collections::HashMap;
 
 use super::*;
 use crate::catalog::{ColumnRefId, TableRefId};
-use crate::types::DataValue;
+use crate::types::{DataType, DataValue};
 
 /// The data type of row number analysis.
 pub type Rows = f32;
@@ -24,7 +24,7 @@ pub fn analyze_rows(egraph: &EGraph, enode: &Expr) -> Rows {
     match enode {
         // for plan nodes, the result represents estimated rows
         Values(v) => v.len() as f32,
-        Scan([tid, _, _]) => {
+        Scan([tid, _, _]) | ScanAsOf([tid, _, _]) => {
             let table_id = egraph[*tid].nodes[0].as_table();
             egraph
                 .analysis
@@ -33,8 +33,8 @@ pub fn analyze_rows(egraph: &EGraph, enode: &Expr) -> Rows {
                 .unwrap_or(DEFAULT_ROW_COUNT) as f32
         }
         Proj([_, c]) | Order([_, c]) | Window([_, c]) => x(c),
-        Agg(_) => 1.0,
-        HashAgg([keys, _, c]) | SortAgg([keys, _, c]) => {
+        Agg(_) | StatAgg(_) => 1.0,
+        HashAgg([keys, _, c]) | SortAgg([keys, _, c]) | PerfectHashAgg([keys, _, c]) => {
             // TODO: consider distinct values of group keys
             10_f32.powi(list_len(keys) as i32).min(x(c))
         }
@@ -100,6 +100,7 @@ const DEFAULT_ROW_COUNT: u32 = 1000;
 pub struct Statistics {
     row_counts: HashMap<TableRefId, u32>,
     distinct_values: HashMap<ColumnRefId, u32>,
+    column_ranges: HashMap<ColumnRefId, (i64, i64)>,
 }
 
 impl Statistics {
@@ -120,4 +121,47 @@ impl Statistics {
         column_id.table_occurrence = 0;
         self.distinct_values.get(&column_id).copied()
     }
+
+    /// Adds the range `[min, max]` of values of an integer column.
+    pub fn add_column_range(&mut self, mut column_id: ColumnRefId, min: i64, max: i64) {
+        column_id.table_occurrence = 0;
+        self.column_ranges.insert(column_id, (min, max));
+    }
+
+    pub fn get_column_range(&self, mut column_id: ColumnRefId) -> Option<(i64, i64)> {
+        column_id.table_occurrence = 0;
+        self.column_ranges.get(&column_id).copied()
+    }
+
+    /// Returns the estimated domain of non-null values of a column with the given type.
+    pub fn get_key_domain(&self, column_id: ColumnRefId, ty: &DataType) -> Option<KeyDomain> {
+        match ty {
+            DataType::Bool => Some(KeyDomain::Range(0, 1)),
+            DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                let (min, max) = self.get_column_range(column_id)?;
+                Some(KeyDomain::Range(min, max))
+            }
+            DataType::String => Some(KeyDomain::Dictionary(self.get_distinct_values(column_id)?)),
+            _ => None,
+        }
+    }
+}
+
+/// The estimated domain of values of a group key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDomain {
+    /// Integers in the range `[min, max]`.
+    Range(i64, i64),
+    /// At most the given number of distinct values.
+    Dictionary(u32),
+}
+
+impl KeyDomain {
+    /// Returns the number of values in the domain, or `None` if it does not fit in `u64`.
+    pub fn size(&self) -> Option<u64> {
+        match *self {
+            Self::Range(min, max) => u64::try_from(max as i128 - min as i128 + 1).ok(),
+            Self::Dictionary(n) => Some(n as u64),
+        }
+    }
 }
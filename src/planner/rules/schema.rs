@@ -32,11 +32,13 @@ pub fn analyze_schema(
         List(ids) => ids.to_vec(),
 
         // plans that change schema
-        Scan([_, columns, _]) => x(columns),
+        Scan([_, columns, _]) | ScanAsOf([_, columns, _]) => x(columns),
         Values(vs) => x(&vs[0]),
-        Proj([exprs, _]) | Agg([exprs, _]) => x(exprs),
+        Proj([exprs, _]) | Agg([exprs, _]) | StatAgg([exprs, _]) => x(exprs),
         Window([exprs, child]) => concat(x(child), x(exprs)),
-        HashAgg([keys, aggs, _]) | SortAgg([keys, aggs, _]) => concat(x(keys), x(aggs)),
+        HashAgg([keys, aggs, _]) | SortAgg([keys, aggs, _]) | PerfectHashAgg([keys, aggs, _]) => {
+            concat(x(keys), x(aggs))
+        }
 
         // not plan node
         _ => vec![],
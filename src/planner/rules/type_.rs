@@ -39,6 +39,8 @@ pub fn analyze_type(
             .ok_or_else(|| TypeError::Unavailable(enode.to_string()))?
             .data_type()),
         Ref(a) => x(a),
+        // parameters are strings unless casted to the inferred type
+        Param(_) => Ok(DataType::String),
         List(list) => Ok(DataType::Struct(list.iter().map(x).try_collect()?)),
 
         // cast
@@ -163,7 +165,7 @@ pub fn analyze_type(
         }
 
         // plans that change schema
-        Scan([_, columns, _]) => x(columns),
+        Scan([_, columns, _]) | ScanAsOf([_, columns, _]) => x(columns),
         Values(rows) => {
             if rows.is_empty() {
                 return Ok(DataType::Null);
@@ -178,9 +180,11 @@ pub fn analyze_type(
             }
             Ok(type_)
         }
-        Proj([exprs, _]) | Agg([exprs, _]) => x(exprs),
+        Proj([exprs, _]) | Agg([exprs, _]) | StatAgg([exprs, _]) => x(exprs),
         Window([exprs, c]) => concat_struct(x(c)?, x(exprs)?),
-        HashAgg([keys, aggs, _]) | SortAgg([keys, aggs, _]) => concat_struct(x(keys)?, x(aggs)?),
+        HashAgg([keys, aggs, _]) | SortAgg([keys, aggs, _]) | PerfectHashAgg([keys, aggs, _]) => {
+            concat_struct(x(keys)?, x(aggs)?)
+        }
         Max1Row(c) => Ok(x(c)?.as_struct()[0].clone()),
 
         // other plan nodes
@@ -0,0 +1,230 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Runtime filter planning.
+//!
+//! A hash join builds a hash table from its build side before reading any row from its probe
+//! side. Once the build side is finished, the set of join keys is known, and any probe-side row
+//! whose key is not in this set will be dropped by the join anyway. So the join can publish a
+//! filter over its build keys, and the table scans on the probe side can apply it before emitting
+//! chunks.
+//!
+//! This module decides which scans may receive a runtime filter from which join. The filter
+//! itself is built and applied in the executor.
+
+use std::collections::HashSet;
+
+use egg::{Id, Language};
+
+use super::Expr;
+use crate::catalog::{ColumnRefId, RootCatalog};
+
+/// A runtime filter published by a hash join to a table scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeFilterDesc {
+    /// The hash join node that builds the filter.
+    pub join: Id,
+    /// The index of the key in the join key list.
+    pub key_index: usize,
+    /// The position of the scan that applies the filter: the path of plan nodes from the probe
+    /// child of the join down to the scan.
+    ///
+    /// Identical subplans share the same `Id`, so a scan is identified by its position under the
+    /// join rather than by its `Id` alone.
+    pub scan: Vec<Id>,
+    /// The column of the scan to be filtered.
+    pub column: ColumnRefId,
+}
+
+/// Returns all runtime filters that can be applied in the plan rooted at `root`.
+///
+/// A filter is only generated when dropping a probe-side row in the scan can not change the
+/// result of the query. That is:
+/// - the join is an inner join, left outer join or semi join (where the probe side is not
+///   preserved),
+/// - the probe key is a plain column, and
+/// - on the path from the join to the scan, there are only filters, projections, orders and inner
+///   joins, which never output a row whose key is not produced by the scan.
+pub fn runtime_filters<'a>(
+    root: Id,
+    node: impl Fn(Id) -> &'a Expr,
+    catalog: &RootCatalog,
+) -> Vec<RuntimeFilterDesc> {
+    let mut filters = vec![];
+    let mut visited = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        stack.extend_from_slice(node(id).children());
+
+        let Expr::HashJoin([ty, cond, lkeys, rkeys, left, right]) = node(id) else {
+            continue;
+        };
+        if *node(*cond) != Expr::true_() {
+            continue;
+        }
+        // the probe side of the hash join
+        let (probe_keys, probe) = match node(*ty) {
+            Expr::Inner | Expr::LeftOuter => (rkeys, right),
+            Expr::Semi => (lkeys, left),
+            _ => continue,
+        };
+        for (key_index, key) in node(*probe_keys).as_list().iter().enumerate() {
+            let Expr::Column(column) = node(*key) else {
+                continue;
+            };
+            if let Some(scan) = find_scan(&node, *probe, column, catalog) {
+                filters.push(RuntimeFilterDesc {
+                    join: id,
+                    key_index,
+                    scan,
+                    column: *column,
+                });
+            }
+        }
+    }
+    filters
+}
+
+/// Finds the table scan that produces `column` in the plan `id`.
+///
+/// Returns the path from `id` to the scan, or `None` if the scan is not found or not reachable
+/// through filter-safe nodes.
+fn find_scan<'a>(
+    node: &impl Fn(Id) -> &'a Expr,
+    id: Id,
+    column: &ColumnRefId,
+    catalog: &RootCatalog,
+) -> Option<Vec<Id>> {
+    use Expr::*;
+    let mut path = match node(id) {
+        Scan([table, list, _]) => {
+            let table_id = node(*table).as_table();
+            // only table scans accept runtime filters
+            if table_id.schema_id == RootCatalog::SYSTEM_SCHEMA_ID
+                || catalog.get_table(&table_id)?.is_view()
+            {
+                return None;
+            }
+            let contains = (node(*list).as_list().iter())
+                .any(|c| matches!(node(*c), Column(c) if c == column));
+            return contains.then(|| vec![id]);
+        }
+        Filter([_, child]) | Proj([_, child]) | Order([_, child]) => {
+            find_scan(node, *child, column, catalog)
+        }
+        Join([ty, _, left, right])
+        | HashJoin([ty, _, _, _, left, right])
+        | MergeJoin([ty, _, _, _, left, right]) => {
+            // both sides of a self join produce the same columns,
+            // so we can not tell which one the key comes from
+            if left == right {
+                return None;
+            }
+            match node(*ty) {
+                Inner => find_scan(node, *left, column, catalog)
+                    .or_else(|| find_scan(node, *right, column, catalog)),
+                Semi => find_scan(node, *left, column, catalog),
+                _ => None,
+            }
+        }
+        _ => None,
+    }?;
+    path.insert(0, id);
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnCatalog, ColumnDesc, TableRefId};
+    use crate::planner::RecExpr;
+    use crate::types::DataType;
+
+    /// Builds plans over tables `t` and `s` with a single column.
+    struct PlanBuilder {
+        catalog: RootCatalog,
+        expr: RecExpr,
+    }
+
+    impl PlanBuilder {
+        fn new() -> Self {
+            let catalog = RootCatalog::new();
+            for name in ["t", "s"] {
+                let col = ColumnCatalog::new(0, ColumnDesc::new("a", DataType::Int32, false));
+                catalog
+                    .add_table(1, name.into(), vec![col], vec![])
+                    .unwrap();
+            }
+            PlanBuilder {
+                catalog,
+                expr: RecExpr::default(),
+            }
+        }
+
+        fn column(&self, table: &str) -> ColumnRefId {
+            let table_id = self
+                .catalog
+                .get_table_id_by_name(RootCatalog::DEFAULT_SCHEMA_NAME, table)
+                .unwrap();
+            ColumnRefId::from_table(table_id, 0, 0)
+        }
+
+        fn scan(&mut self, table: &str) -> Id {
+            let column = self.column(table);
+            let table = self.expr.add(Expr::Table(TableRefId::new(
+                column.schema_id,
+                column.table_id,
+            )));
+            let column = self.expr.add(Expr::Column(column));
+            let list = self.expr.add(Expr::List([column].into()));
+            let cond = self.expr.add(Expr::true_());
+            self.expr.add(Expr::Scan([table, list, cond]))
+        }
+
+        fn hashjoin(&mut self, build: Id, build_table: &str, probe: Id, probe_table: &str) -> Id {
+            let ty = self.expr.add(Expr::Inner);
+            let cond = self.expr.add(Expr::true_());
+            let build_key = self.expr.add(Expr::Column(self.column(build_table)));
+            let lkeys = self.expr.add(Expr::List([build_key].into()));
+            let probe_key = self.expr.add(Expr::Column(self.column(probe_table)));
+            let rkeys = self.expr.add(Expr::List([probe_key].into()));
+            self.expr
+                .add(Expr::HashJoin([ty, cond, lkeys, rkeys, build, probe]))
+        }
+
+        fn filters(&self, root: Id) -> Vec<RuntimeFilterDesc> {
+            runtime_filters(root, |id| &self.expr[id], &self.catalog)
+        }
+    }
+
+    #[test]
+    fn scan_path() {
+        let mut b = PlanBuilder::new();
+        let build = b.scan("s");
+        let scan = b.scan("t");
+        let cond = b.expr.add(Expr::true_());
+        let filter = b.expr.add(Expr::Filter([cond, scan]));
+        let join = b.hashjoin(build, "s", filter, "t");
+
+        let filters = b.filters(join);
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].join, join);
+        assert_eq!(filters[0].scan, [filter, scan]);
+    }
+
+    #[test]
+    fn self_join_on_probe_side() {
+        let mut b = PlanBuilder::new();
+        let build = b.scan("s");
+        // both sides of the inner join are the same scan
+        let scan = b.scan("t");
+        let ty = b.expr.add(Expr::Inner);
+        let cond = b.expr.add(Expr::true_());
+        let probe = b.expr.add(Expr::Join([ty, cond, scan, scan]));
+        let join = b.hashjoin(build, "s", probe, "t");
+
+        assert!(b.filters(join).is_empty());
+    }
+}
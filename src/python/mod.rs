@@ -1,17 +1,18 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use pyo3::prelude::*;
 use tokio::runtime::Runtime;
 
 use crate::storage::SecondaryStorageOptions;
-use crate::Database;
+use crate::{Database, Session};
 
 #[pyclass]
 pub struct PythonDatabase {
     runtime: Runtime,
-    database: Database,
+    session: Session,
 }
 use pyo3::exceptions::PyException;
 
@@ -22,7 +23,7 @@ impl PythonDatabase {
     pub fn query(&self, py: Python<'_>, sql: String) -> PyResult<Vec<Vec<PyObject>>> {
         let result = self
             .runtime
-            .block_on(async { self.database.run(&sql).await });
+            .block_on(async { self.session.run(&sql).await });
         match result {
             Ok(chunks) => {
                 let mut rows = vec![];
@@ -49,7 +50,8 @@ pub fn open(path: String) -> PyResult<PythonDatabase> {
     options.path = PathBuf::new().join(path);
 
     let database = runtime.block_on(async move { Database::new_on_disk(options).await });
-    Ok(PythonDatabase { runtime, database })
+    let session = Session::new(Arc::new(database));
+    Ok(PythonDatabase { runtime, session })
 }
 
 /// Open a database for user in memory
@@ -58,8 +60,8 @@ pub fn open_in_memory() -> PyResult<PythonDatabase> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
-    let database = Database::new_in_memory();
-    Ok(PythonDatabase { runtime, database })
+    let session = Session::new(Arc::new(Database::new_in_memory()));
+    Ok(PythonDatabase { runtime, session })
 }
 
 #[pymodule]
@@ -0,0 +1,221 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Authentication of clients against the users in the catalog.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{Sink, SinkExt};
+use pgwire::api::auth::{
+    finish_authentication, save_startup_parameters_to_metadata, AuthSource,
+    DefaultServerParameterProvider, LoginInfo, Password, StartupHandler,
+};
+use pgwire::api::{ClientInfo, PgWireConnectionState, METADATA_USER};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::response::ErrorResponse;
+use pgwire::messages::startup::Authentication;
+use pgwire::messages::{PgWireBackendMessage, PgWireFrontendMessage};
+
+use crate::catalog::{PasswordVerifier, RootCatalogRef};
+use crate::Session;
+
+/// The startup parameter of the application name.
+const APPLICATION_NAME: &str = "application_name";
+
+/// The method to authenticate clients.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AuthMethod {
+    /// Allow users to connect without a password.
+    ///
+    /// Only the password is not verified. Users must still be allowed to log in,
+    /// and their privileges are checked the same as other methods.
+    #[default]
+    Trust,
+    /// Require a password sent in clear text.
+    Password,
+    /// Require a password hashed with MD5.
+    Md5,
+    /// Require a password verified by SCRAM-SHA-256.
+    #[value(name = "scram-sha-256")]
+    ScramSha256,
+}
+
+/// Returns the password verifier of the user who is allowed to log in.
+fn login_verifier(catalog: &RootCatalogRef, login: &LoginInfo) -> Option<PasswordVerifier> {
+    let user = catalog.get_user_by_name(login.user()?)?;
+    user.can_login().then(|| user.password().cloned()).flatten()
+}
+
+/// Returns true if the password is correct and the user is allowed to log in.
+fn verify(catalog: &RootCatalogRef, login: &LoginInfo, password: &str) -> bool {
+    login_verifier(catalog, login).is_some_and(|verifier| verifier.verify(password))
+}
+
+/// Authenticates clients without passwords.
+pub struct TrustStartupHandler {
+    pub catalog: RootCatalogRef,
+}
+
+#[async_trait]
+impl StartupHandler for TrustStartupHandler {
+    async fn on_startup<C>(
+        &self,
+        client: &mut C,
+        message: PgWireFrontendMessage,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if let PgWireFrontendMessage::Startup(ref startup) = message {
+            save_startup_parameters_to_metadata(client, startup);
+            let login = LoginInfo::from_client_info(client);
+            let name = login.user().unwrap_or_default();
+            let error = match self.catalog.get_user_by_name(name) {
+                Some(user) if user.can_login() => None,
+                Some(_) => Some(format!("role \"{name}\" is not permitted to log in")),
+                None => Some(format!("role \"{name}\" does not exist")),
+            };
+            if let Some(message) = error {
+                let error = ErrorInfo::new("FATAL".to_owned(), "28000".to_owned(), message);
+                client
+                    .feed(PgWireBackendMessage::ErrorResponse(error.into()))
+                    .await?;
+                client.close().await?;
+            } else {
+                finish_authentication(client, &DefaultServerParameterProvider::default()).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A startup handler that starts the session of the connection once the client is authenticated.
+///
+/// The session runs queries as the user in the startup message.
+pub struct SessionStartupHandler<A> {
+    pub inner: Arc<A>,
+    pub session: Arc<Session>,
+}
+
+#[async_trait]
+impl<A: StartupHandler> StartupHandler for SessionStartupHandler<A> {
+    async fn on_startup<C>(
+        &self,
+        client: &mut C,
+        message: PgWireFrontendMessage,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        self.inner.on_startup(client, message).await?;
+        if matches!(client.state(), PgWireConnectionState::ReadyForQuery) {
+            if let Some(user) = client.metadata().get(METADATA_USER) {
+                self.session.set_user(user);
+            }
+            if let Some(name) = client.metadata().get(APPLICATION_NAME) {
+                self.session.set_application_name(name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Authenticates clients with passwords in clear text.
+pub struct CleartextStartupHandler {
+    pub catalog: RootCatalogRef,
+}
+
+#[async_trait]
+impl StartupHandler for CleartextStartupHandler {
+    async fn on_startup<C>(
+        &self,
+        client: &mut C,
+        message: PgWireFrontendMessage,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        match message {
+            PgWireFrontendMessage::Startup(ref startup) => {
+                save_startup_parameters_to_metadata(client, startup);
+                client.set_state(PgWireConnectionState::AuthenticationInProgress);
+                client
+                    .send(PgWireBackendMessage::Authentication(
+                        Authentication::CleartextPassword,
+                    ))
+                    .await?;
+            }
+            PgWireFrontendMessage::PasswordMessageFamily(pwd) => {
+                let pwd = pwd.into_password()?;
+                let login = LoginInfo::from_client_info(client);
+                if verify(&self.catalog, &login, &pwd.password) {
+                    finish_authentication(client, &DefaultServerParameterProvider::default()).await;
+                } else {
+                    let error = ErrorResponse::from(authentication_failed(&login));
+                    client
+                        .feed(PgWireBackendMessage::ErrorResponse(error))
+                        .await?;
+                    client.close().await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Provides salted MD5 hashes of passwords.
+///
+/// Clients not allowed to log in get a hash that never matches,
+/// so that they fail the same way as a wrong password.
+pub struct Md5AuthSource {
+    pub catalog: RootCatalogRef,
+}
+
+#[async_trait]
+impl AuthSource for Md5AuthSource {
+    async fn get_password(&self, login: &LoginInfo) -> PgWireResult<Password> {
+        let salt: [u8; 4] = rand::random();
+        let password = match login_verifier(&self.catalog, login) {
+            Some(verifier) => verifier.md5_response(&salt),
+            None => String::new(),
+        };
+        Ok(Password::new(Some(salt.to_vec()), password.into_bytes()))
+    }
+}
+
+/// Provides salted passwords of SCRAM-SHA-256.
+///
+/// Clients not allowed to log in get a random salted password,
+/// so that they fail the same way as a wrong password.
+pub struct ScramAuthSource {
+    pub catalog: RootCatalogRef,
+}
+
+#[async_trait]
+impl AuthSource for ScramAuthSource {
+    async fn get_password(&self, login: &LoginInfo) -> PgWireResult<Password> {
+        let verifier = login_verifier(&self.catalog, login)
+            .unwrap_or_else(|| PasswordVerifier::new("", &rand::random::<u64>().to_string()));
+        let (salt, salted_password) = verifier.scram_salted_password();
+        Ok(Password::new(Some(salt.to_vec()), salted_password.to_vec()))
+    }
+}
+
+fn authentication_failed(login: &LoginInfo) -> ErrorInfo {
+    ErrorInfo::new(
+        "FATAL".to_owned(),
+        "28P01".to_owned(),
+        format!(
+            "password authentication failed for user \"{}\"",
+            login.user().unwrap_or_default()
+        ),
+    )
+}
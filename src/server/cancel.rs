@@ -0,0 +1,180 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::Sink;
+use pgwire::api::auth::StartupHandler;
+use pgwire::api::{ClientInfo, PgWireConnectionState};
+use pgwire::error::{PgWireError, PgWireResult};
+use pgwire::messages::startup::BackendKeyData;
+use pgwire::messages::{PgWireBackendMessage, PgWireFrontendMessage};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+use crate::Session;
+
+/// The key of a connection that clients send in a `CancelRequest` to cancel its query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CancelKey {
+    /// The ID of the connection, which is the process ID in Postgres.
+    pub pid: i32,
+    pub secret_key: i32,
+}
+
+/// The sessions of connections that can be cancelled by their keys.
+#[derive(Default)]
+pub struct CancelRegistry {
+    sessions: Mutex<HashMap<CancelKey, Weak<Session>>>,
+}
+
+impl CancelRegistry {
+    /// The length of a `CancelRequest` message.
+    const REQUEST_LENGTH: usize = 16;
+    /// The request code of a `CancelRequest` message in place of the protocol version.
+    const REQUEST_CODE: i32 = 80877102;
+
+    /// Registers the session of a new connection and returns its key.
+    ///
+    /// The ID of the session is used as the process ID, like in `pg_stat_activity`.
+    pub fn register(&self, session: &Arc<Session>) -> CancelKey {
+        let key = CancelKey {
+            pid: session.id(),
+            secret_key: rand::random(),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(key, Arc::downgrade(session));
+        key
+    }
+
+    /// Removes the session of a closed connection.
+    pub fn unregister(&self, key: CancelKey) {
+        self.sessions.lock().unwrap().remove(&key);
+    }
+
+    /// Cancels the running query of the session with the key.
+    ///
+    /// Unknown keys are ignored, like in Postgres.
+    pub fn cancel(&self, key: CancelKey) {
+        let session = self.sessions.lock().unwrap().get(&key).cloned();
+        if let Some(session) = session.and_then(|s| s.upgrade()) {
+            session.cancel();
+        }
+    }
+
+    /// Handles the connection if it is opened to send a `CancelRequest`.
+    ///
+    /// Returns `false` without reading anything if the connection starts with another message.
+    pub async fn handle_cancel_request(&self, socket: &mut TcpStream) -> std::io::Result<bool> {
+        let mut buf = [0; Self::REQUEST_LENGTH];
+        let mut len = 0;
+        while len < 8 {
+            len = socket.peek(&mut buf).await?;
+            if len == 0 {
+                return Ok(false);
+            }
+        }
+        let length = i32::from_be_bytes(buf[0..4].try_into().unwrap());
+        let code = i32::from_be_bytes(buf[4..8].try_into().unwrap());
+        if length != Self::REQUEST_LENGTH as i32 || code != Self::REQUEST_CODE {
+            return Ok(false);
+        }
+        socket.read_exact(&mut buf).await?;
+        self.cancel(CancelKey {
+            pid: i32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            secret_key: i32::from_be_bytes(buf[12..16].try_into().unwrap()),
+        });
+        Ok(true)
+    }
+}
+
+/// A startup handler that sends the cancel key of the connection to the client.
+///
+/// `pgwire` sends a random key that it doesn't keep, so we replace it with ours.
+pub struct CancelKeyStartupHandler<A> {
+    pub inner: Arc<A>,
+    pub key: CancelKey,
+}
+
+#[async_trait]
+impl<A: StartupHandler> StartupHandler for CancelKeyStartupHandler<A> {
+    async fn on_startup<C>(
+        &self,
+        client: &mut C,
+        message: PgWireFrontendMessage,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let mut client = KeyedClient {
+            client,
+            key: self.key,
+        };
+        self.inner.on_startup(&mut client, message).await
+    }
+}
+
+/// A client that replaces the `BackendKeyData` sent to it with the cancel key.
+struct KeyedClient<'a, C> {
+    client: &'a mut C,
+    key: CancelKey,
+}
+
+impl<C: ClientInfo> ClientInfo for KeyedClient<'_, C> {
+    fn socket_addr(&self) -> SocketAddr {
+        self.client.socket_addr()
+    }
+
+    fn is_secure(&self) -> bool {
+        self.client.is_secure()
+    }
+
+    fn state(&self) -> PgWireConnectionState {
+        self.client.state()
+    }
+
+    fn set_state(&mut self, new_state: PgWireConnectionState) {
+        self.client.set_state(new_state)
+    }
+
+    fn metadata(&self) -> &HashMap<String, String> {
+        self.client.metadata()
+    }
+
+    fn metadata_mut(&mut self) -> &mut HashMap<String, String> {
+        self.client.metadata_mut()
+    }
+}
+
+impl<C: Sink<PgWireBackendMessage> + Unpin> Sink<PgWireBackendMessage> for KeyedClient<'_, C> {
+    type Error = C::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut *self.client).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: PgWireBackendMessage) -> Result<(), C::Error> {
+        let item = match item {
+            PgWireBackendMessage::BackendKeyData(_) => PgWireBackendMessage::BackendKeyData(
+                BackendKeyData::new(self.key.pid, self.key.secret_key),
+            ),
+            item => item,
+        };
+        Pin::new(&mut *self.client).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut *self.client).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut *self.client).poll_close(cx)
+    }
+}
@@ -1,45 +1,97 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+mod auth;
+mod cancel;
 mod processor;
+mod types;
 
 use std::sync::Arc;
 
-use pgwire::api::auth::noop::NoopStartupHandler;
-use pgwire::api::query::PlaceholderExtendedQueryHandler;
-use pgwire::api::{MakeHandler, StatelessMakeHandler};
+use pgwire::api::auth::md5pass::MakeMd5PasswordAuthStartupHandler;
+use pgwire::api::auth::scram::MakeSASLScramAuthStartupHandler;
+use pgwire::api::auth::{DefaultServerParameterProvider, StartupHandler};
+use pgwire::api::MakeHandler;
 use pgwire::tokio::process_socket;
 use tokio::net::TcpListener;
 use tracing::info;
 
+pub use self::auth::AuthMethod;
+use self::auth::{
+    CleartextStartupHandler, Md5AuthSource, ScramAuthSource, SessionStartupHandler,
+    TrustStartupHandler,
+};
+use self::cancel::{CancelKeyStartupHandler, CancelRegistry};
+use crate::catalog::PasswordVerifier;
 use crate::server::processor::Processor;
-use crate::Database;
+use crate::{Database, Session};
 
-pub async fn run_server(host: Option<String>, port: Option<u16>, db: Database) {
-    let processor = Arc::new(Processor::new(db));
-    let authenticator = Arc::new(NoopStartupHandler);
+pub async fn run_server(host: Option<String>, port: Option<u16>, db: Database, auth: AuthMethod) {
+    let catalog = db.catalog().clone();
+    let db = Arc::new(db);
     let addr = format!(
         "{}:{}",
         host.unwrap_or_else(|| "127.0.0.1".to_string()),
         port.unwrap_or(5432)
     );
     let listener = TcpListener::bind(&addr).await.unwrap();
-    info!("Listening on: {}", addr);
+    info!("Listening on: {} (auth: {:?})", addr, auth);
+    let parameters = Arc::new(DefaultServerParameterProvider::default());
+    match auth {
+        AuthMethod::Trust => {
+            let authenticator = Arc::new(TrustStartupHandler { catalog });
+            serve(listener, &db, || authenticator.clone()).await
+        }
+        AuthMethod::Password => {
+            let authenticator = Arc::new(CleartextStartupHandler { catalog });
+            serve(listener, &db, || authenticator.clone()).await
+        }
+        AuthMethod::Md5 => {
+            let source = Arc::new(Md5AuthSource { catalog });
+            let authenticator = MakeMd5PasswordAuthStartupHandler::new(source, parameters);
+            serve(listener, &db, || authenticator.make()).await
+        }
+        AuthMethod::ScramSha256 => {
+            let source = Arc::new(ScramAuthSource { catalog });
+            let mut authenticator = MakeSASLScramAuthStartupHandler::new(source, parameters);
+            authenticator.set_iterations(PasswordVerifier::SCRAM_ITERATIONS);
+            serve(listener, &db, || authenticator.make()).await
+        }
+    }
+}
+
+/// Accepts connections and authenticates each of them with a new startup handler.
+///
+/// Each connection has its own session of the database, which runs queries as the connected
+/// user. The queries can be cancelled by `CancelRequest`s from other connections.
+async fn serve<A: StartupHandler + 'static>(
+    listener: TcpListener,
+    db: &Arc<Database>,
+    authenticator: impl Fn() -> Arc<A>,
+) {
+    let registry = Arc::new(CancelRegistry::default());
     loop {
-        let incoming_socket = listener.accept().await.unwrap();
-        let authenticator_ref = authenticator.clone();
-        let processor_ref = processor.clone();
-        let placeholder = Arc::new(StatelessMakeHandler::new(Arc::new(
-            PlaceholderExtendedQueryHandler,
-        )));
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let authenticator = authenticator();
+        let db = db.clone();
+        let registry = registry.clone();
         tokio::spawn(async move {
-            process_socket(
-                incoming_socket.0,
-                None,
-                authenticator_ref,
-                processor_ref.clone(),
-                placeholder.make(),
-            )
-            .await
+            if registry.handle_cancel_request(&mut socket).await? {
+                return Ok(());
+            }
+            let session = Arc::new(Session::new(db));
+            let key = registry.register(&session);
+            let authenticator = Arc::new(SessionStartupHandler {
+                inner: Arc::new(CancelKeyStartupHandler {
+                    inner: authenticator,
+                    key,
+                }),
+                session: session.clone(),
+            });
+            let processor = Arc::new(Processor::new(session));
+            let result =
+                process_socket(socket, None, authenticator, processor.clone(), processor).await;
+            registry.unregister(key);
+            result
         });
     }
 }
@@ -1,24 +1,57 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
-use futures::stream;
-use pgwire::api::query::SimpleQueryHandler;
-use pgwire::api::results::{DataRowEncoder, FieldFormat, FieldInfo, QueryResponse, Response, Tag};
-use pgwire::api::{ClientInfo, Type};
-use pgwire::error::{PgWireError, PgWireResult};
+use futures::{stream, Sink, SinkExt, TryStreamExt};
+use pgwire::api::portal::{Format, Portal};
+use pgwire::api::query::{send_execution_response, ExtendedQueryHandler, SimpleQueryHandler};
+use pgwire::api::results::{
+    DataRowEncoder, DescribePortalResponse, DescribeStatementResponse, FieldInfo, QueryResponse,
+    Response, Tag,
+};
+use pgwire::api::stmt::{QueryParser, StoredStatement};
+use pgwire::api::store::PortalStore;
+use pgwire::api::{ClientInfo, ClientPortalStore, Type, DEFAULT_NAME};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+use pgwire::messages::extendedquery::{Execute, PortalSuspended};
+use pgwire::messages::response::EmptyQueryResponse;
+use pgwire::messages::PgWireBackendMessage;
 use tracing::info;
 
-use crate::Database;
+use super::types::{data_type_from_pg, data_type_to_pg, PgValue};
+use crate::array::Chunk;
+use crate::storage::StorageError;
+use crate::types::{DataType, DataValue};
+use crate::{Error, PreparedStatement, Session};
 
+/// Handles the queries of a connection.
 pub struct Processor {
-    db: Database,
+    session: Arc<Session>,
+    parser: Arc<Parser>,
+    /// Portals with rows not sent yet, by the names of portals.
+    suspended: Mutex<HashMap<String, SuspendedPortal>>,
+}
+
+/// A portal whose execution is suspended by the row limit of `Execute`.
+struct SuspendedPortal {
+    portal: Arc<Portal<Arc<PreparedStatement>>>,
+    rows: std::vec::IntoIter<DataRow>,
 }
 
 impl Processor {
-    pub fn new(db: Database) -> Self {
-        Self { db }
+    /// Creates a processor to run queries in the session.
+    pub fn new(session: Arc<Session>) -> Self {
+        Self {
+            parser: Arc::new(Parser {
+                session: session.clone(),
+            }),
+            session,
+            suspended: Mutex::new(HashMap::new()),
+        }
     }
 }
 
@@ -33,44 +66,552 @@ impl SimpleQueryHandler for Processor {
         C: ClientInfo + Unpin + Send + Sync,
     {
         info!("query:{query:?}");
-        let chunks = self
-            .db
-            .run(query)
-            .await
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
-
-        if !query.to_uppercase().starts_with("SELECT") {
-            return Ok(vec![Response::Execution(Tag::new("OK"))]);
-        }
-        let mut results = Vec::new();
-        let mut headers = None;
-        for chunk in chunks {
-            for data_chunk in chunk.data_chunks() {
-                for i in 0..data_chunk.cardinality() {
-                    let headers = headers.get_or_insert_with(|| {
-                        Arc::new(vec![
-                            FieldInfo::new(
-                                "++".into(),
-                                None,
-                                None,
-                                Type::CHAR,
-                                FieldFormat::Text
-                            );
-                            data_chunk.arrays().len()
-                        ])
-                    });
-                    let mut encoder = DataRowEncoder::new(headers.clone());
-                    data_chunk.arrays().iter().for_each(|a| {
-                        let field = a.get_to_string(i);
-                        encoder.encode_field(&field).unwrap();
-                    });
-                    results.push(encoder.finish());
+        let chunks = (self.session.run(query).await).map_err(query_error)?;
+        if chunks.is_empty() {
+            return Ok(vec![Response::EmptyQuery]);
+        }
+        chunks
+            .iter()
+            .map(|chunk| response(chunk, &Format::UnifiedText))
+            .collect()
+    }
+}
+
+/// Prepares statements for the extended query protocol.
+pub struct Parser {
+    session: Arc<Session>,
+}
+
+#[async_trait]
+impl QueryParser for Parser {
+    type Statement = Arc<PreparedStatement>;
+
+    async fn parse_sql(&self, sql: &str, types: &[Type]) -> PgWireResult<Self::Statement> {
+        info!("parse:{sql:?}");
+        let types = types.iter().map(data_type_from_pg).collect::<Vec<_>>();
+        let stmt = (self.session.prepare(sql, &types).await).map_err(query_error)?;
+        Ok(Arc::new(stmt))
+    }
+}
+
+#[async_trait]
+impl ExtendedQueryHandler for Processor {
+    type Statement = Arc<PreparedStatement>;
+    type QueryParser = Parser;
+
+    fn query_parser(&self) -> Arc<Self::QueryParser> {
+        self.parser.clone()
+    }
+
+    /// Sends at most `max_rows` rows of the results, and suspends the portal if there are more.
+    /// The remaining rows are sent by the following executions of the portal.
+    async fn on_execute<C>(&self, client: &mut C, message: Execute) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let name = message.name.as_deref().unwrap_or(DEFAULT_NAME);
+        let Some(portal) = client.portal_store().get_portal(name) else {
+            return Err(PgWireError::PortalNotFound(name.to_owned()));
+        };
+        // the portal may be bound again since it was suspended
+        let suspended = (self.suspended.lock().unwrap().remove(name))
+            .filter(|suspended| Arc::ptr_eq(&suspended.portal, &portal));
+        let mut suspended = match suspended {
+            Some(suspended) => suspended,
+            None => match ExtendedQueryHandler::do_query(self, client, portal.as_ref(), 0).await? {
+                Response::Query(results) => SuspendedPortal {
+                    rows: results
+                        .data_rows()
+                        .try_collect::<Vec<_>>()
+                        .await?
+                        .into_iter(),
+                    portal: portal.clone(),
+                },
+                Response::EmptyQuery => {
+                    let message =
+                        PgWireBackendMessage::EmptyQueryResponse(EmptyQueryResponse::new());
+                    return Ok(client.feed(message).await?);
+                }
+                Response::Execution(tag) => return send_execution_response(client, tag).await,
+                Response::Error(err) => {
+                    let message = PgWireBackendMessage::ErrorResponse((*err).into());
+                    return Ok(client.send(message).await?);
                 }
+            },
+        };
+        let max_rows = match message.max_rows {
+            n if n > 0 => n as usize,
+            _ => usize::MAX,
+        };
+        let mut rows = 0;
+        for row in suspended.rows.by_ref().take(max_rows) {
+            rows += 1;
+            client.feed(PgWireBackendMessage::DataRow(row)).await?;
+        }
+        if suspended.rows.len() == 0 {
+            let tag = Tag::new("SELECT").with_rows(rows);
+            client
+                .send(PgWireBackendMessage::CommandComplete(tag.into()))
+                .await?;
+        } else {
+            client
+                .send(PgWireBackendMessage::PortalSuspended(PortalSuspended::new()))
+                .await?;
+            (self.suspended.lock().unwrap()).insert(name.to_owned(), suspended);
+        }
+        Ok(())
+    }
+
+    /// Runs the statement of the portal. The number of rows is limited by
+    /// [`on_execute`](Self::on_execute).
+    async fn do_query<'a, 'b: 'a, C>(
+        &'b self,
+        _client: &mut C,
+        portal: &'a Portal<Self::Statement>,
+        _max_rows: usize,
+    ) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let stmt = &portal.statement.statement;
+        if stmt.statement().is_none() {
+            return Ok(Response::EmptyQuery);
+        }
+        let params = decode_params(portal, stmt.param_types())?;
+        let chunk = (self.session)
+            .execute_prepared(stmt, &params)
+            .await
+            .map_err(query_error)?;
+        response(&chunk, &portal.result_column_format)
+    }
+
+    async fn do_describe_statement<C>(
+        &self,
+        _client: &mut C,
+        target: &StoredStatement<Self::Statement>,
+    ) -> PgWireResult<DescribeStatementResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let stmt = &target.statement;
+        let parameters = stmt.param_types().iter().map(data_type_to_pg).collect();
+        let fields = fields(output_columns(stmt), &Format::UnifiedText);
+        Ok(DescribeStatementResponse::new(parameters, fields))
+    }
+
+    async fn do_describe_portal<C>(
+        &self,
+        _client: &mut C,
+        target: &Portal<Self::Statement>,
+    ) -> PgWireResult<DescribePortalResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let stmt = &target.statement.statement;
+        let fields = fields(output_columns(stmt), &target.result_column_format);
+        Ok(DescribePortalResponse::new(fields))
+    }
+}
+
+/// Returns the names and types of output columns of a prepared statement.
+fn output_columns(stmt: &PreparedStatement) -> impl Iterator<Item = (&str, &DataType)> {
+    stmt.output().iter().map(|(name, ty)| (name.as_str(), ty))
+}
+
+/// Returns the fields of output columns.
+fn fields<'a>(
+    columns: impl Iterator<Item = (&'a str, &'a DataType)>,
+    format: &Format,
+) -> Vec<FieldInfo> {
+    (columns.enumerate())
+        .map(|(i, (name, ty))| {
+            FieldInfo::new(
+                name.to_string(),
+                None,
+                None,
+                data_type_to_pg(ty),
+                format.format_for(i),
+            )
+        })
+        .collect()
+}
+
+/// Converts the output chunk of a statement to a response.
+///
+/// Chunks with types are results of queries, and the others are results of commands.
+fn response<'a>(chunk: &Chunk, format: &Format) -> PgWireResult<Response<'a>> {
+    let (Some(header), Some(types)) = (chunk.header(), chunk.types()) else {
+        return Ok(Response::Execution(command_tag(chunk)));
+    };
+    let names = header.iter().map(|name| match name.as_str() {
+        "$explain" => "QUERY PLAN",
+        name => name,
+    });
+    let fields = Arc::new(fields(names.zip(types), format));
+    let mut rows = vec![];
+    for data_chunk in chunk.data_chunks() {
+        for row in data_chunk.rows() {
+            let mut encoder = DataRowEncoder::new(fields.clone());
+            for value in row.values() {
+                encoder.encode_field(&PgValue(&value))?;
+            }
+            rows.push(encoder.finish());
+        }
+    }
+    Ok(Response::Query(QueryResponse::new(
+        fields,
+        stream::iter(rows),
+    )))
+}
+
+/// Returns the command tag of a statement that doesn't return rows.
+fn command_tag(chunk: &Chunk) -> Tag {
+    let row_count = || match chunk.data_chunks().first().map(|c| c.array_at(0).get(0)) {
+        Some(DataValue::Int32(n)) => n as usize,
+        Some(DataValue::Int64(n)) => n as usize,
+        _ => 0,
+    };
+    match chunk
+        .header()
+        .and_then(|header| header.first())
+        .map(|s| s.as_str())
+    {
+        Some("$insert.row_counts") => Tag::new("INSERT 0").with_rows(row_count()),
+        Some("$delete.row_counts") => Tag::new("DELETE").with_rows(row_count()),
+        Some("$copy.row_counts") => Tag::new("COPY").with_rows(row_count()),
+        Some("$create") => Tag::new("CREATE TABLE"),
+        Some("$create_view") => Tag::new("CREATE VIEW"),
+        Some("$create_index") => Tag::new("CREATE INDEX"),
+        Some("$create_function") => Tag::new("CREATE FUNCTION"),
+        Some("$drop") => Tag::new("DROP TABLE"),
+        Some("$drop_view") => Tag::new("DROP VIEW"),
+        Some("$alter_table") => Tag::new("ALTER TABLE"),
+        Some("$truncate") => Tag::new("TRUNCATE TABLE"),
+        Some("$set") => Tag::new("SET"),
+        Some("$backup") => Tag::new("BACKUP"),
+        Some("$vacuum") => Tag::new("VACUUM"),
+        Some("$compact") => Tag::new("COMPACT"),
+        Some("$create_role") => Tag::new("CREATE ROLE"),
+        Some("$alter_role") => Tag::new("ALTER ROLE"),
+        Some("$drop_role") => Tag::new("DROP ROLE"),
+        Some("$grant") => Tag::new("GRANT"),
+        Some("$revoke") => Tag::new("REVOKE"),
+        Some("$grant_role") => Tag::new("GRANT ROLE"),
+        Some("$revoke_role") => Tag::new("REVOKE ROLE"),
+        Some("$begin") => Tag::new("BEGIN"),
+        Some("$commit") => Tag::new("COMMIT"),
+        Some("$rollback") => Tag::new("ROLLBACK"),
+        Some("$prepare") => Tag::new("PREPARE"),
+        Some("$deallocate") => Tag::new("DEALLOCATE"),
+        _ => Tag::new("OK"),
+    }
+}
+
+/// Decodes the values of parameters in the portal.
+///
+/// Text values are passed as strings and casted to the parameter types on execution.
+fn decode_params(
+    portal: &Portal<Arc<PreparedStatement>>,
+    types: &[DataType],
+) -> PgWireResult<Vec<DataValue>> {
+    let mut values = Vec::with_capacity(types.len());
+    for (i, ty) in types.iter().enumerate() {
+        let Some(Some(bytes)) = portal.parameters.get(i) else {
+            values.push(DataValue::Null);
+            continue;
+        };
+        if portal.parameter_format.is_text(i) {
+            let s = std::str::from_utf8(bytes).map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            values.push(DataValue::String(s.into()));
+            continue;
+        }
+        // the declared type may be narrower than the parameter type, e.g. FLOAT4 for FLOAT8
+        let pg_type = match portal.statement.parameter_types.get(i) {
+            Some(pg_type) if *pg_type != Type::UNKNOWN => pg_type.clone(),
+            _ => data_type_to_pg(ty),
+        };
+        let value = match ty {
+            DataType::Bool => portal.parameter(i, &pg_type)?.map(DataValue::Bool),
+            DataType::Int16 => portal.parameter(i, &pg_type)?.map(DataValue::Int16),
+            DataType::Int32 => portal.parameter(i, &pg_type)?.map(DataValue::Int32),
+            DataType::Int64 => portal.parameter(i, &pg_type)?.map(DataValue::Int64),
+            DataType::Float64 if pg_type == Type::FLOAT4 => (portal
+                .parameter::<f32>(i, &pg_type)?)
+            .map(|v| DataValue::Float64((v as f64).into())),
+            DataType::Float64 => {
+                (portal.parameter::<f64>(i, &pg_type)?).map(|v| DataValue::Float64(v.into()))
+            }
+            DataType::String => {
+                (portal.parameter::<String>(i, &pg_type)?).map(|v| DataValue::String(v.into()))
             }
+            DataType::Blob => {
+                (portal.parameter::<Vec<u8>>(i, &pg_type)?).map(|v| DataValue::Blob(v.into()))
+            }
+            DataType::Decimal(_, _) => portal.parameter(i, &pg_type)?.map(DataValue::Decimal),
+            _ => return Err(unsupported(&format!("binary format of {ty} parameters"))),
+        };
+        values.push(value.unwrap_or(DataValue::Null));
+    }
+    Ok(values)
+}
+
+/// Converts the error of a statement. Write conflicts are reported as serialization failures, so
+/// that clients know to retry the transaction.
+fn query_error(e: Error) -> PgWireError {
+    if !e.is_write_conflict() {
+        return PgWireError::ApiError(Box::new(e));
+    }
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".into(),
+        "40001".into(),
+        StorageError::WriteConflict.to_string(),
+    )))
+}
+
+fn unsupported(feature: &str) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".into(),
+        "0A000".into(),
+        format!("{feature} is not supported"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use pgwire::api::auth::StartupHandler;
+    use pgwire::api::store::MemPortalStore;
+    use pgwire::api::PgWireConnectionState;
+    use pgwire::messages::extendedquery::{Bind, Parse};
+    use pgwire::messages::startup::Startup;
+    use pgwire::messages::PgWireFrontendMessage;
+
+    use super::*;
+    use crate::server::auth::{SessionStartupHandler, TrustStartupHandler};
+    use crate::Database;
+
+    /// A client that records the messages sent to it.
+    struct MockClient {
+        state: PgWireConnectionState,
+        metadata: HashMap<String, String>,
+        portals: MemPortalStore<Arc<PreparedStatement>>,
+        messages: Vec<PgWireBackendMessage>,
+    }
+
+    impl MockClient {
+        fn new() -> Self {
+            Self {
+                state: PgWireConnectionState::default(),
+                metadata: HashMap::new(),
+                portals: MemPortalStore::new(),
+                messages: vec![],
+            }
+        }
+    }
+
+    impl ClientInfo for MockClient {
+        fn socket_addr(&self) -> SocketAddr {
+            ([127, 0, 0, 1], 5432).into()
+        }
+
+        fn is_secure(&self) -> bool {
+            false
+        }
+
+        fn state(&self) -> PgWireConnectionState {
+            self.state
+        }
+
+        fn set_state(&mut self, new_state: PgWireConnectionState) {
+            self.state = new_state;
+        }
+
+        fn metadata(&self) -> &HashMap<String, String> {
+            &self.metadata
+        }
+
+        fn metadata_mut(&mut self) -> &mut HashMap<String, String> {
+            &mut self.metadata
+        }
+    }
+
+    impl ClientPortalStore for MockClient {
+        type PortalStore = MemPortalStore<Arc<PreparedStatement>>;
+
+        fn portal_store(&self) -> &Self::PortalStore {
+            &self.portals
+        }
+    }
+
+    impl Sink<PgWireBackendMessage> for MockClient {
+        type Error = std::io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: PgWireBackendMessage) -> std::io::Result<()> {
+            self.get_mut().messages.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
         }
-        Ok(vec![Response::Query(QueryResponse::new(
-            headers.expect("fixme: db should return schema even if no output data"),
-            stream::iter(results.into_iter()),
-        ))])
+    }
+
+    /// Returns the names of messages sent to the client, and clears them.
+    fn take_messages(client: &mut MockClient) -> Vec<String> {
+        (client.messages.drain(..))
+            .map(|message| match message {
+                PgWireBackendMessage::DataRow(_) => "DataRow".into(),
+                PgWireBackendMessage::PortalSuspended(_) => "PortalSuspended".into(),
+                PgWireBackendMessage::CommandComplete(tag) => tag.tag,
+                PgWireBackendMessage::ReadyForQuery(_) => "ReadyForQuery".into(),
+                PgWireBackendMessage::ErrorResponse(_) => "ErrorResponse".into(),
+                message => format!("{message:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_extended_query() {
+        let session = Arc::new(Session::new(Arc::new(Database::new_in_memory())));
+        (session
+            .run("create table t (a double); insert into t values (1.5), (2.5), (3.5)")
+            .await)
+            .unwrap();
+        let processor = Processor::new(session);
+        let mut client = MockClient::new();
+
+        // the parameter is declared as FLOAT4 and sent in binary format
+        let query = "select a from t where a > $1".to_string();
+        let parse = Parse::new(None, query, vec![Type::FLOAT4.oid()]);
+        processor.on_parse(&mut client, parse).await.unwrap();
+        let param = Bytes::copy_from_slice(&2.0f32.to_be_bytes());
+        let bind = Bind::new(None, None, vec![1], vec![Some(param)], vec![]);
+        processor.on_bind(&mut client, bind).await.unwrap();
+        take_messages(&mut client);
+
+        // the portal is suspended until all rows are sent
+        let execute = Execute::new(None, 1);
+        processor.on_execute(&mut client, execute).await.unwrap();
+        assert_eq!(take_messages(&mut client), ["DataRow", "PortalSuspended"]);
+        let execute = Execute::new(None, 0);
+        processor.on_execute(&mut client, execute).await.unwrap();
+        assert_eq!(take_messages(&mut client), ["DataRow", "SELECT 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_command_tag() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv = tempdir.path().join("t.csv");
+        std::fs::write(&csv, "3\n4\n5\n").unwrap();
+
+        let session = Session::new(Arc::new(Database::new_in_memory()));
+        let copy = format!("copy t from '{}'", csv.display());
+        let cases = [
+            ("create table t (a int)", Tag::new("CREATE TABLE")),
+            (
+                "create view v (a) as select a from t",
+                Tag::new("CREATE VIEW"),
+            ),
+            (
+                "create index i on t using btree (a)",
+                Tag::new("CREATE INDEX"),
+            ),
+            (
+                "create function f(INT) returns int language sql as 'select $1'",
+                Tag::new("CREATE FUNCTION"),
+            ),
+            (
+                "insert into t values (1), (2)",
+                Tag::new("INSERT 0").with_rows(2),
+            ),
+            (&copy, Tag::new("COPY").with_rows(3)),
+            ("delete from t where a > 3", Tag::new("DELETE").with_rows(2)),
+            ("vacuum t", Tag::new("VACUUM")),
+            ("vacuum full t", Tag::new("VACUUM")),
+            ("compact t", Tag::new("COMPACT")),
+            ("drop view v", Tag::new("DROP VIEW")),
+            ("drop table t", Tag::new("DROP TABLE")),
+            ("set application_name = 'a'", Tag::new("SET")),
+            ("create user alice", Tag::new("CREATE ROLE")),
+            ("alter user alice nologin", Tag::new("ALTER ROLE")),
+            (
+                "grant create on schema postgres to alice",
+                Tag::new("GRANT"),
+            ),
+            (
+                "revoke create on schema postgres from alice",
+                Tag::new("REVOKE"),
+            ),
+            ("drop user alice", Tag::new("DROP ROLE")),
+            ("begin", Tag::new("BEGIN")),
+            ("commit", Tag::new("COMMIT")),
+            ("prepare p as select 1", Tag::new("PREPARE")),
+            ("deallocate p", Tag::new("DEALLOCATE")),
+        ];
+        for (sql, tag) in cases {
+            let chunks = session.run(sql).await.unwrap();
+            assert_eq!(command_tag(&chunks[0]), tag, "{sql}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trust_checks_privileges() {
+        let db = Arc::new(Database::new_in_memory());
+        let admin = Session::new(db.clone());
+        (admin.run("create table t (a int); create user alice").await).unwrap();
+
+        // connects as the user in the startup message
+        let connect = |user: &str| {
+            let session = Arc::new(Session::new(db.clone()));
+            let authenticator = SessionStartupHandler {
+                inner: Arc::new(TrustStartupHandler {
+                    catalog: db.catalog().clone(),
+                }),
+                session: session.clone(),
+            };
+            let mut startup = Startup::new();
+            startup.parameters.insert("user".into(), user.into());
+            async move {
+                let mut client = MockClient::new();
+                let message = PgWireFrontendMessage::Startup(startup);
+                authenticator
+                    .on_startup(&mut client, message)
+                    .await
+                    .unwrap();
+                (Processor::new(session), client)
+            }
+        };
+
+        let (processor, mut client) = connect("alice").await;
+        assert_eq!(take_messages(&mut client).last().unwrap(), "ReadyForQuery");
+        let query = "insert into t values (1)";
+        let Err(err) = SimpleQueryHandler::do_query(&processor, &mut client, query).await else {
+            panic!("insert without privilege should fail");
+        };
+        assert!(err.to_string().contains("permission denied"), "{err}");
+
+        admin.run("grant insert on t to alice").await.unwrap();
+        SimpleQueryHandler::do_query(&processor, &mut client, query)
+            .await
+            .unwrap();
+
+        // users not in the catalog can not connect
+        let (_, mut client) = connect("bob").await;
+        assert_eq!(take_messages(&mut client), ["ErrorResponse"]);
     }
 }
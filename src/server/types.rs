@@ -0,0 +1,237 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Conversion between RisingLight types and Postgres types.
+
+use std::error::Error;
+use std::fmt::Write;
+
+use bytes::{BufMut, BytesMut};
+use pgwire::api::Type;
+use pgwire::types::ToSqlText;
+use postgres_types::{to_sql_checked, IsNull, ToSql};
+
+use crate::types::{DataType, DataValue};
+
+/// The number of days from 1970-01-01 to 2000-01-01, the epoch of Postgres.
+const PG_EPOCH_DAYS: i32 = 10_957;
+/// The number of microseconds from 1970-01-01 to 2000-01-01.
+const PG_EPOCH_MICROS: i64 = PG_EPOCH_DAYS as i64 * 86_400_000_000;
+
+/// Converts a Postgres type to data type. Returns `None` if the type is unknown.
+pub fn data_type_from_pg(ty: &Type) -> Option<DataType> {
+    Some(match *ty {
+        Type::BOOL => DataType::Bool,
+        Type::INT2 => DataType::Int16,
+        Type::INT4 => DataType::Int32,
+        Type::INT8 => DataType::Int64,
+        Type::FLOAT4 | Type::FLOAT8 => DataType::Float64,
+        Type::NUMERIC => DataType::Decimal(None, None),
+        Type::DATE => DataType::Date,
+        Type::TIMESTAMP => DataType::Timestamp,
+        Type::TIMESTAMPTZ => DataType::TimestampTz,
+        Type::INTERVAL => DataType::Interval,
+        Type::CHAR | Type::VARCHAR | Type::TEXT | Type::BPCHAR => DataType::String,
+        Type::BYTEA => DataType::Blob,
+        _ => return None,
+    })
+}
+
+/// Converts a data type to Postgres type.
+///
+/// Types without a Postgres counterpart, such as vectors and structs, are sent as text.
+pub fn data_type_to_pg(ty: &DataType) -> Type {
+    match ty {
+        DataType::Null => Type::UNKNOWN,
+        DataType::Bool => Type::BOOL,
+        DataType::Int16 => Type::INT2,
+        DataType::Int32 => Type::INT4,
+        DataType::Int64 => Type::INT8,
+        DataType::Float64 => Type::FLOAT8,
+        DataType::Decimal(_, _) => Type::NUMERIC,
+        DataType::Date => Type::DATE,
+        DataType::Timestamp => Type::TIMESTAMP,
+        DataType::TimestampTz => Type::TIMESTAMPTZ,
+        DataType::Interval => Type::INTERVAL,
+        DataType::String => Type::VARCHAR,
+        DataType::Blob => Type::BYTEA,
+        DataType::Struct(_) | DataType::Vector(_) => Type::TEXT,
+    }
+}
+
+/// A value to be encoded in text or binary format of Postgres.
+#[derive(Debug)]
+pub struct PgValue<'a>(pub &'a DataValue);
+
+impl ToSql for PgValue<'_> {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self.0 {
+            DataValue::Null => return Ok(IsNull::Yes),
+            DataValue::Bool(v) => out.put_u8(*v as u8),
+            DataValue::Int16(v) => out.put_i16(*v),
+            DataValue::Int32(v) => out.put_i32(*v),
+            DataValue::Int64(v) => out.put_i64(*v),
+            DataValue::Float64(v) => out.put_f64(v.0),
+            DataValue::String(v) => out.put_slice(v.as_bytes()),
+            DataValue::Blob(v) => out.put_slice(v),
+            DataValue::Decimal(v) => return v.to_sql(ty, out),
+            DataValue::Date(v) => out.put_i32(v.get_inner() - PG_EPOCH_DAYS),
+            DataValue::Timestamp(v) => out.put_i64(v.unix_micros() - PG_EPOCH_MICROS),
+            DataValue::TimestampTz(v) => out.put_i64(v.unix_micros() - PG_EPOCH_MICROS),
+            DataValue::Interval(v) => {
+                out.put_i64(v.num_millis() as i64 * 1000);
+                out.put_i32(v.days());
+                out.put_i32(v.num_months());
+            }
+            DataValue::Vector(v) => write!(out, "{v}")?,
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for PgValue<'_> {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self.0 {
+            DataValue::Null => return Ok(IsNull::Yes),
+            DataValue::Bool(v) => out.put_slice(if *v { b"t" } else { b"f" }),
+            DataValue::String(v) => out.put_slice(v.as_bytes()),
+            DataValue::Blob(v) => {
+                out.put_slice(b"\\x");
+                for b in v.iter() {
+                    write!(out, "{b:02x}")?;
+                }
+            }
+            DataValue::Interval(v) if v.is_zero() => out.put_slice(b"00:00:00"),
+            v => write!(out, "{v}")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use postgres_types::FromSql;
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::types::{Date, Interval, Timestamp, TimestampTz, Vector, F64};
+
+    #[test]
+    fn type_round_trip() {
+        let types = [
+            (DataType::Bool, 16),
+            (DataType::Int16, 21),
+            (DataType::Int32, 23),
+            (DataType::Int64, 20),
+            (DataType::Float64, 701),
+            (DataType::Decimal(None, None), 1700),
+            (DataType::Date, 1082),
+            (DataType::Timestamp, 1114),
+            (DataType::TimestampTz, 1184),
+            (DataType::Interval, 1186),
+            (DataType::String, 1043),
+            (DataType::Blob, 17),
+        ];
+        for (ty, oid) in types {
+            let pg_type = data_type_to_pg(&ty);
+            assert_eq!(pg_type.oid(), oid, "{ty}");
+            assert_eq!(data_type_from_pg(&pg_type), Some(ty));
+        }
+        // types without a Postgres counterpart are sent as text
+        let vector = data_type_to_pg(&DataType::Vector(3));
+        assert_eq!(vector, Type::TEXT);
+        assert_eq!(data_type_from_pg(&vector), Some(DataType::String));
+        assert_eq!(data_type_from_pg(&Type::JSON), None);
+    }
+
+    #[test]
+    fn encode_binary() {
+        let encode = |v: DataValue| {
+            let mut out = BytesMut::new();
+            let pg_type = data_type_to_pg(&v.data_type());
+            PgValue(&v).to_sql(&pg_type, &mut out).unwrap();
+            out.to_vec()
+        };
+        // values of types supported by clients are decoded back
+        fn decode<'a, T: FromSql<'a>>(ty: DataType, bytes: &'a [u8]) -> T {
+            T::from_sql(&data_type_to_pg(&ty), bytes).unwrap()
+        }
+        let bytes = encode(DataValue::Bool(true));
+        assert!(decode::<bool>(DataType::Bool, &bytes));
+        let bytes = encode(DataValue::Int16(-2));
+        assert_eq!(decode::<i16>(DataType::Int16, &bytes), -2);
+        let bytes = encode(DataValue::Int32(-3));
+        assert_eq!(decode::<i32>(DataType::Int32, &bytes), -3);
+        let bytes = encode(DataValue::Int64(1 << 40));
+        assert_eq!(decode::<i64>(DataType::Int64, &bytes), 1 << 40);
+        let bytes = encode(DataValue::Float64(1.5.into()));
+        assert_eq!(decode::<f64>(DataType::Float64, &bytes), 1.5);
+        let decimal: Decimal = "-12.345".parse().unwrap();
+        let bytes = encode(DataValue::Decimal(decimal));
+        assert_eq!(
+            decode::<Decimal>(DataType::Decimal(None, None), &bytes),
+            decimal
+        );
+        let bytes = encode(DataValue::String("a'b".into()));
+        assert_eq!(decode::<&str>(DataType::String, &bytes), "a'b");
+        let bytes = encode(DataValue::Blob(b"\x01a".as_slice().into()));
+        assert_eq!(decode::<&[u8]>(DataType::Blob, &bytes), b"\x01a");
+
+        // 2000-01-02
+        let date = Date::new(PG_EPOCH_DAYS + 1);
+        assert_eq!(encode(DataValue::Date(date)), 1i32.to_be_bytes());
+        // 2000-01-01 00:00:01
+        let ts = Timestamp::from_unix_micros(PG_EPOCH_MICROS + 1_000_000);
+        assert_eq!(encode(DataValue::Timestamp(ts)), 1_000_000i64.to_be_bytes());
+        // 2000-01-01 00:00:01 UTC
+        let ts: TimestampTz = "2000-01-01 00:00:01 +00:00".parse().unwrap();
+        assert_eq!(
+            encode(DataValue::TimestampTz(ts)),
+            1_000_000i64.to_be_bytes()
+        );
+        // microseconds, days and months
+        let interval = encode(DataValue::Interval(Interval::from_md(14, 3)));
+        assert_eq!(
+            interval,
+            [[0; 8].as_slice(), &3i32.to_be_bytes(), &14i32.to_be_bytes()].concat()
+        );
+        let interval = encode(DataValue::Interval(Interval::from_secs(2)));
+        assert_eq!(interval[..8], 2_000_000i64.to_be_bytes());
+        // vectors are sent as text
+        let vector = Vector::from(vec![F64::from(1.0), F64::from(2.5)]);
+        assert_eq!(encode(DataValue::Vector(vector)), b"[1,2.5]");
+
+        let mut out = BytesMut::new();
+        let null = PgValue(&DataValue::Null).to_sql(&Type::INT4, &mut out);
+        assert!(matches!(null.unwrap(), IsNull::Yes));
+    }
+
+    #[test]
+    fn encode_text() {
+        let encode = |v: DataValue| {
+            let mut out = BytesMut::new();
+            PgValue(&v).to_sql_text(&Type::UNKNOWN, &mut out).unwrap();
+            String::from_utf8(out.to_vec()).unwrap()
+        };
+        assert_eq!(encode(DataValue::Bool(true)), "t");
+        assert_eq!(encode(DataValue::String("a'b".into())), "a'b");
+        assert_eq!(
+            encode(DataValue::Blob(b"\x01a".as_slice().into())),
+            "\\x0161"
+        );
+        assert_eq!(encode(DataValue::Int32(-1)), "-1");
+    }
+}
@@ -0,0 +1,1161 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use itertools::Itertools;
+use minitrace::collector::SpanContext;
+use minitrace::Span;
+use tokio_util::sync::CancellationToken;
+
+use crate::activity::{ActivityState, LoggedQuery};
+use crate::array::{ArrayBuilder, ArrayBuilderImpl, Chunk, DataChunk, StringArrayBuilder};
+use crate::binder::{bind_header, Binder};
+use crate::catalog::RootCatalog;
+use crate::db::{Database, Error};
+use crate::executor::{ExecutorError, MemoryTracker, QueryContext};
+use crate::parser::{parse, Expr as AstExpr, Ident, Statement, UnaryOperator, Value};
+use crate::planner::{Expr, Optimizer, RecExpr, Statistics};
+use crate::types::{DataType, DataValue};
+
+/// A session of a client connected to the database.
+///
+/// Each session has its own settings, current schema, transaction state and prepared
+/// statements, while all sessions of a [`Database`] share its catalog and storage.
+pub struct Session {
+    db: Arc<Database>,
+    /// The ID of the session in the activity monitor of the database.
+    id: i32,
+    state: Mutex<State>,
+}
+
+/// The state of a session.
+#[derive(Default)]
+struct State {
+    /// The user whose privileges are checked, or `None` to skip the checks.
+    user: Option<String>,
+    disable_optimizer: bool,
+    mock_stat: Option<Statistics>,
+    /// Variables set by `SET`.
+    settings: BTreeMap<String, String>,
+    transaction: TransactionState,
+    /// Statements prepared by `PREPARE`.
+    prepared: HashMap<String, PreparedStatement>,
+    /// The token to cancel the running query.
+    cancel: CancellationToken,
+    /// Statements running longer than this are cancelled.
+    statement_timeout: Option<Duration>,
+    /// The maximum memory in bytes that operators of a query can use for their buffers.
+    query_memory_limit: Option<usize>,
+    /// Statements running at least this long are logged, or `None` to log nothing.
+    log_min_duration: Option<Duration>,
+}
+
+/// The state of the transaction block of a session.
+///
+/// Statements always commit on their own. A transaction block only records whether it has
+/// modified the database, so that `ROLLBACK` fails instead of silently keeping the changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionState {
+    /// Not in a transaction block.
+    #[default]
+    Idle,
+    /// In a transaction block started by `BEGIN`.
+    InBlock { modified: bool },
+}
+
+impl Session {
+    /// The schemas to look up unqualified names by default.
+    pub const DEFAULT_SEARCH_PATH: &'static str = RootCatalog::DEFAULT_SCHEMA_NAME;
+
+    /// Create a new session of the database.
+    pub fn new(db: Arc<Database>) -> Self {
+        Session {
+            id: db.activity().register(),
+            db,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the database of the session.
+    pub fn database(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Returns the ID of the session, which is unique in the database.
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Set the user of the session, whose privileges are checked for all statements.
+    ///
+    /// Without a user, privileges are not checked.
+    pub fn set_user(&self, user: &str) {
+        self.state.lock().unwrap().user = Some(user.into());
+        (self.db.activity()).update(self.id, |a| a.user = Some(user.into()));
+    }
+
+    /// Set the name of the application connected to the session.
+    pub fn set_application_name(&self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .settings
+            .insert("application_name".into(), name.into());
+        (self.db.activity()).update(self.id, |a| a.application_name = Some(name.into()));
+    }
+
+    /// Returns the value of a variable, or `None` if it is not set.
+    pub fn get_setting(&self, name: &str) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        match name {
+            "search_path" => Some(state.search_path().into()),
+            name => state.settings.get(name).cloned(),
+        }
+    }
+
+    /// Returns the schema of unqualified names, which is the first schema in the search path.
+    pub fn current_schema(&self) -> String {
+        self.state.lock().unwrap().current_schema()
+    }
+
+    /// Cancel the running query of the session.
+    ///
+    /// The query fails with a cancellation error. It does nothing if no query is running.
+    pub fn cancel(&self) {
+        self.state.lock().unwrap().cancel.cancel();
+    }
+
+    /// Returns the state of the transaction block.
+    pub fn transaction_state(&self) -> TransactionState {
+        self.state.lock().unwrap().transaction
+    }
+
+    /// Run SQL queries and return the outputs.
+    pub async fn run(&self, sql: &str) -> Result<Vec<Chunk>, Error> {
+        let _root = Span::root("run_sql", SpanContext::random());
+
+        let sql = if let Some(cmd) = sql.trim().strip_prefix('\\') {
+            command_to_sql(cmd)?
+        } else {
+            sql.to_string()
+        };
+
+        let cancel = self.start_query();
+        let optimizer = self.optimizer().await?;
+        let stmts = parse(&sql)?;
+        let mut outputs: Vec<Chunk> = vec![];
+        for stmt in stmts {
+            if cancel.is_cancelled() {
+                return Err(ExecutorError::cancelled().into());
+            }
+            let query = stmt.to_string();
+            let output = (self.track(query, self.run_statement(&optimizer, stmt, &sql))).await?;
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+
+    /// Run a statement. Statements about the session are handled here without planning.
+    async fn run_statement(
+        &self,
+        optimizer: &Optimizer,
+        stmt: Statement,
+        sql: &str,
+    ) -> Result<Chunk, Error> {
+        match stmt {
+            Statement::StartTransaction { .. } => {
+                let mut state = self.state.lock().unwrap();
+                if state.transaction == TransactionState::Idle {
+                    state.transaction = TransactionState::InBlock { modified: false };
+                }
+                Ok(tag_chunk("$begin"))
+            }
+            Statement::Commit { .. } => {
+                self.state.lock().unwrap().transaction = TransactionState::Idle;
+                Ok(tag_chunk("$commit"))
+            }
+            Statement::Rollback {
+                savepoint: None, ..
+            } => {
+                let mut state = self.state.lock().unwrap();
+                let transaction = std::mem::take(&mut state.transaction);
+                if transaction == (TransactionState::InBlock { modified: true }) {
+                    return Err(Error::Internal(
+                        "cannot roll back a transaction that modified the database".into(),
+                    ));
+                }
+                Ok(tag_chunk("$rollback"))
+            }
+            Statement::ShowVariable { variable } => self.show(&variable),
+            Statement::Prepare {
+                name,
+                data_types,
+                statement,
+            } => {
+                let types = data_types.iter().map(|ty| Some(ty.into())).collect_vec();
+                let prepared = self.prepare_statement(*statement, &types, sql).await?;
+                let mut state = self.state.lock().unwrap();
+                let name = name.value.to_lowercase();
+                if state.prepared.contains_key(&name) {
+                    return Err(Error::Internal(format!(
+                        "prepared statement \"{name}\" already exists"
+                    )));
+                }
+                state.prepared.insert(name, prepared);
+                Ok(tag_chunk("$prepare"))
+            }
+            Statement::Execute {
+                name, parameters, ..
+            } => {
+                let name = name.to_string().to_lowercase();
+                let prepared = (self.state.lock().unwrap().prepared.get(&name).cloned())
+                    .ok_or_else(|| {
+                        Error::Internal(format!("prepared statement \"{name}\" does not exist"))
+                    })?;
+                let params: Vec<_> = parameters.iter().map(param_value).try_collect()?;
+                self.execute_prepared_statement(&prepared, &params).await
+            }
+            Statement::Deallocate { name, .. } => {
+                let mut state = self.state.lock().unwrap();
+                let name = name.value.to_lowercase();
+                if name == "all" {
+                    state.prepared.clear();
+                } else if state.prepared.remove(&name).is_none() {
+                    return Err(Error::Internal(format!(
+                        "prepared statement \"{name}\" does not exist"
+                    )));
+                }
+                Ok(tag_chunk("$deallocate"))
+            }
+            stmt => {
+                let mut binder = self.binder();
+                let mut plan = binder.bind(stmt.clone()).map_err(|e| e.with_sql(sql))?;
+                if self.handle_set(&plan)? {
+                    return Ok(tag_chunk("$set"));
+                }
+                if !self.state.lock().unwrap().disable_optimizer {
+                    plan = optimizer.optimize(plan);
+                }
+                let output = self.db.output_schema(&binder, &plan);
+                self.mark_modified(&stmt);
+                let chunk = self.execute(optimizer, &plan, &stmt).await?;
+                Ok(bind_output(chunk, &stmt, &output))
+            }
+        }
+    }
+
+    /// Prepare a SQL statement with parameters like `$1`.
+    ///
+    /// The types of parameters are inferred from the statement unless given in `param_types`.
+    /// The statement is bound once, and can be executed multiple times with
+    /// [`execute_prepared`](Self::execute_prepared). The plan is optimized once if the statement
+    /// has no parameters, or on each execution with the values of parameters otherwise.
+    /// Privileges are checked when the statement is prepared, and checked again on each execution.
+    pub async fn prepare(
+        &self,
+        sql: &str,
+        param_types: &[Option<DataType>],
+    ) -> Result<PreparedStatement, Error> {
+        let mut stmts = parse(sql)?;
+        if stmts.len() > 1 {
+            return Err(Error::Internal(
+                "cannot insert multiple commands into a prepared statement".into(),
+            ));
+        }
+        let Some(stmt) = stmts.pop() else {
+            return Ok(PreparedStatement {
+                stmt: None,
+                plan: RecExpr::default(),
+                optimizer: self.optimizer().await?,
+                param_types: vec![],
+                output: vec![],
+            });
+        };
+        self.prepare_statement(stmt, param_types, sql).await
+    }
+
+    async fn prepare_statement(
+        &self,
+        stmt: Statement,
+        param_types: &[Option<DataType>],
+        sql: &str,
+    ) -> Result<PreparedStatement, Error> {
+        let optimizer = self.optimizer().await?;
+        let mut binder = self.binder();
+        binder.set_param_types(param_types.to_vec());
+        let mut plan = binder.bind(stmt.clone()).map_err(|e| e.with_sql(sql))?;
+        // plans with parameters are optimized on execution, when the values are known
+        if binder.param_types().is_empty()
+            && !is_set(&plan)
+            && !self.state.lock().unwrap().disable_optimizer
+        {
+            plan = optimizer.optimize(plan);
+        }
+
+        let output = self.db.output_schema(&binder, &plan);
+        Ok(PreparedStatement {
+            stmt: Some(stmt),
+            plan,
+            optimizer,
+            param_types: binder.param_types(),
+            output,
+        })
+    }
+
+    /// Execute a prepared statement with the values of parameters.
+    pub async fn execute_prepared(
+        &self,
+        prepared: &PreparedStatement,
+        params: &[DataValue],
+    ) -> Result<Chunk, Error> {
+        let _root = Span::root("execute_prepared", SpanContext::random());
+        self.start_query();
+
+        let Some(stmt) = &prepared.stmt else {
+            return Ok(Chunk::new(vec![]));
+        };
+        let execute = self.execute_prepared_statement(prepared, params);
+        self.track(stmt.to_string(), execute).await
+    }
+
+    async fn execute_prepared_statement(
+        &self,
+        prepared: &PreparedStatement,
+        params: &[DataValue],
+    ) -> Result<Chunk, Error> {
+        let Some(stmt) = &prepared.stmt else {
+            return Ok(Chunk::new(vec![]));
+        };
+        if params.len() != prepared.param_types.len() {
+            return Err(Error::Internal(format!(
+                "expect {} parameters, but got {}",
+                prepared.param_types.len(),
+                params.len()
+            )));
+        }
+        // privileges may be revoked after the statement is prepared, so the statement is bound
+        // again to check them
+        if self.state.lock().unwrap().user.is_some() {
+            let mut binder = self.binder();
+            binder.set_param_types(prepared.param_types.iter().cloned().map(Some).collect());
+            binder.bind(stmt.clone())?;
+        }
+        // parameters are constants of their types in the plan, so that they can be folded and
+        // pushed down like literals
+        let mut values = Vec::with_capacity(params.len());
+        for (value, ty) in params.iter().zip(&prepared.param_types) {
+            if value.is_null() {
+                values.push(DataValue::Null);
+                continue;
+            }
+            let value = value.cast(ty).map_err(|e| Error::Internal(e.to_string()))?;
+            values.push(value);
+        }
+        let nodes = (prepared.plan.as_ref().iter())
+            .map(|node| match node {
+                Expr::Param(index) => {
+                    let index = prepared.plan[*index]
+                        .as_const()
+                        .as_usize()
+                        .unwrap()
+                        .unwrap();
+                    Expr::Constant(values[index - 1].clone())
+                }
+                node => node.clone(),
+            })
+            .collect_vec();
+        let mut plan = RecExpr::from(nodes);
+        if self.handle_set(&plan)? {
+            return Ok(tag_chunk("$set"));
+        }
+        if !values.is_empty() && !self.state.lock().unwrap().disable_optimizer {
+            plan = prepared.optimizer.optimize(plan);
+        }
+        self.mark_modified(stmt);
+        let chunk = self.execute(&prepared.optimizer, &plan, stmt).await?;
+        Ok(bind_output(chunk, stmt, &prepared.output))
+    }
+
+    /// Returns the cancellation token of a new query.
+    fn start_query(&self) -> CancellationToken {
+        let mut state = self.state.lock().unwrap();
+        state.cancel = CancellationToken::new();
+        state.cancel.clone()
+    }
+
+    /// Runs a query and records it in the activity of the session.
+    ///
+    /// The query is logged if it runs at least `log_min_duration_statement`.
+    async fn track(
+        &self,
+        query: String,
+        run: impl Future<Output = Result<Chunk, Error>>,
+    ) -> Result<Chunk, Error> {
+        let (user, log_min_duration) = {
+            let state = self.state.lock().unwrap();
+            (state.user.clone(), state.log_min_duration)
+        };
+        let query_start = SystemTime::now();
+        let start = Instant::now();
+        self.db.activity().update(self.id, |a| {
+            a.state = ActivityState::Active;
+            a.query = Some(query.clone());
+            a.query_start = Some(query_start);
+        });
+        let result = run.await;
+        let duration = start.elapsed();
+
+        let in_transaction = self.transaction_state() != TransactionState::Idle;
+        self.db.activity().update(self.id, |a| {
+            a.state = match in_transaction {
+                true => ActivityState::IdleInTransaction,
+                false => ActivityState::Idle,
+            };
+        });
+        if log_min_duration.is_some_and(|min| duration >= min) {
+            self.db.activity().log(LoggedQuery {
+                pid: self.id,
+                user,
+                query,
+                query_start,
+                duration,
+                rows: (result.as_ref().ok())
+                    .map(|chunk| chunk.data_chunks().iter().map(|c| c.cardinality()).sum()),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            });
+        }
+        result
+    }
+
+    /// Execute a plan of the running query, which is cancelled on statement timeout.
+    ///
+    /// The plan waits in the queue of the database until it is admitted to run.
+    async fn execute(
+        &self,
+        optimizer: &Optimizer,
+        plan: &RecExpr,
+        stmt: &Statement,
+    ) -> Result<Chunk, Error> {
+        let (context, user, timeout) = {
+            let state = self.state.lock().unwrap();
+            let context = QueryContext {
+                cancel: state.cancel.clone(),
+                memory: MemoryTracker::new(state.query_memory_limit),
+                queue: self.db.queue().clone(),
+                activity: self.db.activity().clone(),
+            };
+            (context, state.user.clone(), state.statement_timeout)
+        };
+        let cancel = context.cancel.clone();
+        let _permit = (self.db.queue())
+            .admit(user.as_deref(), &stmt.to_string(), &cancel)
+            .await?;
+        let execute = self.db.execute(optimizer, plan, context);
+        let Some(timeout) = timeout else {
+            return execute.await;
+        };
+        match tokio::time::timeout(timeout, execute).await {
+            Ok(result) => result,
+            Err(_) => {
+                cancel.cancel();
+                Err(ExecutorError::timeout().into())
+            }
+        }
+    }
+
+    /// Returns a binder with the user and current schema of the session.
+    fn binder(&self) -> Binder {
+        let state = self.state.lock().unwrap();
+        let mut binder = Binder::new(self.db.catalog().clone());
+        if let Some(user) = &state.user {
+            binder.set_user(user);
+        }
+        binder.set_schema(&state.current_schema());
+        binder
+    }
+
+    /// Returns an optimizer with the current statistics.
+    async fn optimizer(&self) -> Result<Optimizer, Error> {
+        let mock_stat = self.state.lock().unwrap().mock_stat.clone();
+        self.db.optimizer(mock_stat).await
+    }
+
+    /// Records that the transaction block has modified the database if the statement may
+    /// modify it.
+    fn mark_modified(&self, stmt: &Statement) {
+        let mut state = self.state.lock().unwrap();
+        if let TransactionState::InBlock { modified } = &mut state.transaction {
+            *modified |= !is_read_only(stmt);
+        }
+    }
+
+    /// Handle PRAGMA and SET statements.
+    fn handle_set(&self, plan: &RecExpr) -> Result<bool, Error> {
+        let root = &plan.as_ref()[plan.as_ref().len() - 1];
+        let mut state = self.state.lock().unwrap();
+        match root {
+            Expr::Pragma([name, _value]) => match plan[*name].as_const().as_str() {
+                "enable_optimizer" => {
+                    state.disable_optimizer = false;
+                    Ok(true)
+                }
+                "disable_optimizer" => {
+                    state.disable_optimizer = true;
+                    Ok(true)
+                }
+                name => Err(Error::Internal(format!("no such pragma: {name}"))),
+            },
+            Expr::Set([name, value]) => {
+                let name = plan[*name].as_const().as_str().to_string();
+                let value = plan[*value].as_const();
+                // Mock the row count of a table for planner test.
+                if let Some(table_name) = name.strip_prefix("mock_rowcount_") {
+                    let count = (value.as_usize())
+                        .map_err(|e| Error::Internal(e.to_string()))?
+                        .ok_or_else(|| Error::Internal("row count must not be null".into()))?
+                        as u32;
+                    let table_id = (self.db.catalog())
+                        .get_table_id_by_name(&state.current_schema(), table_name)
+                        .ok_or_else(|| Error::Internal("table not found".into()))?;
+                    (state.mock_stat.get_or_insert_with(Default::default))
+                        .add_row_count(table_id, count);
+                    return Ok(true);
+                }
+                match name.as_str() {
+                    "statement_timeout" => state.statement_timeout = parse_timeout(&value)?,
+                    "query_memory_limit" => state.query_memory_limit = parse_memory(&value)?,
+                    "log_min_duration_statement" => {
+                        state.log_min_duration = parse_log_min_duration(&value)?
+                    }
+                    _ => {}
+                }
+                let value = match value {
+                    DataValue::String(s) => s.to_string(),
+                    value => value.to_string(),
+                };
+                if name == "application_name" {
+                    let app = Some(value.clone());
+                    (self.db.activity()).update(self.id, |a| a.application_name = app);
+                }
+                state.settings.insert(name, value);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns the value of a variable for `SHOW`, or all variables for `SHOW ALL`.
+    fn show(&self, variable: &[Ident]) -> Result<Chunk, Error> {
+        let name = variable.iter().map(|ident| &ident.value).join(" ");
+        let name = name.to_lowercase();
+        let (header, rows) = if name == "all" {
+            let state = self.state.lock().unwrap();
+            let mut settings = state.settings.clone();
+            settings.insert("search_path".into(), state.search_path().into());
+            let rows = settings.into_iter().map(|(k, v)| vec![k, v]).collect_vec();
+            (vec!["name".to_string(), "setting".to_string()], rows)
+        } else {
+            let value = self.get_setting(&name).ok_or_else(|| {
+                Error::Internal(format!("unrecognized configuration parameter \"{name}\""))
+            })?;
+            (vec![name], vec![vec![value]])
+        };
+        let mut builders = header
+            .iter()
+            .map(|_| StringArrayBuilder::new())
+            .collect_vec();
+        for row in &rows {
+            for (builder, value) in builders.iter_mut().zip(row) {
+                builder.push(Some(value));
+            }
+        }
+        let data_chunk: DataChunk = builders.into_iter().map(ArrayBuilderImpl::from).collect();
+        let mut chunk = Chunk::new(vec![data_chunk]);
+        chunk.set_types(vec![DataType::String; header.len()]);
+        chunk.set_header(header);
+        Ok(chunk)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.db.activity().unregister(self.id);
+    }
+}
+
+impl State {
+    fn search_path(&self) -> &str {
+        (self.settings.get("search_path")).map_or(Session::DEFAULT_SEARCH_PATH, |s| s.as_str())
+    }
+
+    fn current_schema(&self) -> String {
+        let first = self.search_path().split(',').next().unwrap_or_default();
+        first.trim().trim_matches('"').to_string()
+    }
+}
+
+/// A statement prepared by [`Session::prepare`], whose plan is bound and optimized.
+///
+/// The plan may contain parameters, which are replaced by their values on execution.
+#[derive(Clone)]
+pub struct PreparedStatement {
+    /// The statement, or `None` if the query is empty.
+    stmt: Option<Statement>,
+    /// The plan, which is optimized after binding parameters if there are any.
+    plan: RecExpr,
+    /// The optimizer used to build the plan.
+    optimizer: Optimizer,
+    param_types: Vec<DataType>,
+    output: Vec<(String, DataType)>,
+}
+
+impl PreparedStatement {
+    /// Returns the statement, or `None` if the query is empty.
+    pub fn statement(&self) -> Option<&Statement> {
+        self.stmt.as_ref()
+    }
+
+    /// Returns the types of parameters.
+    pub fn param_types(&self) -> &[DataType] {
+        &self.param_types
+    }
+
+    /// Returns the names and types of output columns.
+    ///
+    /// The result is empty if the statement doesn't return rows.
+    pub fn output(&self) -> &[(String, DataType)] {
+        &self.output
+    }
+}
+
+/// Convert a command to SQL.
+fn command_to_sql(cmd: &str) -> Result<String, Error> {
+    let tokens = cmd.split_whitespace().collect::<Vec<_>>();
+    Ok(match tokens.as_slice() {
+        ["dt"] => "SELECT * FROM pg_catalog.pg_tables".to_string(),
+        ["di"] => "SELECT * FROM pg_catalog.pg_indexes".to_string(),
+        ["d", table] => format!(
+            "SELECT * FROM pg_catalog.pg_attribute WHERE table_name = '{table}'",
+        ),
+        ["stat"] => "SELECT * FROM pg_catalog.pg_stat".to_string(),
+        ["stat", table] => format!("SELECT * FROM pg_catalog.pg_stat WHERE table_name = '{table}'"),
+        ["stat", table, column] => format!(
+            "SELECT * FROM pg_catalog.pg_stat WHERE table_name = '{table}' AND column_name = '{column}'",
+        ),
+        _ => return Err(Error::Internal("invalid command".into())),
+    })
+}
+
+/// Parses the value of `statement_timeout`, which is in milliseconds without a unit.
+///
+/// Returns `None` if the timeout is disabled by zero.
+fn parse_timeout(value: &DataValue) -> Result<Option<Duration>, Error> {
+    let timeout = parse_duration("statement_timeout", value)?;
+    Ok(Some(timeout).filter(|t| !t.is_zero()))
+}
+
+/// Parses the value of `log_min_duration_statement`, which is in milliseconds without a unit.
+///
+/// Returns `None` if logging is disabled by -1. Zero logs all statements.
+fn parse_log_min_duration(value: &DataValue) -> Result<Option<Duration>, Error> {
+    let disabled = match value {
+        DataValue::String(s) => s.trim() == "-1",
+        value => matches!(
+            value,
+            DataValue::Int16(-1) | DataValue::Int32(-1) | DataValue::Int64(-1)
+        ),
+    };
+    if disabled {
+        return Ok(None);
+    }
+    parse_duration("log_min_duration_statement", value).map(Some)
+}
+
+/// Parses a duration, which is in milliseconds without a unit.
+fn parse_duration(name: &str, value: &DataValue) -> Result<Duration, Error> {
+    let invalid = || Error::Internal(format!("invalid value for {name}: {value}"));
+    let duration = match value {
+        DataValue::Int16(_) | DataValue::Int32(_) | DataValue::Int64(_) => {
+            Duration::from_millis(value.as_usize().map_err(|_| invalid())?.unwrap() as u64)
+        }
+        DataValue::String(s) => match s.trim().parse::<u64>() {
+            Ok(ms) => Duration::from_millis(ms),
+            Err(_) => humantime::parse_duration(s.trim()).map_err(|_| invalid())?,
+        },
+        _ => return Err(invalid()),
+    };
+    Ok(duration)
+}
+
+/// Parses the value of `query_memory_limit`, which is in bytes without a unit.
+///
+/// Units `kB`, `MB`, `GB` and `TB` are multiples of 1024 like in Postgres.
+/// Returns `None` if the limit is disabled by zero.
+fn parse_memory(value: &DataValue) -> Result<Option<usize>, Error> {
+    let invalid = || Error::Internal(format!("invalid value for query_memory_limit: {value}"));
+    let bytes = match value {
+        DataValue::Int16(_) | DataValue::Int32(_) | DataValue::Int64(_) => {
+            value.as_usize().map_err(|_| invalid())?.unwrap()
+        }
+        DataValue::String(s) => {
+            let s = s.trim();
+            let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (number, unit) = s.split_at(split);
+            let number: usize = number.parse().map_err(|_| invalid())?;
+            let unit: usize = match unit.trim().to_lowercase().as_str() {
+                "" | "b" => 1,
+                "kb" => 1 << 10,
+                "mb" => 1 << 20,
+                "gb" => 1 << 30,
+                "tb" => 1 << 40,
+                _ => return Err(invalid()),
+            };
+            number.checked_mul(unit).ok_or_else(invalid)?
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(Some(bytes).filter(|b| *b != 0))
+}
+
+/// Returns the value of a parameter of `EXECUTE`, which must be a literal.
+fn param_value(expr: &AstExpr) -> Result<DataValue, Error> {
+    match expr {
+        AstExpr::Value(value) => Ok(value.clone().into()),
+        // negative numbers are casted from strings to the parameter type
+        AstExpr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } if matches!(**expr, AstExpr::Value(Value::Number(..))) => {
+            Ok(DataValue::String(format!("-{expr}").into()))
+        }
+        expr => Err(Error::Internal(format!(
+            "parameters must be literals: {expr}"
+        ))),
+    }
+}
+
+/// Returns true if the statement never modifies the database.
+fn is_read_only(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Query(_)
+        | Statement::ShowVariable { .. }
+        | Statement::SetVariable { .. }
+        | Statement::Pragma { .. }
+        | Statement::Copy { to: true, .. } => true,
+        Statement::Explain {
+            statement, analyze, ..
+        } => !analyze || is_read_only(statement),
+        _ => false,
+    }
+}
+
+/// Sets the header and types of the output chunk of a statement.
+fn bind_output(chunk: Chunk, stmt: &Statement, output: &[(String, DataType)]) -> Chunk {
+    let mut chunk = bind_header(chunk, stmt);
+    if !output.is_empty() {
+        let (names, types) = output.iter().cloned().unzip();
+        if chunk.header().is_none() {
+            chunk.set_header(names);
+        }
+        chunk.set_types(types);
+    }
+    chunk
+}
+
+/// Returns an empty output chunk with the command tag as header.
+fn tag_chunk(tag: &str) -> Chunk {
+    let mut chunk = Chunk::new(vec![]);
+    chunk.set_header(vec![tag.to_string()]);
+    chunk
+}
+
+/// Returns true if the plan is a `SET` or `PRAGMA` statement.
+fn is_set(plan: &RecExpr) -> bool {
+    matches!(plan.as_ref().last(), Some(Expr::Set(_) | Expr::Pragma(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admission::AdmissionConfig;
+
+    fn new_session() -> Session {
+        Session::new(Arc::new(Database::new_in_memory()))
+    }
+
+    #[tokio::test]
+    async fn test_session_isolation() {
+        let s1 = new_session();
+        let s2 = Session::new(s1.database().clone());
+
+        s1.run("set application_name = 'a'; set search_path to pg_catalog, postgres;")
+            .await
+            .unwrap();
+        assert_eq!(s1.get_setting("application_name").as_deref(), Some("a"));
+        assert_eq!(s1.current_schema(), "pg_catalog");
+        assert_eq!(s2.get_setting("application_name"), None);
+        assert_eq!(s2.current_schema(), "postgres");
+
+        // unqualified names are resolved in the current schema
+        s1.run("select * from pg_tables").await.unwrap();
+        assert!(s2.run("select * from pg_tables").await.is_err());
+
+        s1.run("pragma disable_optimizer").await.unwrap();
+        assert!(s1.state.lock().unwrap().disable_optimizer);
+        assert!(!s2.state.lock().unwrap().disable_optimizer);
+
+        let chunks = s1.run("show application_name").await.unwrap();
+        assert_eq!(chunks[0].header().unwrap(), ["application_name"]);
+        assert!(s2.run("show application_name").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let session = new_session();
+        session.run("create table t (a int)").await.unwrap();
+
+        session.run("begin; select * from t;").await.unwrap();
+        assert_eq!(
+            session.transaction_state(),
+            TransactionState::InBlock { modified: false }
+        );
+        session.run("rollback").await.unwrap();
+        assert_eq!(session.transaction_state(), TransactionState::Idle);
+
+        session
+            .run("begin; insert into t values (1);")
+            .await
+            .unwrap();
+        assert_eq!(
+            session.transaction_state(),
+            TransactionState::InBlock { modified: true }
+        );
+        assert!(session.run("rollback").await.is_err());
+        assert_eq!(session.transaction_state(), TransactionState::Idle);
+    }
+
+    /// Returns a session with a table `t` and a query that runs for a long time on it.
+    async fn slow_query() -> (Session, &'static str) {
+        slow_query_in(new_session()).await
+    }
+
+    /// Creates the table of [`slow_query`] in the session.
+    async fn slow_query_in(session: Session) -> (Session, &'static str) {
+        let values = (0..1000).map(|i| format!("({i})")).join(",");
+        session
+            .run(&format!(
+                "create table t (a int); insert into t values {values};"
+            ))
+            .await
+            .unwrap();
+        (session, "select count(*) from t as x, t as y, t as z")
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let (session, sql) = slow_query().await;
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            session.cancel();
+        };
+        let (result, _) = tokio::join!(session.run(sql), cancel);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("due to user request"), "{err}");
+
+        // the next query is not cancelled
+        session.run("select count(*) from t").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_statement_timeout() {
+        let (session, sql) = slow_query().await;
+        assert!(session.run("set statement_timeout = 'soon'").await.is_err());
+        session
+            .run("set statement_timeout = '100ms'")
+            .await
+            .unwrap();
+        let err = session.run(sql).await.unwrap_err().to_string();
+        assert!(err.contains("due to statement timeout"), "{err}");
+        session.run("select count(*) from t").await.unwrap();
+
+        session.run("set statement_timeout = 0").await.unwrap();
+        assert_eq!(session.state.lock().unwrap().statement_timeout, None);
+    }
+
+    #[tokio::test]
+    async fn test_query_queue() {
+        let mut db = Database::new_in_memory();
+        db.set_admission_config(AdmissionConfig {
+            max_concurrent: Some(1),
+            max_queued: Some(0),
+            queue_timeout: None,
+        });
+        let s1 = Session::new(Arc::new(db));
+        let s2 = Session::new(s1.database().clone());
+        let (s1, sql) = slow_query_in(s1).await;
+
+        // the running query sees itself in the queue
+        let chunks = (s1.run("select * from pg_catalog.pg_query_queue"))
+            .await
+            .unwrap();
+        let chunk = chunks[0].get_first_data_chunk();
+        assert_eq!(chunk.cardinality(), 1);
+        assert_eq!(chunk.array_at(2).get_to_string(0), "running");
+
+        // the second query is rejected while the first is running
+        let other = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let err = s2.run("select 1").await.unwrap_err().to_string();
+            s1.cancel();
+            err
+        };
+        let (_, err) = tokio::join!(s1.run(sql), other);
+        assert!(err.contains("too many queued queries"), "{err}");
+        s2.run("select 1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_activity() {
+        let s1 = new_session();
+        let s2 = Session::new(s1.database().clone());
+        assert_ne!(s1.id(), s2.id());
+        s1.run("set application_name = 'app'; begin;")
+            .await
+            .unwrap();
+
+        let chunks = (s2.run("select * from pg_catalog.pg_stat_activity"))
+            .await
+            .unwrap();
+        let chunk = chunks[0].get_first_data_chunk();
+        let rows = (0..chunk.cardinality())
+            .map(|i| (2..5).map(|j| chunk.array_at(j).get_to_string(i)).join("|"))
+            .collect_vec();
+        assert_eq!(
+            rows,
+            [
+                "app|idle in transaction|BEGIN",
+                "NULL|active|SELECT * FROM pg_catalog.pg_stat_activity"
+            ]
+        );
+
+        drop(s2);
+        let activity = s1.database().activity();
+        assert_eq!(activity.sessions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_log() {
+        let (session, sql) = slow_query().await;
+        assert!(session
+            .run("set log_min_duration_statement = 'never'")
+            .await
+            .is_err());
+        session.run("select 1").await.unwrap();
+        session
+            .run("set log_min_duration_statement = 0")
+            .await
+            .unwrap();
+        session.run("select a from t where a < 10").await.unwrap();
+        assert!(session.run("select * from t where b = 1").await.is_err());
+        session
+            .run("set log_min_duration_statement = '1h'")
+            .await
+            .unwrap();
+        session.run(sql).await.unwrap();
+        session
+            .run("set log_min_duration_statement = -1")
+            .await
+            .unwrap();
+
+        let queries = session.database().activity().logged_queries();
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].rows, Some(10));
+        assert!(queries[1].error.as_ref().unwrap().contains("b"));
+        assert_eq!(queries[2].query, "SET log_min_duration_statement = '1h'");
+
+        let chunks = (session.run("select * from pg_catalog.pg_query_log"))
+            .await
+            .unwrap();
+        assert_eq!(chunks[0].get_first_data_chunk().cardinality(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_query_memory_limit() {
+        let (session, _) = slow_query().await;
+        let sql = "select a from t order by a desc";
+        session.run(sql).await.unwrap();
+
+        // peak memory of buffering operators is shown in the plan
+        let chunks = session
+            .run(&format!("explain analyze {sql}"))
+            .await
+            .unwrap();
+        let plan = chunks[0]
+            .get_first_data_chunk()
+            .array_at(0)
+            .get_to_string(0);
+        assert!(plan.contains("memory"), "{plan}");
+
+        assert!(session
+            .run("set query_memory_limit = '1 XB'")
+            .await
+            .is_err());
+        session.run("set query_memory_limit = '1kB'").await.unwrap();
+        let err = session.run(sql).await.unwrap_err().to_string();
+        assert!(err.contains("out of memory"), "{err}");
+
+        session
+            .run("set query_memory_limit = '64MB'")
+            .await
+            .unwrap();
+        session.run(sql).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statement() {
+        let session = new_session();
+        session
+            .run("create table t (a int, b string); insert into t values (1, 'x'), (2, 'y');")
+            .await
+            .unwrap();
+
+        let insert = (session.prepare("insert into t values ($1, $2)", &[]).await).unwrap();
+        assert_eq!(insert.param_types(), [DataType::Int32, DataType::String]);
+        session
+            .execute_prepared(&insert, &[DataValue::Int32(3), DataValue::Null])
+            .await
+            .unwrap();
+
+        let select = (session
+            .prepare("select a, b as c from t where a > $1", &[])
+            .await)
+            .unwrap();
+        assert_eq!(select.param_types(), [DataType::Int32]);
+        assert_eq!(
+            select.output(),
+            [
+                ("a".to_string(), DataType::Int32),
+                ("c".to_string(), DataType::String)
+            ]
+        );
+        // text parameters are casted to the inferred type
+        let chunk = (session
+            .execute_prepared(&select, &[DataValue::String("1".into())])
+            .await)
+            .unwrap();
+        let rows = (chunk.data_chunks().iter())
+            .flat_map(|c| c.rows().map(|row| row.values().collect_vec()))
+            .collect_vec();
+        assert_eq!(
+            rows,
+            [
+                vec![DataValue::Int32(2), DataValue::String("y".into())],
+                vec![DataValue::Int32(3), DataValue::Null]
+            ]
+        );
+
+        let params = [DataValue::String("a".into())];
+        assert!(session.execute_prepared(&select, &params).await.is_err());
+
+        // parameters are pushed down to the storage like literals
+        let options = crate::storage::SecondaryStorageOptions::default_for_test();
+        let session = Session::new(Arc::new(Database::new_on_disk(options).await));
+        session
+            .run("create table pk (a int primary key, b int)")
+            .await
+            .unwrap();
+        let explain = (session
+            .prepare("explain select * from pk where a = $1", &[])
+            .await)
+            .unwrap();
+        let chunk = (session
+            .execute_prepared(&explain, &[DataValue::Int32(1)])
+            .await)
+            .unwrap();
+        let plan = chunk.data_chunks()[0].array_at(0).get(0).to_string();
+        assert!(
+            !plan.contains("Filter") && !plan.contains("filter: true"),
+            "{plan}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prepare_execute() {
+        let session = new_session();
+        session.run("create table t (a int)").await.unwrap();
+        session
+            .run("prepare ins (int) as insert into t values ($1); execute ins (1); execute ins (-2);")
+            .await
+            .unwrap();
+        assert!(session.run("prepare ins as select 1").await.is_err());
+        let chunks = session.run("select sum(a) from t").await.unwrap();
+        assert_eq!(
+            chunks[0].get_first_data_chunk().array_at(0).get(0),
+            DataValue::Int32(-1)
+        );
+
+        // prepared statements belong to the session
+        let other = Session::new(session.database().clone());
+        assert!(other.run("execute ins (3)").await.is_err());
+        session.run("deallocate ins").await.unwrap();
+        assert!(session.run("execute ins (3)").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_privileges() {
+        let db = Arc::new(Database::new_in_memory());
+        let admin = Session::new(db.clone());
+        let alice = Session::new(db);
+        alice.set_user("alice");
+        admin
+            .run("create table t (a int); create user alice; create role writer;")
+            .await
+            .unwrap();
+        #[track_caller]
+        fn denied<T>(result: Result<T, Error>) {
+            let err = result.err().expect("permission denied");
+            assert!(err.to_string().contains("denied"), "{err}");
+        }
+
+        denied(alice.run("select * from t").await);
+        denied(alice.run("create table u (a int)").await);
+        denied(alice.prepare("insert into t values ($1)", &[]).await);
+        // everyone can read system tables
+        alice
+            .run("select * from pg_catalog.pg_tables")
+            .await
+            .unwrap();
+
+        admin
+            .run("grant select on t to alice; grant insert, delete on t to writer;")
+            .await
+            .unwrap();
+        admin
+            .run("grant writer to alice; grant create on schema postgres to alice;")
+            .await
+            .unwrap();
+        alice
+            .run("insert into t values (1); select * from t; delete from t where a = 1;")
+            .await
+            .unwrap();
+        alice.run("create table u (a int)").await.unwrap();
+        denied(alice.run("drop table t").await);
+        assert!(alice.run("grant select on t to alice").await.is_err());
+
+        let insert = (alice.prepare("insert into t values ($1)", &[]))
+            .await
+            .unwrap();
+        admin.run("revoke writer from alice").await.unwrap();
+        denied(alice.run("insert into t values (1)").await);
+        // statements prepared before are checked again on execution
+        denied(
+            alice
+                .execute_prepared(&insert, &[DataValue::Int32(1)])
+                .await,
+        );
+        admin.run("grant all on t to alice").await.unwrap();
+        alice.run("drop table t").await.unwrap();
+    }
+}
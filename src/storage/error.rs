@@ -6,6 +6,7 @@ use std::sync::Arc;
 use thiserror::Error;
 
 use crate::catalog::ColumnId;
+use crate::types::ConvertError;
 
 #[derive(Error, Debug)]
 pub enum StorageError {
@@ -31,6 +32,12 @@ pub enum StorageError {
     ProstEncode(prost::EncodeError),
     #[error("Prost decode error: {0}")]
     ProstDecode(prost::DecodeError),
+    #[error("not supported: {0}")]
+    NotSupported(String),
+    #[error("could not serialize access due to concurrent update")]
+    WriteConflict,
+    #[error("failed to evaluate scan predicate: {0}")]
+    Predicate(#[from] ConvertError),
     #[error("{0}")]
     Nested(
         #[from]
@@ -67,6 +74,13 @@ impl From<prost::DecodeError> for TracedStorageError {
     }
 }
 
+impl From<ConvertError> for TracedStorageError {
+    #[inline]
+    fn from(e: ConvertError) -> TracedStorageError {
+        StorageError::Predicate(e).into()
+    }
+}
+
 impl From<Arc<TracedStorageError>> for TracedStorageError {
     #[inline]
     fn from(e: Arc<TracedStorageError>) -> TracedStorageError {
@@ -105,6 +119,22 @@ impl TracedStorageError {
     pub fn checksum(found: u64, expected: u64) -> Self {
         StorageError::Checksum(found, expected).into()
     }
+
+    pub fn not_supported(feature: impl ToString) -> Self {
+        StorageError::NotSupported(feature.to_string()).into()
+    }
+
+    pub fn write_conflict() -> Self {
+        StorageError::WriteConflict.into()
+    }
+
+    /// Returns the error without backtraces, unwrapping nested errors.
+    pub fn inner(&self) -> &StorageError {
+        match &self.source {
+            StorageError::Nested(e) => e.inner(),
+            e => e,
+        }
+    }
 }
 
 pub type StorageResult<T> = std::result::Result<T, TracedStorageError>;
@@ -6,7 +6,7 @@ use std::sync::Arc;
 use bitvec::prelude::BitVec;
 
 use crate::array::{ArrayImpl, DataChunk, I64Array};
-use crate::storage::{StorageColumnRef, StorageResult, TxnIterator};
+use crate::storage::{ScanPredicate, StorageColumnRef, StorageResult, TxnIterator};
 
 /// An iterator over all data in a transaction.
 ///
@@ -17,6 +17,7 @@ pub struct InMemoryTxnIterator {
     chunks: Arc<Vec<DataChunk>>,
     deleted_rows: Arc<HashSet<usize>>,
     col_idx: Vec<StorageColumnRef>,
+    predicate: Option<Arc<dyn ScanPredicate>>,
     cnt: usize,
     row_cnt: usize,
 }
@@ -26,10 +27,12 @@ impl InMemoryTxnIterator {
         chunks: Arc<Vec<DataChunk>>,
         deleted_rows: Arc<HashSet<usize>>,
         col_idx: &[StorageColumnRef],
+        predicate: Option<Arc<dyn ScanPredicate>>,
     ) -> Self {
         Self {
             chunks,
             col_idx: col_idx.to_vec(),
+            predicate,
             cnt: 0,
             row_cnt: 0,
             deleted_rows,
@@ -51,7 +54,7 @@ impl InMemoryTxnIterator {
                 .map(|x| !self.deleted_rows.contains(&x))
                 .collect::<BitVec>();
 
-            let chunk = if self.col_idx.is_empty() {
+            let mut chunk = if self.col_idx.is_empty() {
                 DataChunk::no_column(visibility.count_ones())
             } else {
                 self.col_idx
@@ -68,6 +71,10 @@ impl InMemoryTxnIterator {
                     .collect::<DataChunk>()
             };
 
+            if let Some(predicate) = &self.predicate {
+                chunk = chunk.filter(&predicate.eval_all(&chunk)?);
+            }
+
             self.cnt += 1;
             self.row_cnt += selected_chunk.cardinality();
 
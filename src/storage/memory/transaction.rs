@@ -8,7 +8,9 @@ use itertools::Itertools;
 use super::table::InMemoryTableInnerRef;
 use super::{InMemoryRowHandler, InMemoryTable, InMemoryTxnIterator};
 use crate::array::{ArrayBuilderImpl, ArrayImplBuilderPickExt, DataChunk};
-use crate::storage::{ScanOptions, StorageColumnRef, StorageResult, Table, Transaction};
+use crate::storage::{
+    ScanOptions, StorageColumnRef, StorageResult, Table, TracedStorageError, Transaction,
+};
 
 /// A transaction running on `InMemoryStorage`.
 pub struct InMemoryTransaction {
@@ -124,6 +126,9 @@ impl Transaction for InMemoryTransaction {
         opts: ScanOptions,
     ) -> StorageResult<InMemoryTxnIterator> {
         assert!(opts.filter.is_none(), "MemTxn doesn't support filter scan");
+        if opts.as_of.is_some() {
+            return Err(TracedStorageError::not_supported("time travel"));
+        }
         assert!(!opts.reversed, "reverse iterator is not supported for now");
 
         let snapshot = if opts.is_sorted {
@@ -136,6 +141,7 @@ impl Transaction for InMemoryTransaction {
             snapshot,
             self.deleted_rows.clone(),
             col_idx,
+            opts.predicate,
         ))
     }
 
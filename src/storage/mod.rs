@@ -6,7 +6,10 @@ mod memory;
 pub use memory::InMemoryStorage;
 
 mod secondary;
-pub use secondary::{SecondaryStorage, StorageOptions as SecondaryStorageOptions};
+pub use secondary::{
+    CheckReport, CompactionMode, SecondaryStorage, StorageOptions as SecondaryStorageOptions,
+    TableCheckInfo,
+};
 
 mod index;
 pub use index::InMemoryIndex;
@@ -19,6 +22,7 @@ mod chunk;
 use std::future::Future;
 use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 pub use chunk::*;
 use enum_dispatch::enum_dispatch;
@@ -66,6 +70,14 @@ impl StorageImpl {
             Self::InMemoryStorage(_) => false,
         }
     }
+
+    /// Returns true if the storage engine may answer aggregations from statistics.
+    pub fn support_statistics_agg(&self) -> bool {
+        match self {
+            Self::SecondaryStorage(_) => true,
+            Self::InMemoryStorage(_) => false,
+        }
+    }
 }
 
 /// Represents a storage engine.
@@ -186,6 +198,24 @@ pub trait Transaction: Sync + Send + 'static {
 
     /// Abort a transaction.
     fn abort(self) -> impl Future<Output = StorageResult<()>> + Send;
+
+    /// Compute aggregations from the statistics of the table, without scanning it.
+    ///
+    /// Returns `None` if any of them can not be answered precisely from statistics.
+    fn aggregate_statistics(&self, _aggs: &[StatisticsAgg]) -> Option<Vec<DataValue>> {
+        None
+    }
+}
+
+/// An aggregation that may be answered from the statistics of a table.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StatisticsAgg {
+    /// The number of rows in the table.
+    RowCount,
+    /// The minimum non-null value of a column, identified by the storage column index.
+    Min(u32),
+    /// The maximum non-null value of a column, identified by the storage column index.
+    Max(u32),
 }
 
 /// Options for scanning.
@@ -194,6 +224,18 @@ pub struct ScanOptions {
     is_sorted: bool,
     reversed: bool,
     filter: Option<KeyRange>,
+    predicate: Option<Arc<dyn ScanPredicate>>,
+    as_of: Option<AsOf>,
+    partitions: Option<Vec<u32>>,
+}
+
+/// A historical version of a table to scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// The version committed at the epoch.
+    Epoch(u64),
+    /// The latest version committed at or before the time.
+    Timestamp(SystemTime),
 }
 
 impl ScanOptions {
@@ -203,10 +245,48 @@ impl ScanOptions {
         self
     }
 
+    /// Scan with a predicate. Only rows satisfying the predicate will be returned.
+    pub fn with_predicate_opt(mut self, predicate: Option<Arc<dyn ScanPredicate>>) -> Self {
+        self.predicate = predicate;
+        self
+    }
+
     pub fn with_sorted(mut self, sorted: bool) -> Self {
         self.is_sorted = sorted;
         self
     }
+
+    /// Scan a historical version instead of the one of the transaction.
+    pub fn with_as_of_opt(mut self, as_of: Option<AsOf>) -> Self {
+        self.as_of = as_of;
+        self
+    }
+
+    /// Scan only the partitions of a partitioned table.
+    pub fn with_partitions_opt(mut self, partitions: Option<Vec<u32>>) -> Self {
+        self.partitions = partitions;
+        self
+    }
+}
+
+/// A predicate to be evaluated by the storage engine during scan.
+///
+/// Storage engines that support late materialization read the columns required by the predicate
+/// first, and only read other columns for blocks where some rows pass the predicate.
+pub trait ScanPredicate: std::fmt::Debug + Send + Sync + 'static {
+    /// Returns the indexes of columns required by the predicate. The indexes are positions in the
+    /// column list of the scan.
+    fn columns(&self) -> &[usize];
+
+    /// Evaluates the predicate on a chunk of the required columns, in the order of
+    /// [`columns`](ScanPredicate::columns). Returns whether each row passes the predicate.
+    fn eval(&self, chunk: &DataChunk) -> StorageResult<Vec<bool>>;
+
+    /// Evaluates the predicate on a chunk of all scanned columns.
+    fn eval_all(&self, chunk: &DataChunk) -> StorageResult<Vec<bool>> {
+        let columns = self.columns().iter().map(|i| chunk.array_at(*i).clone());
+        self.eval(&columns.collect())
+    }
 }
 
 /// A range of keys.
@@ -0,0 +1,259 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Online backup and restore of the storage.
+//!
+//! A backup pins the latest version, and copies the files of its RowSets and DVs together with a
+//! manifest checkpoint of the version into the backup directory. As RowSets and DVs are never
+//! modified once written, they are hard-linked when possible. The CRC32 of each file is recorded
+//! in `checksums.json`, which is written at last and marks the backup as complete.
+//!
+//! A restore verifies the checksums of all files before copying them into a new database
+//! directory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+use itertools::Itertools;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use super::manifest::*;
+use super::{
+    sync_dir, IOBackend, SecondaryStorage, StorageResult, TracedStorageError, MANIFEST_FILE_NAME,
+};
+
+const CHECKSUM_FILE_NAME: &str = "checksums.json";
+
+/// CRC32 of each file in a backup, keyed by the path relative to the backup directory.
+type Checksums = BTreeMap<PathBuf, u32>;
+
+impl SecondaryStorage {
+    /// Back up the latest version of the database into an empty or non-existent directory.
+    pub async fn backup(&self, dir: &Path) -> StorageResult<()> {
+        if self.options.disable_all_disk_operation {
+            return Err(TracedStorageError::not_supported(
+                "backup of a database not on disk",
+            ));
+        }
+        create_empty_dir(dir).await?;
+
+        // rows in memtables are only persisted in the WAL, so flush them into RowSets.
+        // like a deletion, the backup keeps rows out of memtables until the version is pinned,
+        // so that rows committed meanwhile are written to RowSets and included in the version.
+        let tables = self.tables.read().clone();
+        let guards = tables.values().map(|t| t.begin_deletion()).collect_vec();
+        for table in tables.values() {
+            table.flush_memtable().await?;
+        }
+        // files of the version are not vacuumed until it is unpinned
+        let (_version, header, entries) = self.version.pin_checkpoint();
+        drop(guards);
+
+        let mut files = vec![];
+        for entry in &entries {
+            match entry {
+                ManifestOperation::AddRowSet(entry) => {
+                    let name = format!("{}_{}", entry.table_id.table_id, entry.rowset_id);
+                    files.extend(self.rowset_files(Path::new(&name)).await?);
+                }
+                ManifestOperation::AddDV(entry) => files.push(PathBuf::from(format!(
+                    "dv/{}_{}_{}.dv",
+                    entry.table_id.table_id, entry.rowset_id, entry.dv_id
+                ))),
+                _ => {}
+            }
+        }
+
+        let mut checksums = Checksums::new();
+        let mut dirs = BTreeSet::new();
+        for file in files {
+            let checksum = self.backup_file(&file, dir).await?;
+            dirs.insert(dir.join(&file).parent().unwrap().to_path_buf());
+            checksums.insert(file, checksum);
+        }
+        for dir in dirs {
+            sync_dir(dir).await?;
+        }
+
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        let mut manifest = Manifest::open(&manifest_path, true).await?;
+        manifest.checkpoint(&header, &entries).await?;
+        let manifest_checksum = crc32fast::hash(&fs::read(&manifest_path).await?);
+        checksums.insert(MANIFEST_FILE_NAME.into(), manifest_checksum);
+
+        let temp_path = dir.join(CHECKSUM_FILE_NAME).with_extension("tmp.json");
+        write_file(&temp_path, &serde_json::to_vec(&checksums)?).await?;
+        fs::rename(&temp_path, dir.join(CHECKSUM_FILE_NAME)).await?;
+        sync_dir(dir).await?;
+
+        info!("backup of {} files written to {:?}", checksums.len(), dir);
+        Ok(())
+    }
+
+    /// Returns the files of a RowSet, relative to the database directory.
+    async fn rowset_files(&self, rowset: &Path) -> StorageResult<Vec<PathBuf>> {
+        let directory = self.options.path.join(rowset);
+        if let IOBackend::InMemory(map) = &self.options.io_backend {
+            let map = map.lock();
+            let files = map.keys().filter(|path| path.parent() == Some(&directory));
+            return Ok(files
+                .map(|path| rowset.join(path.file_name().unwrap()))
+                .collect());
+        }
+        let mut files = vec![];
+        let mut dir = fs::read_dir(&directory).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            files.push(rowset.join(entry.file_name()));
+        }
+        Ok(files)
+    }
+
+    /// Copy a file of the database into the backup directory and return its checksum.
+    async fn backup_file(&self, file: &Path, dir: &Path) -> StorageResult<u32> {
+        let source = self.options.path.join(file);
+        let target = dir.join(file);
+        fs::create_dir_all(target.parent().unwrap()).await?;
+        if let IOBackend::InMemory(map) = &self.options.io_backend {
+            let data = (map.lock().get(&source).cloned())
+                .ok_or_else(|| TracedStorageError::not_found("file", source.display()))?;
+            write_file(&target, &data).await?;
+            return Ok(crc32fast::hash(&data));
+        }
+        if fs::hard_link(&source, &target).await.is_err() {
+            // e.g. the backup is on another file system
+            fs::copy(&source, &target).await?;
+            fs::File::open(&target).await?.sync_all().await?;
+        }
+        Ok(crc32fast::hash(&fs::read(&target).await?))
+    }
+
+    /// Restore a backup into an empty or non-existent database directory.
+    ///
+    /// The checksums of all files are verified before anything is written.
+    pub async fn restore(backup: &Path, path: &Path) -> StorageResult<()> {
+        let checksum_path = backup.join(CHECKSUM_FILE_NAME);
+        if fs::metadata(&checksum_path).await.is_err() {
+            return Err(TracedStorageError::not_found(
+                "complete backup",
+                backup.display(),
+            ));
+        }
+        let checksums: Checksums = serde_json::from_slice(&fs::read(&checksum_path).await?)?;
+        for (file, expected) in &checksums {
+            if !file.components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(TracedStorageError::decode(format!(
+                    "invalid file in backup: {file:?}"
+                )));
+            }
+            let found = crc32fast::hash(&fs::read(backup.join(file)).await?);
+            if found != *expected {
+                warn!("corrupted file in backup: {:?}", file);
+                return Err(TracedStorageError::checksum(found as u64, *expected as u64));
+            }
+        }
+
+        if !checksums.contains_key(Path::new(MANIFEST_FILE_NAME)) {
+            return Err(TracedStorageError::decode("no manifest in backup"));
+        }
+
+        create_empty_dir(path).await?;
+        let mut dirs = BTreeSet::new();
+        for file in checksums.keys() {
+            if file == Path::new(MANIFEST_FILE_NAME) {
+                continue;
+            }
+            let target = path.join(file);
+            fs::create_dir_all(target.parent().unwrap()).await?;
+            write_file(&target, &fs::read(backup.join(file)).await?).await?;
+            dirs.insert(target.parent().unwrap().to_path_buf());
+        }
+        for dir in dirs {
+            sync_dir(dir).await?;
+        }
+        // the manifest is restored at last, after all the files it refers to are persisted
+        let manifest = fs::read(backup.join(MANIFEST_FILE_NAME)).await?;
+        write_file(&path.join(MANIFEST_FILE_NAME), &manifest).await?;
+        sync_dir(path).await?;
+        info!("{} files restored from {:?}", checksums.len(), backup);
+        Ok(())
+    }
+}
+
+/// Create a directory if it doesn't exist. Returns an error if it is not empty.
+async fn create_empty_dir(dir: &Path) -> StorageResult<()> {
+    fs::create_dir_all(dir).await?;
+    if fs::read_dir(dir).await?.next_entry().await?.is_some() {
+        return Err(TracedStorageError::duplicated(
+            "non-empty directory",
+            dir.display(),
+        ));
+    }
+    Ok(())
+}
+
+/// Write a new file and persist its content.
+async fn write_file(path: &Path, data: &[u8]) -> StorageResult<()> {
+    let mut file = fs::File::create_new(path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::secondary::tests::{disk_options, open, query};
+    use crate::storage::secondary::StorageOptions;
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let backup = tempdir.path().join("backup");
+        let options = StorageOptions {
+            io_backend: IOBackend::PositionedRead,
+            ..disk_options(tempdir.path())
+        };
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+        session.run("delete from t where a = 1").await.unwrap();
+        // rows in the memtable are included in the backup
+        session.run("insert into t values (3)").await.unwrap();
+        let sql = format!("backup to '{}'", backup.display());
+        session.run(&sql).await.unwrap();
+        // the directory must be empty
+        assert!(session.run(&sql).await.is_err());
+        session.run("insert into t values (4)").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        let restored = tempdir.path().join("restored");
+        SecondaryStorage::restore(&backup, &restored).await.unwrap();
+        let options = StorageOptions {
+            path: restored,
+            ..options
+        };
+        let (db, session) = open(&options).await;
+        assert_eq!(
+            query(&session, "select a from t order by a").await,
+            ["2", "3"]
+        );
+        db.shutdown().await.unwrap();
+
+        // corrupt a data file of the backup
+        let rowset = (std::fs::read_dir(&backup).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.is_dir() && !path.ends_with("dv"))
+            .unwrap();
+        let column = rowset.join("0.col");
+        let mut data = std::fs::read(&column).unwrap();
+        data[0] ^= 0xff;
+        // the file is hard-linked with the database, so it is replaced instead of modified
+        std::fs::remove_file(&column).unwrap();
+        std::fs::write(&column, data).unwrap();
+        let restored = tempdir.path().join("corrupted");
+        assert!(SecondaryStorage::restore(&backup, &restored).await.is_err());
+        assert!(!restored.exists());
+    }
+}
@@ -19,6 +19,10 @@ mod rle_block_iterator;
 mod vector_block_builder;
 mod vector_block_iterator;
 
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
 use bitvec::prelude::{BitVec, Lsb0};
 pub use blob_block_builder::*;
 pub use blob_block_iterator::*;
@@ -38,6 +42,7 @@ pub use rle_block_iterator::*;
 mod block_index_builder;
 pub use block_index_builder::*;
 use bytes::{Buf, BufMut, Bytes};
+use moka::future::Cache;
 use risinglight_proto::rowset::block_checksum::ChecksumType;
 use risinglight_proto::rowset::block_index::BlockType;
 pub use vector_block_builder::*;
@@ -147,6 +152,64 @@ impl BlockCacheKey {
     }
 }
 
+/// The block cache of the storage engine, which counts its hits and misses.
+#[derive(Clone)]
+pub struct BlockCache {
+    cache: Cache<BlockCacheKey, Block>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl BlockCache {
+    /// Create a block cache holding at most `capacity` blocks.
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            cache: Cache::new(capacity),
+            hits: Default::default(),
+            misses: Default::default(),
+        }
+    }
+
+    /// Returns the cached block of the key, or loads it by `init` on a miss.
+    pub async fn try_get_with(
+        &self,
+        key: BlockCacheKey,
+        init: impl Future<Output = StorageResult<Block>>,
+    ) -> Result<Block, Arc<TracedStorageError>> {
+        let mut miss = false;
+        let block = (self.cache)
+            .try_get_with(key, async {
+                miss = true;
+                init.await
+            })
+            .await;
+        let counter = if miss { &self.misses } else { &self.hits };
+        counter.fetch_add(1, Ordering::Relaxed);
+        block
+    }
+
+    /// Returns the maximum number of blocks in the cache.
+    pub fn capacity(&self) -> u64 {
+        self.cache.policy().max_capacity().unwrap_or_default()
+    }
+
+    /// Returns the number of blocks in the cache.
+    pub async fn entry_count(&self) -> u64 {
+        self.cache.run_pending_tasks().await;
+        self.cache.entry_count()
+    }
+
+    /// Returns the number of lookups that found the block in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of lookups that loaded the block from the file.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct BlockMeta {
     pub block_type: BlockType,
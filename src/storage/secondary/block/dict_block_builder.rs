@@ -85,7 +85,12 @@ where
             block_stat_type: BlockStatisticsType::DistinctValue as i32,
             body: distinct_count.to_le_bytes().to_vec(),
         };
-        vec![distinct_stat]
+        // zone maps are computed over the dictionary
+        let zone_map_stats = (self.data_builder.get_statistics().into_iter())
+            .filter(|stat| stat.block_stat_type() != BlockStatisticsType::DistinctValue);
+        std::iter::once(distinct_stat)
+            .chain(zone_map_stats)
+            .collect()
     }
 
     fn should_finish(&self, next_item: &Option<&A::Item>) -> bool {
@@ -6,7 +6,7 @@ use bitvec::prelude::{BitVec, Lsb0};
 use risinglight_proto::rowset::BlockStatistics;
 
 use super::super::encode::PrimitiveFixedWidthEncode;
-use super::super::statistics::StatisticsBuilder;
+use super::super::statistics::{StatisticsBuilder, ZoneMapBuilder};
 use super::{BlockBuilder, NonNullableBlockBuilder};
 use crate::array::Array;
 
@@ -43,12 +43,16 @@ impl<T: PrimitiveFixedWidthEncode> NonNullableBlockBuilder<T::ArrayType>
     fn get_statistics_with_bitmap(&self, selection: &BitVec<u8, Lsb0>) -> Vec<BlockStatistics> {
         let selection_empty = selection.is_empty();
         let mut stats_builder = StatisticsBuilder::new();
+        let mut zone_map_builder = ZoneMapBuilder::<T>::new();
         for (idx, item) in self.data.chunks(T::WIDTH).enumerate() {
             if selection_empty || selection[idx] {
                 stats_builder.add_item(Some(item));
+                zone_map_builder.add_item(T::decode(&mut &item[..]));
             }
         }
-        stats_builder.get_statistics()
+        let mut stats = stats_builder.get_statistics();
+        stats.extend(zone_map_builder.get_statistics());
+        stats
     }
 
     fn estimated_size_with_next_item(
@@ -0,0 +1,386 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Offline integrity checks of a database directory.
+//!
+//! A check replays the manifest, opens every live RowSet and reads all of its blocks, so that the
+//! footer magic and checksum of each index and the checksum of each block are verified. DVs are
+//! decoded as well. Problems are collected instead of failing at the first one.
+//!
+//! Unlike opening the database, nothing is written: a torn tail of the manifest is reported but
+//! not truncated, and the WAL is not replayed.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use risinglight_proto::rowset::BlockIndex;
+use serde_json::json;
+use tokio::fs;
+
+use super::manifest::*;
+use super::{
+    BlockCache, DeleteVector, DiskRowset, IOBackend, SecondaryStorage, StorageResult,
+    TracedStorageError, MANIFEST_FILE_NAME,
+};
+use crate::catalog::{ColumnCatalog, RootCatalog, TableRefId};
+
+/// The result of [`SecondaryStorage::check`].
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    pub tables: Vec<TableCheckInfo>,
+    /// Corrupted or missing files and other inconsistencies found.
+    pub problems: Vec<String>,
+}
+
+/// Statistics of a table found by a check.
+#[derive(Debug, Clone)]
+pub struct TableCheckInfo {
+    pub table_id: TableRefId,
+    pub table_name: String,
+    pub rowsets: usize,
+    /// The number of blocks in all columns.
+    pub blocks: usize,
+    /// The number of rows, including deleted ones.
+    pub rows: u64,
+    pub deleted_rows: u64,
+    /// The size of all column files in bytes.
+    pub on_disk_size: u64,
+}
+
+/// The live tables, RowSets and DVs described by a manifest.
+struct ManifestState {
+    header: ManifestHeader,
+    ops: Vec<ManifestOperation>,
+    /// The length of the torn tail in bytes.
+    torn_bytes: usize,
+    tables: BTreeMap<TableRefId, CreateTableEntry>,
+    rowsets: BTreeMap<(TableRefId, u32), AddRowSetEntry>,
+    dvs: BTreeMap<(TableRefId, u32, u64), AddDVEntry>,
+}
+
+impl ManifestState {
+    async fn load(path: &Path) -> StorageResult<Self> {
+        let manifest_path = path.join(MANIFEST_FILE_NAME);
+        if fs::metadata(&manifest_path).await.is_err() {
+            return Err(TracedStorageError::not_found(
+                "manifest",
+                manifest_path.display(),
+            ));
+        }
+        let data = fs::read(&manifest_path).await?;
+        let (header, ops, committed_offset) = Manifest::decode(&data)?;
+
+        // ids of tables in manifests of version 0 are assigned by the catalog
+        let catalog = RootCatalog::new();
+        let mut tables = BTreeMap::new();
+        let mut rowsets = BTreeMap::new();
+        let mut dvs = BTreeMap::new();
+        for op in &ops {
+            match op.clone() {
+                ManifestOperation::CreateTable(mut entry) => {
+                    let schema_id = entry.schema_id;
+                    if catalog.get_schema_by_id(schema_id).is_none() {
+                        return Err(TracedStorageError::not_found("schema", schema_id));
+                    }
+                    let name = entry.table_name.clone();
+                    let columns = entry.column_descs.clone();
+                    let pk_ids = entry.ordered_pk_ids.clone();
+                    let table_id = match entry.table_id {
+                        Some(id) => catalog.add_table_with_id(schema_id, id, name, columns, pk_ids),
+                        None => catalog.add_table(schema_id, name, columns, pk_ids),
+                    }
+                    .map_err(|_| TracedStorageError::duplicated("table", &entry.table_name))?;
+                    entry.table_id = Some(table_id);
+                    let id = TableRefId {
+                        schema_id,
+                        table_id,
+                    };
+                    tables.insert(id, entry);
+                }
+                ManifestOperation::DropTable(entry) => {
+                    if tables.remove(&entry.table_id).is_some() {
+                        catalog.drop_table(entry.table_id);
+                    }
+                }
+                ManifestOperation::DropPartition(entry) => {
+                    if let Some(table) = tables.get_mut(&entry.table_id) {
+                        table.drop_partitions(&entry.partition_ids);
+                    }
+                }
+                ManifestOperation::AddRowSet(entry) => {
+                    rowsets.insert((entry.table_id, entry.rowset_id), entry);
+                }
+                ManifestOperation::DeleteRowSet(entry) => {
+                    rowsets.remove(&(entry.table_id, entry.rowset_id));
+                }
+                ManifestOperation::AddDV(entry) => {
+                    dvs.insert((entry.table_id, entry.rowset_id, entry.dv_id), entry);
+                }
+                ManifestOperation::DeleteDV(entry) => {
+                    dvs.remove(&(entry.table_id, entry.rowset_id, entry.dv_id));
+                }
+                ManifestOperation::PutUser(_)
+                | ManifestOperation::DropUser(_)
+                | ManifestOperation::Header(_)
+                | ManifestOperation::Begin
+                | ManifestOperation::End => {}
+            }
+        }
+        Ok(Self {
+            header,
+            ops,
+            torn_bytes: data.len() - committed_offset,
+            tables,
+            rowsets,
+            dvs,
+        })
+    }
+}
+
+/// Returns the directory of a RowSet.
+fn rowset_path(path: &Path, table_id: TableRefId, rowset_id: u32) -> PathBuf {
+    path.join(format!("{}_{}", table_id.table_id, rowset_id))
+}
+
+/// Returns the size of a column file in bytes.
+fn column_size(indexes: &[BlockIndex]) -> u64 {
+    indexes
+        .last()
+        .map_or(0, |index| index.offset + index.length)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).join("")
+}
+
+impl SecondaryStorage {
+    /// Check the integrity of a database directory without opening it.
+    pub async fn check(path: &Path) -> StorageResult<CheckReport> {
+        let state = ManifestState::load(path).await?;
+        let mut report = CheckReport::default();
+        if state.torn_bytes != 0 {
+            report
+                .problems
+                .push(format!("manifest: {} bytes of torn tail", state.torn_bytes));
+        }
+
+        let mut tables: BTreeMap<TableRefId, TableCheckInfo> = (state.tables.iter())
+            .map(|(&table_id, entry)| {
+                let info = TableCheckInfo {
+                    table_id,
+                    table_name: entry.table_name.clone(),
+                    rowsets: 0,
+                    blocks: 0,
+                    rows: 0,
+                    deleted_rows: 0,
+                    on_disk_size: 0,
+                };
+                (table_id, info)
+            })
+            .collect();
+
+        // blocks are read only once, so they are not cached
+        let block_cache = BlockCache::new(0);
+        for &(table_id, rowset_id) in state.rowsets.keys() {
+            let name = format!("{}_{}", table_id.table_id, rowset_id);
+            let (Some(entry), Some(info)) =
+                (state.tables.get(&table_id), tables.get_mut(&table_id))
+            else {
+                report
+                    .problems
+                    .push(format!("rowset {name}: table {table_id} not found"));
+                continue;
+            };
+            let rowset = match DiskRowset::open(
+                rowset_path(path, table_id, rowset_id),
+                entry.column_descs.clone().into(),
+                block_cache.clone(),
+                rowset_id,
+                IOBackend::NormalRead,
+            )
+            .await
+            {
+                Ok(rowset) => rowset,
+                Err(e) => {
+                    report
+                        .problems
+                        .push(format!("rowset {name}: {}", e.inner()));
+                    continue;
+                }
+            };
+            info.rowsets += 1;
+
+            let mut rows = vec![];
+            for (i, column) in rowset.get_columns().iter().enumerate() {
+                let indexes = column.index().indexes();
+                for block_id in 0..indexes.len() as u32 {
+                    if let Err(e) = column.get_block(block_id).await {
+                        report.problems.push(format!(
+                            "rowset {name}: column {}: block {block_id}: {}",
+                            rowset.column_info(i).id(),
+                            e.inner()
+                        ));
+                    }
+                }
+                info.blocks += indexes.len();
+                info.on_disk_size += column_size(indexes);
+                rows.push(
+                    indexes
+                        .iter()
+                        .map(|index| index.row_count as u64)
+                        .sum::<u64>(),
+                );
+            }
+            if !rows.iter().all_equal() {
+                report.problems.push(format!(
+                    "rowset {name}: columns have different numbers of rows: {rows:?}"
+                ));
+            }
+            info.rows += rows.first().copied().unwrap_or(0);
+        }
+
+        for (&(table_id, rowset_id, dv_id), entry) in &state.dvs {
+            let name = format!("{}_{}_{}", table_id.table_id, rowset_id, dv_id);
+            if !state.rowsets.contains_key(&(table_id, rowset_id)) {
+                report
+                    .problems
+                    .push(format!("DV {name}: rowset {rowset_id} not found"));
+                continue;
+            }
+            let dv_path = path.join(format!("dv/{name}.dv"));
+            match DeleteVector::open(dv_id, entry.rowset_id, dv_path, &IOBackend::NormalRead).await
+            {
+                Ok(dv) => {
+                    if let Some(info) = tables.get_mut(&table_id) {
+                        info.deleted_rows += dv.len() as u64;
+                    }
+                }
+                Err(e) => report.problems.push(format!("DV {name}: {}", e.inner())),
+            }
+        }
+
+        report.tables = tables.into_values().collect();
+        Ok(report)
+    }
+
+    /// Write the manifest entries and the block indexes of all live RowSets of a database
+    /// directory as JSON, one object per line.
+    pub async fn dump(path: &Path, out: &mut impl Write) -> StorageResult<()> {
+        let state = ManifestState::load(path).await?;
+        let header = ManifestOperation::Header(state.header.clone());
+        for op in std::iter::once(&header).chain(&state.ops) {
+            writeln!(out, "{}", serde_json::to_string(op)?)?;
+        }
+
+        let block_cache = BlockCache::new(0);
+        for &(table_id, rowset_id) in state.rowsets.keys() {
+            let Some(entry) = state.tables.get(&table_id) else {
+                continue;
+            };
+            let columns: Arc<[ColumnCatalog]> = entry.column_descs.clone().into();
+            let rowset = DiskRowset::open(
+                rowset_path(path, table_id, rowset_id),
+                columns,
+                block_cache.clone(),
+                rowset_id,
+                IOBackend::NormalRead,
+            )
+            .await?;
+            for (i, column) in rowset.get_columns().iter().enumerate() {
+                let blocks = (column.index().indexes().iter())
+                    .map(|index| {
+                        let stats = (index.stats.iter())
+                            .map(|stat| {
+                                json!({
+                                    "type": stat.block_stat_type().as_str_name(),
+                                    "body": to_hex(&stat.body),
+                                })
+                            })
+                            .collect_vec();
+                        json!({
+                            "offset": index.offset,
+                            "length": index.length,
+                            "first_rowid": index.first_rowid,
+                            "row_count": index.row_count,
+                            "first_key": to_hex(&index.first_key),
+                            "is_first_key_null": index.is_first_key_null,
+                            "stats": stats,
+                        })
+                    })
+                    .collect_vec();
+                let line = json!({
+                    "table_id": table_id,
+                    "rowset_id": rowset_id,
+                    "column_id": rowset.column_info(i).id(),
+                    "blocks": blocks,
+                });
+                writeln!(out, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use risinglight_proto::rowset::block_checksum::ChecksumType;
+
+    use super::*;
+    use crate::storage::secondary::StorageOptions;
+    use crate::{Database, Session};
+
+    #[tokio::test]
+    async fn test_check_and_dump() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().to_path_buf();
+        let options = StorageOptions {
+            path: path.clone(),
+            io_backend: IOBackend::PositionedRead,
+            checksum_type: ChecksumType::Crc32,
+            disable_all_disk_operation: false,
+            ..StorageOptions::default_for_test()
+        };
+        let db = Arc::new(Database::new_on_disk(options).await);
+        let session = Session::new(db.clone());
+        session.run("create table t (a int, b int)").await.unwrap();
+        session
+            .run("insert into t values (1, 1), (2, 2)")
+            .await
+            .unwrap();
+        session.run("delete from t where a = 1").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        let report = SecondaryStorage::check(&path).await.unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        let [table] = &report.tables[..] else {
+            panic!("expect one table: {:?}", report.tables);
+        };
+        assert_eq!(table.table_name, "t");
+        assert_eq!((table.rowsets, table.blocks), (1, 2));
+        assert_eq!((table.rows, table.deleted_rows), (2, 1));
+
+        let mut output = vec![];
+        SecondaryStorage::dump(&path, &mut output).await.unwrap();
+        let lines = String::from_utf8(output).unwrap();
+        let lines = lines.lines().collect_vec();
+        assert!(lines[0].starts_with(r#"{"Header""#));
+        // one line for each column
+        assert!(lines[lines.len() - 2].contains(r#""row_count":2"#));
+
+        // corrupt a block of the first column
+        let rowset = (std::fs::read_dir(&path).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.is_dir() && path.file_name().unwrap().to_str().unwrap().contains('_'))
+            .unwrap();
+        let column = rowset.join("0.col");
+        let mut data = std::fs::read(&column).unwrap();
+        data[0] ^= 0xff;
+        std::fs::write(&column, data).unwrap();
+
+        let report = SecondaryStorage::check(&path).await.unwrap();
+        assert_eq!(report.problems.len(), 1, "{:?}", report.problems);
+        assert!(report.problems[0].contains("column 0: block 0"));
+    }
+}
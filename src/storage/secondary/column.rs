@@ -31,7 +31,6 @@ pub use char_column_factory::*;
 pub use column_builder::*;
 pub use column_iterator::*;
 pub use concrete_column_iterator::*;
-use moka::future::Cache;
 pub use primitive_column_builder::*;
 pub use primitive_column_factory::*;
 use risinglight_proto::rowset::BlockIndex;
@@ -40,7 +39,7 @@ pub use vector_column_builder::*;
 pub use vector_column_factory::*;
 
 use super::block::BLOCK_META_CHECKSUM_SIZE;
-use super::{Block, BlockCacheKey, BlockMeta, ColumnIndex, BLOCK_META_SIZE};
+use super::{Block, BlockCache, BlockCacheKey, BlockMeta, ColumnIndex, BLOCK_META_SIZE};
 use crate::array::Array;
 use crate::storage::secondary::verify_checksum;
 use crate::storage::{StorageResult, TracedStorageError};
@@ -119,7 +118,7 @@ pub enum ColumnReadableFile {
 pub struct Column {
     index: ColumnIndex,
     file: ColumnReadableFile,
-    block_cache: Cache<BlockCacheKey, Block>,
+    block_cache: BlockCache,
     base_block_key: BlockCacheKey,
 }
 
@@ -127,7 +126,7 @@ impl Column {
     pub fn new(
         index: ColumnIndex,
         file: ColumnReadableFile,
-        block_cache: Cache<BlockCacheKey, Block>,
+        block_cache: BlockCache,
         base_block_key: BlockCacheKey,
     ) -> Self {
         Self {
@@ -158,41 +157,20 @@ impl Column {
         let mut do_verify_checksum = false;
 
         // support multiple I/O backend
-        let block =
-            self.block_cache
-                .try_get_with(key, async {
-                    // block has not been in cache, so we fetch it from disk
-                    let file = self.file.clone();
-                    let info = self.index.index(block_id).clone();
-                    let block = tokio::task::spawn_blocking(move || {
-                        let data = match file {
-                            ColumnReadableFile::PositionedRead(file) => {
-                                let mut data = vec![0; info.length as usize];
-                                file.read_exact_at(&mut data[..], info.offset)?;
-                                Bytes::from(data)
-                            }
-                            ColumnReadableFile::NormalRead(file) => {
-                                let mut data = vec![0; info.length as usize];
-                                let mut file = file.lock().unwrap();
-                                file.seek(SeekFrom::Start(info.offset))?;
-                                file.read_exact(&mut data[..])?;
-                                Bytes::from(data)
-                            }
-                            ColumnReadableFile::InMemory(file) => file
-                                .slice(info.offset as usize..(info.offset + info.length) as usize),
-                        };
-                        Ok::<_, TracedStorageError>(data)
-                    })
-                    .await
-                    .unwrap();
-                    // TODO(chi): we should invalidate cache item after a RowSet has been compacted.
-                    // self.block_cache.insert(key, block.clone()).await;
-
-                    // need to verify checksum when read from disk
-                    do_verify_checksum = true;
-                    block
-                })
-                .await?;
+        let block = self
+            .block_cache
+            .try_get_with(key, async {
+                // block has not been in cache, so we fetch it from disk
+                let info = self.index.index(block_id);
+                let block = self.read(info.offset, info.length).await;
+                // TODO(chi): we should invalidate cache item after a RowSet has been compacted.
+                // self.block_cache.insert(key, block.clone()).await;
+
+                // need to verify checksum when read from disk
+                do_verify_checksum = true;
+                block
+            })
+            .await?;
 
         if block.len() < BLOCK_META_SIZE {
             return Err(TracedStorageError::decode(
@@ -212,4 +190,47 @@ impl Column {
 
         Ok((block_header, block.slice(..block.len() - BLOCK_META_SIZE)))
     }
+
+    /// Returns the header of a block. Only the header is read from the file, without going
+    /// through the block cache.
+    pub async fn get_block_meta(&self, block_id: u32) -> StorageResult<BlockMeta> {
+        let info = self.index.index(block_id);
+        if info.length < BLOCK_META_SIZE as u64 {
+            return Err(TracedStorageError::decode(
+                "block is smaller than header size",
+            ));
+        }
+        let offset = info.offset + info.length - BLOCK_META_SIZE as u64;
+        let header = self.read(offset, BLOCK_META_SIZE as u64).await?;
+        let mut block_header = BlockMeta::default();
+        block_header.decode(&mut &header[..])?;
+        Ok(block_header)
+    }
+
+    /// Read `length` bytes at `offset` of the file.
+    async fn read(&self, offset: u64, length: u64) -> StorageResult<Bytes> {
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || {
+            let data = match file {
+                ColumnReadableFile::PositionedRead(file) => {
+                    let mut data = vec![0; length as usize];
+                    file.read_exact_at(&mut data[..], offset)?;
+                    Bytes::from(data)
+                }
+                ColumnReadableFile::NormalRead(file) => {
+                    let mut data = vec![0; length as usize];
+                    let mut file = file.lock().unwrap();
+                    file.seek(SeekFrom::Start(offset))?;
+                    file.read_exact(&mut data[..])?;
+                    Bytes::from(data)
+                }
+                ColumnReadableFile::InMemory(file) => {
+                    file.slice(offset as usize..(offset + length) as usize)
+                }
+            };
+            Ok(data)
+        })
+        .await
+        .unwrap()
+    }
 }
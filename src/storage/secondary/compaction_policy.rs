@@ -0,0 +1,241 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Policies deciding which RowSets of a table are merged by a compaction.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use itertools::Itertools;
+
+/// Statistics of a RowSet, from which a [`CompactionPolicy`] selects RowSets.
+#[derive(Debug, Clone, Copy)]
+pub struct RowsetStats {
+    pub rowset_id: u32,
+    /// The size of all column files in bytes.
+    pub on_disk_size: u64,
+    /// The number of rows, including deleted ones.
+    pub rows: u64,
+    pub deleted_rows: u64,
+}
+
+impl RowsetStats {
+    /// Returns the fraction of deleted rows.
+    pub fn delete_ratio(&self) -> f64 {
+        if self.rows == 0 {
+            return 0.0;
+        }
+        self.deleted_rows as f64 / self.rows as f64
+    }
+}
+
+/// Selects the RowSets of a table to be merged into one RowSet.
+///
+/// The deleted rows of the selected RowSets are dropped and their DVs are purged, so selecting a
+/// single RowSet rewrites it without the deleted rows.
+pub trait CompactionPolicy: Debug + Send + Sync {
+    /// The name of the policy shown in `rw_compactions`.
+    fn name(&self) -> &str;
+
+    /// Returns the ids of RowSets to be merged, or an empty list if the table needs no compaction.
+    fn select(&self, rowsets: &[RowsetStats], target_rowset_size: u64) -> Vec<u32>;
+}
+
+/// Merges RowSets of similar sizes, so that each row is rewritten a logarithmic number of times.
+///
+/// RowSets smaller than `min_size` are in tier 0, and each following tier holds RowSets `fanout`
+/// times as large as the previous one. The smallest RowSets of the lowest tier with at least
+/// `min_rowsets` RowSets are merged, as long as the result is within the target RowSet size.
+#[derive(Debug, Clone)]
+pub struct SizeTieredPolicy {
+    pub min_size: u64,
+    pub fanout: u64,
+    pub min_rowsets: usize,
+}
+
+impl Default for SizeTieredPolicy {
+    fn default() -> Self {
+        Self {
+            min_size: 1 << 20, // 1MB
+            fanout: 4,
+            min_rowsets: 2,
+        }
+    }
+}
+
+impl SizeTieredPolicy {
+    fn tier(&self, size: u64) -> u32 {
+        if size < self.min_size {
+            0
+        } else {
+            1 + (size / self.min_size.max(1)).ilog(self.fanout.max(2))
+        }
+    }
+}
+
+impl CompactionPolicy for SizeTieredPolicy {
+    fn name(&self) -> &str {
+        "size_tiered"
+    }
+
+    fn select(&self, rowsets: &[RowsetStats], target_rowset_size: u64) -> Vec<u32> {
+        let mut tiers: BTreeMap<u32, Vec<&RowsetStats>> = BTreeMap::new();
+        for rowset in rowsets {
+            tiers
+                .entry(self.tier(rowset.on_disk_size))
+                .or_default()
+                .push(rowset);
+        }
+        for (_, tier) in tiers {
+            let mut selected = vec![];
+            let mut size = 0;
+            for rowset in tier
+                .into_iter()
+                .sorted_by_key(|r| (r.on_disk_size, r.rowset_id))
+            {
+                if size + rowset.on_disk_size > target_rowset_size {
+                    break;
+                }
+                size += rowset.on_disk_size;
+                selected.push(rowset.rowset_id);
+            }
+            if selected.len() >= self.min_rowsets.max(2) {
+                return selected;
+            }
+        }
+        vec![]
+    }
+}
+
+/// Rewrites RowSets with a high fraction of deleted rows, so that the rows and their DVs are
+/// purged.
+///
+/// RowSets with at least `min_deleted_rows` deleted rows and a delete ratio of at least
+/// `min_ratio` are selected from the highest ratio, up to the target RowSet size.
+#[derive(Debug, Clone)]
+pub struct DeleteRatioPolicy {
+    pub min_ratio: f64,
+    pub min_deleted_rows: u64,
+}
+
+impl Default for DeleteRatioPolicy {
+    fn default() -> Self {
+        Self {
+            min_ratio: 0.2,
+            min_deleted_rows: 1024,
+        }
+    }
+}
+
+impl DeleteRatioPolicy {
+    /// Returns a policy selecting all RowSets with deleted rows, used by `VACUUM`.
+    pub fn all() -> Self {
+        Self {
+            min_ratio: 0.0,
+            min_deleted_rows: 1,
+        }
+    }
+}
+
+impl CompactionPolicy for DeleteRatioPolicy {
+    fn name(&self) -> &str {
+        "delete_ratio"
+    }
+
+    fn select(&self, rowsets: &[RowsetStats], target_rowset_size: u64) -> Vec<u32> {
+        let candidates = (rowsets.iter())
+            .filter(|r| {
+                r.deleted_rows >= self.min_deleted_rows.max(1) && r.delete_ratio() >= self.min_ratio
+            })
+            .sorted_by(|a, b| {
+                (b.delete_ratio().total_cmp(&a.delete_ratio())).then(a.rowset_id.cmp(&b.rowset_id))
+            });
+        let mut selected = vec![];
+        let mut size = 0;
+        for rowset in candidates {
+            // the size of the rows left
+            let live_size = (rowset.on_disk_size as f64 * (1.0 - rowset.delete_ratio())) as u64;
+            if !selected.is_empty() && size + live_size > target_rowset_size {
+                break;
+            }
+            size += live_size;
+            selected.push(rowset.rowset_id);
+        }
+        selected
+    }
+}
+
+/// Merges all RowSets of a table into one, regardless of the target RowSet size. Used by
+/// `VACUUM FULL`.
+#[derive(Debug, Clone, Default)]
+pub struct MergeAllPolicy;
+
+impl CompactionPolicy for MergeAllPolicy {
+    fn name(&self) -> &str {
+        "merge_all"
+    }
+
+    fn select(&self, rowsets: &[RowsetStats], _target_rowset_size: u64) -> Vec<u32> {
+        if rowsets.len() <= 1 && rowsets.iter().all(|r| r.deleted_rows == 0) {
+            return vec![];
+        }
+        rowsets.iter().map(|r| r.rowset_id).sorted().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rowset(rowset_id: u32, on_disk_size: u64, rows: u64, deleted_rows: u64) -> RowsetStats {
+        RowsetStats {
+            rowset_id,
+            on_disk_size,
+            rows,
+            deleted_rows,
+        }
+    }
+
+    #[test]
+    fn test_size_tiered() {
+        let policy = SizeTieredPolicy {
+            min_size: 100,
+            fanout: 4,
+            min_rowsets: 2,
+        };
+        let rowsets = [
+            rowset(1, 1000, 10, 0),
+            rowset(2, 50, 10, 0),
+            rowset(3, 1200, 10, 0),
+            rowset(4, 5000, 10, 0),
+        ];
+        // 50 is alone in tier 0, and 1000 and 1200 are in tier 2
+        assert_eq!(policy.select(&rowsets, 10000), [1, 3]);
+        // the result must be within the target size
+        assert!(policy.select(&rowsets, 2000).is_empty());
+        assert!(policy.select(&rowsets[..2], 10000).is_empty());
+    }
+
+    #[test]
+    fn test_delete_ratio() {
+        let policy = DeleteRatioPolicy {
+            min_ratio: 0.2,
+            min_deleted_rows: 2,
+        };
+        let rowsets = [
+            rowset(1, 1000, 10, 1),
+            rowset(2, 1000, 10, 5),
+            rowset(3, 1000, 10, 3),
+            rowset(4, 1000, 10, 10),
+        ];
+        assert_eq!(policy.select(&rowsets, 10000), [4, 2, 3]);
+        // the size of rows left must be within the target size
+        assert_eq!(policy.select(&rowsets, 100), [4]);
+        assert_eq!(
+            DeleteRatioPolicy::all().select(&rowsets, 10000),
+            [4, 2, 3, 1]
+        );
+        assert!(DeleteRatioPolicy::default()
+            .select(&rowsets, 10000)
+            .is_empty());
+    }
+}
@@ -1,26 +1,44 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use itertools::Itertools;
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
+use risinglight_proto::rowset::DeleteRecord;
 use tokio::sync::oneshot::Receiver;
 use tracing::{info, warn};
 
-use super::{SecondaryStorage, SecondaryTable, Snapshot};
-use crate::catalog::find_sort_key_id;
+use super::{
+    CompactionInfo, CompactionPolicy, DeleteRatioPolicy, MergeAllPolicy, RowsetRemap, RowsetStats,
+    SecondaryRowHandler, SecondaryStorage, SecondaryTable, Snapshot,
+};
+use crate::catalog::{find_sort_key_id, TableRefId};
 use crate::storage::secondary::column::ColumnSeekPosition;
 use crate::storage::secondary::concat_iterator::ConcatIterator;
-use crate::storage::secondary::manifest::{AddRowSetEntry, DeleteRowsetEntry};
+use crate::storage::secondary::manifest::{
+    AddDVEntry, AddRowSetEntry, DeleteDVEntry, DeleteRowsetEntry,
+};
 use crate::storage::secondary::merge_iterator::MergeIterator;
 use crate::storage::secondary::rowset::{DiskRowset, RowsetBuilder, RowsetWriter};
 use crate::storage::secondary::statistics::create_statistics_global_aggregator;
 use crate::storage::secondary::version_manager::EpochOp;
 use crate::storage::secondary::{ColumnBuilderOptions, EncodeType, SecondaryIterator};
-use crate::storage::{StorageColumnRef, StorageResult};
+use crate::storage::{RowHandler, StorageColumnRef, StorageResult};
 use crate::types::DataValue;
 
+/// A compaction requested by a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionMode {
+    /// `COMPACT`: compact with the policies of the storage until none of them selects RowSets.
+    Compact,
+    /// `VACUUM`: rewrite all RowSets with deleted rows.
+    Vacuum,
+    /// `VACUUM FULL`: merge all RowSets into one.
+    VacuumFull,
+}
+
 /// Manages all compactions happening in the storage engine.
 pub struct Compactor {
     storage: Arc<SecondaryStorage>,
@@ -32,36 +50,193 @@ impl Compactor {
         Self { storage, stop }
     }
 
-    async fn compact_table(&self, snapshot: &Snapshot, table: SecondaryTable) -> StorageResult<()> {
-        let rowsets = if let Some(rowsets) = snapshot.get_rowsets_of(table.table_id()) {
-            rowsets
-        } else {
-            // No rowset available for this table
-            return Ok(());
+    /// Compact a table with the first policy selecting some RowSets.
+    async fn compact_table(&self, table: &SecondaryTable) -> StorageResult<Option<CompactionInfo>> {
+        if !self.storage.options.background_compaction {
+            return Ok(None);
+        }
+        let Some(_guard) = (self.storage.txn_mgr).try_lock_for_compaction(table.table_id()) else {
+            return Ok(None);
+        };
+        // pinned after locking, so that the RowSets are not compacted by others
+        let version = self.storage.version.pin();
+        for policy in &self.storage.options.compaction_policies {
+            let compaction = (self.storage)
+                .compact_with(
+                    &version.snapshot,
+                    table,
+                    policy.as_ref(),
+                    &HashSet::new(),
+                    false,
+                )
+                .await?;
+            if compaction.is_some() {
+                return Ok(compaction);
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn run(mut self) -> StorageResult<()> {
+        loop {
+            {
+                let tables = self.storage.tables.read().clone();
+                for table in tables.values() {
+                    if table.memtable_needs_flush() {
+                        if let Err(err) = table.flush_memtable().await {
+                            warn!("failed to flush memtable: {:?}", err);
+                        }
+                    }
+                }
+                for (_, table) in tables {
+                    match self.compact_table(&table).await {
+                        Ok(Some(compaction)) => {
+                            if let Some(rate) = self.storage.options.compaction_rate_limit {
+                                let expected = compaction.input_size as f64 / rate.max(1) as f64;
+                                let delay = Duration::from_secs_f64(expected)
+                                    .saturating_sub(compaction.duration);
+                                tokio::time::sleep(delay).await;
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(err) => warn!("failed to compact: {:?}", err),
+                    }
+                }
+                match self.stop.try_recv() {
+                    Ok(_) => break,
+                    Err(tokio::sync::oneshot::error::TryRecvError::Closed) => break,
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(self.storage.options.compaction_interval).await;
+        }
+
+        Ok(())
+    }
+}
+
+impl SecondaryStorage {
+    /// Compact a table as requested by a `COMPACT` or `VACUUM` statement, and returns the number
+    /// of compactions.
+    pub async fn compact(
+        &self,
+        table_id: TableRefId,
+        mode: CompactionMode,
+    ) -> StorageResult<usize> {
+        let table = self.get_table_inner(table_id)?;
+        // rows in the memtable are compacted as well
+        table.flush_memtable().await?;
+        let _guard = self.txn_mgr.lock_for_compaction(table.table_id()).await;
+
+        let policies: Vec<Arc<dyn CompactionPolicy>> = match mode {
+            CompactionMode::Compact => self.options.compaction_policies.clone(),
+            CompactionMode::Vacuum => vec![Arc::new(DeleteRatioPolicy::all())],
+            CompactionMode::VacuumFull => vec![Arc::new(MergeAllPolicy)],
         };
-        let mut selected_rowsets = vec![];
-        let mut current_size = 0;
-        for rowset_id in rowsets {
-            let rowset = self
-                .storage
-                .version
-                .get_rowset(table.table_id(), *rowset_id);
-            let on_disk_size = rowset.on_disk_size();
-            if on_disk_size + current_size <= self.storage.options.target_rowset_size as u64 {
-                current_size += on_disk_size;
-                selected_rowsets.push(rowset);
+        let mut count = 0;
+        // partitions merged by `VACUUM FULL`
+        let mut merged = HashSet::new();
+        // each compaction reduces the number of RowSets or purges DVs, so the loop ends
+        'compact: loop {
+            let version = self.version.pin();
+            for policy in &policies {
+                let compaction = self
+                    .compact_with(&version.snapshot, &table, policy.as_ref(), &merged, true)
+                    .await?;
+                if let Some(compaction) = compaction {
+                    count += 1;
+                    if mode == CompactionMode::VacuumFull {
+                        merged.insert(compaction.partition);
+                    }
+                    continue 'compact;
+                }
             }
+            break;
         }
-        if selected_rowsets.len() <= 1 {
-            return Ok(());
+        Ok(count)
+    }
+
+    /// Returns the statistics of the RowSets of a table in the snapshot.
+    fn rowset_stats(&self, snapshot: &Snapshot, table: &SecondaryTable) -> Vec<RowsetStats> {
+        let Some(rowsets) = snapshot.get_rowsets_of(table.table_id()) else {
+            return vec![];
+        };
+        (rowsets.iter().sorted())
+            .map(|&rowset_id| {
+                let rowset = self.version.get_rowset(table.table_id(), rowset_id);
+                let rows = (rowset.get_columns().first())
+                    .map(|c| c.index().indexes().iter().map(|i| i.row_count as u64).sum())
+                    .unwrap_or_default();
+                let deleted_rows = (snapshot.get_dvs_of(table.table_id(), rowset_id))
+                    .map(|dvs| {
+                        (dvs.iter())
+                            .map(|dv_id| self.version.get_dv(table.table_id(), *dv_id).len() as u64)
+                            .sum()
+                    })
+                    .unwrap_or_default();
+                RowsetStats {
+                    rowset_id,
+                    on_disk_size: rowset.on_disk_size(),
+                    rows,
+                    deleted_rows,
+                }
+            })
+            .collect()
+    }
+
+    /// Merge the RowSets selected by the policy into one, dropping the deleted rows and purging
+    /// their DVs. Returns `None` if no RowSet is selected.
+    ///
+    /// RowSets of different partitions are never merged. The policy selects RowSets of each
+    /// partition in order, except the ones in `skip`, and the first selection is compacted.
+    ///
+    /// Rows deleted by transactions committed during the compaction are remapped onto the output
+    /// RowSet, and so are rows deleted by running transactions when they are committed.
+    ///
+    /// The caller must hold the compaction lock of the table, and `snapshot` must be pinned after
+    /// the lock is acquired.
+    async fn compact_with(
+        &self,
+        snapshot: &Snapshot,
+        table: &SecondaryTable,
+        policy: &dyn CompactionPolicy,
+        skip: &HashSet<Option<u32>>,
+        manual: bool,
+    ) -> StorageResult<Option<CompactionInfo>> {
+        let mut partitions: BTreeMap<Option<u32>, Vec<RowsetStats>> = BTreeMap::new();
+        for stats in self.rowset_stats(snapshot, table) {
+            let partition = (self.version).get_rowset_partition(table.table_id(), stats.rowset_id);
+            partitions.entry(partition).or_default().push(stats);
         }
+        let Some((partition, stats, selected)) = (partitions.into_iter())
+            .filter(|(partition, _)| !skip.contains(partition))
+            .map(|(partition, stats)| {
+                let selected = policy.select(&stats, self.options.target_rowset_size as u64);
+                (partition, stats, selected)
+            })
+            .find(|(_, _, selected)| !selected.is_empty())
+        else {
+            return Ok(None);
+        };
+        let mut selected_rowsets = (selected.iter())
+            .map(|rowset_id| self.version.get_rowset(table.table_id(), *rowset_id))
+            .collect_vec();
+        let current_size: u64 = selected_rowsets.iter().map(|r| r.on_disk_size()).sum();
+        let input_rows: u64 = (stats.iter())
+            .filter(|r| selected.contains(&r.rowset_id))
+            .map(|r| r.rows)
+            .sum();
+        let start_time = SystemTime::now();
+        let start = Instant::now();
 
         // sort RowSets by id so that the output RowSet will have old rows in the front and new rows
         // at the end.
         selected_rowsets.sort_by_key(|x| x.rowset_id());
 
+        // row handlers are scanned at last to locate the rows in the output RowSet
         let column_refs: Arc<[StorageColumnRef]> = (0..table.columns.len())
             .map(|idx| StorageColumnRef::Idx(idx as u32))
+            .chain([StorageColumnRef::RowHandler])
             .collect_vec()
             .into();
         let mut iters = vec![];
@@ -70,7 +245,7 @@ impl Compactor {
                 .get_dvs_of(table.table_id(), rowset.rowset_id())
                 .map(|dvs| {
                     dvs.iter()
-                        .map(|dv_id| self.storage.version.get_dv(table.table_id(), *dv_id))
+                        .map(|dv_id| self.version.get_dv(table.table_id(), *dv_id))
                         .collect_vec()
                 })
                 .unwrap_or_default();
@@ -90,7 +265,7 @@ impl Compactor {
             )
             .into()
         } else {
-            ConcatIterator::new(iters).into()
+            ConcatIterator::new(iters.into_iter().map(|iter| iter.into()).collect_vec()).into()
         };
 
         let mut distinct_value = 0;
@@ -132,11 +307,27 @@ impl Compactor {
             )
         };
 
+        // the new row id of each input row
+        let mut row_ids: HashMap<u32, Vec<u32>> = (stats.iter())
+            .filter(|r| selected.contains(&r.rowset_id))
+            .map(|r| (r.rowset_id, vec![RowsetRemap::DELETED; r.rows as usize]))
+            .collect();
+        let mut next_row_id = 0;
         while let Some(batch) = iter.next_batch(None).await? {
-            builder.append(batch.to_data_chunk());
+            let chunk = batch.to_data_chunk();
+            let (handlers, arrays) = chunk.arrays().split_last().unwrap();
+            for i in 0..handlers.len() {
+                let handler = SecondaryRowHandler::from_column(handlers, i);
+                row_ids.get_mut(&handler.rowset_id()).unwrap()[handler.row_id() as usize] =
+                    next_row_id;
+                next_row_id += 1;
+            }
+            builder.append(arrays.iter().cloned().collect());
         }
 
         let rowset = builder.finish();
+        let output_rows = rowset.cardinality() as u64;
+        let mut output_size = 0;
 
         let mut changes: Vec<EpochOp> = vec![];
 
@@ -150,24 +341,26 @@ impl Compactor {
             let rowset_id = rowset_id.unwrap();
             let directory = table.get_rowset_path(rowset_id);
 
-            let writer = RowsetWriter::new(&directory, self.storage.options.io_backend.clone());
+            let writer = RowsetWriter::new(&directory, self.options.io_backend.clone());
             writer.create_dir().await?;
             writer.flush(rowset).await?;
 
             let rowset = DiskRowset::open(
                 directory,
                 table.columns.clone(),
-                self.storage.block_cache.clone(),
+                self.block_cache.clone(),
                 rowset_id,
-                self.storage.options.io_backend.clone(),
+                self.options.io_backend.clone(),
             )
             .await?;
+            output_size = rowset.on_disk_size();
 
             // Add RowSets
             let add_rowset_op = EpochOp::AddRowSet((
                 AddRowSetEntry {
                     rowset_id: rowset.rowset_id(),
                     table_id: table.table_ref_id,
+                    partition,
                 },
                 rowset,
             ));
@@ -175,16 +368,90 @@ impl Compactor {
             changes.push(add_rowset_op);
         }
 
-        // Remove old RowSets
-        // and TODO: remove old DVs
-        changes.extend(selected_rowsets.iter().map(|x| {
-            EpochOp::DeleteRowSet(DeleteRowsetEntry {
-                rowset_id: x.rowset_id(),
+        // Deletions committed from now on are remapped onto the output RowSet.
+        let _commit_lock = self.txn_mgr.lock_for_commit(table.table_id()).await;
+        let latest = self.version.pin();
+        let rowsets = latest.snapshot.get_rowsets_of(table.table_id());
+        if !(selected_rowsets.iter())
+            .all(|rowset| rowsets.is_some_and(|r| r.contains(&rowset.rowset_id())))
+        {
+            // the RowSets are dropped by a range deletion or with their partition, or the table
+            // is dropped
+            if let (Some(rowset_id), false) = (rowset_id, self.options.disable_all_disk_operation) {
+                tokio::fs::remove_dir_all(table.get_rowset_path(rowset_id)).await?;
+            }
+            info!("compaction aborted as the RowSets are deleted");
+            return Ok(None);
+        }
+
+        // Remove old RowSets and their DVs, and remap rows deleted during the compaction
+        let mut purged_dvs = 0;
+        let mut deletes = vec![];
+        for rowset in &selected_rowsets {
+            changes.push(EpochOp::DeleteRowSet(DeleteRowsetEntry {
+                rowset_id: rowset.rowset_id(),
                 table_id: table.table_ref_id,
-            })
-        }));
+            }));
+            let dvs = latest
+                .snapshot
+                .get_dvs_of(table.table_id(), rowset.rowset_id());
+            for dv_id in dvs.into_iter().flatten() {
+                purged_dvs += 1;
+                changes.push(EpochOp::DeleteDV(DeleteDVEntry {
+                    table_id: table.table_ref_id,
+                    dv_id: *dv_id,
+                    rowset_id: rowset.rowset_id(),
+                }));
+                let compacted = snapshot.get_dvs_of(table.table_id(), rowset.rowset_id());
+                if compacted.is_some_and(|dvs| dvs.contains(dv_id)) {
+                    continue;
+                }
+                let dv = self.version.get_dv(table.table_id(), *dv_id);
+                let new_row_ids = &row_ids[&rowset.rowset_id()];
+                deletes.extend(
+                    (dv.row_ids())
+                        .map(|row_id| new_row_ids[row_id as usize])
+                        .filter(|row_id| *row_id != RowsetRemap::DELETED)
+                        .map(|row_id| DeleteRecord {
+                            row_id,
+                            row_count: 0,
+                        }),
+                );
+            }
+        }
+        if let (Some(rowset_id), false) = (rowset_id, deletes.is_empty()) {
+            let dv = table.write_dv(rowset_id, deletes).await?;
+            changes.push(EpochOp::AddDV((
+                AddDVEntry {
+                    rowset_id,
+                    dv_id: dv.dv_id(),
+                    table_id: table.table_ref_id,
+                },
+                dv,
+            )));
+        }
 
-        self.storage.version.commit_changes(changes).await?;
+        let epoch = self.version.commit_changes(changes).await?;
+        for (input, row_ids) in row_ids {
+            let remap = RowsetRemap::new(epoch, rowset_id, row_ids);
+            self.txn_mgr.add_remap(table.table_id(), input, remap);
+        }
+        let compaction = CompactionInfo {
+            table_id: table.table_ref_id,
+            policy: policy.name().to_string(),
+            manual,
+            partition,
+            input_rowsets: selected_rowsets.iter().map(|x| x.rowset_id()).collect(),
+            output_rowset: rowset_id,
+            input_size: current_size,
+            output_size,
+            input_rows,
+            output_rows,
+            purged_dvs,
+            start_time,
+            duration: start.elapsed(),
+        };
+        self.record_compaction(compaction.clone());
 
         match rowset_id {
             Some(rowset_id) => {
@@ -202,34 +469,154 @@ impl Compactor {
             }
         }
 
-        Ok(())
+        Ok(Some(compaction))
     }
+}
 
-    pub async fn run(mut self) -> StorageResult<()> {
-        loop {
-            {
-                let tables = self.storage.tables.read().clone();
-                let pin_version = self.storage.version.pin();
-                for (_, table) in tables {
-                    if let Some(_guard) = self
-                        .storage
-                        .txn_mgr
-                        .try_lock_for_compaction(table.table_id())
-                    {
-                        if let Err(err) = self.compact_table(&pin_version.snapshot, table).await {
-                            warn!("failed to compact: {:?}", err);
-                        }
-                    }
-                }
-                match self.stop.try_recv() {
-                    Ok(_) => break,
-                    Err(tokio::sync::oneshot::error::TryRecvError::Closed) => break,
-                    _ => {}
-                }
-            }
-            tokio::time::sleep(Duration::from_secs(1)).await;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::secondary::tests::{self, insert, open, query, scan};
+    use crate::storage::secondary::StorageOptions;
+    use crate::storage::{StorageError, Table, Transaction};
+
+    /// Create a table `t (a int)` where every insert creates a RowSet.
+    async fn create_table() -> (Arc<SecondaryStorage>, SecondaryTable) {
+        tests::create_table(StorageOptions {
+            memtable_size: 0,
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        })
+        .await
+    }
+
+    fn values(rows: &[(i32, SecondaryRowHandler)]) -> Vec<i32> {
+        rows.iter().map(|(value, _)| *value).collect()
+    }
+
+    #[tokio::test]
+    async fn test_delete_during_compaction() {
+        let (storage, table) = create_table().await;
+        insert(&table, &[1, 2]).await;
+        insert(&table, &[3, 4]).await;
+        let rows = scan(&table).await;
+
+        // a deletion doesn't block compactions
+        let mut txn = table.update().await.unwrap();
+        txn.delete(&rows[0].1).await.unwrap();
+        let count = (storage.compact(table.table_ref_id, CompactionMode::VacuumFull))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // the deleted row is remapped onto the compacted RowSet
+        txn.commit().await.unwrap();
+        let rows = scan(&table).await;
+        assert_eq!(values(&rows), [2, 3, 4]);
+        assert!(rows
+            .iter()
+            .all(|(_, row)| row.rowset_id() == rows[0].1.rowset_id()));
+
+        // deletions committed during a compaction are remapped as well
+        let version = storage.version.pin();
+        let mut txn = table.update().await.unwrap();
+        txn.delete(&rows[1].1).await.unwrap();
+        txn.commit().await.unwrap();
+        let _guard = storage.txn_mgr.lock_for_compaction(table.table_id()).await;
+        let compaction = (storage)
+            .compact_with(
+                &version.snapshot,
+                &table,
+                &MergeAllPolicy,
+                &HashSet::new(),
+                true,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        // the DVs of both deletions are purged
+        assert_eq!(compaction.purged_dvs, 2);
+        assert_eq!(values(&scan(&table).await), [2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_write_conflict() {
+        let (storage, table) = create_table().await;
+        insert(&table, &[1, 2]).await;
+        insert(&table, &[3, 4]).await;
+        let rows = scan(&table).await;
+
+        let mut txn1 = table.update().await.unwrap();
+        let mut txn2 = table.update().await.unwrap();
+        let mut txn3 = table.update().await.unwrap();
+        txn1.delete(&rows[0].1).await.unwrap();
+        txn2.delete(&rows[0].1).await.unwrap();
+        txn3.delete(&rows[1].1).await.unwrap();
+        txn3.delete(&rows[2].1).await.unwrap();
+
+        // the first committer wins
+        txn1.commit().await.unwrap();
+        let err = txn2.commit().await.unwrap_err();
+        assert!(matches!(err.inner(), StorageError::WriteConflict));
+
+        // deletions of different rows don't conflict, even if the rows are compacted
+        (storage.compact(table.table_ref_id, CompactionMode::VacuumFull))
+            .await
+            .unwrap();
+        txn3.commit().await.unwrap();
+        assert_eq!(values(&scan(&table).await), [4]);
+
+        // rows dropped by a compaction conflict too
+        let mut txn = table.update().await.unwrap();
+        txn.delete(&rows[0].1).await.unwrap();
+        let err = txn.commit().await.unwrap_err();
+        assert!(matches!(err.inner(), StorageError::WriteConflict));
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_and_compact() {
+        let options = StorageOptions {
+            // every insert creates a RowSet
+            memtable_size: 0,
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        };
+        let (_db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        for sql in [
+            "insert into t values (1), (2)",
+            "insert into t values (3), (4)",
+            "insert into t values (5)",
+            "delete from t where a = 1 or a = 3",
+        ] {
+            session.run(sql).await.unwrap();
         }
+        // system tables only support `select *`
+        let rowsets = "select * from pg_catalog.rw_rowsets";
+        let dvs = "select * from pg_catalog.rw_delete_vectors";
+        assert_eq!(query(&session, rowsets).await.len(), 3);
+        assert_eq!(query(&session, dvs).await.len(), 2);
 
-        Ok(())
+        // the RowSets with deleted rows are rewritten into one
+        session.run("vacuum t").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 2);
+        assert!(query(&session, dvs).await.is_empty());
+        let compactions = "select * from pg_catalog.rw_compactions";
+        let chunks = session.run(compactions).await.unwrap();
+        let chunk = chunks[0].get_first_data_chunk();
+        assert_eq!(chunk.cardinality(), 1);
+        // output_rows, policy, manual, input_rows and purged_dvs
+        let row = [6, 9, 10, 11, 12].map(|i| chunk.array_at(i).get_to_string(0));
+        assert_eq!(row, ["2", "delete_ratio", "true", "4", "2"]);
+
+        // small RowSets are in the same tier
+        session.run("compact t").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 1);
+
+        session.run("insert into t values (6)").await.unwrap();
+        session.run("delete from t where a = 6").await.unwrap();
+        session.run("vacuum full t").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 1);
+        assert!(query(&session, dvs).await.is_empty());
     }
 }
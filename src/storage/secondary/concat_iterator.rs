@@ -1,18 +1,18 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use super::{RowSetIterator, SecondaryIteratorImpl};
+use super::{SecondaryIterator, SecondaryIteratorImpl};
 use crate::storage::{StorageChunk, StorageResult};
 
-/// [`ConcatIterator`] concats data from `RowSet`s and yields data
+/// [`ConcatIterator`] concats data from `RowSet`s and memtables and yields data
 /// from them one by one. This iterator should only be used on
 /// non-overlapping `RowSet`s.
 pub struct ConcatIterator {
-    iters: Vec<RowSetIterator>,
+    iters: Vec<SecondaryIterator>,
     current_iter: usize,
 }
 
 impl ConcatIterator {
-    pub fn new(iters: Vec<RowSetIterator>) -> Self {
+    pub fn new(iters: Vec<SecondaryIterator>) -> Self {
         Self {
             iters,
             current_iter: 0,
@@ -0,0 +1,335 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Fast paths deleting rows in a key range without locating each row.
+//!
+//! RowSets within the range are dropped as a whole, and rows in RowSets overlapping the range are
+//! deleted by DVs of row ranges, as rows of a RowSet are sorted by the sort key.
+
+use std::ops::{Bound, Range, RangeBounds};
+use std::sync::Arc;
+
+use bitvec::prelude::BitVec;
+use itertools::Itertools;
+use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
+use risinglight_proto::rowset::DeleteRecord;
+use tracing::info;
+
+use super::statistics::{has_zone_map, StatisticsGlobalAgg, ZoneMapGlobalAgg};
+use super::{
+    AddDVEntry, ColumnSeekPosition, DeleteDVEntry, DeleteRowsetEntry, DeleteVector, DiskRowset,
+    EpochOp, SecondaryRowHandler, SecondaryStorage,
+};
+use crate::catalog::{find_sort_key_id, TableRefId};
+use crate::storage::{KeyRange, RowHandler, StorageColumnRef, StorageResult};
+use crate::types::{DataType, DataValue};
+
+/// How a RowSet overlaps with the deleted range.
+enum Overlap {
+    /// All rows are in the range.
+    Covered,
+    /// No row is in the range.
+    Disjoint,
+    /// The rows in the range have to be located.
+    Partial,
+}
+
+impl SecondaryStorage {
+    /// Delete all rows whose first column is in the range, and returns the number of deleted rows.
+    /// An unbounded range deletes all rows of the table, e.g. by `TRUNCATE`.
+    ///
+    /// Returns `None` if the rows are not sorted by the first column, in which case the rows
+    /// should be located by a scan.
+    pub async fn delete_range(
+        &self,
+        table_id: TableRefId,
+        range: &KeyRange,
+    ) -> StorageResult<Option<usize>> {
+        let table = self.get_table_inner(table_id)?;
+        let all = matches!(
+            (&range.start, &range.end),
+            (Bound::Unbounded, Bound::Unbounded)
+        );
+        if !all && find_sort_key_id(&table.columns).first() != Some(&0) {
+            return Ok(None);
+        }
+        // rows in the memtable are deleted as well, and no rows are appended to it meanwhile
+        let _deletion = table.begin_deletion();
+        table.flush_memtable().await?;
+
+        // the deletion is applied on the latest version, so it never conflicts
+        let _commit_lock = self.txn_mgr.lock_for_commit(table.table_id()).await;
+        let version = self.version.pin();
+        let snapshot = &version.snapshot;
+        let rowsets = snapshot.get_rowsets_of(table.table_id());
+        let data_type = table.columns[0].data_type();
+
+        let mut changes = vec![];
+        let mut count = 0;
+        for rowset_id in rowsets.into_iter().flatten().sorted() {
+            let rowset = self.version.get_rowset(table.table_id(), *rowset_id);
+            let dv_ids = snapshot
+                .get_dvs_of(table.table_id(), *rowset_id)
+                .map(|dvs| dvs.iter().copied().sorted().collect_vec())
+                .unwrap_or_default();
+            let dvs = (dv_ids.iter())
+                .map(|dv_id| self.version.get_dv(table.table_id(), *dv_id))
+                .collect_vec();
+            let live_rows = live_rows(rowset_rows(&rowset), &dvs);
+
+            // `None` if all rows are deleted
+            let deleted = match overlap(&rowset, &data_type, range, all) {
+                Overlap::Disjoint => continue,
+                Overlap::Covered => None,
+                Overlap::Partial => Some(locate_rows(&rowset, dvs, range).await?),
+            };
+            let deleted_rows = (deleted.as_ref()).map_or(live_rows, |deleted| {
+                deleted.iter().map(|rows| rows.len()).sum()
+            });
+            if deleted.is_some() && deleted_rows == 0 {
+                continue;
+            }
+            let Some(deleted) = deleted.filter(|_| deleted_rows < live_rows) else {
+                // drop the whole RowSet
+                count += live_rows;
+                changes.push(EpochOp::DeleteRowSet(DeleteRowsetEntry {
+                    rowset_id: *rowset_id,
+                    table_id: table.table_ref_id,
+                }));
+                for dv_id in dv_ids {
+                    changes.push(EpochOp::DeleteDV(DeleteDVEntry {
+                        table_id: table.table_ref_id,
+                        dv_id,
+                        rowset_id: *rowset_id,
+                    }));
+                }
+                continue;
+            };
+            count += deleted_rows;
+            let deletes = (deleted.into_iter())
+                .map(|rows| DeleteRecord {
+                    row_id: rows.start,
+                    row_count: rows.len() as u32,
+                })
+                .collect();
+            let dv = table.write_dv(*rowset_id, deletes).await?;
+            changes.push(EpochOp::AddDV((
+                AddDVEntry {
+                    rowset_id: *rowset_id,
+                    dv_id: dv.dv_id(),
+                    table_id: table.table_ref_id,
+                },
+                dv,
+            )));
+        }
+
+        if !changes.is_empty() {
+            self.version.commit_changes(changes).await?;
+        }
+        info!("{} rows deleted by range from table {}", count, table_id);
+        Ok(Some(count))
+    }
+}
+
+/// Returns the number of rows in a RowSet, including deleted ones.
+fn rowset_rows(rowset: &DiskRowset) -> usize {
+    (rowset.get_columns().first())
+        .map(|c| {
+            c.index()
+                .indexes()
+                .iter()
+                .map(|i| i.row_count as usize)
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the number of rows not deleted by any of the DVs.
+///
+/// DVs may delete the same row more than once, so their lengths can not be simply summed.
+fn live_rows(rows: usize, dvs: &[Arc<DeleteVector>]) -> usize {
+    let mut visible = BitVec::repeat(true, rows);
+    for dv in dvs {
+        dv.apply_to(&mut visible, 0);
+    }
+    visible.count_ones()
+}
+
+/// Decide the overlap from the zone map of the first column.
+fn overlap(rowset: &DiskRowset, data_type: &DataType, range: &KeyRange, all: bool) -> Overlap {
+    if all {
+        return Overlap::Covered;
+    }
+    let column = rowset.column(0);
+    if !has_zone_map(column.index()) {
+        return Overlap::Partial;
+    }
+    let bound = |ty| {
+        let mut agg = ZoneMapGlobalAgg::create(ty, data_type.clone());
+        agg.apply_batch(column.index());
+        agg.get_output()
+    };
+    let (min, max) = (
+        bound(BlockStatisticsType::Min),
+        bound(BlockStatisticsType::Max),
+    );
+    if min.is_null() || max.is_null() {
+        return Overlap::Partial;
+    }
+    let before_start = |v: &DataValue| match &range.start {
+        Bound::Included(k) => v < k,
+        Bound::Excluded(k) => v <= k,
+        Bound::Unbounded => false,
+    };
+    let after_end = |v: &DataValue| match &range.end {
+        Bound::Included(k) => v > k,
+        Bound::Excluded(k) => v >= k,
+        Bound::Unbounded => false,
+    };
+    if before_start(&max) || after_end(&min) {
+        Overlap::Disjoint
+    } else if range.contains(&min) && range.contains(&max) {
+        Overlap::Covered
+    } else {
+        Overlap::Partial
+    }
+}
+
+/// Returns the ranges of live rows in the key range, by scanning the first column.
+async fn locate_rows(
+    rowset: &Arc<DiskRowset>,
+    dvs: Vec<Arc<DeleteVector>>,
+    range: &KeyRange,
+) -> StorageResult<Vec<Range<u32>>> {
+    let column_refs = [StorageColumnRef::Idx(0), StorageColumnRef::RowHandler];
+    let mut iter = rowset
+        .iter(
+            column_refs.into(),
+            dvs,
+            ColumnSeekPosition::start(),
+            Some(range.clone()),
+        )
+        .await?;
+    let mut ranges: Vec<Range<u32>> = vec![];
+    while let Some(batch) = iter.next_batch(None).await? {
+        let chunk = batch.to_data_chunk();
+        let handlers = chunk.array_at(1);
+        for i in 0..handlers.len() {
+            let row_id = SecondaryRowHandler::from_column(handlers, i).row_id();
+            match ranges.last_mut() {
+                Some(last) if last.end == row_id => last.end += 1,
+                _ => ranges.push(row_id..row_id + 1),
+            }
+        }
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::secondary::tests::{create_table, disk_options, insert, open, query, scan};
+    use crate::storage::secondary::StorageOptions;
+    use crate::storage::{StorageError, Table, Transaction};
+
+    #[tokio::test]
+    async fn test_delete_range() {
+        let options = StorageOptions {
+            // every insert creates a RowSet
+            memtable_size: 0,
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        };
+        let (_db, session) = open(&options).await;
+        session
+            .run("create table t (a int primary key, b int)")
+            .await
+            .unwrap();
+        for sql in [
+            "insert into t values (1, 1), (2, 2), (3, 3)",
+            "insert into t values (4, 4), (5, 5), (6, 6)",
+            "insert into t values (7, 7), (8, 8)",
+        ] {
+            session.run(sql).await.unwrap();
+        }
+        // system tables only support `select *`
+        let rowsets = "select * from pg_catalog.rw_rowsets";
+        let dvs = "select * from pg_catalog.rw_delete_vectors";
+
+        // rows in the range of a RowSet are deleted by one DV
+        session.run("delete from t where a < 3").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 3);
+        assert_eq!(query(&session, dvs).await.len(), 1);
+
+        // RowSets within the range, or whose live rows are all in it, are dropped
+        session.run("delete from t where a <= 6").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 1);
+        assert!(query(&session, dvs).await.is_empty());
+
+        // the condition isn't on the sort key, so the rows are located by a scan
+        session.run("delete from t where b = 7").await.unwrap();
+        assert_eq!(query(&session, dvs).await.len(), 1);
+
+        session.run("truncate table t").await.unwrap();
+        assert!(query(&session, rowsets).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_truncate_conflict() {
+        let (storage, table) = create_table(StorageOptions {
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        })
+        .await;
+        insert(&table, &[1, 2]).await;
+
+        // rows in the memtable are truncated as well
+        let rows = scan(&table).await;
+        let mut txn = table.update().await.unwrap();
+        txn.delete(&rows[0].1).await.unwrap();
+
+        let all = KeyRange {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        };
+        let count = storage
+            .delete_range(table.table_ref_id, &all)
+            .await
+            .unwrap();
+        assert_eq!(count, Some(2));
+
+        // deleting a row of a dropped RowSet conflicts
+        let err = txn.commit().await.unwrap_err();
+        assert!(matches!(err.inner(), StorageError::WriteConflict));
+    }
+
+    #[test]
+    fn test_live_rows() {
+        let dv = |dv_id, row_id, row_count| {
+            let delete = DeleteRecord { row_id, row_count };
+            Arc::new(DeleteVector::new(dv_id, 0, vec![delete]))
+        };
+        // the DVs both delete row 2
+        assert_eq!(live_rows(5, &[dv(0, 0, 3), dv(1, 2, 2)]), 1);
+        assert_eq!(live_rows(5, &[dv(0, 1, 1), dv(1, 1, 1)]), 4);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_and_restart() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = disk_options(tempdir.path());
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+        session.run("truncate table t").await.unwrap();
+        // rows appended after the truncation are kept
+        session.run("insert into t values (3)").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        for _ in 0..2 {
+            let (db, session) = open(&options).await;
+            assert_eq!(query(&session, "select a from t").await, ["3"]);
+            db.shutdown().await.unwrap();
+        }
+    }
+}
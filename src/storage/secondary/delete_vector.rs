@@ -1,5 +1,6 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::ops::Range;
 use std::path::Path;
 
 use bitvec::prelude::BitVec;
@@ -9,37 +10,50 @@ use prost::Message;
 use risinglight_proto::rowset::DeleteRecord;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
 
-use crate::storage::StorageResult;
+use super::IOBackend;
+use crate::storage::{StorageResult, TracedStorageError};
 
 pub struct DeleteVector {
     dv_id: u64,
     rowset_id: u32,
-    deletes: Vec<u32>,
+    /// Sorted and disjoint ranges of deleted rows.
+    deletes: Vec<Range<u32>>,
+    /// The number of deleted rows.
+    len: usize,
 }
 
 impl DeleteVector {
-    pub async fn open(dv_id: u64, rowset_id: u32, path: impl AsRef<Path>) -> StorageResult<Self> {
-        let mut reader = BufReader::new(tokio::fs::File::open(path).await?);
-        let mut data = Vec::new();
+    pub async fn open(
+        dv_id: u64,
+        rowset_id: u32,
+        path: impl AsRef<Path>,
+        io_backend: &IOBackend,
+    ) -> StorageResult<Self> {
+        let data = match io_backend {
+            IOBackend::InMemory(map) => {
+                let map = map.lock();
+                (map.get(path.as_ref()))
+                    .ok_or_else(|| TracedStorageError::not_found("DV", path.as_ref().display()))?
+                    .to_vec()
+            }
+            _ => {
+                let mut reader = BufReader::new(tokio::fs::File::open(path).await?);
+                let mut data = Vec::new();
 
-        // TODO: don't read all to memory
-        reader.read_to_end(&mut data).await?;
+                // TODO: don't read all to memory
+                reader.read_to_end(&mut data).await?;
+                data
+            }
+        };
 
         let mut buf = &data[..];
         let mut deletes = vec![];
 
         while !buf.is_empty() {
-            deletes.push(DeleteRecord::decode_length_delimited(&mut buf)?.row_id);
+            deletes.push(DeleteRecord::decode_length_delimited(&mut buf)?);
         }
 
-        deletes.sort_unstable();
-        deletes.dedup();
-
-        Ok(Self {
-            dv_id,
-            rowset_id,
-            deletes,
-        })
+        Ok(Self::new(dv_id, rowset_id, deletes))
     }
 
     pub async fn write_all(
@@ -56,14 +70,24 @@ impl DeleteVector {
     }
 
     pub fn new(dv_id: u64, rowset_id: u32, deletes: Vec<DeleteRecord>) -> Self {
-        let mut deletes = deletes.into_iter().map(|x| x.row_id).collect_vec();
-        deletes.sort_unstable();
-        deletes.dedup();
+        let ranges = (deletes.into_iter())
+            .map(|x| x.row_id..x.row_id.saturating_add(x.row_count.max(1)))
+            .sorted_unstable_by_key(|range| range.start);
+        // merge overlapping and adjacent ranges
+        let mut deletes: Vec<Range<u32>> = vec![];
+        for range in ranges {
+            match deletes.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => deletes.push(range),
+            }
+        }
+        let len = deletes.iter().map(|range| range.len()).sum();
 
         Self {
             dv_id,
             rowset_id,
             deletes,
+            len,
         }
     }
 
@@ -75,21 +99,42 @@ impl DeleteVector {
         self.rowset_id
     }
 
+    /// Returns the sorted ids of deleted rows.
+    pub fn row_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.deletes.iter().flat_map(|range| range.clone())
+    }
+
+    /// Returns the number of deleted rows.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deletes.is_empty()
+    }
+
     /// Apply the current DV info to a visibility bitmap
     pub fn apply_to(&self, data: &mut BitVec, offset_row_id: u32) {
-        let pos = self.deletes.partition_point(|x| *x < offset_row_id);
-
-        let mut iter = self.deletes.iter().skip(pos).peekable();
+        let end_row_id = offset_row_id.saturating_add(data.len() as u32);
+        let pos = self.deletes.partition_point(|x| x.end <= offset_row_id);
 
-        for (row_id, mut bitref) in (offset_row_id as usize..).zip(data.iter_mut()) {
-            if let Some(unset_row_id) = iter.peek() {
-                if **unset_row_id == row_id as u32 {
-                    bitref.set(false);
-                    iter.next();
-                }
+        for range in &self.deletes[pos..] {
+            if range.start >= end_row_id {
+                break;
             }
+            let start = range.start.max(offset_row_id) - offset_row_id;
+            let end = range.end.min(end_row_id) - offset_row_id;
+            data[start as usize..end as usize].fill(false);
         }
     }
+
+    /// Returns true if any row in the range is deleted.
+    pub fn has_deletes_in(&self, range: Range<u32>) -> bool {
+        let pos = self.deletes.partition_point(|x| x.end <= range.start);
+        self.deletes
+            .get(pos)
+            .is_some_and(|x| x.start < range.end && !range.is_empty())
+    }
 }
 
 #[cfg(test)]
@@ -103,7 +148,16 @@ mod tests {
         let dv = DeleteVector::new(
             0,
             0,
-            vec![DeleteRecord { row_id: 3 }, DeleteRecord { row_id: 5 }],
+            vec![
+                DeleteRecord {
+                    row_id: 3,
+                    row_count: 0,
+                },
+                DeleteRecord {
+                    row_id: 5,
+                    row_count: 0,
+                },
+            ],
         );
         let mut bv = BitVec::new();
         bv.resize(3, true);
@@ -125,4 +179,59 @@ mod tests {
         dv.apply_to(&mut bv, 4);
         assert_eq!(bv, bitvec![1, 0, 1]);
     }
+
+    #[test]
+    fn test_dv_has_deletes_in() {
+        let dv = DeleteVector::new(
+            0,
+            0,
+            vec![
+                DeleteRecord {
+                    row_id: 3,
+                    row_count: 0,
+                },
+                DeleteRecord {
+                    row_id: 5,
+                    row_count: 0,
+                },
+            ],
+        );
+        assert!(!dv.has_deletes_in(0..3));
+        assert!(dv.has_deletes_in(0..4));
+        assert!(!dv.has_deletes_in(4..5));
+        assert!(dv.has_deletes_in(5..6));
+        assert!(!dv.has_deletes_in(6..100));
+    }
+
+    #[test]
+    fn test_dv_range() {
+        let dv = DeleteVector::new(
+            0,
+            0,
+            vec![
+                DeleteRecord {
+                    row_id: 2,
+                    row_count: 3,
+                },
+                DeleteRecord {
+                    row_id: 5,
+                    row_count: 0,
+                },
+                DeleteRecord {
+                    row_id: 8,
+                    row_count: 2,
+                },
+            ],
+        );
+        assert_eq!(dv.len(), 6);
+        assert_eq!(dv.row_ids().collect_vec(), [2, 3, 4, 5, 8, 9]);
+        assert!(!dv.has_deletes_in(0..2));
+        assert!(dv.has_deletes_in(4..5));
+        assert!(!dv.has_deletes_in(6..8));
+
+        let mut bv = BitVec::new();
+        bv.resize(8, true);
+        dv.apply_to(&mut bv, 3);
+        assert_eq!(bv, bitvec![0, 0, 0, 1, 1, 0, 0, 1]);
+    }
 }
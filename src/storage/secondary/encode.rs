@@ -11,7 +11,7 @@ use crate::types::{BlobRef, Date, Interval, Timestamp, TimestampTz, F64};
 
 /// Encode a primitive value into fixed-width buffer
 pub trait PrimitiveFixedWidthEncode:
-    Copy + Clone + 'static + Send + Sync + PartialEq + Hash + Eq
+    Copy + Clone + 'static + Send + Sync + PartialEq + Hash + Eq + PartialOrd
 {
     /// Width of each element
     const WIDTH: usize;
@@ -0,0 +1,278 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Information about the files and caches of the storage engine for system tables.
+
+use std::time::{Duration, SystemTime};
+
+use itertools::Itertools;
+
+use super::{SecondaryStorage, StorageResult};
+use crate::catalog::{ColumnId, TableRefId};
+
+/// The maximum number of compactions kept in the history.
+const MAX_COMPACTION_HISTORY: usize = 100;
+
+/// Information about a rowset.
+#[derive(Debug, Clone)]
+pub struct RowsetInfo {
+    pub table_id: TableRefId,
+    pub rowset_id: u32,
+    /// The number of rows, including deleted ones.
+    pub rows: u64,
+    /// The size of all column files in bytes.
+    pub on_disk_size: u64,
+    pub columns: Vec<RowsetColumnInfo>,
+}
+
+/// Information about a column in a rowset.
+#[derive(Debug, Clone)]
+pub struct RowsetColumnInfo {
+    pub column_id: ColumnId,
+    /// The size of the column file in bytes.
+    pub on_disk_size: u64,
+    pub blocks: usize,
+    /// The type of the first block, or `None` if the column has no block.
+    pub encoding: Option<String>,
+}
+
+/// Information about a delete vector.
+#[derive(Debug, Clone)]
+pub struct DeleteVectorInfo {
+    pub table_id: TableRefId,
+    pub rowset_id: u32,
+    pub dv_id: u64,
+    pub deleted_rows: usize,
+}
+
+/// A compaction that has finished.
+#[derive(Debug, Clone)]
+pub struct CompactionInfo {
+    pub table_id: TableRefId,
+    /// The name of the policy selecting the input rowsets.
+    pub policy: String,
+    /// Whether the compaction is requested by `COMPACT` or `VACUUM`.
+    pub manual: bool,
+    /// The partition of the rowsets, if the table is partitioned.
+    pub partition: Option<u32>,
+    pub input_rowsets: Vec<u32>,
+    /// The compacted rowset, or `None` if all rows are deleted.
+    pub output_rowset: Option<u32>,
+    pub input_size: u64,
+    pub output_size: u64,
+    /// The number of rows in the input rowsets, including deleted ones.
+    pub input_rows: u64,
+    /// The number of rows in the compacted rowset.
+    pub output_rows: u64,
+    /// The number of DVs purged.
+    pub purged_dvs: usize,
+    pub start_time: SystemTime,
+    pub duration: Duration,
+}
+
+/// An epoch readable by time travel.
+#[derive(Debug, Clone)]
+pub struct EpochInfo {
+    pub epoch: u64,
+    pub commit_time: SystemTime,
+    /// The number of transactions and scans reading the epoch.
+    pub pinned: usize,
+}
+
+/// Statistics of the block cache.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockCacheInfo {
+    /// The maximum number of blocks.
+    pub capacity: u64,
+    /// The number of cached blocks.
+    pub entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl SecondaryStorage {
+    /// Returns the rowsets of all tables in the latest version.
+    pub async fn rowsets(&self) -> StorageResult<Vec<RowsetInfo>> {
+        let version = self.version.pin();
+        let tables = self.tables.read().clone();
+        let mut infos = vec![];
+        for (table_id, table) in tables.iter().sorted_by_key(|(id, _)| **id) {
+            let Some(rowset_ids) = version.snapshot.get_rowsets_of(table.table_id()) else {
+                continue;
+            };
+            for rowset_id in rowset_ids.iter().sorted() {
+                let rowset = self.version.get_rowset(table.table_id(), *rowset_id);
+                let mut columns = vec![];
+                for (idx, column) in rowset.get_columns().iter().enumerate() {
+                    let blocks = column.index().len();
+                    let encoding = match blocks {
+                        0 => None,
+                        _ => Some(column.get_block_meta(0).await?.block_type),
+                    };
+                    columns.push(RowsetColumnInfo {
+                        column_id: rowset.column_info(idx).id(),
+                        on_disk_size: if blocks == 0 {
+                            0
+                        } else {
+                            column.on_disk_size()
+                        },
+                        blocks,
+                        encoding: encoding.map(|ty| ty.as_str_name().to_string()),
+                    });
+                }
+                let rows = (rowset.get_columns().first())
+                    .map(|c| c.index().indexes().iter().map(|i| i.row_count as u64).sum())
+                    .unwrap_or_default();
+                infos.push(RowsetInfo {
+                    table_id: *table_id,
+                    rowset_id: *rowset_id,
+                    rows,
+                    on_disk_size: columns.iter().map(|c| c.on_disk_size).sum(),
+                    columns,
+                });
+            }
+        }
+        Ok(infos)
+    }
+
+    /// Returns the delete vectors of all tables in the latest version.
+    pub fn delete_vectors(&self) -> Vec<DeleteVectorInfo> {
+        let version = self.version.pin();
+        let tables = self.tables.read().clone();
+        let mut infos = vec![];
+        for (table_id, table) in tables.iter().sorted_by_key(|(id, _)| **id) {
+            let Some(rowset_ids) = version.snapshot.get_rowsets_of(table.table_id()) else {
+                continue;
+            };
+            for rowset_id in rowset_ids.iter().sorted() {
+                let Some(dv_ids) = version.snapshot.get_dvs_of(table.table_id(), *rowset_id) else {
+                    continue;
+                };
+                for dv_id in dv_ids.iter().sorted() {
+                    let dv = self.version.get_dv(table.table_id(), *dv_id);
+                    infos.push(DeleteVectorInfo {
+                        table_id: *table_id,
+                        rowset_id: *rowset_id,
+                        dv_id: *dv_id,
+                        deleted_rows: dv.len(),
+                    });
+                }
+            }
+        }
+        infos
+    }
+
+    /// Returns the latest compactions from the oldest to the latest.
+    pub fn compactions(&self) -> Vec<CompactionInfo> {
+        self.compactions.lock().iter().cloned().collect()
+    }
+
+    /// Returns the epochs readable by time travel from the oldest to the latest.
+    pub fn epochs(&self) -> Vec<EpochInfo> {
+        self.version.epochs()
+    }
+
+    /// Returns the statistics of the block cache.
+    pub async fn block_cache_info(&self) -> BlockCacheInfo {
+        BlockCacheInfo {
+            capacity: self.block_cache.capacity(),
+            entries: self.block_cache.entry_count().await,
+            hits: self.block_cache.hits(),
+            misses: self.block_cache.misses(),
+        }
+    }
+
+    /// Records a finished compaction in the history.
+    pub(super) fn record_compaction(&self, compaction: CompactionInfo) {
+        let mut compactions = self.compactions.lock();
+        if compactions.len() >= MAX_COMPACTION_HISTORY {
+            compactions.pop_front();
+        }
+        compactions.push_back(compaction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::array::Chunk;
+    use crate::storage::secondary::tests::query;
+    use crate::storage::SecondaryStorageOptions;
+    use crate::{Database, Session};
+
+    #[tokio::test]
+    async fn test_storage_tables() {
+        let db = Database::new_on_disk(SecondaryStorageOptions::default_for_test()).await;
+        let session = Session::new(Arc::new(db));
+        session
+            .run("create table t (a int, b string); insert into t values (1, 'x'), (2, 'y');")
+            .await
+            .unwrap();
+        session.run("delete from t where a = 1").await.unwrap();
+
+        assert_eq!(
+            query(&session, "select * from pg_catalog.rw_rowsets")
+                .await
+                .len(),
+            1
+        );
+        assert_eq!(
+            query(&session, "select * from pg_catalog.rw_rowset_columns")
+                .await
+                .len(),
+            2
+        );
+        assert_eq!(
+            query(&session, "select * from pg_catalog.rw_delete_vectors")
+                .await
+                .len(),
+            1
+        );
+        assert_eq!(
+            query(&session, "select * from pg_catalog.rw_block_cache")
+                .await
+                .len(),
+            1
+        );
+        let chunks = (session.run("select * from pg_catalog.rw_rowsets"))
+            .await
+            .unwrap();
+        let row = chunks[0].get_first_data_chunk().row(0);
+        let row = row.values().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(row[..5], ["'postgres'", "'t'", row[2].as_str(), "2", "1"]);
+
+        // the encodings of columns are read without going through the block cache
+        let block_cache = "select * from pg_catalog.rw_block_cache";
+        let lookups = |chunks: Vec<Chunk>| {
+            let chunk = chunks[0].get_first_data_chunk();
+            [2, 3].map(|i| chunk.array_at(i).get_to_string(0))
+        };
+        let before = lookups(session.run(block_cache).await.unwrap());
+        (session.run("select * from pg_catalog.rw_rowset_columns"))
+            .await
+            .unwrap();
+        assert_eq!(lookups(session.run(block_cache).await.unwrap()), before);
+
+        // the compactor merges small rowsets in the background
+        // (deletions flush the memtable into a rowset)
+        session
+            .run("insert into t values (3, 'z'); delete from t where a = 4;")
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            let compactions = query(&session, "select * from pg_catalog.rw_compactions").await;
+            if !compactions.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let chunks = (session.run("select * from pg_catalog.rw_compactions"))
+            .await
+            .unwrap();
+        let chunk = chunks[0].get_first_data_chunk();
+        assert_eq!(chunk.cardinality(), 1);
+        // the deleted row is dropped by the compaction
+        assert_eq!(chunk.array_at(6).get_to_string(0), "2");
+    }
+}
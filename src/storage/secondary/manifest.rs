@@ -2,13 +2,18 @@
 
 //! Basic serialization implementation of `RisingLight`.
 //!
-//! Note that this storage format is not stable. The current manifest persistence
-//! depends on the stability of state machine of in-memory catalog. Any change in
-//! catalog implementation, e.g., [`TableId`](crate::catalog::TableId) assignment, will break the
-//! manifest. We will later come up with a better manifest design.
+//! The manifest is a log of JSON entries. It starts with a [`ManifestHeader`] recording the
+//! format version, followed by a checkpoint of all tables, RowSets and DVs, and then the
+//! changes committed after the checkpoint. Each group of changes is wrapped in `Begin` and `End`.
+//! When the log grows too long, it is rewritten into a new checkpoint.
+//!
+//! Manifests of version 0 have no header and don't record the [`TableId`] of tables, which
+//! are assigned by replaying the catalog operations in order. They are upgraded by rewriting
+//! a checkpoint of the latest version on open.
 
+use std::collections::BTreeSet;
 use std::io::SeekFrom;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Deserializer;
@@ -17,15 +22,48 @@ use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
 use tracing::warn;
 
 use super::version_manager::EpochOp;
-use super::{SecondaryStorage, SecondaryTable, StorageResult, TracedStorageError};
-use crate::catalog::{ColumnCatalog, ColumnId, SchemaId, TableRefId};
+use super::{sync_dir, SecondaryStorage, SecondaryTable, StorageResult, TracedStorageError};
+use crate::catalog::{
+    ColumnCatalog, ColumnId, PartitionSpec, Privilege, PrivilegeObject, SchemaId, TableId,
+    TableRefId, UserCatalog, UserId, UserOptions,
+};
+
+/// The version of the manifest format written by this build.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestHeader {
+    pub version: u32,
+    /// The next id to be assigned in each schema, so that ids of dropped tables are not reused.
+    #[serde(default)]
+    pub next_table_ids: Vec<(SchemaId, TableId)>,
+    /// Memtables with rowset ids below the watermark have been flushed, so their records in the
+    /// WAL are not replayed even if their RowSets are no longer in the checkpoint.
+    #[serde(default)]
+    pub memtable_watermark: u32,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CreateTableEntry {
     pub schema_id: SchemaId,
+    /// The id of the table, or `None` in manifests of version 0.
+    #[serde(default)]
+    pub table_id: Option<TableId>,
     pub table_name: String,
     pub column_descs: Vec<ColumnCatalog>,
     pub ordered_pk_ids: Vec<ColumnId>,
+    /// The range partitioning of the table, updated by dropping partitions in checkpoints.
+    #[serde(default)]
+    pub partition: Option<PartitionSpec>,
+}
+
+impl CreateTableEntry {
+    /// Removes the dropped partitions from the range partitioning of the table.
+    pub fn drop_partitions(&mut self, partition_ids: &[u32]) {
+        if let Some(partition) = &mut self.partition {
+            partition.drop_partitions(partition_ids);
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -33,10 +71,59 @@ pub struct DropTableEntry {
     pub table_id: TableRefId,
 }
 
+/// The state of a user after it is created or altered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PutUserEntry {
+    pub user_id: UserId,
+    pub name: String,
+    pub options: UserOptions,
+    /// Roles that the user is a direct member of.
+    #[serde(default)]
+    pub roles: BTreeSet<String>,
+    /// Privileges directly granted to the user.
+    #[serde(default)]
+    pub privileges: Vec<(PrivilegeObject, BTreeSet<Privilege>)>,
+}
+
+impl PutUserEntry {
+    pub fn new(user: &UserCatalog) -> Self {
+        Self {
+            user_id: user.id(),
+            name: user.name().into(),
+            options: user.options(),
+            roles: user.roles().clone(),
+            privileges: (user.privileges().iter())
+                .map(|(object, privileges)| (*object, privileges.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn to_user(&self) -> UserCatalog {
+        UserCatalog::new(self.user_id, self.name.clone(), &self.options).with_grants(
+            self.roles.clone(),
+            self.privileges.iter().cloned().collect(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DropUserEntry {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DropPartitionEntry {
+    pub table_id: TableRefId,
+    pub partition_ids: Vec<u32>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AddRowSetEntry {
     pub table_id: TableRefId,
     pub rowset_id: u32,
+    /// The partition of the rows, or `None` if the table is not partitioned.
+    #[serde(default)]
+    pub partition: Option<u32>,
 }
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DeleteRowsetEntry {
@@ -60,12 +147,16 @@ pub struct DeleteDVEntry {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ManifestOperation {
+    Header(ManifestHeader),
     CreateTable(CreateTableEntry),
     DropTable(DropTableEntry),
+    DropPartition(DropPartitionEntry),
     AddRowSet(AddRowSetEntry),
     DeleteRowSet(DeleteRowsetEntry),
     AddDV(AddDVEntry),
     DeleteDV(DeleteDVEntry),
+    PutUser(PutUserEntry),
+    DropUser(DropUserEntry),
     // begin transaction
     Begin,
     // end transaction
@@ -75,7 +166,10 @@ pub enum ManifestOperation {
 /// Handles all reads and writes to a manifest file
 pub struct Manifest {
     file: Option<tokio::fs::File>,
+    path: PathBuf,
     enable_fsync: bool,
+    /// Number of entries appended since the last checkpoint.
+    appended: usize,
 }
 
 impl Manifest {
@@ -83,7 +177,9 @@ impl Manifest {
     pub fn new_mock() -> Self {
         Self {
             file: None,
+            path: PathBuf::new(),
             enable_fsync: false,
+            appended: 0,
         }
     }
 
@@ -96,54 +192,86 @@ impl Manifest {
             .await?;
         Ok(Self {
             file: Some(file),
+            path: path.as_ref().to_path_buf(),
             enable_fsync,
+            appended: 0,
         })
     }
 
-    // Reopen manifest file at `path` and seek to the end of file.
-    pub async fn reopen(&mut self, path: impl AsRef<Path>) -> StorageResult<()> {
-        if self.file.is_some() {
-            let mut file = OpenOptions::default()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(path.as_ref())
-                .await?;
-            // Seek to end directly as the compacted manifest won't be replayed.
-            file.seek(SeekFrom::End(0)).await?;
-            self.file = Some(file);
-        }
-
-        Ok(())
+    /// Number of entries appended since the last checkpoint.
+    pub fn appended(&self) -> usize {
+        self.appended
     }
 
-    pub async fn replay(&mut self) -> StorageResult<Vec<ManifestOperation>> {
+    /// Returns the header and the committed entries. The header of version 0 is returned if the
+    /// manifest has no header.
+    ///
+    /// A torn write at the tail, i.e. an incomplete entry or a group without `End`, is truncated
+    /// from the file. An undecodable entry followed by committed groups is reported as an error.
+    pub async fn replay(&mut self) -> StorageResult<(ManifestHeader, Vec<ManifestOperation>)> {
         let file = if let Some(file) = &mut self.file {
             file
         } else {
-            return Ok(vec![]);
+            return Ok(Default::default());
         };
 
-        let mut data = String::new();
+        let mut data = vec![];
         file.seek(SeekFrom::Start(0)).await?;
-        let mut reader = BufReader::new(file);
+        let mut reader = BufReader::new(&mut *file);
 
         // TODO: don't read all to memory
-        reader.read_to_string(&mut data).await?;
+        reader.read_to_end(&mut data).await?;
+
+        let (header, ops, committed_offset) = Self::decode(&data)?;
+        if committed_offset < data.len() {
+            warn!(
+                "manifest: truncate {} bytes of torn tail",
+                data.len() - committed_offset
+            );
+            file.set_len(committed_offset as u64).await?;
+            file.sync_all().await?;
+        }
+        file.seek(SeekFrom::End(0)).await?;
+
+        self.appended = ops.len();
+        Ok((header, ops))
+    }
 
-        let stream = Deserializer::from_str(&data).into_iter::<ManifestOperation>();
+    /// Decode the content of a manifest. Returns the header, the committed entries and the length
+    /// of the committed prefix, after which is a torn tail.
+    pub fn decode(data: &[u8]) -> StorageResult<(ManifestHeader, Vec<ManifestOperation>, usize)> {
+        let mut stream = Deserializer::from_slice(data).into_iter::<ManifestOperation>();
 
+        let mut header = None;
         let mut ops = vec![];
         let mut buffered_ops = vec![];
         let mut begin = false;
-
-        for value in stream {
-            let value = value?;
+        // the end of the last committed group
+        let mut committed_offset = 0;
+
+        while let Some(value) = stream.next() {
+            let value = match value {
+                Ok(value) => value,
+                Err(e) => {
+                    let rest = &data[stream.byte_offset()..];
+                    if !e.is_eof() && rest.windows(5).any(|w| w == b"\"End\"") {
+                        return Err(e.into());
+                    }
+                    warn!("manifest: find torn entry: {e}");
+                    break;
+                }
+            };
             match value {
+                ManifestOperation::Header(h) if committed_offset == 0 && header.is_none() => {
+                    header = Some(h);
+                    committed_offset = stream.byte_offset();
+                }
+                ManifestOperation::Header(_) => warn!("manifest: find header in the middle"),
                 ManifestOperation::Begin => begin = true,
                 ManifestOperation::End => {
                     ops.append(&mut buffered_ops);
                     begin = false;
+                    committed_offset = stream.byte_offset();
                 }
                 op => {
                     if begin {
@@ -159,7 +287,58 @@ impl Manifest {
             warn!("manifest: find uncommitted entries");
         }
 
-        Ok(ops)
+        let header = header.unwrap_or_default();
+        if header.version > MANIFEST_VERSION {
+            return Err(TracedStorageError::decode(format!(
+                "unsupported manifest version {}, expected at most {}",
+                header.version, MANIFEST_VERSION
+            )));
+        }
+        Ok((header, ops, committed_offset))
+    }
+
+    /// Replace the manifest with a checkpoint containing `entries`.
+    ///
+    /// The checkpoint is written to a temporary file and renamed to the manifest, so a crash
+    /// leaves either the old or the new manifest.
+    pub async fn checkpoint(
+        &mut self,
+        header: &ManifestHeader,
+        entries: &[ManifestOperation],
+    ) -> StorageResult<()> {
+        if self.file.is_none() {
+            return Ok(());
+        }
+        let mut json = Vec::new();
+        serde_json::to_writer(&mut json, &ManifestOperation::Header(header.clone()))?;
+        serde_json::to_writer(&mut json, &ManifestOperation::Begin)?;
+        for entry in entries {
+            serde_json::to_writer(&mut json, entry)?;
+        }
+        serde_json::to_writer(&mut json, &ManifestOperation::End)?;
+
+        let temp_path = self.path.with_extension("tmp.json");
+        let mut temp_file = OpenOptions::default()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_path)
+            .await?;
+        temp_file.write_all(&json).await?;
+        temp_file.sync_all().await?;
+        drop(temp_file);
+        tokio::fs::rename(&temp_path, &self.path).await?;
+        sync_dir(self.path.parent().unwrap()).await?;
+
+        let mut file = OpenOptions::default()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .await?;
+        file.seek(SeekFrom::End(0)).await?;
+        self.file = Some(file);
+        self.appended = 0;
+        Ok(())
     }
 
     pub async fn append(&mut self, entries: &[ManifestOperation]) -> StorageResult<()> {
@@ -179,17 +358,21 @@ impl Manifest {
         if self.enable_fsync {
             file.sync_data().await?;
         }
+        self.appended += entries.len();
         Ok(())
     }
 }
 
 impl SecondaryStorage {
-    pub(super) fn apply_create_table(&self, entry: &CreateTableEntry) -> StorageResult<()> {
+    /// Adds the table to the catalog and returns its id.
+    pub(super) fn apply_create_table(&self, entry: &CreateTableEntry) -> StorageResult<TableRefId> {
         let CreateTableEntry {
             schema_id,
+            table_id,
             table_name,
             column_descs,
             ordered_pk_ids,
+            partition,
         } = entry.clone();
 
         let schema = self
@@ -199,20 +382,30 @@ impl SecondaryStorage {
         if schema.get_table_by_name(&table_name).is_some() {
             return Err(TracedStorageError::duplicated("table", table_name));
         }
-        let table_id = self
-            .catalog
-            .add_table(
+        let table_id = match table_id {
+            Some(table_id) => self.catalog.add_table_with_id(
+                schema_id,
+                table_id,
+                table_name.clone(),
+                column_descs.to_vec(),
+                ordered_pk_ids.clone(),
+            ),
+            None => self.catalog.add_table(
                 schema_id,
                 table_name.clone(),
                 column_descs.to_vec(),
                 ordered_pk_ids.clone(),
-            )
-            .map_err(|_| TracedStorageError::duplicated("table", table_name))?;
+            ),
+        }
+        .map_err(|_| TracedStorageError::duplicated("table", table_name))?;
 
         let id = TableRefId {
             schema_id,
             table_id,
         };
+        if partition.is_some() {
+            self.catalog.set_partition(id, partition);
+        }
         let table = SecondaryTable::new(
             self.options.clone(),
             id,
@@ -221,11 +414,12 @@ impl SecondaryStorage {
             self.version.clone(),
             self.block_cache.clone(),
             self.txn_mgr.clone(),
+            self.wal.clone(),
             ordered_pk_ids,
         );
         self.tables.write().insert(id, table);
 
-        Ok(())
+        Ok(id)
     }
 
     pub(super) async fn create_table_inner(
@@ -234,21 +428,29 @@ impl SecondaryStorage {
         table_name: &str,
         column_descs: &[ColumnCatalog],
         ordered_pk_ids: &[ColumnId],
+        partition: Option<PartitionSpec>,
     ) -> StorageResult<()> {
-        let entry = CreateTableEntry {
+        let mut entry = CreateTableEntry {
             schema_id,
+            table_id: None,
             table_name: table_name.to_string(),
             column_descs: column_descs.to_vec(),
             ordered_pk_ids: ordered_pk_ids.to_vec(),
+            partition,
         };
 
-        // persist to manifest first
-        self.version
-            .commit_changes(vec![EpochOp::CreateTable(entry.clone())])
-            .await?;
-
-        // then apply to catalog
-        self.apply_create_table(&entry)?;
+        // apply to catalog first to assign the table id
+        let id = self.apply_create_table(&entry)?;
+        entry.table_id = Some(id.table_id);
+
+        // then persist to manifest, and revert the catalog on failure
+        if let Err(e) = (self.version)
+            .commit_changes(vec![EpochOp::CreateTable(entry)])
+            .await
+        {
+            self.apply_drop_table(&DropTableEntry { table_id: id })?;
+            return Err(e);
+        }
 
         Ok(())
     }
@@ -281,6 +483,7 @@ impl SecondaryStorage {
         let entry = DropTableEntry { table_id };
 
         // contrary to create table, we first modify the catalog
+        let table = self.get_table_inner(table_id)?;
         self.apply_drop_table(&entry)?;
 
         changeset.push(EpochOp::DropTable(entry));
@@ -312,6 +515,124 @@ impl SecondaryStorage {
         // and then persist to manifest
         self.version.commit_changes(changeset).await?;
 
+        // the WAL is needed until the drop is persisted
+        table.discard_memtable().await?;
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::array::Chunk;
+    use crate::catalog::{ColumnDesc, RootCatalog};
+    use crate::storage::secondary::{IOBackend, StorageOptions, MANIFEST_FILE_NAME};
+    use crate::types::DataType;
+    use crate::{Database, Session};
+
+    /// Returns the rows of user tables as `(table_id, table_name)`.
+    async fn user_tables(session: &Session) -> Vec<(String, String)> {
+        let chunks: Vec<Chunk> = (session.run("select * from pg_catalog.pg_tables"))
+            .await
+            .unwrap();
+        let mut tables = vec![];
+        for chunk in chunks[0].data_chunks() {
+            for i in 0..chunk.cardinality() {
+                if chunk.array_at(1).get_to_string(i) == RootCatalog::DEFAULT_SCHEMA_NAME {
+                    let id = chunk.array_at(2).get_to_string(i);
+                    tables.push((id, chunk.array_at(3).get_to_string(i)));
+                }
+            }
+        }
+        tables.sort();
+        tables
+    }
+
+    fn create_table_entry(table_name: &str) -> ManifestOperation {
+        let schema_id = RootCatalog::new()
+            .get_schema_id_by_name(RootCatalog::DEFAULT_SCHEMA_NAME)
+            .unwrap();
+        ManifestOperation::CreateTable(CreateTableEntry {
+            schema_id,
+            table_id: None,
+            table_name: table_name.into(),
+            column_descs: vec![ColumnCatalog::new(
+                0,
+                ColumnDesc::new("a", DataType::Int32, true),
+            )],
+            ordered_pk_ids: vec![],
+            partition: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_manifest_upgrade_and_checkpoint() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = StorageOptions {
+            path: tempdir.path().to_path_buf(),
+            io_backend: IOBackend::NormalRead,
+            disable_all_disk_operation: false,
+            manifest_checkpoint_threshold: 4,
+            ..StorageOptions::default_for_test()
+        };
+        let manifest_path = options.path.join(MANIFEST_FILE_NAME);
+
+        // a manifest of version 0, where table ids are assigned by replaying in order
+        let mut manifest = Manifest::open(&manifest_path, false).await.unwrap();
+        let (header, _) = manifest.replay().await.unwrap();
+        assert_eq!(header.version, 0);
+        manifest
+            .append(&[create_table_entry("a"), create_table_entry("b")])
+            .await
+            .unwrap();
+        let schema_id = RootCatalog::new()
+            .get_schema_id_by_name(RootCatalog::DEFAULT_SCHEMA_NAME)
+            .unwrap();
+        manifest
+            .append(&[ManifestOperation::DropTable(DropTableEntry {
+                table_id: TableRefId::new(schema_id, 0),
+            })])
+            .await
+            .unwrap();
+        drop(manifest);
+
+        let db = Arc::new(Database::new_on_disk(options.clone()).await);
+        let session = Session::new(db.clone());
+        assert_eq!(user_tables(&session).await, [("1".into(), "b".into())]);
+        // the manifest is upgraded on open
+        let mut manifest = Manifest::open(&manifest_path, false).await.unwrap();
+        let (header, ops) = manifest.replay().await.unwrap();
+        assert_eq!(header.version, MANIFEST_VERSION);
+        assert_eq!(header.next_table_ids, [(schema_id, 2)]);
+        assert!(matches!(
+            &ops[..],
+            [ManifestOperation::CreateTable(CreateTableEntry {
+                table_id: Some(1),
+                ..
+            })]
+        ));
+
+        // the log is truncated into checkpoints as it grows
+        for i in 0..10 {
+            let sql = format!("create table t{i} (a int); drop table t{i};");
+            session.run(&sql).await.unwrap();
+        }
+        session.run("create table c (a int)").await.unwrap();
+        db.shutdown().await.unwrap();
+        let mut manifest = Manifest::open(&manifest_path, false).await.unwrap();
+        let (_, ops) = manifest.replay().await.unwrap();
+        assert!(ops.len() < 4, "{ops:?}");
+
+        // ids of dropped tables are not reused
+        let db = Arc::new(Database::new_on_disk(options).await);
+        let session = Session::new(db.clone());
+        assert_eq!(
+            user_tables(&session).await,
+            [("1".into(), "b".into()), ("12".into(), "c".into())]
+        );
+        db.shutdown().await.unwrap();
+    }
+}
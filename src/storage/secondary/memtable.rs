@@ -0,0 +1,426 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Memtables that absorb small writes before they are flushed into rowsets.
+//!
+//! Each table has a memtable. Rows committed by small transactions are written to the [`Wal`]
+//! and appended to the memtable instead of creating a rowset for each transaction. The memtable
+//! is flushed into a rowset when it is large or old enough, or before rows of the table are
+//! deleted. A memtable is assigned a rowset id when it receives its first rows, and it is
+//! flushed into the rowset of that id.
+
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use bitvec::prelude::BitVec;
+use itertools::Itertools;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use super::version_manager::{EpochOp, Version};
+use super::{
+    AddRowSetEntry, ColumnBuilderOptions, DiskRowset, RowsetWriter, SecondaryIteratorImpl,
+    SecondaryMemRowsetImpl, SecondaryRowHandler, SecondaryTable, WalRecord,
+};
+use crate::array::{ArrayBuilderImpl, ArrayImpl, DataChunk, I64Array};
+use crate::catalog::{find_sort_key_id, ColumnCatalog};
+use crate::storage::{KeyRange, ScanPredicate, StorageChunk, StorageColumnRef, StorageResult};
+
+/// When `expected_size` is not specified, we should limit the maximum size of the chunk.
+const MEMTABLE_MAX_OUTPUT: usize = 2048;
+
+/// The memtable of a table.
+#[derive(Default)]
+pub struct SecondaryMemTable {
+    inner: Mutex<MemTableInner>,
+    /// Only one flush of the memtable is allowed at a time.
+    flush_lock: Mutex<()>,
+    /// Number of appends, counted after the rows become visible.
+    appends: AtomicU64,
+}
+
+impl SecondaryMemTable {
+    /// Returns the number of appends to the memtable.
+    pub fn appends(&self) -> u64 {
+        self.appends.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Default)]
+struct MemTableInner {
+    /// The memtable receiving new rows.
+    active: Option<MemTableData>,
+    /// The memtable being flushed. Its rows stay visible until the rowset is committed.
+    frozen: Option<MemTableData>,
+}
+
+#[derive(Clone)]
+struct MemTableData {
+    rowset_id: u32,
+    chunks: Vec<DataChunk>,
+    /// Estimated size of the chunks in bytes.
+    size: usize,
+    /// When the first rows were appended.
+    created_at: Instant,
+}
+
+impl MemTableData {
+    fn new(rowset_id: u32) -> Self {
+        Self {
+            rowset_id,
+            chunks: vec![],
+            size: 0,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// The rows in the memtable of a table when a transaction starts.
+#[derive(Default)]
+pub struct MemTableSnapshot {
+    /// The rowset id of the latest memtable.
+    rowset_id: u32,
+    chunks: Vec<DataChunk>,
+}
+
+impl MemTableSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn chunks(&self) -> &[DataChunk] {
+        &self.chunks
+    }
+
+    /// Create an iterator over the rows. Rows are ordered by the sort key if the table has one.
+    pub fn iter(
+        &self,
+        columns: &[ColumnCatalog],
+        column_refs: &[StorageColumnRef],
+        filter: Option<KeyRange>,
+        predicate: Option<Arc<dyn ScanPredicate>>,
+    ) -> MemTableIterator {
+        let chunk = sort_chunk(columns, concat_chunks(columns, &self.chunks));
+        let mut chunk: DataChunk = column_refs
+            .iter()
+            .map(|column_ref| match column_ref {
+                StorageColumnRef::Idx(idx) => chunk.array_at(*idx as usize).clone(),
+                StorageColumnRef::RowHandler => ArrayImpl::new_int64(
+                    (0..chunk.cardinality())
+                        .map(|row_id| SecondaryRowHandler(self.rowset_id, row_id as u32).as_i64())
+                        .collect::<I64Array>(),
+                ),
+            })
+            .collect();
+        // Like rowsets, the range filter is applied on the first column.
+        if let Some(range) = &filter {
+            let array = chunk.array_at(0);
+            let visibility = (0..array.len())
+                .map(|idx| range.contains(&array.get(idx)))
+                .collect_vec();
+            chunk = chunk.filter(&visibility);
+        }
+        MemTableIterator {
+            chunk,
+            predicate,
+            offset: 0,
+        }
+    }
+}
+
+/// Iterates on the rows of a memtable.
+///
+/// Row handlers of the rows are only placeholders, as rows in a memtable are never deleted: the
+/// memtable is flushed when a deletion starts, and no rows are appended until it ends.
+pub struct MemTableIterator {
+    /// All rows in the scanned columns.
+    chunk: DataChunk,
+    predicate: Option<Arc<dyn ScanPredicate>>,
+    offset: usize,
+}
+
+impl MemTableIterator {
+    pub async fn next_batch(
+        &mut self,
+        expected_size: Option<usize>,
+    ) -> StorageResult<Option<StorageChunk>> {
+        while self.offset < self.chunk.cardinality() {
+            let size = expected_size.unwrap_or(MEMTABLE_MAX_OUTPUT);
+            let end = (self.offset + size).min(self.chunk.cardinality());
+            let batch = self.chunk.slice(self.offset..end);
+            self.offset = end;
+            let visibility = match &self.predicate {
+                Some(predicate) => {
+                    Some(predicate.eval_all(&batch)?.into_iter().collect::<BitVec>())
+                }
+                None => None,
+            };
+            let chunk =
+                StorageChunk::construct(visibility, batch.arrays().iter().cloned().collect());
+            if chunk.is_some() {
+                return Ok(chunk);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl SecondaryIteratorImpl for MemTableIterator {}
+
+/// Concatenate chunks with the columns of a table into one chunk.
+fn concat_chunks(columns: &[ColumnCatalog], chunks: &[DataChunk]) -> DataChunk {
+    let mut builders = columns
+        .iter()
+        .map(|column| ArrayBuilderImpl::new(&column.data_type()))
+        .collect_vec();
+    for chunk in chunks {
+        for (builder, array) in builders.iter_mut().zip(chunk.arrays()) {
+            builder.append(array);
+        }
+    }
+    builders.into_iter().collect()
+}
+
+/// Sort rows by the sort key of the table, keeping the order of rows with the same key.
+fn sort_chunk(columns: &[ColumnCatalog], chunk: DataChunk) -> DataChunk {
+    let sort_keys = find_sort_key_id(columns);
+    if sort_keys.is_empty() {
+        return chunk;
+    }
+    let mut indexes = (0..chunk.cardinality() as u32).collect_vec();
+    indexes.sort_by_cached_key(|idx| {
+        (sort_keys.iter())
+            .map(|key| chunk.array_at(*key).get(*idx as usize))
+            .collect_vec()
+    });
+    chunk.arrays().iter().map(|a| a.gather(&indexes)).collect()
+}
+
+impl SecondaryTable {
+    /// Pin the latest version, and take a snapshot of the memtable consistent with it.
+    pub(super) async fn pin_with_memtable(&self) -> (Arc<Version>, MemTableSnapshot) {
+        let inner = self.memtable.inner.lock().await;
+        let version = self.version.pin();
+        let memtables = inner.frozen.iter().chain(&inner.active).collect_vec();
+        let snapshot = MemTableSnapshot {
+            rowset_id: memtables.last().map_or(0, |data| data.rowset_id),
+            chunks: (memtables.iter())
+                .flat_map(|data| data.chunks.iter().cloned())
+                .collect(),
+        };
+        (version, snapshot)
+    }
+
+    /// Append rows committed by a transaction to the memtable. The rows are persisted in the WAL
+    /// before they become visible.
+    ///
+    /// Returns false if rows of the table are being deleted, in which case the rows should be
+    /// written to a rowset instead, so that row handlers of deleted rows only refer to rowsets.
+    pub(super) async fn append_memtable(&self, chunks: &[DataChunk]) -> StorageResult<bool> {
+        let chunk = concat_chunks(&self.columns, chunks);
+        if chunk.cardinality() == 0 {
+            return Ok(true);
+        }
+        {
+            // a deletion is registered before it flushes the memtable, which takes the lock
+            let mut inner = self.memtable.inner.lock().await;
+            if self.txn_mgr.is_deleting(self.table_id()) {
+                return Ok(false);
+            }
+            let rowset_id = match &inner.active {
+                Some(data) => data.rowset_id,
+                None => (self.version).register_memtable(|| self.generate_rowset_id()),
+            };
+            let record = WalRecord {
+                table_id: self.table_ref_id,
+                rowset_id,
+                chunk,
+            };
+            self.wal.append(&record).await?;
+            let data = (inner.active).get_or_insert_with(|| MemTableData::new(rowset_id));
+            data.size += record.chunk.estimated_size();
+            data.chunks.push(record.chunk);
+            self.memtable.appends.fetch_add(1, Ordering::Release);
+        }
+        if self.memtable_needs_flush() {
+            self.flush_memtable().await?;
+        }
+        Ok(true)
+    }
+
+    /// Restore rows replayed from the WAL into the memtable.
+    pub(super) async fn restore_memtable(&self, rowset_id: u32, chunks: Vec<DataChunk>) {
+        let mut data = MemTableData::new(rowset_id);
+        data.size = chunks.iter().map(|chunk| chunk.estimated_size()).sum();
+        data.chunks = chunks;
+        self.memtable.inner.lock().await.active = Some(data);
+    }
+
+    /// Returns true if the memtable exceeds the size or age threshold.
+    pub(super) fn memtable_needs_flush(&self) -> bool {
+        let Ok(inner) = self.memtable.inner.try_lock() else {
+            return false;
+        };
+        inner.active.as_ref().is_some_and(|data| {
+            data.size >= self.storage_options.memtable_size
+                || data.created_at.elapsed() >= self.storage_options.memtable_flush_interval
+        })
+    }
+
+    /// Flush the memtable into a rowset.
+    pub(super) async fn flush_memtable(&self) -> StorageResult<()> {
+        let _guard = self.memtable.flush_lock.lock().await;
+        // retry the frozen memtable if the last flush failed
+        let data = {
+            let mut inner = self.memtable.inner.lock().await;
+            if inner.frozen.is_none() {
+                let Some(data) = inner.active.take() else {
+                    return Ok(());
+                };
+                inner.frozen = Some(data);
+                self.wal.rotate().await?;
+            }
+            inner.frozen.clone().unwrap()
+        };
+
+        let rowset_id = data.rowset_id;
+        let directory = self.get_rowset_path(rowset_id);
+        let io_backend = self.storage_options.io_backend.clone();
+        if !self.storage_options.disable_all_disk_operation {
+            if tokio::fs::metadata(&directory).await.is_ok() {
+                tokio::fs::remove_dir_all(&directory).await?;
+            }
+            (RowsetWriter::new(&directory, io_backend.clone()))
+                .create_dir()
+                .await?;
+        }
+        let mut mem = SecondaryMemRowsetImpl::new(
+            self.columns.clone(),
+            ColumnBuilderOptions::from_storage_options(&self.storage_options),
+            rowset_id,
+        );
+        for chunk in data.chunks {
+            mem.append(chunk).await?;
+        }
+        mem.flush(io_backend.clone(), &directory).await?;
+        let rowset = DiskRowset::open(
+            directory,
+            self.columns.clone(),
+            self.block_cache.clone(),
+            rowset_id,
+            io_backend,
+        )
+        .await?;
+
+        {
+            // the rows move from the memtable to the rowset atomically for readers
+            let mut inner = self.memtable.inner.lock().await;
+            self.version
+                .commit_changes(vec![EpochOp::AddRowSet((
+                    AddRowSetEntry {
+                        rowset_id,
+                        table_id: self.table_ref_id,
+                        partition: None,
+                    },
+                    rowset,
+                ))])
+                .await?;
+            inner.frozen = None;
+        }
+        info!("memtable flushed to RowSet #{}", rowset_id);
+        self.wal.release(rowset_id).await
+    }
+
+    /// Drop the rows in the memtable of a dropped table.
+    pub(super) async fn discard_memtable(&self) -> StorageResult<()> {
+        let mut inner = self.memtable.inner.lock().await;
+        for data in [inner.active.take(), inner.frozen.take()]
+            .into_iter()
+            .flatten()
+        {
+            self.version.unregister_memtable(data.rowset_id);
+            self.wal.release(data.rowset_id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::secondary::tests::{disk_options, open, query};
+    use crate::storage::secondary::StorageOptions;
+
+    #[tokio::test]
+    async fn test_memtable_recovery() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = disk_options(tempdir.path());
+        let rowsets = "select * from pg_catalog.rw_rowsets";
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        for i in 0..3 {
+            (session.run(&format!("insert into t values ({i})")))
+                .await
+                .unwrap();
+        }
+        // small inserts stay in the memtable
+        assert_eq!(query(&session, rowsets).await.len(), 0);
+        db.shutdown().await.unwrap();
+
+        // rows are recovered from the WAL, twice to replay the checkpoint
+        for _ in 0..2 {
+            let (db, session) = open(&options).await;
+            assert_eq!(query(&session, "select a from t").await, ["0", "1", "2"]);
+            assert_eq!(query(&session, rowsets).await.len(), 0);
+            db.shutdown().await.unwrap();
+        }
+
+        // deletions flush the memtable into a rowset
+        let (db, session) = open(&options).await;
+        session.run("delete from t where a = 1").await.unwrap();
+        session.run("insert into t values (3)").await.unwrap();
+        assert_eq!(query(&session, rowsets).await.len(), 1);
+        db.shutdown().await.unwrap();
+
+        // flushed rows are not replayed again
+        let (db, session) = open(&options).await;
+        assert_eq!(query(&session, "select a from t").await, ["0", "2", "3"]);
+        assert_eq!(
+            query(&session, "select count(*) from t where a < 3").await,
+            ["2"]
+        );
+        db.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flushed_memtable_not_replayed_after_checkpoint() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = StorageOptions {
+            // write a checkpoint on every commit
+            manifest_checkpoint_threshold: 1,
+            ..disk_options(tempdir.path())
+        };
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("create table u (a int)").await.unwrap();
+        // the WAL segment is kept for the memtable of `u` after `t` is flushed
+        (session.run("insert into t values (0), (1), (2)"))
+            .await
+            .unwrap();
+        session.run("insert into u values (0)").await.unwrap();
+        // the memtable of `t` is flushed into a RowSet, which is then compacted away
+        session.run("delete from t where a = 1").await.unwrap();
+        session.run("vacuum t").await.unwrap();
+        session.run("insert into u values (1)").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        for _ in 0..2 {
+            let (db, session) = open(&options).await;
+            assert_eq!(query(&session, "select a from t").await, ["0", "2"]);
+            assert_eq!(query(&session, "select a from u").await, ["0", "1"]);
+            db.shutdown().await.unwrap();
+        }
+    }
+}
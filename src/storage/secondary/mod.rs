@@ -1,23 +1,43 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
 //! Secondary storage engine for RisingLight
-
-use std::collections::HashMap;
+//!
+//! # Durability
+//!
+//! Changes are made durable in the following order, so that a crash at any point leaves either
+//! the old or the new state:
+//!
+//! 1. Files of a RowSet or DV are written and fsynced, and so are the directories containing them,
+//!    e.g. the RowSet directory and the database directory.
+//! 2. The change is appended to the manifest as a group of entries wrapped in `Begin` and `End`,
+//!    and the manifest is fsynced.
+//!
+//! On open, a torn group at the tail of the manifest is ignored and truncated. Files not
+//! referenced by the manifest, e.g. RowSets written before a crash, are garbage-collected.
+//! Manifest checkpoints and WAL segments are written to temporary files and renamed, followed
+//! by an fsync of the directory.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
 use std::sync::atomic::{AtomicU32, AtomicU64};
 use std::sync::Arc;
 
 use block::*;
+pub use check::*;
 pub use checksum::*;
 use column::*;
+pub use compaction_policy::*;
+pub use compactor::CompactionMode;
 use compactor::*;
 use concat_iterator::*;
 use delete_vector::*;
 use encode::*;
 use index::*;
 use index_builder::*;
+pub use introspection::*;
 use manifest::*;
+use memtable::*;
 use merge_iterator::*;
-use moka::future::Cache;
 pub use options::*;
 use parking_lot::RwLock;
 pub use row_handler::*;
@@ -31,6 +51,7 @@ pub use transaction::*;
 use transaction_manager::*;
 pub use txn_iterator::*;
 use version_manager::*;
+use wal::*;
 
 use super::index::InMemoryIndexes;
 use super::{InMemoryIndex, Storage, StorageError, StorageResult, TracedStorageError};
@@ -40,6 +61,9 @@ use crate::catalog::{
 };
 
 // public modules and structures
+mod check;
+mod compaction_policy;
+mod introspection;
 mod options;
 mod row_handler;
 mod table;
@@ -47,28 +71,46 @@ mod transaction;
 mod txn_iterator;
 
 // internal modules and structures
+mod backup;
 mod block;
 mod checksum;
 mod column;
 mod compactor;
 mod concat_iterator;
+mod delete_range;
 mod delete_vector;
 mod encode;
 mod index;
 mod index_builder;
 mod manifest;
+mod memtable;
 mod merge_iterator;
+mod partition;
 mod rowset;
 mod statistics;
 mod storage;
 mod transaction_manager;
+mod user;
 mod version_manager;
+mod wal;
 
 const MANIFEST_FILE_NAME: &str = "manifest.json";
+const WAL_DIRECTORY_NAME: &str = "wal";
 
 #[cfg(test)]
 mod tests;
 
+/// Persist the entries of a directory, e.g. files created or renamed in it.
+async fn sync_dir(path: impl AsRef<Path>) -> StorageResult<()> {
+    tokio::fs::File::open(path.as_ref())
+        .await?
+        .sync_all()
+        .await?;
+    #[cfg(test)]
+    tests::record_synced_dir(path.as_ref());
+    Ok(())
+}
+
 /// Secondary storage of RisingLight.
 pub struct SecondaryStorage {
     /// Catalog of the database
@@ -81,7 +123,7 @@ pub struct SecondaryStorage {
     options: Arc<StorageOptions>,
 
     /// Block cache of the storage engine
-    block_cache: Cache<BlockCacheKey, Block>,
+    block_cache: BlockCache,
 
     /// Next RowSet Id and DV Id of the current storage engine
     next_id: Arc<(AtomicU32, AtomicU64)>,
@@ -105,6 +147,15 @@ pub struct SecondaryStorage {
 
     /// Indexes of the current storage engine
     indexes: Mutex<InMemoryIndexes>,
+
+    /// History of the latest compactions
+    compactions: parking_lot::Mutex<VecDeque<CompactionInfo>>,
+
+    /// Write-ahead log of the memtables
+    wal: Arc<Wal>,
+
+    /// Serializes persisting users
+    users_lock: Mutex<()>,
 }
 
 impl SecondaryStorage {
@@ -116,6 +167,16 @@ impl SecondaryStorage {
         &self.catalog
     }
 
+    /// Returns a key that changes whenever committed rows change: the epoch of the latest version
+    /// and the number of appends to memtables.
+    pub fn data_version(&self) -> (u64, u64) {
+        let epoch = self.version.latest_epoch();
+        let appends = (self.tables.read().values())
+            .map(|table| table.memtable.appends())
+            .sum();
+        (epoch, appends)
+    }
+
     pub async fn spawn_compactor(self: &Arc<Self>) {
         let (tx, rx) = tokio::sync::oneshot::channel();
         let storage = self.clone();
@@ -179,7 +240,7 @@ impl Storage for SecondaryStorage {
         column_descs: &[ColumnCatalog],
         ordered_pk_ids: &[ColumnId],
     ) -> StorageResult<()> {
-        self.create_table_inner(schema_id, table_name, column_descs, ordered_pk_ids)
+        self.create_table_inner(schema_id, table_name, column_descs, ordered_pk_ids, None)
             .await
     }
 
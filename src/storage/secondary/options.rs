@@ -3,12 +3,15 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
 use parking_lot::Mutex;
 use risinglight_proto::rowset::block_checksum::ChecksumType;
 use tracing::warn;
 
+use super::{CompactionPolicy, DeleteRatioPolicy, SizeTieredPolicy};
+
 /// IO Backend of the rowset readers
 #[derive(Clone)]
 pub enum IOBackend {
@@ -66,6 +69,44 @@ pub struct StorageOptions {
 
     /// Whether to disable all disk operations, only for test use
     pub disable_all_disk_operation: bool,
+
+    /// Size (in bytes) of a memtable to be flushed into a RowSet. Transactions writing at least
+    /// this size create RowSets directly. Set to 0 to disable memtables.
+    pub memtable_size: usize,
+
+    /// Age of a memtable to be flushed into a RowSet
+    pub memtable_flush_interval: Duration,
+
+    /// Number of entries appended to the manifest before it is rewritten into a checkpoint
+    pub manifest_checkpoint_threshold: usize,
+
+    /// How long historical epochs stay readable by time-travel queries. If it is not zero,
+    /// memtables are bypassed so that every write commits an epoch. Otherwise time travel is
+    /// rejected, as rows in memtables are not in any epoch.
+    pub time_travel_retention: Duration,
+
+    /// Policies selecting RowSets for compactions, tried in order until one selects some RowSets.
+    /// They are used by background compactions and `COMPACT` statements.
+    pub compaction_policies: Vec<Arc<dyn CompactionPolicy>>,
+
+    /// Whether to compact RowSets in background
+    pub background_compaction: bool,
+
+    /// Interval between two rounds of background compactions
+    pub compaction_interval: Duration,
+
+    /// Maximum size (in bytes) of RowSets compacted per second in background, or `None` for
+    /// unlimited. `COMPACT` and `VACUUM` statements are not throttled.
+    pub compaction_rate_limit: Option<u64>,
+}
+
+impl StorageOptions {
+    fn default_compaction_policies() -> Vec<Arc<dyn CompactionPolicy>> {
+        vec![
+            Arc::new(SizeTieredPolicy::default()),
+            Arc::new(DeleteRatioPolicy::default()),
+        ]
+    }
 }
 
 impl StorageOptions {
@@ -86,6 +127,14 @@ impl StorageOptions {
             // required by range-filter scan rule
             record_first_key: true,
             disable_all_disk_operation: false,
+            memtable_size: 16 * (1 << 20), // 16MB
+            memtable_flush_interval: Duration::from_secs(60),
+            manifest_checkpoint_threshold: 10000,
+            time_travel_retention: Duration::ZERO,
+            compaction_policies: Self::default_compaction_policies(),
+            background_compaction: true,
+            compaction_interval: Duration::from_secs(1),
+            compaction_rate_limit: None,
         }
     }
 
@@ -101,6 +150,14 @@ impl StorageOptions {
             // required by range-filter scan rule
             record_first_key: true,
             disable_all_disk_operation: true,
+            memtable_size: 1 << 18, // 256KB
+            memtable_flush_interval: Duration::from_secs(60),
+            manifest_checkpoint_threshold: 100,
+            time_travel_retention: Duration::ZERO,
+            compaction_policies: Self::default_compaction_policies(),
+            background_compaction: true,
+            compaction_interval: Duration::from_secs(1),
+            compaction_rate_limit: None,
         }
     }
 }
@@ -0,0 +1,236 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Range-partitioned tables.
+//!
+//! Rows of a partitioned table are written to RowSets of their partitions, and each RowSet is
+//! tagged by its partition in the manifest. Scans skip RowSets of the pruned partitions, and a
+//! partition is dropped by deleting its RowSets as a whole.
+
+use itertools::Itertools;
+use tracing::info;
+
+use super::{
+    DeleteDVEntry, DeleteRowsetEntry, DropPartitionEntry, EpochOp, SecondaryStorage, StorageResult,
+    TracedStorageError,
+};
+use crate::catalog::{ColumnCatalog, ColumnId, PartitionSpec, SchemaId, TableRefId};
+
+impl SecondaryStorage {
+    /// Create a table partitioned by the range of a column.
+    pub async fn create_partitioned_table(
+        &self,
+        schema_id: SchemaId,
+        table_name: &str,
+        column_descs: &[ColumnCatalog],
+        ordered_pk_ids: &[ColumnId],
+        partition: PartitionSpec,
+    ) -> StorageResult<()> {
+        self.create_table_inner(
+            schema_id,
+            table_name,
+            column_descs,
+            ordered_pk_ids,
+            Some(partition),
+        )
+        .await
+    }
+
+    pub(super) fn apply_drop_partition(&self, entry: &DropPartitionEntry) {
+        let Some(table) = self.catalog.get_table(&entry.table_id) else {
+            return;
+        };
+        if let Some(mut spec) = table.partition().cloned() {
+            spec.drop_partitions(&entry.partition_ids);
+            self.catalog.set_partition(entry.table_id, Some(spec));
+        }
+    }
+
+    /// Drop partitions of a table with all their rows, and returns the number of dropped
+    /// RowSets. The ranges of the dropped partitions are taken over by the next partitions.
+    pub async fn drop_partitions(
+        &self,
+        table_id: TableRefId,
+        names: &[String],
+    ) -> StorageResult<usize> {
+        let table = self.get_table_inner(table_id)?;
+        let spec = (self.version.get_partition_spec(table.table_id()))
+            .ok_or_else(|| TracedStorageError::not_found("partitioned table", table_id))?;
+        let partition_ids: Vec<u32> = (names.iter())
+            .map(|name| {
+                (spec.get_partition_by_name(name))
+                    .map(|p| p.id)
+                    .ok_or_else(|| TracedStorageError::not_found("partition", name))
+            })
+            .try_collect()?;
+
+        // the RowSets are dropped from the latest version, so deletions and compactions on them
+        // committed afterwards conflict
+        let _commit_lock = self.txn_mgr.lock_for_commit(table.table_id()).await;
+        let version = self.version.pin();
+        let snapshot = &version.snapshot;
+        let rowsets = snapshot.get_rowsets_of(table.table_id());
+
+        let entry = DropPartitionEntry {
+            table_id,
+            partition_ids,
+        };
+        let mut changes = vec![EpochOp::DropPartition(entry.clone())];
+        let mut count = 0;
+        for rowset_id in rowsets.into_iter().flatten().sorted() {
+            let partition = self
+                .version
+                .get_rowset_partition(table.table_id(), *rowset_id);
+            if !partition.is_some_and(|p| entry.partition_ids.contains(&p)) {
+                continue;
+            }
+            count += 1;
+            changes.push(EpochOp::DeleteRowSet(DeleteRowsetEntry {
+                rowset_id: *rowset_id,
+                table_id,
+            }));
+            for dv_id in snapshot
+                .get_dvs_of(table.table_id(), *rowset_id)
+                .into_iter()
+                .flatten()
+                .sorted()
+            {
+                changes.push(EpochOp::DeleteDV(DeleteDVEntry {
+                    table_id,
+                    dv_id: *dv_id,
+                    rowset_id: *rowset_id,
+                }));
+            }
+        }
+        self.version.commit_changes(changes).await?;
+        self.apply_drop_partition(&entry);
+
+        info!(
+            "{} rowsets dropped with partitions {:?} of table {}",
+            count, names, table_id
+        );
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::array::{ArrayImpl, DataChunk, I32Array};
+    use crate::catalog::{ColumnDesc, Partition, RootCatalog};
+    use crate::storage::secondary::{IOBackend, SecondaryTable, StorageOptions};
+    use crate::storage::{ScanOptions, Storage, StorageColumnRef, Table, Transaction, TxnIterator};
+    use crate::types::{DataType, DataValue};
+
+    /// Opens the storage with files on disk, so that it can be restarted.
+    async fn open(path: &std::path::Path) -> Arc<SecondaryStorage> {
+        let options = StorageOptions {
+            path: path.to_path_buf(),
+            io_backend: IOBackend::NormalRead,
+            disable_all_disk_operation: false,
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        };
+        Arc::new(SecondaryStorage::open(options).await.unwrap())
+    }
+
+    fn get_table(storage: &SecondaryStorage) -> SecondaryTable {
+        let schema = RootCatalog::DEFAULT_SCHEMA_NAME;
+        let table_id = storage.catalog().get_table_id_by_name(schema, "t").unwrap();
+        storage.get_table(table_id).unwrap()
+    }
+
+    async fn insert(table: &SecondaryTable, values: &[i32]) {
+        let mut txn = table.write().await.unwrap();
+        let chunk: DataChunk = [ArrayImpl::new_int32(
+            values.iter().copied().collect::<I32Array>(),
+        )]
+        .into_iter()
+        .collect();
+        txn.append(chunk).await.unwrap();
+        txn.commit().await.unwrap();
+    }
+
+    /// Returns the sorted values in the partitions.
+    async fn scan(table: &SecondaryTable, partitions: Option<Vec<u32>>) -> Vec<i32> {
+        let txn = table.read().await.unwrap();
+        let options = ScanOptions::default().with_partitions_opt(partitions);
+        let mut iter = (txn.scan(&[StorageColumnRef::Idx(0)], options))
+            .await
+            .unwrap();
+        let mut values = vec![];
+        while let Some(chunk) = iter.next_batch(None).await.unwrap() {
+            for i in 0..chunk.cardinality() {
+                let DataValue::Int32(value) = chunk.array_at(0).get(i) else {
+                    panic!("unexpected value");
+                };
+                values.push(value);
+            }
+        }
+        txn.abort().await.unwrap();
+        values.sort();
+        values
+    }
+
+    fn rowsets(storage: &SecondaryStorage, table: &SecondaryTable) -> usize {
+        let version = storage.version.pin();
+        (version.snapshot.get_rowsets_of(table.table_id())).map_or(0, |r| r.len())
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_table() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let storage = open(tempdir.path()).await;
+        let schema_id = (storage.catalog())
+            .get_schema_id_by_name(RootCatalog::DEFAULT_SCHEMA_NAME)
+            .unwrap();
+        let column = ColumnCatalog::new(0, ColumnDesc::new("a", DataType::Int32, false));
+        let partition = |id, name: &str, upper_bound| Partition {
+            id,
+            name: name.into(),
+            upper_bound,
+        };
+        let spec = PartitionSpec {
+            column: 0,
+            partitions: vec![
+                partition(0, "p0", Some(DataValue::Int32(10))),
+                partition(1, "p1", None),
+            ],
+        };
+        (storage.create_partitioned_table(schema_id, "t", &[column], &[], spec))
+            .await
+            .unwrap();
+        let table = get_table(&storage);
+
+        // rows are written to a RowSet of each partition, and scans skip the other partitions
+        insert(&table, &[1, 15, 5, 25]).await;
+        assert_eq!(rowsets(&storage, &table), 2);
+        assert_eq!(scan(&table, None).await, [1, 5, 15, 25]);
+        assert_eq!(scan(&table, Some(vec![0])).await, [1, 5]);
+        assert_eq!(scan(&table, Some(vec![1])).await, [15, 25]);
+
+        // the range of the dropped partition is taken over by the next partition
+        let p0 = ["p0".to_string()];
+        let dropped = storage.drop_partitions(table.table_ref_id, &p0).await;
+        assert_eq!(dropped.unwrap(), 1);
+        assert_eq!(rowsets(&storage, &table), 1);
+        insert(&table, &[3]).await;
+        assert_eq!(scan(&table, Some(vec![1])).await, [3, 15, 25]);
+        assert!((storage.drop_partitions(table.table_ref_id, &p0))
+            .await
+            .is_err());
+        drop((table, storage));
+
+        // partitions of the table and its RowSets are restored from the manifest
+        let storage = open(tempdir.path()).await;
+        let table = get_table(&storage);
+        let spec = storage.catalog().get_table(&table.table_ref_id).unwrap();
+        let names = (spec.partition().unwrap().partitions.iter())
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["p1"]);
+        assert_eq!(scan(&table, Some(vec![0])).await, [] as [i32; 0]);
+        assert_eq!(scan(&table, Some(vec![1])).await, [3, 15, 25]);
+    }
+}
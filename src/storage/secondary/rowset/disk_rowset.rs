@@ -5,10 +5,9 @@ use std::sync::{Arc, Mutex};
 
 use bytes::Bytes;
 use itertools::Itertools;
-use moka::future::Cache;
 use tokio::fs::{read, OpenOptions};
 
-use super::super::{Block, BlockCacheKey, Column, ColumnIndex, ColumnSeekPosition, IOBackend};
+use super::super::{BlockCache, BlockCacheKey, Column, ColumnIndex, ColumnSeekPosition, IOBackend};
 use super::{path_of_data_column, path_of_index_column, RowSetIterator};
 use crate::catalog::ColumnCatalog;
 use crate::storage::secondary::column::ColumnReadableFile;
@@ -30,7 +29,7 @@ impl DiskRowset {
     pub async fn open(
         directory: PathBuf,
         column_infos: Arc<[ColumnCatalog]>,
-        block_cache: Cache<BlockCacheKey, Block>,
+        block_cache: BlockCache,
         rowset_id: u32,
         io_backend: IOBackend,
     ) -> StorageResult<Self> {
@@ -218,7 +217,7 @@ pub mod tests {
         DiskRowset::open(
             tempdir.path().to_path_buf(),
             columns.into(),
-            Cache::new(2333),
+            BlockCache::new(2333),
             0,
             backend,
         )
@@ -257,7 +256,7 @@ pub mod tests {
         DiskRowset::open(
             tempdir.path().to_path_buf(),
             columns.into(),
-            Cache::new(2333),
+            BlockCache::new(2333),
             0,
             backend,
         )
@@ -296,7 +295,7 @@ pub mod tests {
         DiskRowset::open(
             tempdir.path().to_path_buf(),
             columns.into(),
-            Cache::new(2333),
+            BlockCache::new(2333),
             0,
             backend,
         )
@@ -345,7 +344,7 @@ pub mod tests {
         DiskRowset::open(
             tempdir.path().to_path_buf(),
             columns.into(),
-            Cache::new(2333),
+            BlockCache::new(2333),
             0,
             backend,
         )
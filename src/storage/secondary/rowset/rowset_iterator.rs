@@ -1,16 +1,17 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::ops::Bound;
+use std::ops::{Bound, Range};
 use std::sync::Arc;
 
 use bitvec::prelude::BitVec;
-use smallvec::smallvec;
 
 use super::super::{ColumnIteratorImpl, ColumnSeekPosition, SecondaryIteratorImpl};
 use super::DiskRowset;
-use crate::array::ArrayImpl;
+use crate::array::{ArrayImpl, DataChunk};
 use crate::storage::secondary::DeleteVector;
-use crate::storage::{KeyRange, PackedVec, StorageChunk, StorageColumnRef, StorageResult};
+use crate::storage::{
+    KeyRange, PackedVec, ScanPredicate, StorageChunk, StorageColumnRef, StorageResult,
+};
 
 /// When `expected_size` is not specified, we should limit the maximum size of the chunk.
 const ROWSET_MAX_OUTPUT: usize = 2048;
@@ -22,6 +23,8 @@ pub struct RowSetIterator {
     column_iterators: Vec<ColumnIteratorImpl>,
     /// An optional filter for the first column.
     filter: Option<KeyRange>,
+    /// An optional predicate evaluated before fetching columns not required by it.
+    predicate: Option<Arc<dyn ScanPredicate>>,
     /// Indicate whether the iterator has reached the end.
     end: bool,
 }
@@ -86,6 +89,7 @@ impl RowSetIterator {
             dvs,
             column_iterators,
             filter,
+            predicate: None,
             end: false,
         })
     }
@@ -188,73 +192,147 @@ impl RowSetIterator {
             visibility_map = Some(visi);
         }
 
-        let mut arrays: PackedVec<ArrayImpl> = smallvec![];
+        let mut arrays: Vec<Option<ArrayImpl>> = vec![None; self.column_refs.len()];
         // to make sure all columns have the same chunk range
         let mut common_chunk_range = None;
 
+        // Late materialization: fetch the columns required by the predicate first, and evaluate
+        // the predicate on them. If no row survives, skip other columns without decoding them.
+        if let Some(predicate) = self.predicate.clone() {
+            // the range filter is applied when the first column is fetched
+            let first = self.filter.as_ref().map(|_| 0);
+            for id in first.into_iter().chain(predicate.columns().iter().copied()) {
+                if arrays[id].is_some() {
+                    continue;
+                }
+                let Some(array) = self
+                    .fetch_column(id, fetch_size, &mut common_chunk_range, &mut visibility_map)
+                    .await?
+                else {
+                    return Ok(None);
+                };
+                arrays[id] = Some(array);
+            }
+            let chunk: DataChunk = (predicate.columns().iter())
+                .map(|id| arrays[*id].clone().unwrap())
+                .collect();
+            // the predicate may fail on deleted or filtered rows, e.g. by division by zero,
+            // so it is only evaluated on visible rows
+            let bitmap: BitVec = match &visibility_map {
+                Some(vis) => {
+                    let len = arrays.iter().flatten().next().map_or(0, |a| a.len());
+                    let visible = vis.iter().by_vals().take(len).collect::<Vec<_>>();
+                    let mut passed = predicate.eval(&chunk.filter(&visible))?.into_iter();
+                    (visible.iter())
+                        .map(|v| *v && passed.next().unwrap())
+                        .collect()
+                }
+                None => predicate.eval(&chunk)?.into_iter().collect(),
+            };
+            visibility_map = Some(bitmap);
+
+            // All rows in this batch have been filtered out, call `skip` on other columns
+            let vis = visibility_map.as_ref().unwrap();
+            if vis.not_any() {
+                for (id, array) in arrays.iter().enumerate() {
+                    if array.is_none() {
+                        self.column_iterators[id].skip(vis.len());
+                    }
+                }
+                return Ok(None);
+            }
+        }
+
         // At this stage, we know that some rows survived from the filter scan if happend, so
         // just fetch the next batch for every other columns, and we have `visibility_map` to
         // indicate the visibility of its rows
-        // TODO: Implement the skip interface for column_iterator and call it here.
-        // For those already fetched columns, they also need to delete corrensponding blocks.
-        for (id, _) in self.column_refs.iter().enumerate() {
-            let Some((row_id, array)) = self.column_iterators[id]
-                .next_batch(Some(fetch_size))
+        let unfetched = (0..arrays.len())
+            .filter(|id| arrays[*id].is_none())
+            .collect::<Vec<_>>();
+        for id in unfetched {
+            let Some(array) = self
+                .fetch_column(id, fetch_size, &mut common_chunk_range, &mut visibility_map)
                 .await?
             else {
-                self.end = true;
                 return Ok(None);
             };
+            arrays[id] = Some(array);
+        }
 
-            // check chunk range
-            let current_range = row_id..row_id + array.len() as u32;
-            if let Some(common_range) = &common_chunk_range {
-                if common_range != &current_range {
-                    panic!(
-                        "unmatched row range from column iterator: {:?} of [{:?}], {:?} != {:?}",
-                        self.column_refs[id], self.column_refs, common_range, current_range
-                    );
-                }
-            } else {
-                common_chunk_range = Some(current_range);
+        let arrays: PackedVec<ArrayImpl> = arrays.into_iter().map(Option::unwrap).collect();
+        Ok(StorageChunk::construct(visibility_map, arrays))
+    }
+
+    /// Fetches the next batch of the `id`-th column, and applies the range filter on it.
+    ///
+    /// Returns `None` and marks the iterator as ended if the column has no more data.
+    async fn fetch_column(
+        &mut self,
+        id: usize,
+        fetch_size: usize,
+        common_chunk_range: &mut Option<Range<u32>>,
+        visibility_map: &mut Option<BitVec>,
+    ) -> StorageResult<Option<ArrayImpl>> {
+        let Some((row_id, array)) = self.column_iterators[id]
+            .next_batch(Some(fetch_size))
+            .await?
+        else {
+            self.end = true;
+            return Ok(None);
+        };
+
+        // check chunk range
+        let current_range = row_id..row_id + array.len() as u32;
+        if let Some(common_range) = &common_chunk_range {
+            if common_range != &current_range {
+                panic!(
+                    "unmatched row range from column iterator: {:?} of [{:?}], {:?} != {:?}",
+                    self.column_refs[id], self.column_refs, common_range, current_range
+                );
             }
+        } else {
+            *common_chunk_range = Some(current_range);
+        }
 
-            // For now, we only support range-filter scan by first column.
-            if let Some(range) = &self.filter
-                && id == 0
-            {
-                let len = array.len();
-                let start_row_id = match &range.start {
-                    Bound::Included(key) => (0..array.len()).position(|idx| &array.get(idx) >= key),
-                    Bound::Excluded(key) => (0..array.len()).position(|idx| &array.get(idx) > key),
-                    Bound::Unbounded => Some(0),
-                }
-                .unwrap_or(len);
-                let end_row_id = match &range.end {
-                    Bound::Included(key) => (0..array.len()).position(|idx| &array.get(idx) > key),
-                    Bound::Excluded(key) => (0..array.len()).position(|idx| &array.get(idx) >= key),
-                    Bound::Unbounded => None,
-                }
-                .unwrap_or(len);
-                if (start_row_id..end_row_id) != (0..len) {
-                    let bitmap = (0..len)
-                        .map(|i| (start_row_id..end_row_id).contains(&i))
-                        .collect();
-                    if let Some(ref mut vis) = visibility_map {
-                        *vis &= bitmap;
-                    } else {
-                        visibility_map = Some(bitmap);
-                    }
-                }
-                if end_row_id == 0 {
-                    self.end = true;
+        // For now, we only support range-filter scan by first column.
+        if let Some(range) = &self.filter
+            && id == 0
+        {
+            let len = array.len();
+            let start_row_id = match &range.start {
+                Bound::Included(key) => (0..array.len()).position(|idx| &array.get(idx) >= key),
+                Bound::Excluded(key) => (0..array.len()).position(|idx| &array.get(idx) > key),
+                Bound::Unbounded => Some(0),
+            }
+            .unwrap_or(len);
+            let end_row_id = match &range.end {
+                Bound::Included(key) => (0..array.len()).position(|idx| &array.get(idx) > key),
+                Bound::Excluded(key) => (0..array.len()).position(|idx| &array.get(idx) >= key),
+                Bound::Unbounded => None,
+            }
+            .unwrap_or(len);
+            if (start_row_id..end_row_id) != (0..len) {
+                let bitmap = (0..len)
+                    .map(|i| (start_row_id..end_row_id).contains(&i))
+                    .collect();
+                if let Some(ref mut vis) = visibility_map {
+                    *vis &= bitmap;
+                } else {
+                    *visibility_map = Some(bitmap);
                 }
             }
-
-            arrays.push(array);
+            if end_row_id == 0 {
+                self.end = true;
+            }
         }
 
-        Ok(StorageChunk::construct(visibility_map, arrays))
+        Ok(Some(array))
+    }
+
+    /// Sets a predicate to be evaluated during the scan.
+    pub fn with_predicate(mut self, predicate: Option<Arc<dyn ScanPredicate>>) -> Self {
+        self.predicate = predicate;
+        self
     }
 }
 
@@ -267,7 +345,7 @@ mod tests {
     use itertools::Itertools;
 
     use super::*;
-    use crate::array::{Array, ArrayToVecExt};
+    use crate::array::{Array, ArrayToVecExt, DataChunk};
     use crate::storage::secondary::rowset::tests::{
         helper_build_rowset, helper_build_rowset_with_first_key_recorded,
     };
@@ -389,6 +467,119 @@ mod tests {
         }
     }
 
+    /// A predicate for test that checks whether an int32 column equals to a value.
+    #[derive(Debug)]
+    struct EqPredicate {
+        columns: Vec<usize>,
+        value: i32,
+    }
+
+    impl ScanPredicate for EqPredicate {
+        fn columns(&self) -> &[usize] {
+            &self.columns
+        }
+
+        fn eval(&self, chunk: &DataChunk) -> StorageResult<Vec<bool>> {
+            let ArrayImpl::Int32(array) = chunk.array_at(0) else {
+                unreachable!()
+            };
+            Ok(array.iter().map(|v| v == Some(&self.value)).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rowset_iterator_with_predicate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let rowset = Arc::new(helper_build_rowset(&tempdir, false, 1000).await);
+        let column_refs: Arc<[StorageColumnRef]> =
+            vec![StorageColumnRef::Idx(0), StorageColumnRef::Idx(2)].into();
+
+        // v3 = 2: only the first row of every 7 rows survives
+        let mut it = rowset
+            .iter(
+                column_refs.clone(),
+                vec![],
+                ColumnSeekPosition::RowId(1000),
+                None,
+            )
+            .await
+            .unwrap()
+            .with_predicate(Some(Arc::new(EqPredicate {
+                columns: vec![1],
+                value: 2,
+            })));
+        let chunk = it.next_batch(Some(1000)).await.unwrap().unwrap();
+        assert_eq!(chunk.row_count(), 20);
+        assert_eq!(chunk.cardinality(), 3);
+        let visibility = chunk.visibility().as_ref().unwrap();
+        for i in 0..20 {
+            assert_eq!(visibility[i], i % 7 == 0);
+        }
+
+        // v3 = 4: no row survives
+        let mut it = rowset
+            .iter(column_refs, vec![], ColumnSeekPosition::RowId(1000), None)
+            .await
+            .unwrap()
+            .with_predicate(Some(Arc::new(EqPredicate {
+                columns: vec![1],
+                value: 4,
+            })));
+        assert!(it.next_batch(Some(1000)).await.unwrap().is_none());
+    }
+
+    /// A predicate for test that checks the values of an int32 column are in a range.
+    #[derive(Debug)]
+    struct InRangePredicate {
+        columns: Vec<usize>,
+        range: std::ops::RangeInclusive<i32>,
+    }
+
+    impl ScanPredicate for InRangePredicate {
+        fn columns(&self) -> &[usize] {
+            &self.columns
+        }
+
+        fn eval(&self, chunk: &DataChunk) -> StorageResult<Vec<bool>> {
+            let ArrayImpl::Int32(array) = chunk.array_at(0) else {
+                unreachable!()
+            };
+            let values = array.iter().map(|v| *v.unwrap()).collect_vec();
+            assert!(values.iter().all(|v| self.range.contains(v)), "{values:?}");
+            Ok(vec![true; values.len()])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rowset_iterator_with_range_filter_and_predicate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let rowset = Arc::new(helper_build_rowset_with_first_key_recorded(&tempdir).await);
+        let column_refs: Arc<[StorageColumnRef]> =
+            vec![StorageColumnRef::Idx(0), StorageColumnRef::Idx(1)].into();
+        // the predicate on v2 is only evaluated on rows in the range of v1
+        let mut it = rowset
+            .iter(
+                column_refs,
+                vec![],
+                ColumnSeekPosition::RowId(168),
+                Some(KeyRange {
+                    start: Bound::Included(DataValue::Int32(180)),
+                    end: Bound::Included(DataValue::Int32(195)),
+                }),
+            )
+            .await
+            .unwrap()
+            .with_predicate(Some(Arc::new(InRangePredicate {
+                columns: vec![1],
+                range: 181..=196,
+            })));
+        let mut column0 = vec![];
+        while let Some(chunk) = it.next_batch(None).await.unwrap() {
+            data_from_chunk(&chunk, &mut column0, 0).await;
+        }
+        assert_eq!(column0, (180..=195).collect_vec());
+    }
+
     #[tokio::test]
     async fn test_rowset_iterator_with_range_filter() {
         {
@@ -3,12 +3,12 @@
 use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
-use tokio::fs::{File, OpenOptions};
+use tokio::fs::OpenOptions;
 use tokio::io::{AsyncWriteExt, BufWriter};
 
 use crate::catalog::ColumnCatalog;
 use crate::storage::secondary::rowset::EncodedRowset;
-use crate::storage::secondary::IOBackend;
+use crate::storage::secondary::{sync_dir, IOBackend};
 use crate::storage::StorageResult;
 
 pub fn path_of_data_column(base: impl AsRef<Path>, column_info: &ColumnCatalog) -> PathBuf {
@@ -45,14 +45,15 @@ impl RowsetWriter {
         }
     }
 
+    /// Create the directory of the rowset and persist its entry in the parent directory.
     pub async fn create_dir(&self) -> StorageResult<()> {
         if !self.io_backend.is_in_memory() {
-            tokio::fs::create_dir(&self.directory)
-                .await
-                .map_err(|err| err.into())
-        } else {
-            Ok(())
+            tokio::fs::create_dir(&self.directory).await?;
+            if let Some(parent) = self.directory.parent() {
+                sync_dir(parent).await?;
+            }
         }
+        Ok(())
     }
 
     async fn pipe_to_file(
@@ -84,13 +85,6 @@ impl RowsetWriter {
         Ok(())
     }
 
-    async fn sync_dir(io_backend: &IOBackend, path: &impl AsRef<Path>) -> StorageResult<()> {
-        if !io_backend.is_in_memory() {
-            File::open(path.as_ref()).await?.sync_data().await?;
-        }
-        Ok(())
-    }
-
     /// Flush rows in an encoded rowset. Create files if not exists.
     /// Panics if the rowset is empty.
     pub async fn flush(self, rowset: EncodedRowset) -> StorageResult<()> {
@@ -114,7 +108,9 @@ impl RowsetWriter {
             .await?;
         }
 
-        Self::sync_dir(&self.io_backend, &self.directory).await?;
+        if !self.io_backend.is_in_memory() {
+            sync_dir(&self.directory).await?;
+        }
 
         Ok(())
     }
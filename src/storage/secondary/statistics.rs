@@ -10,22 +10,38 @@
 //!
 //! RowCount is NOT a precise statistics. It simply adds up the row counts of all blocks. As there
 //! might be rows deleted in deletion vector, the aggregated RowCount is not always accurate.
+//!
+//! ## Min / Max
+//!
+//! Min and Max form the zone map of a block of primitive type. The body is the value encoded by
+//! `PrimitiveFixedWidthEncode`, or empty if all values in the block are null. Like RowCount, they
+//! don't take deletion vectors into account.
+//!
+//! ## DistinctValue
+//!
+//! DistinctValue is the number of distinct non-null values in a block. The aggregated value is the
+//! sum of all blocks, which is an upper bound of the number of distinct values in the table.
 
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 
 use super::index::ColumnIndex;
+use crate::array::ArrayImpl;
 use crate::types::DataValue;
 
 mod row_count;
 use row_count::*;
 mod distinct_value;
 use distinct_value::*;
+mod zone_map;
+pub use zone_map::*;
 mod statistics_builder;
 pub use statistics_builder::*;
 
 /// Get the aggregated statistics from pre-aggregated per-block statistics.
 pub trait StatisticsGlobalAgg {
     fn apply_batch(&mut self, index: &ColumnIndex);
+    /// Apply the values of a column in memory, e.g. in a memtable.
+    fn apply_array(&mut self, array: &ArrayImpl);
     fn get_output(&self) -> DataValue;
 }
 
@@ -35,5 +51,8 @@ pub fn create_statistics_global_aggregator(
     match ty {
         BlockStatisticsType::RowCount => Box::new(RowCountGlobalAgg::create()),
         BlockStatisticsType::DistinctValue => Box::new(DistinctValueGlobalAgg::create()),
+        BlockStatisticsType::Min | BlockStatisticsType::Max => {
+            panic!("zone maps should be aggregated by `ZoneMapGlobalAgg` with the column type")
+        }
     }
 }
@@ -1,8 +1,11 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::collections::HashSet;
+
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 
 use super::StatisticsGlobalAgg;
+use crate::array::ArrayImpl;
 use crate::storage::secondary::index::ColumnIndex;
 use crate::types::DataValue;
 
@@ -28,6 +31,14 @@ impl StatisticsGlobalAgg for DistinctValueGlobalAgg {
         }
     }
 
+    fn apply_array(&mut self, array: &ArrayImpl) {
+        let values = array
+            .iter()
+            .filter(|v| !v.is_null())
+            .collect::<HashSet<_>>();
+        self.distinct_cnt += values.len() as u64;
+    }
+
     fn get_output(&self) -> DataValue {
         DataValue::Int64(self.distinct_cnt as i64)
     }
@@ -1,6 +1,7 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
 use super::StatisticsGlobalAgg;
+use crate::array::ArrayImpl;
 use crate::storage::secondary::index::ColumnIndex;
 use crate::types::DataValue;
 
@@ -22,6 +23,10 @@ impl StatisticsGlobalAgg for RowCountGlobalAgg {
         }
     }
 
+    fn apply_array(&mut self, array: &ArrayImpl) {
+        self.cnt += array.len() as u64;
+    }
+
     fn get_output(&self) -> DataValue {
         DataValue::Int64(self.cnt as i64)
     }
@@ -6,6 +6,7 @@ use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 use risinglight_proto::rowset::BlockStatistics;
 
 use crate::array::PrimitiveValueType;
+use crate::storage::secondary::encode::PrimitiveFixedWidthEncode;
 
 pub struct StatisticsBuilder<'a, U: PrimitiveValueType = u8> {
     distinct_values: HashSet<&'a [U]>,
@@ -34,6 +35,46 @@ impl<'a, U: PrimitiveValueType> StatisticsBuilder<'a, U> {
     }
 }
 
+/// Builds the zone map (min and max value) of a block of primitive values.
+pub struct ZoneMapBuilder<T: PrimitiveFixedWidthEncode> {
+    min_max: Option<(T, T)>,
+}
+
+impl<T: PrimitiveFixedWidthEncode> ZoneMapBuilder<T> {
+    pub fn new() -> Self {
+        Self { min_max: None }
+    }
+
+    pub fn add_item(&mut self, item: T) {
+        self.min_max = match self.min_max {
+            None => Some((item, item)),
+            Some((min, max)) => Some((
+                if item < min { item } else { min },
+                if item > max { item } else { max },
+            )),
+        };
+    }
+
+    /// Returns the min and max statistics. The body is empty if all values are null.
+    pub fn get_statistics(self) -> Vec<BlockStatistics> {
+        let (mut min, mut max) = (vec![], vec![]);
+        if let Some((min_value, max_value)) = self.min_max {
+            min_value.encode(&mut min);
+            max_value.encode(&mut max);
+        }
+        vec![
+            BlockStatistics {
+                block_stat_type: BlockStatisticsType::Min as i32,
+                body: min,
+            },
+            BlockStatistics {
+                block_stat_type: BlockStatisticsType::Max as i32,
+                body: max,
+            },
+        ]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::Buf;
@@ -56,4 +97,21 @@ mod tests {
         let mut body = &stats[0].body[..];
         assert_eq!(body.get_u64_le(), 3);
     }
+
+    #[test]
+    fn test_zone_map() {
+        let mut builder = ZoneMapBuilder::new();
+        builder.add_item(3);
+        builder.add_item(-1);
+        builder.add_item(2);
+        let stats = builder.get_statistics();
+        assert_eq!(stats[0].block_stat_type(), BlockStatisticsType::Min);
+        assert_eq!(stats[1].block_stat_type(), BlockStatisticsType::Max);
+        assert_eq!(i32::decode(&mut &stats[0].body[..]), -1);
+        assert_eq!(i32::decode(&mut &stats[1].body[..]), 3);
+
+        let stats = ZoneMapBuilder::<i32>::new().get_statistics();
+        assert!(stats[0].body.is_empty());
+        assert!(stats[1].body.is_empty());
+    }
 }
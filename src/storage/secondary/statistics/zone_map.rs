@@ -0,0 +1,94 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
+use rust_decimal::Decimal;
+
+use super::StatisticsGlobalAgg;
+use crate::array::ArrayImpl;
+use crate::storage::secondary::encode::PrimitiveFixedWidthEncode;
+use crate::storage::secondary::index::ColumnIndex;
+use crate::types::{DataType, DataValue, Date, Interval, Timestamp, TimestampTz, F64};
+
+/// Gather the minimum or maximum value from the zone maps of column index.
+pub struct ZoneMapGlobalAgg {
+    ty: BlockStatisticsType,
+    data_type: DataType,
+    value: DataValue,
+}
+
+impl ZoneMapGlobalAgg {
+    pub fn create(ty: BlockStatisticsType, data_type: DataType) -> Self {
+        Self {
+            ty,
+            data_type,
+            value: DataValue::Null,
+        }
+    }
+
+    fn update(&mut self, value: DataValue) {
+        let replace = self.value.is_null()
+            || match self.ty {
+                BlockStatisticsType::Min => value < self.value,
+                BlockStatisticsType::Max => value > self.value,
+                _ => unreachable!(),
+            };
+        if replace {
+            self.value = value;
+        }
+    }
+}
+
+impl StatisticsGlobalAgg for ZoneMapGlobalAgg {
+    fn apply_batch(&mut self, index: &ColumnIndex) {
+        for index in index.indexes() {
+            for stat in &index.stats {
+                if stat.block_stat_type() != self.ty || stat.body.is_empty() {
+                    continue;
+                }
+                let value = decode_value(&self.data_type, &stat.body);
+                self.update(value);
+            }
+        }
+    }
+
+    fn apply_array(&mut self, array: &ArrayImpl) {
+        for value in array.iter().filter(|v| !v.is_null()) {
+            self.update(value);
+        }
+    }
+
+    fn get_output(&self) -> DataValue {
+        self.value.clone()
+    }
+}
+
+/// Returns true if all blocks of the column index have zone maps.
+pub fn has_zone_map(index: &ColumnIndex) -> bool {
+    index.indexes().iter().all(|index| {
+        index
+            .stats
+            .iter()
+            .any(|stat| stat.block_stat_type() == BlockStatisticsType::Min)
+            && index
+                .stats
+                .iter()
+                .any(|stat| stat.block_stat_type() == BlockStatisticsType::Max)
+    })
+}
+
+/// Decodes a value encoded by [`PrimitiveFixedWidthEncode`].
+fn decode_value(data_type: &DataType, mut body: &[u8]) -> DataValue {
+    match data_type {
+        DataType::Bool => DataValue::Bool(bool::decode(&mut body)),
+        DataType::Int16 => DataValue::Int16(i16::decode(&mut body)),
+        DataType::Int32 => DataValue::Int32(i32::decode(&mut body)),
+        DataType::Int64 => DataValue::Int64(i64::decode(&mut body)),
+        DataType::Float64 => DataValue::Float64(F64::decode(&mut body)),
+        DataType::Decimal(_, _) => DataValue::Decimal(Decimal::decode(&mut body)),
+        DataType::Date => DataValue::Date(Date::decode(&mut body)),
+        DataType::Timestamp => DataValue::Timestamp(Timestamp::decode(&mut body)),
+        DataType::TimestampTz => DataValue::TimestampTz(TimestampTz::decode(&mut body)),
+        DataType::Interval => DataValue::Interval(Interval::decode(&mut body)),
+        _ => panic!("zone map is not supported on type {data_type}"),
+    }
+}
@@ -1,22 +1,27 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
 use std::sync::atomic::{AtomicU32, AtomicU64};
 use std::sync::Arc;
 
-use moka::future::Cache;
+use itertools::Itertools;
 use parking_lot::RwLock;
 use tokio::fs;
 use tokio::sync::Mutex;
 use tracing::info;
 
-use super::{DiskRowset, Manifest, SecondaryStorage, StorageOptions, StorageResult};
-use crate::catalog::RootCatalog;
+use super::{
+    DiskRowset, Manifest, SecondaryStorage, StorageOptions, StorageResult, Wal, WalRecord,
+    WAL_DIRECTORY_NAME,
+};
+use crate::array::DataChunk;
+use crate::catalog::{RootCatalog, TableRefId};
 use crate::storage::index::InMemoryIndexes;
 use crate::storage::secondary::manifest::*;
 use crate::storage::secondary::transaction_manager::TransactionManager;
 use crate::storage::secondary::version_manager::{EpochOp, VersionManager};
-use crate::storage::secondary::{DeleteVector, IOBackend, MANIFEST_FILE_NAME};
+use crate::storage::secondary::{BlockCache, DeleteVector, IOBackend, MANIFEST_FILE_NAME};
 
 impl SecondaryStorage {
     pub(super) async fn bootstrap(options: StorageOptions) -> StorageResult<Self> {
@@ -45,14 +50,26 @@ impl SecondaryStorage {
             Manifest::open(options.path.join(MANIFEST_FILE_NAME), enable_fsync).await?
         };
 
-        let manifest_ops = manifest.replay().await?;
+        let (header, manifest_ops) = manifest.replay().await?;
+        if header.version < MANIFEST_VERSION {
+            info!(
+                "upgrading manifest from version {} to {}",
+                header.version, MANIFEST_VERSION
+            );
+        }
+
+        let wal = if options.disable_all_disk_operation {
+            Wal::new_mock()
+        } else {
+            Wal::new(options.path.join(WAL_DIRECTORY_NAME), enable_fsync)
+        };
 
         let options = Arc::new(options);
 
         let engine = Self {
             catalog: Arc::new(catalog),
             tables: RwLock::new(tables),
-            block_cache: Cache::new(options.cache_size as u64),
+            block_cache: BlockCache::new(options.cache_size as u64),
             options: options.clone(),
             next_id: Arc::new((AtomicU32::new(0), AtomicU64::new(0))),
             version: Arc::new(VersionManager::new(manifest, options.clone())),
@@ -60,26 +77,41 @@ impl SecondaryStorage {
             vacuum_handler: Mutex::new((None, None)),
             txn_mgr: Arc::new(TransactionManager::default()),
             indexes: Mutex::new(InMemoryIndexes::new()),
+            compactions: Default::default(),
+            wal: Arc::new(wal),
+            users_lock: Mutex::new(()),
         };
 
         info!("applying {} manifest entries", manifest_ops.len());
 
         let mut rowsets_to_open = HashMap::new();
         let mut dvs_to_open = HashMap::new();
+        // memtables with these ids have been flushed
+        let mut flushed_rowsets = HashSet::new();
 
-        let mut table_changeset = vec![];
+        // tables are recorded with their ids, so dropped tables are not needed in the checkpoint
+        let mut tables_to_create = BTreeMap::new();
+        let mut users_to_put = BTreeSet::new();
+        let mut next_table_ids: BTreeMap<_, _> = header.next_table_ids.into_iter().collect();
         for op in manifest_ops {
             match op {
-                ManifestOperation::CreateTable(entry) => {
-                    engine.apply_create_table(&entry)?;
-                    table_changeset.push(EpochOp::CreateTable(entry));
+                ManifestOperation::CreateTable(mut entry) => {
+                    // ids of tables in manifests of version 0 are assigned by the catalog
+                    let id = engine.apply_create_table(&entry)?;
+                    entry.table_id = Some(id.table_id);
+                    let next = next_table_ids.entry(id.schema_id).or_default();
+                    *next = (*next).max(id.table_id + 1);
+                    tables_to_create.insert(id, entry);
                 }
                 ManifestOperation::DropTable(entry) => {
                     engine.apply_drop_table(&entry)?;
-                    // TODO: actually drop table entries are not needed for a compacted manifest.
-                    // However, these are needed to restore correct `table_id`. Persist `table_id`
-                    // to manifest may solve it, and there may be other solutions.
-                    table_changeset.push(EpochOp::DropTable(entry));
+                    tables_to_create.remove(&entry.table_id);
+                }
+                ManifestOperation::DropPartition(entry) => {
+                    engine.apply_drop_partition(&entry);
+                    if let Some(table) = tables_to_create.get_mut(&entry.table_id) {
+                        table.drop_partitions(&entry.partition_ids);
+                    }
                 }
                 ManifestOperation::AddRowSet(entry) => {
                     engine
@@ -87,6 +119,7 @@ impl SecondaryStorage {
                         .0
                         .fetch_max(entry.rowset_id + 1, std::sync::atomic::Ordering::SeqCst);
 
+                    flushed_rowsets.insert(entry.rowset_id);
                     rowsets_to_open.insert((entry.table_id.table_id, entry.rowset_id), entry);
                 }
                 ManifestOperation::DeleteRowSet(entry) => {
@@ -106,7 +139,17 @@ impl SecondaryStorage {
                 ManifestOperation::DeleteDV(entry) => {
                     dvs_to_open.remove(&(entry.table_id.table_id, entry.rowset_id, entry.dv_id));
                 }
-                ManifestOperation::Begin | ManifestOperation::End => {}
+                ManifestOperation::PutUser(entry) => {
+                    engine.apply_put_user(&entry);
+                    users_to_put.insert(entry.name);
+                }
+                ManifestOperation::DropUser(entry) => {
+                    engine.apply_drop_user(&entry);
+                    users_to_put.remove(&entry.name);
+                }
+                ManifestOperation::Header(_)
+                | ManifestOperation::Begin
+                | ManifestOperation::End => {}
             }
         }
 
@@ -117,26 +160,49 @@ impl SecondaryStorage {
             dvs_to_open.len()
         );
 
-        let mut changeset = vec![];
+        for (schema_id, next_id) in next_table_ids {
+            engine.catalog.reserve_ids(schema_id, next_id);
+            engine.version.reserve_table_ids(schema_id, next_id);
+        }
+
+        // privileges on the dropped tables are revoked by replaying the drops
+        let mut changeset = (users_to_put.iter())
+            .filter_map(|name| engine.catalog.get_user_by_name(name))
+            .map(|user| EpochOp::PutUser(PutUserEntry::new(&user)))
+            .chain(tables_to_create.into_values().map(EpochOp::CreateTable))
+            .collect_vec();
 
+        // vacuum unused RowSets and DVs, e.g. the ones written before a crash
+        let is_orphan = |path: &Path| -> bool {
+            let Ok(path) = path.strip_prefix(&options.path) else {
+                return false;
+            };
+            let mut components = path.iter().map(|c| c.to_str().unwrap_or_default());
+            match (components.next(), components.next()) {
+                (Some("dv"), Some(name)) => {
+                    parse_dv_file_name(name).is_some_and(|key| !dvs_to_open.contains_key(&key))
+                }
+                (Some(name), _) => parse_rowset_directory_name(name)
+                    .is_some_and(|key| !rowsets_to_open.contains_key(&key)),
+                _ => false,
+            }
+        };
+        if let IOBackend::InMemory(map) = &options.io_backend {
+            map.lock().retain(|path, _| !is_orphan(path));
+        }
         if !options.disable_all_disk_operation {
-            // vacuum unused RowSets
             let mut dir = fs::read_dir(&options.path).await?;
             while let Some(entry) = dir.next_entry().await? {
-                if entry.path().is_dir() {
-                    if let Some((table_id, rowset_id)) =
-                        entry.file_name().to_str().unwrap().split_once('_')
-                    {
-                        if let (Ok(table_id), Ok(rowset_id)) =
-                            (table_id.parse::<u32>(), rowset_id.parse::<u32>())
-                        {
-                            if !rowsets_to_open.contains_key(&(table_id, rowset_id)) {
-                                fs::remove_dir_all(entry.path())
-                                    .await
-                                    .expect("failed to vacuum unused rowsets");
-                            }
-                        }
-                    }
+                if entry.path().is_dir() && is_orphan(&entry.path()) {
+                    info!("vacuum unused rowset {:?}", entry.file_name());
+                    fs::remove_dir_all(entry.path()).await?;
+                }
+            }
+            let mut dir = fs::read_dir(options.path.join("dv")).await?;
+            while let Some(entry) = dir.next_entry().await? {
+                if is_orphan(&entry.path()) {
+                    info!("vacuum unused DV {:?}", entry.file_name());
+                    fs::remove_file(entry.path()).await?;
                 }
             }
         }
@@ -164,22 +230,307 @@ impl SecondaryStorage {
                 entry.dv_id,
                 entry.rowset_id,
                 table.get_dv_path(entry.rowset_id, entry.dv_id),
+                &options.io_backend,
             )
             .await?;
             changeset.push(EpochOp::AddDV((entry, dv)));
         }
 
-        if options.disable_all_disk_operation {
-            engine.version.commit_changes(changeset).await?;
-        } else {
-            // Add table changeset, so that they can be reflected in compacted manifest.
-            changeset.extend(table_changeset);
+        // replay the WAL into memtables, and write the rows not flushed yet to a new checkpoint
+        let records = engine
+            .wal
+            .replay(|table_id| {
+                let table = tables.get(&table_id)?;
+                Some(table.columns.iter().map(|c| c.data_type()).collect())
+            })
+            .await?;
+        let mut memtables: HashMap<TableRefId, Vec<DataChunk>> = HashMap::new();
+        for record in records {
             engine
-                .version
-                .rewrite_changes(changeset, &options.path)
-                .await?;
+                .next_id
+                .0
+                .fetch_max(record.rowset_id + 1, std::sync::atomic::Ordering::SeqCst);
+            // memtables below the watermark have been flushed, even if their RowSets are gone
+            if record.rowset_id >= header.memtable_watermark
+                && !flushed_rowsets.contains(&record.rowset_id)
+            {
+                memtables
+                    .entry(record.table_id)
+                    .or_default()
+                    .push(record.chunk);
+            }
         }
+        // ids of new memtables never fall below the watermark
+        let next_rowset_id = (engine.next_id.0)
+            .fetch_max(
+                header.memtable_watermark,
+                std::sync::atomic::Ordering::SeqCst,
+            )
+            .max(header.memtable_watermark);
+        engine.version.reserve_memtable_ids(next_rowset_id);
+        let mut records = vec![];
+        for (table_id, chunks) in memtables {
+            let table = tables.get(&table_id).unwrap();
+            let rowset_id = (engine.version).register_memtable(|| table.generate_rowset_id());
+            records.extend(chunks.iter().map(|chunk| WalRecord {
+                table_id,
+                rowset_id,
+                chunk: chunk.clone(),
+            }));
+            table.restore_memtable(rowset_id, chunks).await;
+        }
+        info!("{} WAL records replayed", records.len());
+        engine.wal.checkpoint(&records).await?;
+
+        // write a checkpoint of the latest version, which also upgrades the manifest
+        engine.version.rewrite_changes(changeset).await?;
 
         Ok(engine)
     }
 }
+
+/// Parses `<TableId>_<RowSetId>`.
+fn parse_rowset_directory_name(name: &str) -> Option<(u32, u32)> {
+    let (table_id, rowset_id) = name.split_once('_')?;
+    Some((table_id.parse().ok()?, rowset_id.parse().ok()?))
+}
+
+/// Parses `<TableId>_<RowSetId>_<DVId>.dv`.
+fn parse_dv_file_name(name: &str) -> Option<(u32, u32, u64)> {
+    let mut ids = name.strip_suffix(".dv")?.split('_');
+    let key = (
+        ids.next()?.parse().ok()?,
+        ids.next()?.parse().ok()?,
+        ids.next()?.parse().ok()?,
+    );
+    ids.next().is_none().then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::array::Chunk;
+    use crate::storage::secondary::tests::{disk_options, open, query, simulate_power_loss};
+    use crate::storage::secondary::StorageOptions;
+    use crate::{Database, Session};
+
+    /// Options keeping RowSets in memory and the manifest on disk, so that crashes can be
+    /// simulated by modifying the files between restarts.
+    fn options(path: &Path) -> StorageOptions {
+        StorageOptions {
+            io_backend: IOBackend::in_memory(),
+            // write RowSets directly
+            memtable_size: 0,
+            ..disk_options(path)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_time_travel() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let retained = StorageOptions {
+            time_travel_retention: Duration::from_secs(60),
+            ..options(tempdir.path())
+        };
+        let (db, session) = open(&retained).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+
+        let chunks: Vec<Chunk> = (session.run("select * from pg_catalog.rw_epochs"))
+            .await
+            .unwrap();
+        let chunk = chunks[0].get_first_data_chunk();
+        let last = chunk.cardinality() - 1;
+        let epoch = chunk.array_at(0).get_to_string(last);
+        assert_eq!(chunk.array_at(2).get_to_string(last), "0");
+
+        // timestamps in queries have a resolution of seconds
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let time = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S");
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        session.run("delete from t where a = 1").await.unwrap();
+        session.run("insert into t values (3)").await.unwrap();
+        assert_eq!(
+            query(&session, "select a from t order by a").await,
+            ["2", "3"]
+        );
+        assert_eq!(
+            query(
+                &session,
+                &format!("select a from t as of epoch {epoch} order by a")
+            )
+            .await,
+            ["1", "2"]
+        );
+        assert_eq!(
+            query(
+                &session,
+                &format!("select x.a from t as of timestamp '{time}' as x order by x.a")
+            )
+            .await,
+            ["1", "2"]
+        );
+        // the epoch before the table has rows
+        let epoch = epoch.parse::<u64>().unwrap() - 1;
+        assert_eq!(
+            query(&session, &format!("select a from t as of epoch {epoch}")).await,
+            Vec::<String>::new()
+        );
+
+        // unknown epochs and timestamps before the retained history are rejected
+        assert!((session.run("select a from t as of epoch 1000000"))
+            .await
+            .is_err());
+        assert!(
+            (session.run("select a from t as of timestamp '2000-01-01 00:00:00'"))
+                .await
+                .is_err()
+        );
+        db.shutdown().await.unwrap();
+
+        // time travel is rejected if epochs are not retained, as rows in memtables are not in
+        // any epoch
+        let tempdir = tempfile::tempdir().unwrap();
+        let (db, session) = open(&options(tempdir.path())).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1)").await.unwrap();
+        let epochs = query(&session, "select * from pg_catalog.rw_epochs").await;
+        let epoch = epochs.last().unwrap();
+        let err = (session.run(&format!("select a from t as of epoch {epoch}")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("time travel"), "{err}");
+        db.shutdown().await.unwrap();
+
+        // time travel is not supported by the in-memory engine
+        let session = Session::new(Arc::new(Database::new_in_memory()));
+        session.run("create table t (a int)").await.unwrap();
+        assert!((session.run("select a from t as of epoch 1"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recover_torn_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = options(tempdir.path());
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        // crash in the middle of appending a group
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(options.path.join(MANIFEST_FILE_NAME))
+            .unwrap();
+        write!(file, r#""Begin"{{"AddRowSet":{{"table_id":"#).unwrap();
+        drop(file);
+
+        for i in 3..5 {
+            let (db, session) = open(&options).await;
+            (session.run(&format!("insert into t values ({i})")))
+                .await
+                .unwrap();
+            let expected = (1..=i).map(|i| i.to_string()).collect_vec();
+            assert_eq!(
+                query(&session, "select a from t order by a").await,
+                expected
+            );
+            db.shutdown().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join(MANIFEST_FILE_NAME);
+        // an undecodable entry followed by a committed group is not a torn write
+        std::fs::write(
+            &path,
+            r#""Begin""End""Begin"{"Unknown":0}"End""Begin""End""#,
+        )
+        .unwrap();
+        let mut manifest = Manifest::open(&path, false).await.unwrap();
+        assert!(manifest.replay().await.is_err());
+
+        // a torn tail is truncated
+        std::fs::write(&path, r#""Begin""End""Begin"{"DropTable":{"table_id":"#).unwrap();
+        let mut manifest = Manifest::open(&path, false).await.unwrap();
+        assert!(manifest.replay().await.unwrap().1.is_empty());
+        assert_eq!(std::fs::read(&path).unwrap(), br#""Begin""End""#);
+    }
+
+    #[tokio::test]
+    async fn test_rowset_directories_survive_power_loss() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = StorageOptions {
+            io_backend: IOBackend::NormalRead,
+            ..options(tempdir.path())
+        };
+
+        // RowSets written by transactions
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+        db.shutdown().await.unwrap();
+        simulate_power_loss(&options.path);
+
+        // RowSets flushed from memtables, without compacting them
+        let options = StorageOptions {
+            memtable_size: 1 << 20,
+            compaction_policies: vec![],
+            ..options
+        };
+        let (db, session) = open(&options).await;
+        assert_eq!(
+            query(&session, "select a from t order by a").await,
+            ["1", "2"]
+        );
+        session.run("insert into t values (3)").await.unwrap();
+        session.run("compact t").await.unwrap();
+        db.shutdown().await.unwrap();
+        simulate_power_loss(&options.path);
+
+        let (db, session) = open(&options).await;
+        assert_eq!(
+            query(&session, "select a from t order by a").await,
+            ["1", "2", "3"]
+        );
+        db.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_orphan_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = options(tempdir.path());
+        let IOBackend::InMemory(files) = options.io_backend.clone() else {
+            unreachable!()
+        };
+
+        let (db, session) = open(&options).await;
+        session.run("create table t (a int)").await.unwrap();
+        session.run("insert into t values (1), (2)").await.unwrap();
+        session.run("delete from t where a = 2").await.unwrap();
+        db.shutdown().await.unwrap();
+
+        // crash after writing a RowSet and a DV, but before appending them to the manifest
+        let orphans = ["0_100/0.col", "0_100/0.idx", "dv/0_0_100.dv"].map(|f| options.path.join(f));
+        for path in &orphans {
+            (files.lock()).insert(path.clone(), Bytes::from_static(b"garbage"));
+        }
+
+        let (db, session) = open(&options).await;
+        assert_eq!(query(&session, "select a from t").await, ["1"]);
+        db.shutdown().await.unwrap();
+        assert!(orphans.iter().all(|path| !files.lock().contains_key(path)));
+    }
+}
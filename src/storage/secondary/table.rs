@@ -4,8 +4,8 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicU32, AtomicU64};
 use std::sync::Arc;
 
-use moka::future::Cache;
-use tokio::sync::OwnedMutexGuard;
+use bytes::Bytes;
+use risinglight_proto::rowset::DeleteRecord;
 
 use super::*;
 use crate::catalog::TableRefId;
@@ -42,7 +42,13 @@ pub struct SecondaryTable {
 
     /// Block cache of the storage engine. Note that this should be removed after we have
     /// refactored the storage API to have snapshot interface.
-    pub block_cache: Cache<BlockCacheKey, Block>,
+    pub block_cache: BlockCache,
+
+    /// Memtable of small writes not flushed into RowSets yet.
+    pub memtable: Arc<SecondaryMemTable>,
+
+    /// Write-ahead log of the memtables, shared by all tables.
+    pub wal: Arc<Wal>,
 
     /// Next RowSet Id and DV Id of the current storage engine
     next_id: Arc<(AtomicU32, AtomicU64)>,
@@ -56,8 +62,9 @@ impl SecondaryTable {
         columns: &[ColumnCatalog],
         next_id: Arc<(AtomicU32, AtomicU64)>,
         version: Arc<VersionManager>,
-        block_cache: Cache<BlockCacheKey, Block>,
+        block_cache: BlockCache,
         txn_mgr: Arc<TransactionManager>,
+        wal: Arc<Wal>,
         ordered_pk_ids: Vec<ColumnId>,
     ) -> Self {
         Self {
@@ -72,6 +79,8 @@ impl SecondaryTable {
             next_id,
             version,
             block_cache,
+            memtable: Default::default(),
+            wal,
             txn_mgr,
             ordered_pk_ids,
         }
@@ -105,8 +114,38 @@ impl SecondaryTable {
         self.table_ref_id.table_id
     }
 
-    pub async fn lock_for_deletion(&self) -> OwnedMutexGuard<()> {
-        self.txn_mgr.lock_for_deletion(self.table_id()).await
+    /// Register a transaction deleting rows from the table.
+    pub(super) fn begin_deletion(&self) -> DeletionGuard {
+        (self.txn_mgr).begin_deletion(self.table_id(), || self.version.latest_epoch())
+    }
+
+    /// Write a DV of the deleted rows in a RowSet.
+    pub(super) async fn write_dv(
+        &self,
+        rowset_id: u32,
+        deletes: Vec<DeleteRecord>,
+    ) -> StorageResult<DeleteVector> {
+        let dv_id = self.generate_dv_id();
+        let path = self.get_dv_path(rowset_id, dv_id);
+        match &self.storage_options.io_backend {
+            IOBackend::InMemory(map) => {
+                let mut buf = vec![];
+                DeleteVector::write_all(&mut buf, &deletes).await?;
+                let mut guard = map.lock();
+                guard.insert(path, Bytes::from(buf));
+            }
+            _ => {
+                let mut file = tokio::fs::OpenOptions::default()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .await?;
+                DeleteVector::write_all(&mut file, &deletes).await?;
+                file.sync_data().await?;
+                sync_dir(path.parent().unwrap()).await?;
+            }
+        }
+        Ok(DeleteVector::new(dv_id, rowset_id, deletes))
     }
 }
 
@@ -1,7 +1,24 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use super::SecondaryIteratorImpl;
-use crate::storage::{StorageChunk, StorageResult};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{
+    IOBackend, SecondaryIteratorImpl, SecondaryRowHandler, SecondaryStorage, SecondaryTable,
+    StorageOptions,
+};
+use crate::array::{ArrayImpl, Chunk, DataChunk, I32Array};
+use crate::catalog::{ColumnCatalog, ColumnDesc, RootCatalog};
+use crate::storage::{
+    RowHandler, ScanOptions, Storage, StorageChunk, StorageColumnRef, StorageResult, Table,
+    Transaction, TxnIterator,
+};
+use crate::types::{DataType, DataValue};
+use crate::{Database, Session};
 
 pub struct TestIterator {
     chunks: Vec<StorageChunk>,
@@ -29,3 +46,103 @@ impl TestIterator {
 }
 
 impl SecondaryIteratorImpl for TestIterator {}
+
+/// Entries of the directories at the time they were last synced.
+static SYNCED_DIRS: Mutex<Option<HashMap<PathBuf, HashSet<OsString>>>> = Mutex::new(None);
+
+pub fn record_synced_dir(path: &Path) {
+    let entries = (std::fs::read_dir(path).unwrap())
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    let mut dirs = SYNCED_DIRS.lock();
+    dirs.get_or_insert_with(HashMap::new)
+        .insert(path.to_path_buf(), entries);
+}
+
+/// Simulate a power loss by removing the subdirectories of `path` whose entries were created
+/// after the last sync of `path`.
+pub fn simulate_power_loss(path: &Path) {
+    let synced = (SYNCED_DIRS.lock().as_ref())
+        .and_then(|dirs| dirs.get(path).cloned())
+        .unwrap_or_default();
+    for entry in std::fs::read_dir(path).unwrap() {
+        let entry = entry.unwrap();
+        if entry.path().is_dir() && !synced.contains(&entry.file_name()) {
+            std::fs::remove_dir_all(entry.path()).unwrap();
+        }
+    }
+}
+
+/// Options of a database with files in `path`, so that it can be restarted.
+pub fn disk_options(path: &Path) -> StorageOptions {
+    StorageOptions {
+        path: path.join("db"),
+        io_backend: IOBackend::NormalRead,
+        disable_all_disk_operation: false,
+        background_compaction: false,
+        ..StorageOptions::default_for_test()
+    }
+}
+
+/// Opens the database and a session of it. The database should be shut down before it is
+/// opened again.
+pub async fn open(options: &StorageOptions) -> (Arc<Database>, Session) {
+    let db = Arc::new(Database::new_on_disk(options.clone()).await);
+    (db.clone(), Session::new(db))
+}
+
+/// Returns the first column of the query output.
+pub async fn query(session: &Session, sql: &str) -> Vec<String> {
+    let chunks: Vec<Chunk> = session.run(sql).await.unwrap();
+    (chunks[0].data_chunks().iter())
+        .flat_map(|chunk| (0..chunk.cardinality()).map(|i| chunk.array_at(0).get_to_string(i)))
+        .collect()
+}
+
+/// Create a table `t (a int)` in a storage opened with `options`.
+pub async fn create_table(options: StorageOptions) -> (Arc<SecondaryStorage>, SecondaryTable) {
+    let storage = Arc::new(SecondaryStorage::open(options).await.unwrap());
+    let schema = RootCatalog::DEFAULT_SCHEMA_NAME;
+    let schema_id = storage.catalog().get_schema_id_by_name(schema).unwrap();
+    let column = ColumnCatalog::new(0, ColumnDesc::new("a", DataType::Int32, false));
+    (storage.create_table(schema_id, "t", &[column], &[]))
+        .await
+        .unwrap();
+    let table_id = storage.catalog().get_table_id_by_name(schema, "t").unwrap();
+    let table = storage.get_table(table_id).unwrap();
+    (storage, table)
+}
+
+/// Insert the values into the table `t (a int)` in a transaction.
+pub async fn insert(table: &SecondaryTable, values: &[i32]) {
+    let mut txn = table.write().await.unwrap();
+    let chunk: DataChunk = [ArrayImpl::new_int32(
+        values.iter().copied().collect::<I32Array>(),
+    )]
+    .into_iter()
+    .collect();
+    txn.append(chunk).await.unwrap();
+    txn.commit().await.unwrap();
+}
+
+/// Returns the rows of the table `t (a int)` with their row handlers, sorted by value.
+pub async fn scan(table: &SecondaryTable) -> Vec<(i32, SecondaryRowHandler)> {
+    let txn = table.read().await.unwrap();
+    let columns = [StorageColumnRef::Idx(0), StorageColumnRef::RowHandler];
+    let mut iter = txn.scan(&columns, ScanOptions::default()).await.unwrap();
+    let mut rows = vec![];
+    while let Some(chunk) = iter.next_batch(None).await.unwrap() {
+        for i in 0..chunk.cardinality() {
+            let DataValue::Int32(value) = chunk.array_at(0).get(i) else {
+                panic!("unexpected value");
+            };
+            rows.push((
+                value,
+                SecondaryRowHandler::from_column(chunk.array_at(1), i),
+            ));
+        }
+    }
+    txn.abort().await.unwrap();
+    rows.sort();
+    rows
+}
@@ -1,25 +1,28 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::ops::Bound;
 use std::sync::Arc;
 
 use itertools::Itertools;
 use risinglight_proto::rowset::block_statistics::BlockStatisticsType;
 use risinglight_proto::rowset::DeleteRecord;
-use tokio::sync::OwnedMutexGuard;
 use tracing::{info, warn};
 
 use super::version_manager::{Snapshot, Version, VersionManager};
 use super::{
-    AddDVEntry, AddRowSetEntry, ColumnBuilderOptions, ConcatIterator, DeleteVector, DiskRowset,
-    EpochOp, MergeIterator, RowSetIterator, SecondaryMemRowsetImpl, SecondaryRowHandler,
-    SecondaryTable, SecondaryTableTxnIterator,
+    AddDVEntry, AddRowSetEntry, ColumnBuilderOptions, ConcatIterator, DeletionGuard, DiskRowset,
+    EpochOp, MemTableSnapshot, MergeIterator, RowsetWriter, SecondaryIterator,
+    SecondaryMemRowsetImpl, SecondaryRowHandler, SecondaryTable, SecondaryTableTxnIterator,
 };
 use crate::array::DataChunk;
 use crate::catalog::find_sort_key_id;
-use crate::storage::secondary::statistics::create_statistics_global_aggregator;
-use crate::storage::{ScanOptions, StorageColumnRef, StorageResult, Transaction};
+use crate::storage::secondary::statistics::{
+    create_statistics_global_aggregator, has_zone_map, StatisticsGlobalAgg, ZoneMapGlobalAgg,
+};
+use crate::storage::{
+    ScanOptions, StatisticsAgg, StorageColumnRef, StorageResult, TracedStorageError, Transaction,
+};
 use crate::types::DataValue;
 
 /// A transaction running on `SecondaryStorage`.
@@ -29,8 +32,8 @@ pub struct SecondaryTransaction {
     /// the transaction will panic.
     finished: bool,
 
-    /// Includes all to-be-committed data.
-    mem: Option<SecondaryMemRowsetImpl>,
+    /// Includes all to-be-committed data not written into RowSets yet.
+    pending: Vec<DataChunk>,
 
     /// Includes all to-be-deleted rows
     delete_buffer: Vec<SecondaryRowHandler>,
@@ -44,10 +47,14 @@ pub struct SecondaryTransaction {
     /// Snapshot content
     snapshot: Arc<Snapshot>,
 
-    /// The rowsets produced in the txn.
-    to_be_committed_rowsets: Vec<DiskRowset>,
+    /// Rows in the memtable consistent with the snapshot
+    memtable: MemTableSnapshot,
 
-    delete_lock: Option<OwnedMutexGuard<()>>,
+    /// The rowsets produced in the txn, with their partitions.
+    to_be_committed_rowsets: Vec<(DiskRowset, Option<u32>)>,
+
+    /// Registers the txn as deleting rows, which stops appending rows to the memtable.
+    deletion: Option<DeletionGuard>,
 
     read_only: bool,
 
@@ -61,27 +68,32 @@ pub struct SecondaryTransaction {
 }
 
 impl SecondaryTransaction {
-    /// Start a transaction on Secondary. If `update` is set to true, we will hold the delete lock
-    /// of a table.
+    /// Start a transaction on Secondary. If `update` is set to true, we will register the txn as
+    /// a deletion of the table, and flush its memtable so that all rows to be deleted are in
+    /// RowSets.
     pub(super) async fn start(
         table: &SecondaryTable,
         read_only: bool,
         update: bool,
     ) -> StorageResult<Self> {
+        let deletion = if update {
+            let deletion = table.begin_deletion();
+            table.flush_memtable().await?;
+            Some(deletion)
+        } else {
+            None
+        };
         // pin a snapshot at version manager
-        let pin_version = table.version.pin();
+        let (pin_version, memtable) = table.pin_with_memtable().await;
         Ok(Self {
             finished: false,
-            mem: None,
+            pending: vec![],
             delete_buffer: vec![],
             table: table.clone(),
             version: table.version.clone(),
             snapshot: pin_version.snapshot.clone(),
-            delete_lock: if update {
-                Some(table.lock_for_deletion().await)
-            } else {
-                None
-            },
+            memtable,
+            deletion,
             to_be_committed_rowsets: vec![],
             read_only,
             total_size: 0,
@@ -90,74 +102,126 @@ impl SecondaryTransaction {
     }
 
     async fn flush_rowset(&mut self) -> StorageResult<()> {
-        // only flush when we have memtables
-        let mem = if let Some(mem) = self.mem.take() {
-            mem
-        } else {
+        // only flush when we have data
+        if self.pending.is_empty() {
             return Ok(());
+        }
+        let chunks = std::mem::take(&mut self.pending);
+        for (partition, chunks) in self.split_by_partition(chunks)? {
+            let rowset = self.write_rowset(chunks).await?;
+            self.to_be_committed_rowsets.push((rowset, partition));
+        }
+        Ok(())
+    }
+
+    /// Split the rows by their partitions, or returns them as a whole if the table is not
+    /// partitioned.
+    #[allow(clippy::type_complexity)]
+    fn split_by_partition(
+        &self,
+        chunks: Vec<DataChunk>,
+    ) -> StorageResult<Vec<(Option<u32>, Vec<DataChunk>)>> {
+        let Some(spec) = self.version.get_partition_spec(self.table.table_id()) else {
+            return Ok(vec![(None, chunks)]);
         };
+        let column = self.table.column_map[&spec.column];
+        let mut partitions: BTreeMap<u32, Vec<DataChunk>> = BTreeMap::new();
+        for chunk in chunks {
+            let chunk = chunk.compact();
+            let array = chunk.array_at(column);
+            let mut visibilities: BTreeMap<u32, Vec<bool>> = BTreeMap::new();
+            for i in 0..chunk.cardinality() {
+                let value = array.get(i);
+                let partition = spec
+                    .partition_of(&value)
+                    .ok_or_else(|| TracedStorageError::not_found("partition of value", value))?;
+                visibilities
+                    .entry(partition)
+                    .or_insert_with(|| vec![false; chunk.cardinality()])[i] = true;
+            }
+            for (partition, visibility) in visibilities {
+                (partitions.entry(partition).or_default()).push(chunk.filter(&visibility));
+            }
+        }
+        Ok(partitions
+            .into_iter()
+            .map(|(partition, chunks)| (Some(partition), chunks))
+            .collect())
+    }
+
+    async fn write_rowset(&self, chunks: Vec<DataChunk>) -> StorageResult<DiskRowset> {
+        let mut mem = SecondaryMemRowsetImpl::new(
+            self.table.columns.clone(),
+            ColumnBuilderOptions::from_storage_options(&self.table.storage_options),
+            self.table.generate_rowset_id(),
+        );
+        for chunk in chunks {
+            mem.append(chunk).await?;
+        }
         let rowset_id = mem.get_rowset_id();
         let directory = self.table.get_rowset_path(rowset_id);
 
+        if !self.table.storage_options.disable_all_disk_operation {
+            (RowsetWriter::new(&directory, self.table.storage_options.io_backend.clone()))
+                .create_dir()
+                .await?;
+        }
+
         // flush data to disk
         mem.flush(self.table.storage_options.io_backend.clone(), &directory)
             .await?;
 
-        let on_disk = DiskRowset::open(
+        DiskRowset::open(
             directory,
             self.table.columns.clone(),
             self.table.block_cache.clone(),
             rowset_id,
             self.table.storage_options.io_backend.clone(),
         )
-        .await?;
-
-        self.to_be_committed_rowsets.push(on_disk);
+        .await
+    }
 
-        Ok(())
+    /// Returns true if the txn only appends a few rows, which are written to the memtable
+    /// instead of a new RowSet. Rows of partitioned tables are always written to RowSets.
+    fn is_small_write(&self) -> bool {
+        let options = &self.table.storage_options;
+        options.time_travel_retention.is_zero()
+            && !self.pending.is_empty()
+            && self.to_be_committed_rowsets.is_empty()
+            && self.delete_buffer.is_empty()
+            && self.deletion.is_none()
+            && self.total_size < options.memtable_size
+            && (self.version.get_partition_spec(self.table.table_id())).is_none()
     }
 
     async fn commit_inner(mut self) -> StorageResult<()> {
+        if self.is_small_write() && self.table.append_memtable(&self.pending).await? {
+            self.pending.clear();
+            self.finished = true;
+            return Ok(());
+        }
+
         self.flush_rowset().await?;
 
-        // flush deletes to disk
-        let mut delete_split_map = HashMap::new();
-        for delete in self.delete_buffer.drain(..) {
-            delete_split_map
-                .entry(delete.rowset_id())
-                .or_insert_with(Vec::new)
-                .push(DeleteRecord {
-                    row_id: delete.row_id(),
-                });
-        }
+        // Deletions of a table are committed one at a time, so that the deleted rows are checked
+        // against the latest version.
+        let _commit_lock = match self.delete_buffer.is_empty() {
+            true => None,
+            false => Some(
+                self.table
+                    .txn_mgr
+                    .lock_for_commit(self.table.table_id())
+                    .await,
+            ),
+        };
+        let delete_split_map = self.locate_deletes()?;
 
         let rowsets = std::mem::take(&mut self.to_be_committed_rowsets);
 
+        // flush deletes to disk
         let mut dvs = vec![];
         for (rowset_id, deletes) in delete_split_map {
-            let dv_id = self.table.generate_dv_id();
-            use bytes::Bytes;
-
-            use super::IOBackend;
-            let path = self.table.get_dv_path(rowset_id, dv_id);
-            match &self.table.storage_options.io_backend {
-                IOBackend::InMemory(map) => {
-                    let mut buf = vec![];
-                    DeleteVector::write_all(&mut buf, &deletes).await?;
-                    let mut guard = map.lock();
-                    guard.insert(path, Bytes::from(buf));
-                }
-                _ => {
-                    let mut file = tokio::fs::OpenOptions::default()
-                        .write(true)
-                        .create_new(true)
-                        .open(path)
-                        .await?;
-                    DeleteVector::write_all(&mut file, &deletes).await?;
-                    file.sync_data().await?;
-                }
-            }
-            dvs.push(DeleteVector::new(dv_id, rowset_id, deletes));
+            dvs.push(self.table.write_dv(rowset_id, deletes).await?);
         }
 
         let mut changeset = vec![];
@@ -176,7 +240,7 @@ impl SecondaryTransaction {
                     "RowSet {} flushed, DV {} flushed",
                     rowsets
                         .iter()
-                        .map(|x| format!("#{}", x.rowset_id()))
+                        .map(|(x, _)| format!("#{}", x.rowset_id()))
                         .join(","),
                     dvs.iter()
                         .map(|x| format!("#{}(RS{})", x.dv_id(), x.rowset_id()))
@@ -186,11 +250,12 @@ impl SecondaryTransaction {
         }
 
         // Add RowSets
-        changeset.extend(rowsets.into_iter().map(|x| {
+        changeset.extend(rowsets.into_iter().map(|(x, partition)| {
             EpochOp::AddRowSet((
                 AddRowSetEntry {
                     rowset_id: x.rowset_id(),
                     table_id: self.table.table_ref_id,
+                    partition,
                 },
                 x,
             ))
@@ -216,6 +281,45 @@ impl SecondaryTransaction {
         Ok(())
     }
 
+    /// Locate the deleted rows in the latest version, remapping rows in RowSets compacted after
+    /// they were read. Returns a serialization error if any row has been deleted by a transaction
+    /// committed after they were read.
+    ///
+    /// The caller must hold the commit lock of the table.
+    fn locate_deletes(&mut self) -> StorageResult<BTreeMap<u32, Vec<DeleteRecord>>> {
+        let mut delete_split_map = BTreeMap::new();
+        if self.delete_buffer.is_empty() {
+            return Ok(delete_split_map);
+        }
+        let table_id = self.table.table_id();
+        let latest = self.version.pin();
+        let rowsets = latest
+            .snapshot
+            .get_rowsets_of(table_id)
+            .cloned()
+            .unwrap_or_default();
+        for delete in self.delete_buffer.drain(..).sorted().dedup() {
+            let Some(row) = self.table.txn_mgr.remap(table_id, &rowsets, delete) else {
+                return Err(TracedStorageError::write_conflict());
+            };
+            let dvs = latest.snapshot.get_dvs_of(table_id, row.rowset_id());
+            for dv_id in dvs.into_iter().flatten() {
+                let dv = self.version.get_dv(table_id, *dv_id);
+                if dv.has_deletes_in(row.row_id()..row.row_id() + 1) {
+                    return Err(TracedStorageError::write_conflict());
+                }
+            }
+            delete_split_map
+                .entry(row.rowset_id())
+                .or_insert_with(Vec::new)
+                .push(DeleteRecord {
+                    row_id: row.row_id(),
+                    row_count: 0,
+                });
+        }
+        Ok(delete_split_map)
+    }
+
     async fn scan_inner(
         &self,
         col_idx: &[StorageColumnRef],
@@ -223,15 +327,38 @@ impl SecondaryTransaction {
     ) -> StorageResult<SecondaryTableTxnIterator> {
         assert!(!opts.reversed, "reverse iterator is not supported for now");
 
-        let mut iters: Vec<RowSetIterator> = vec![];
+        let mut iters: Vec<SecondaryIterator> = vec![];
 
-        if let Some(rowsets) = self.snapshot.get_rowsets_of(self.table.table_id()) {
+        // time travel reads the RowSets of a historical version, which contains all rows as
+        // memtables are bypassed when epochs are retained
+        if opts.as_of.is_some() && self.table.storage_options.time_travel_retention.is_zero() {
+            return Err(TracedStorageError::not_supported(
+                "time travel without time travel retention",
+            ));
+        }
+        let historical = match opts.as_of {
+            Some(as_of) => Some(self.version.pin_as_of(as_of)?),
+            None => None,
+        };
+        let snapshot = match &historical {
+            Some(version) => &version.snapshot,
+            None => &self.snapshot,
+        };
+
+        if let Some(rowsets) = snapshot.get_rowsets_of(self.table.table_id()) {
             for rowset_id in rowsets {
+                // skip RowSets of the pruned partitions without opening them
+                if let Some(partitions) = &opts.partitions {
+                    let partition =
+                        (self.version).get_rowset_partition(self.table.table_id(), *rowset_id);
+                    if !partition.is_some_and(|p| partitions.contains(&p)) {
+                        continue;
+                    }
+                }
                 let rowset = self.version.get_rowset(self.table.table_id(), *rowset_id);
 
                 // Get DV id and read DVs
-                let dvs = self
-                    .snapshot
+                let dvs = snapshot
                     .get_dvs_of(self.table.table_id(), *rowset_id)
                     .map(|dvs| {
                         dvs.iter()
@@ -251,13 +378,28 @@ impl SecondaryTransaction {
                 iters.push(
                     rowset
                         .iter(col_idx.into(), dvs, start_rowid, opts.filter.clone())
-                        .await?,
+                        .await?
+                        .with_predicate(opts.predicate.clone())
+                        .into(),
                 )
             }
         }
 
+        if historical.is_none() && !self.memtable.is_empty() {
+            iters.push(
+                self.memtable
+                    .iter(
+                        &self.table.columns,
+                        col_idx,
+                        opts.filter.clone(),
+                        opts.predicate.clone(),
+                    )
+                    .into(),
+            );
+        }
+
         let final_iter = if iters.len() == 1 {
-            iters.pop().unwrap().into()
+            iters.pop().unwrap()
         } else if opts.is_sorted {
             let sort_keys = find_sort_key_id(&self.table.columns);
             if !sort_keys.is_empty() {
@@ -273,11 +415,7 @@ impl SecondaryTransaction {
                             .expect("sorting key not in column list")
                     })
                     .collect_vec();
-                MergeIterator::new(
-                    iters.into_iter().map(|iter| iter.into()).collect_vec(),
-                    real_col_idx,
-                )
-                .into()
+                MergeIterator::new(iters, real_col_idx).into()
             } else {
                 ConcatIterator::new(iters).into()
             }
@@ -285,60 +423,146 @@ impl SecondaryTransaction {
             ConcatIterator::new(iters).into()
         };
 
-        Ok(SecondaryTableTxnIterator::new(final_iter))
+        Ok(SecondaryTableTxnIterator::new(final_iter, historical))
     }
 
     /// Aggregate block statistics of one column. In the future, we might support predicate
     /// push-down, and this function will add filter-scan-aggregate functionality.
     ///
     /// This function can gather multiple statistics at a time (in the future).
+    ///
+    /// For `Min` and `Max`, the result is a bound of values regardless of deletions, or null if
+    /// some blocks don't have zone maps.
     pub fn aggreagate_block_stat(
         &self,
         ty: &[(BlockStatisticsType, StorageColumnRef)],
     ) -> Vec<DataValue> {
+        let user_col_idx = |col_idx: &StorageColumnRef| match col_idx {
+            StorageColumnRef::Idx(idx) => *idx as usize,
+            _ => panic!("unsupported column ref for block aggregation"),
+        };
         let mut agg = ty
             .iter()
-            .map(|(ty, _)| create_statistics_global_aggregator(*ty))
+            .map(|(ty, col_idx)| match ty {
+                BlockStatisticsType::Min | BlockStatisticsType::Max => {
+                    let data_type = self.table.columns[user_col_idx(col_idx)].data_type();
+                    Box::new(ZoneMapGlobalAgg::create(*ty, data_type)) as _
+                }
+                _ => create_statistics_global_aggregator(*ty),
+            })
             .collect_vec();
+        let mut complete = vec![true; ty.len()];
 
         if let Some(rowsets) = self.snapshot.get_rowsets_of(self.table.table_id()) {
             for rowset_id in rowsets {
                 let rowset = self.version.get_rowset(self.table.table_id(), *rowset_id);
-                for ((_, col_idx), agg) in ty.iter().zip(agg.iter_mut()) {
-                    let user_col_idx = match col_idx {
-                        StorageColumnRef::Idx(idx) => idx,
-                        _ => panic!("unsupported column ref for block aggregation"),
-                    };
-                    let column = rowset.column(*user_col_idx as usize);
+                for (((ty, col_idx), agg), complete) in
+                    ty.iter().zip(agg.iter_mut()).zip(complete.iter_mut())
+                {
+                    let column = rowset.column(user_col_idx(col_idx));
+                    if matches!(ty, BlockStatisticsType::Min | BlockStatisticsType::Max)
+                        && !has_zone_map(column.index())
+                    {
+                        *complete = false;
+                    }
                     agg.apply_batch(column.index());
                 }
             }
         }
 
-        agg.into_iter().map(|agg| agg.get_output()).collect_vec()
+        for chunk in self.memtable.chunks() {
+            for ((_, col_idx), agg) in ty.iter().zip(agg.iter_mut()) {
+                agg.apply_array(chunk.array_at(user_col_idx(col_idx)));
+            }
+        }
+
+        (agg.into_iter().zip(complete))
+            .map(|(agg, complete)| match complete {
+                true => agg.get_output(),
+                false => DataValue::Null,
+            })
+            .collect_vec()
     }
 
-    pub async fn append_inner(&mut self, columns: DataChunk) -> StorageResult<()> {
-        if self.read_only {
-            panic!("Txn is read-only but append is called");
+    /// Aggregate precise statistics from block indexes.
+    ///
+    /// Returns `None` if any block involved is touched by a delete vector, or lacks the required
+    /// statistics.
+    fn aggregate_statistics_inner(&self, aggs: &[StatisticsAgg]) -> Option<Vec<DataValue>> {
+        // uncommitted changes are not reflected in block indexes
+        if !self.pending.is_empty() || !self.delete_buffer.is_empty() {
+            return None;
+        }
+        let table_id = self.table.table_id();
+        let mut global_aggs: Vec<(usize, Box<dyn StatisticsGlobalAgg>)> = aggs
+            .iter()
+            .map(|agg| match *agg {
+                // row count of any column is the same
+                StatisticsAgg::RowCount => (
+                    0,
+                    create_statistics_global_aggregator(BlockStatisticsType::RowCount),
+                ),
+                StatisticsAgg::Min(idx) | StatisticsAgg::Max(idx) => {
+                    let ty = match agg {
+                        StatisticsAgg::Min(_) => BlockStatisticsType::Min,
+                        _ => BlockStatisticsType::Max,
+                    };
+                    let data_type = self.table.columns[idx as usize].data_type();
+                    let agg: Box<dyn StatisticsGlobalAgg> =
+                        Box::new(ZoneMapGlobalAgg::create(ty, data_type));
+                    (idx as usize, agg)
+                }
+            })
+            .collect();
+
+        if let Some(rowsets) = self.snapshot.get_rowsets_of(table_id) {
+            for rowset_id in rowsets {
+                let rowset = self.version.get_rowset(table_id, *rowset_id);
+                let dvs = self
+                    .snapshot
+                    .get_dvs_of(table_id, *rowset_id)
+                    .map(|dvs| {
+                        dvs.iter()
+                            .map(|dv_id| self.version.get_dv(table_id, *dv_id))
+                            .collect_vec()
+                    })
+                    .unwrap_or_default();
+                for ((column_idx, global_agg), agg) in global_aggs.iter_mut().zip(aggs) {
+                    let column = rowset.column(*column_idx);
+                    let index = column.index();
+                    let touched = index.indexes().iter().any(|block| {
+                        let rows = block.first_rowid..block.first_rowid + block.row_count;
+                        dvs.iter().any(|dv| dv.has_deletes_in(rows.clone()))
+                    });
+                    if touched || (*agg != StatisticsAgg::RowCount && !has_zone_map(index)) {
+                        return None;
+                    }
+                    global_agg.apply_batch(index);
+                }
+            }
         }
-        if self.mem.is_none() {
-            let rowset_id = self.table.generate_rowset_id();
-            let directory = self.table.get_rowset_path(rowset_id);
 
-            if !self.table.storage_options.disable_all_disk_operation {
-                tokio::fs::create_dir(&directory).await?;
+        // rows in the memtable are never deleted
+        for chunk in self.memtable.chunks() {
+            for (column_idx, global_agg) in &mut global_aggs {
+                global_agg.apply_array(chunk.array_at(*column_idx));
             }
+        }
 
-            self.mem = Some(SecondaryMemRowsetImpl::new(
-                self.table.columns.clone(),
-                ColumnBuilderOptions::from_storage_options(&self.table.storage_options),
-                rowset_id,
-            ));
+        Some(
+            global_aggs
+                .iter()
+                .map(|(_, agg)| agg.get_output())
+                .collect(),
+        )
+    }
+
+    pub async fn append_inner(&mut self, columns: DataChunk) -> StorageResult<()> {
+        if self.read_only {
+            panic!("Txn is read-only but append is called");
         }
-        let mem = self.mem.as_mut().unwrap();
         self.total_size += columns.estimated_size();
-        mem.append(columns).await?;
+        self.pending.push(columns);
         if self.total_size >= self.table.storage_options.target_rowset_size {
             if self.total_size >= self.table.storage_options.target_rowset_size * 2 {
                 warn!("DataChunk is too big, target_row_size exceed 2x limit.")
@@ -369,8 +593,8 @@ impl Transaction for SecondaryTransaction {
 
     async fn delete(&mut self, id: &Self::RowHandlerType) -> StorageResult<()> {
         assert!(
-            self.delete_lock.is_some(),
-            "delete lock is not held for this txn"
+            self.deletion.is_some(),
+            "txn is not registered as a deletion"
         );
         self.delete_buffer.push(*id);
         Ok(())
@@ -384,4 +608,8 @@ impl Transaction for SecondaryTransaction {
         self.finished = true;
         Ok(())
     }
+
+    fn aggregate_statistics(&self, aggs: &[StatisticsAgg]) -> Option<Vec<DataValue>> {
+        self.aggregate_statistics_inner(aggs)
+    }
 }
@@ -1,29 +1,128 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
 use parking_lot::Mutex as PLMutex;
 use tokio::sync::{Mutex, OwnedMutexGuard};
 
+use super::SecondaryRowHandler;
+
 /// Secondary's Transaction Manager.
 ///
-/// The storage engine of RisingLight is designed to provide snapshot isolation (SI) or even SSI.
-/// Therefore, we need a txn manager to coordinate this. We plan to implement this in 3 phases:
+/// The storage engine of RisingLight provides snapshot isolation (SI) for transactions deleting
+/// rows, which is implemented in 3 phases:
 ///
 /// * Only allow one deletion and one compaction on one table. Therefore, the system is by nature
 ///   SI.
-/// * Implement concurrent deletion and compaction, and allow lazy detection of conflicts.
-/// * Implement true SI write conflict detection.
+/// * Implement concurrent deletion and compaction. A compaction records where the rows of its input
+///   RowSets are moved to, and deletions committed after the compaction started are remapped onto
+///   the output RowSet. A deletion of rows in compacted RowSets is remapped in the same way when it
+///   is committed.
+/// * Implement true SI write conflict detection. The first committer wins: a transaction fails to
+///   commit if any row it deletes has been deleted by a transaction committed after its snapshot.
+///
+/// Only one compaction runs on a table at a time, while any number of deletions may run
+/// concurrently with it. Committing deletions and compactions of a table is serialized by the
+/// commit lock of the table.
 #[derive(Default)]
 pub struct TransactionManager {
-    /// A single big lock for each table
-    lock_map: PLMutex<HashMap<u32, Arc<Mutex<()>>>>,
+    /// Only one compaction is allowed on each table.
+    compaction_locks: PLMutex<HashMap<u32, Arc<Mutex<()>>>>,
+
+    /// Serializes committing deletions and compactions of each table, which is short compared
+    /// with the transactions.
+    commit_locks: PLMutex<HashMap<u32, Arc<Mutex<()>>>>,
+
+    inner: Arc<PLMutex<TransactionManagerInner>>,
+}
+
+#[derive(Default)]
+struct TransactionManagerInner {
+    /// `TableId` -> start epoch -> the number of running deletions.
+    deletions: HashMap<u32, BTreeMap<u64, usize>>,
+
+    /// `TableId` -> compacted `RowSetId` -> where its rows are moved to.
+    remaps: HashMap<u32, HashMap<u32, RowsetRemap>>,
+}
+
+/// The rows of a compacted RowSet in the output RowSet of the compaction.
+pub struct RowsetRemap {
+    /// The epoch where the compaction is committed.
+    epoch: u64,
+    /// The output RowSet, or `None` if all rows were deleted.
+    rowset_id: Option<u32>,
+    /// The new row id of each row, or [`RowsetRemap::DELETED`] if the row was deleted.
+    row_ids: Vec<u32>,
+}
+
+impl RowsetRemap {
+    /// Marks rows dropped by the compaction.
+    pub const DELETED: u32 = u32::MAX;
+
+    pub fn new(epoch: u64, rowset_id: Option<u32>, row_ids: Vec<u32>) -> Self {
+        Self {
+            epoch,
+            rowset_id,
+            row_ids,
+        }
+    }
+
+    /// Returns the row in the output RowSet, or `None` if the row was deleted.
+    fn get(&self, row_id: u32) -> Option<SecondaryRowHandler> {
+        let rowset_id = self.rowset_id?;
+        match self.row_ids.get(row_id as usize) {
+            Some(&row_id) if row_id != Self::DELETED => {
+                Some(SecondaryRowHandler(rowset_id, row_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TransactionManagerInner {
+    /// Drop the remaps that no running deletion needs, i.e. those committed no later than the
+    /// start of all deletions.
+    fn prune(&mut self, table: u32) {
+        let oldest = (self.deletions.get(&table))
+            .and_then(|deletions| deletions.keys().next().copied())
+            .unwrap_or(u64::MAX);
+        if let Some(remaps) = self.remaps.get_mut(&table) {
+            remaps.retain(|_, remap| remap.epoch > oldest);
+            if remaps.is_empty() {
+                self.remaps.remove(&table);
+            }
+        }
+    }
+}
+
+/// A running deletion registered in the [`TransactionManager`]. Dropping it unregisters the
+/// deletion.
+pub struct DeletionGuard {
+    table: u32,
+    epoch: u64,
+    inner: Arc<PLMutex<TransactionManagerInner>>,
+}
+
+impl Drop for DeletionGuard {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock();
+        let deletions = inner.deletions.get_mut(&self.table).unwrap();
+        let count = deletions.get_mut(&self.epoch).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            deletions.remove(&self.epoch);
+        }
+        if deletions.is_empty() {
+            inner.deletions.remove(&self.table);
+        }
+        inner.prune(self.table);
+    }
 }
 
 impl TransactionManager {
-    fn get_lock_for_table(&self, table: u32) -> Arc<Mutex<()>> {
-        let mut lock_map = self.lock_map.lock();
+    fn get_lock(locks: &PLMutex<HashMap<u32, Arc<Mutex<()>>>>, table: u32) -> Arc<Mutex<()>> {
+        let mut lock_map = locks.lock();
         lock_map
             .entry(table)
             .or_insert_with(|| Arc::new(Mutex::new(())))
@@ -32,26 +131,72 @@ impl TransactionManager {
 
     /// Get a lock for compaction, return immediately
     pub fn try_lock_for_compaction(&self, table: u32) -> Option<OwnedMutexGuard<()>> {
-        let mutex = self.get_lock_for_table(table);
-        if let Ok(guard) = mutex.try_lock_owned() {
-            Some(guard)
-        } else {
-            None
-        }
+        let mutex = Self::get_lock(&self.compaction_locks, table);
+        mutex.try_lock_owned().ok()
     }
 
-    async fn lock(&self, table: u32) -> OwnedMutexGuard<()> {
-        let mutex = self.get_lock_for_table(table);
+    /// Get a lock for compaction
+    pub async fn lock_for_compaction(&self, table: u32) -> OwnedMutexGuard<()> {
+        let mutex = Self::get_lock(&self.compaction_locks, table);
         mutex.lock_owned().await
     }
 
-    /// Get a lock for compaction
-    pub async fn lock_for_compaction(&self, table: u32) -> OwnedMutexGuard<()> {
-        self.lock(table).await
+    /// Get a lock for committing deletions or compactions
+    pub async fn lock_for_commit(&self, table: u32) -> OwnedMutexGuard<()> {
+        let mutex = Self::get_lock(&self.commit_locks, table);
+        mutex.lock_owned().await
+    }
+
+    /// Register a deletion on the table. `latest_epoch` is called with the manager locked, and
+    /// the deletion must pin a version no older than the returned epoch.
+    pub fn begin_deletion(&self, table: u32, latest_epoch: impl FnOnce() -> u64) -> DeletionGuard {
+        let mut inner = self.inner.lock();
+        let epoch = latest_epoch();
+        *inner
+            .deletions
+            .entry(table)
+            .or_default()
+            .entry(epoch)
+            .or_default() += 1;
+        DeletionGuard {
+            table,
+            epoch,
+            inner: self.inner.clone(),
+        }
     }
 
-    /// Get a lock for deletion
-    pub async fn lock_for_deletion(&self, table: u32) -> OwnedMutexGuard<()> {
-        self.lock(table).await
+    /// Returns true if any rows of the table are being deleted.
+    pub fn is_deleting(&self, table: u32) -> bool {
+        self.inner.lock().deletions.contains_key(&table)
+    }
+
+    /// Record where the rows of a compacted RowSet are moved to. The caller must hold the commit
+    /// lock of the table.
+    pub fn add_remap(&self, table: u32, rowset_id: u32, remap: RowsetRemap) {
+        let mut inner = self.inner.lock();
+        inner
+            .remaps
+            .entry(table)
+            .or_default()
+            .insert(rowset_id, remap);
+        inner.prune(table);
+    }
+
+    /// Locate a row in `rowsets` of the latest version, following the remaps of the compactions
+    /// committed after the row was read. Returns `None` if the row was deleted by a compaction.
+    ///
+    /// The caller must hold the commit lock of the table.
+    pub fn remap(
+        &self,
+        table: u32,
+        rowsets: &HashSet<u32>,
+        mut row: SecondaryRowHandler,
+    ) -> Option<SecondaryRowHandler> {
+        let inner = self.inner.lock();
+        let remaps = inner.remaps.get(&table);
+        while !rowsets.contains(&row.rowset_id()) {
+            row = remaps?.get(&row.rowset_id())?.get(row.row_id())?;
+        }
+        Some(row)
     }
 }
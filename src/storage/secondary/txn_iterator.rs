@@ -1,9 +1,12 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
+use std::sync::Arc;
+
 use async_recursion::async_recursion;
 use enum_dispatch::enum_dispatch;
 
-use super::{ConcatIterator, MergeIterator, RowSetIterator};
+use super::version_manager::Version;
+use super::{ConcatIterator, MemTableIterator, MergeIterator, RowSetIterator};
 use crate::array::DataChunk;
 use crate::storage::{StorageChunk, StorageResult, TxnIterator};
 
@@ -12,6 +15,7 @@ pub enum SecondaryIterator {
     Concat(ConcatIterator),
     Merge(MergeIterator),
     RowSet(RowSetIterator),
+    MemTable(MemTableIterator),
     #[cfg(test)]
     Test(super::tests::TestIterator),
 }
@@ -27,11 +31,16 @@ pub trait SecondaryIteratorImpl {}
 /// To achieve this, we must enable GAT.
 pub struct SecondaryTableTxnIterator {
     iter: SecondaryIterator,
+    /// The historical version pinned for time travel, so that its RowSets won't be vacuumed.
+    _version: Option<Arc<Version>>,
 }
 
 impl SecondaryTableTxnIterator {
-    pub(super) fn new(iter: SecondaryIterator) -> Self {
-        Self { iter }
+    pub(super) fn new(iter: SecondaryIterator, version: Option<Arc<Version>>) -> Self {
+        Self {
+            iter,
+            _version: version,
+        }
     }
 }
 
@@ -45,6 +54,7 @@ impl SecondaryIterator {
             SecondaryIterator::Concat(iter) => iter.next_batch(expected_size).await,
             SecondaryIterator::Merge(iter) => iter.next_batch(expected_size).await,
             SecondaryIterator::RowSet(iter) => iter.next_batch(expected_size).await,
+            SecondaryIterator::MemTable(iter) => iter.next_batch(expected_size).await,
             #[cfg(test)]
             SecondaryIterator::Test(iter) => iter.next_batch(expected_size).await,
         }
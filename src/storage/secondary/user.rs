@@ -0,0 +1,141 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Users persisted in the manifest.
+//!
+//! Statements on users, their privileges and role memberships change the catalog first, and then
+//! the latest state of the changed users is appended to the manifest, so that they are restored
+//! on restart.
+
+use super::{DropUserEntry, EpochOp, PutUserEntry, SecondaryStorage, StorageResult};
+
+impl SecondaryStorage {
+    /// Persist the users in the catalog, or their removal if they no longer exist.
+    pub async fn persist_users(&self, names: Vec<String>) -> StorageResult<()> {
+        // the state is read with the lock held, so the latest one is appended last
+        let _guard = self.users_lock.lock().await;
+        let changes = (names.into_iter())
+            .map(|name| match self.catalog.get_user_by_name(&name) {
+                Some(user) => EpochOp::PutUser(PutUserEntry::new(&user)),
+                None => EpochOp::DropUser(DropUserEntry { name }),
+            })
+            .collect();
+        self.version.commit_changes(changes).await?;
+        Ok(())
+    }
+
+    pub(super) fn apply_put_user(&self, entry: &PutUserEntry) {
+        self.catalog.put_user(entry.to_user());
+    }
+
+    pub(super) fn apply_drop_user(&self, entry: &DropUserEntry) {
+        // the user may be dropped by an earlier entry
+        _ = self.catalog.drop_user(&entry.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::catalog::{Privilege, PrivilegeObject, RootCatalog};
+    use crate::storage::secondary::{IOBackend, StorageOptions};
+    use crate::{Database, Session};
+
+    fn options(path: &Path) -> StorageOptions {
+        StorageOptions {
+            path: path.to_path_buf(),
+            io_backend: IOBackend::NormalRead,
+            disable_all_disk_operation: false,
+            background_compaction: false,
+            ..StorageOptions::default_for_test()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_users_restored() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = options(tempdir.path());
+
+        let db = Arc::new(Database::new_on_disk(options.clone()).await);
+        let session = Session::new(db.clone());
+        for sql in [
+            "create user alice with password 'secret' superuser",
+            "alter user alice nosuperuser",
+            "create user bob",
+            "create role r",
+            "drop user bob",
+        ] {
+            session.run(sql).await.unwrap();
+        }
+        db.shutdown().await.unwrap();
+
+        // the second restart reads the users from the checkpoint written by the first one
+        for _ in 0..2 {
+            let db = Arc::new(Database::new_on_disk(options.clone()).await);
+            let catalog = db.catalog();
+            let alice = catalog.get_user_by_name("alice").unwrap();
+            assert_eq!(alice.id(), 1);
+            assert!(!alice.is_superuser() && alice.can_login());
+            assert!(alice.password().unwrap().verify("secret"));
+            let r = catalog.get_user_by_name("r").unwrap();
+            assert_eq!(r.id(), 3);
+            assert!(!r.can_login());
+            assert!(catalog.get_user_by_name("bob").is_none());
+            db.shutdown().await.unwrap();
+        }
+
+        // ids of dropped users are not reused
+        let db = Arc::new(Database::new_on_disk(options.clone()).await);
+        let session = Session::new(db.clone());
+        session.run("create user carol").await.unwrap();
+        let carol = db.catalog().get_user_by_name("carol").unwrap();
+        assert_eq!(carol.id(), 4);
+        db.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_grants_restored() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = options(tempdir.path());
+
+        let db = Arc::new(Database::new_on_disk(options.clone()).await);
+        let session = Session::new(db.clone());
+        for sql in [
+            "create table t (a int)",
+            "create table u (a int)",
+            "create user alice",
+            "create role r",
+            "create role s",
+            "grant select on t to r",
+            "grant insert on t to alice",
+            "grant insert on u to alice",
+            "grant create on schema postgres to alice",
+            "grant r to alice",
+            "grant s to alice",
+            "revoke insert on t from alice",
+            "drop table u",
+            "drop role s",
+        ] {
+            session.run(sql).await.unwrap();
+        }
+        let schema = RootCatalog::DEFAULT_SCHEMA_NAME;
+        let t = db.catalog().get_table_id_by_name(schema, "t").unwrap();
+        db.shutdown().await.unwrap();
+
+        // the second restart reads the users from the checkpoint written by the first one
+        for _ in 0..2 {
+            let db = Arc::new(Database::new_on_disk(options.clone()).await);
+            let catalog = db.catalog();
+            let alice = catalog.get_user_by_name("alice").unwrap();
+            assert_eq!(alice.roles().iter().collect::<Vec<_>>(), ["r"]);
+            // privileges on the dropped table are revoked
+            let privileges = alice.privileges().keys().copied().collect::<Vec<_>>();
+            assert_eq!(privileges, [PrivilegeObject::Schema(t.schema_id)]);
+            let table = PrivilegeObject::Table(t);
+            assert!(catalog.has_privilege("alice", Privilege::Select, table));
+            assert!(!catalog.has_privilege("alice", Privilege::Insert, table));
+            db.shutdown().await.unwrap();
+        }
+    }
+}
@@ -1,27 +1,35 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use futures::lock::Mutex;
+use itertools::Itertools;
 use parking_lot::Mutex as PLMutex;
-use tokio::fs::OpenOptions;
 use tokio::select;
 use tracing::{info, warn};
 
 use super::manifest::*;
-use super::{DeleteVector, DiskRowset, StorageOptions, StorageResult, MANIFEST_FILE_NAME};
+use super::{
+    DeleteVector, DiskRowset, EpochInfo, IOBackend, StorageOptions, StorageResult,
+    TracedStorageError,
+};
+use crate::catalog::{PartitionSpec, SchemaId, TableId, TableRefId};
+use crate::storage::AsOf;
 
 /// The operations sent to the version manager. Compared with manifest entries, operations
 /// like `AddRowSet` needs to be associated with a `DiskRowSet` struct.
 pub enum EpochOp {
     CreateTable(CreateTableEntry),
     DropTable(DropTableEntry),
+    DropPartition(DropPartitionEntry),
     AddRowSet((AddRowSetEntry, DiskRowset)),
     DeleteRowSet(DeleteRowsetEntry),
     AddDV((AddDVEntry, DeleteVector)),
     DeleteDV(DeleteDVEntry),
+    PutUser(PutUserEntry),
+    DropUser(DropUserEntry),
 }
 
 impl std::fmt::Debug for EpochOp {
@@ -29,10 +37,13 @@ impl std::fmt::Debug for EpochOp {
         match self {
             Self::CreateTable(e) => f.debug_tuple("EpochOp::CreateTable").field(e).finish(),
             Self::DropTable(e) => f.debug_tuple("EpochOp::DropTable").field(e).finish(),
+            Self::DropPartition(e) => f.debug_tuple("EpochOp::DropPartition").field(e).finish(),
             Self::AddRowSet((e, _)) => f.debug_tuple("EpochOp::AddRowSet").field(e).finish(),
             Self::DeleteRowSet(e) => f.debug_tuple("EpochOp::DeleteRowSet").field(e).finish(),
             Self::AddDV((e, _)) => f.debug_tuple("EpochOp::AddDV").field(e).finish(),
             Self::DeleteDV(e) => f.debug_tuple("EpochOp::DeleteDV").field(e).finish(),
+            Self::PutUser(e) => f.debug_tuple("EpochOp::PutUser").field(e).finish(),
+            Self::DropUser(e) => f.debug_tuple("EpochOp::DropUser").field(e).finish(),
         }
     }
 }
@@ -104,11 +115,19 @@ impl Snapshot {
 pub struct VersionManagerInner {
     /// To make things easy, we store the full snapshot of each epoch. In the future, we will use a
     /// MVCC structure for this, and only record changes compared with last epoch.
-    status: HashMap<u64, Arc<Snapshot>>,
+    ///
+    /// Snapshots older than the vacuumed epoch are removed, as their RowSets may be deleted.
+    status: BTreeMap<u64, Arc<Snapshot>>,
+
+    /// Commit time of each epoch in `status`.
+    commit_times: BTreeMap<u64, SystemTime>,
 
     /// (`TableId`, `RowSetId`) -> Object mapping
     rowsets: HashMap<(u32, u32), Arc<DiskRowset>>,
 
+    /// (`TableId`, `RowSetId`) -> the partition of the RowSet, for partitioned tables.
+    rowset_partitions: HashMap<(u32, u32), u32>,
+
     /// (`TableId`, `DVId`) -> Object mapping
     dvs: HashMap<(u32, u64), Arc<DeleteVector>>,
 
@@ -118,8 +137,66 @@ pub struct VersionManagerInner {
     /// Deletion to apply in each epoch.
     rowset_deletion_to_apply: HashMap<u64, Vec<(u32, u32)>>,
 
+    /// Deletion of DVs to apply in each epoch, as (`TableId`, `RowSetId`, `DVId`).
+    dv_deletion_to_apply: HashMap<u64, Vec<(u32, u32, u64)>>,
+
     /// Current epoch number.
     epoch: u64,
+
+    /// `TableId` -> the entry creating the table, for writing checkpoints.
+    tables: BTreeMap<u32, CreateTableEntry>,
+
+    /// The next table id of each schema, for writing checkpoints.
+    next_table_ids: BTreeMap<SchemaId, TableId>,
+
+    /// User name -> the latest state of the user, for writing checkpoints.
+    users: BTreeMap<String, PutUserEntry>,
+
+    /// Rowset ids of the memtables not flushed yet.
+    memtables: BTreeSet<u32>,
+
+    /// The rowset id above all memtables assigned so far.
+    next_memtable_id: u32,
+}
+
+impl VersionManagerInner {
+    /// Returns the manifest header and the entries of all users, tables, RowSets and DVs in the
+    /// latest version.
+    fn checkpoint(&self) -> (ManifestHeader, Vec<ManifestOperation>) {
+        let snapshot = self.status.get(&self.epoch).cloned().unwrap_or_default();
+        let header = ManifestHeader {
+            version: MANIFEST_VERSION,
+            next_table_ids: self.next_table_ids.clone().into_iter().collect(),
+            memtable_watermark: (self.memtables.first().copied()).unwrap_or(self.next_memtable_id),
+        };
+        let mut entries = (self.users.values().cloned())
+            .map(ManifestOperation::PutUser)
+            .collect_vec();
+        for (table_id, entry) in &self.tables {
+            entries.push(ManifestOperation::CreateTable(entry.clone()));
+            let table_ref_id = TableRefId::new(entry.schema_id, *table_id);
+            let rowsets = snapshot.get_rowsets_of(*table_id).into_iter().flatten();
+            for rowset_id in rowsets.sorted() {
+                entries.push(ManifestOperation::AddRowSet(AddRowSetEntry {
+                    table_id: table_ref_id,
+                    rowset_id: *rowset_id,
+                    partition: self
+                        .rowset_partitions
+                        .get(&(*table_id, *rowset_id))
+                        .copied(),
+                }));
+                let dvs = snapshot.get_dvs_of(*table_id, *rowset_id).into_iter();
+                for dv_id in dvs.flatten().sorted() {
+                    entries.push(ManifestOperation::AddDV(AddDVEntry {
+                        table_id: table_ref_id,
+                        dv_id: *dv_id,
+                        rowset_id: *rowset_id,
+                    }));
+                }
+            }
+        }
+        (header, entries)
+    }
 }
 
 /// Manages the state history of the storage engine and vacuum the stale files on disk.
@@ -181,33 +258,14 @@ impl VersionManager {
         }
     }
 
-    pub async fn rewrite_changes(
-        &self,
-        ops: Vec<EpochOp>,
-        manifest_dir_path: &Path,
-    ) -> StorageResult<u64> {
+    /// Apply changes and replace the manifest with a checkpoint of the new version.
+    pub async fn rewrite_changes(&self, ops: Vec<EpochOp>) -> StorageResult<u64> {
         // Hold the manifest lock so that no one else could commit changes.
         let mut manifest = self.manifest.lock().await;
-        // Create and truncate a tempfile.
-        let temp_manifest_path = manifest_dir_path.join("manifest.tmp.json");
-        {
-            OpenOptions::default()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(&temp_manifest_path)
-                .await?;
-        }
-        // Write to tempfile
-        let epoch = {
-            let mut temp_manifest = Manifest::open(&temp_manifest_path, true).await?;
-            self.commit_changes_with_custom_manifest(ops, &mut temp_manifest)
-                .await?
-        };
-        // Rename this tempfile to manifest
-        let manifest_path = manifest_dir_path.join(MANIFEST_FILE_NAME);
-        tokio::fs::rename(&temp_manifest_path, &manifest_path).await?;
-        manifest.reopen(&manifest_path).await?;
+        let epoch = self
+            .commit_changes_with_custom_manifest(ops, &mut Manifest::new_mock())
+            .await?;
+        self.checkpoint(&mut manifest).await?;
         Ok(epoch)
     }
 
@@ -216,8 +274,52 @@ impl VersionManager {
         // Hold the manifest lock so that no one else could commit changes.
         let mut manifest = self.manifest.lock().await;
 
-        self.commit_changes_with_custom_manifest(ops, &mut manifest)
-            .await
+        let epoch = self
+            .commit_changes_with_custom_manifest(ops, &mut manifest)
+            .await?;
+        if manifest.appended() >= self.storage_options.manifest_checkpoint_threshold {
+            self.checkpoint(&mut manifest).await?;
+        }
+        Ok(epoch)
+    }
+
+    /// Make sure the table ids below `next_id` are recorded as used in checkpoints.
+    pub fn reserve_table_ids(&self, schema_id: SchemaId, next_id: TableId) {
+        let mut inner = self.inner.lock();
+        let next = inner.next_table_ids.entry(schema_id).or_default();
+        *next = (*next).max(next_id);
+    }
+
+    /// Make sure the memtables assigned from now on have rowset ids no less than `next_id`.
+    pub fn reserve_memtable_ids(&self, next_id: u32) {
+        let mut inner = self.inner.lock();
+        inner.next_memtable_id = inner.next_memtable_id.max(next_id);
+    }
+
+    /// Assign a rowset id to a new memtable, which is tracked as not flushed until a RowSet of
+    /// the id is committed.
+    ///
+    /// Ids are assigned with the lock held, so that the watermark in checkpoints never passes a
+    /// memtable whose records are being written to the WAL.
+    pub fn register_memtable(&self, generate_rowset_id: impl FnOnce() -> u32) -> u32 {
+        let mut inner = self.inner.lock();
+        let rowset_id = generate_rowset_id();
+        inner.memtables.insert(rowset_id);
+        inner.next_memtable_id = inner.next_memtable_id.max(rowset_id + 1);
+        rowset_id
+    }
+
+    /// Stop tracking a memtable whose rows are discarded, e.g. of a dropped table.
+    pub fn unregister_memtable(&self, rowset_id: u32) {
+        self.inner.lock().memtables.remove(&rowset_id);
+    }
+
+    /// Write a checkpoint of the latest version to the manifest.
+    async fn checkpoint(&self, manifest: &mut Manifest) -> StorageResult<()> {
+        let (header, entries) = self.inner.lock().checkpoint();
+        manifest.checkpoint(&header, &entries).await?;
+        info!("manifest checkpoint written with {} entries", entries.len());
+        Ok(())
     }
 
     async fn commit_changes_with_custom_manifest(
@@ -229,6 +331,8 @@ impl VersionManager {
         let mut entries;
         let current_epoch;
         let mut rowset_deletion_to_apply = vec![];
+        let mut dv_deletion_to_apply = vec![];
+        let mut flushed_memtables = vec![];
 
         {
             // Hold the inner lock, so as to apply the changes to the current status, and add new
@@ -246,6 +350,29 @@ impl VersionManager {
                 .map(|x| x.as_ref().clone())
                 .unwrap_or_default();
 
+            // RowSets can't be added to dropped partitions, e.g. by a transaction writing rows
+            // while the partition is dropped.
+            let get_table = |table_id| {
+                (ops.iter())
+                    .find_map(|op| match op {
+                        EpochOp::CreateTable(entry) if entry.table_id == Some(table_id) => {
+                            Some(entry)
+                        }
+                        _ => None,
+                    })
+                    .or_else(|| inner.tables.get(&table_id))
+            };
+            for op in &ops {
+                if let EpochOp::AddRowSet((entry, _)) = op
+                    && let Some(partition) = entry.partition
+                    && !get_table(entry.table_id.table_id)
+                        .and_then(|table| table.partition.as_ref())
+                        .is_some_and(|spec| spec.contains(partition))
+                {
+                    return Err(TracedStorageError::write_conflict());
+                }
+            }
+
             // Store entries to be committed into the manifest
             entries = Vec::with_capacity(ops.len());
 
@@ -255,9 +382,30 @@ impl VersionManager {
                     // doesn't create MVCC map for catalog operations, and
                     // doesn't not provide interface to access them.
                     EpochOp::CreateTable(entry) => {
+                        let table_id = entry.table_id.expect("table id not assigned");
+                        let next = inner.next_table_ids.entry(entry.schema_id).or_default();
+                        *next = (*next).max(table_id + 1);
+                        inner.tables.insert(table_id, entry.clone());
                         entries.push(ManifestOperation::CreateTable(entry))
                     }
-                    EpochOp::DropTable(entry) => entries.push(ManifestOperation::DropTable(entry)),
+                    EpochOp::DropTable(entry) => {
+                        inner.tables.remove(&entry.table_id.table_id);
+                        entries.push(ManifestOperation::DropTable(entry))
+                    }
+                    EpochOp::DropPartition(entry) => {
+                        if let Some(table) = inner.tables.get_mut(&entry.table_id.table_id) {
+                            table.drop_partitions(&entry.partition_ids);
+                        }
+                        entries.push(ManifestOperation::DropPartition(entry))
+                    }
+                    EpochOp::PutUser(entry) => {
+                        inner.users.insert(entry.name.clone(), entry.clone());
+                        entries.push(ManifestOperation::PutUser(entry))
+                    }
+                    EpochOp::DropUser(entry) => {
+                        inner.users.remove(&entry.name);
+                        entries.push(ManifestOperation::DropUser(entry))
+                    }
 
                     // For other operations, maintain the snapshot in version manager
                     EpochOp::AddRowSet((entry, rowset)) => {
@@ -265,6 +413,14 @@ impl VersionManager {
                         inner
                             .rowsets
                             .insert((entry.table_id.table_id, entry.rowset_id), Arc::new(rowset));
+                        if let Some(partition) = entry.partition {
+                            inner
+                                .rowset_partitions
+                                .insert((entry.table_id.table_id, entry.rowset_id), partition);
+                        }
+                        if inner.memtables.contains(&entry.rowset_id) {
+                            flushed_memtables.push(entry.rowset_id);
+                        }
                         // update the snapshot
                         snapshot.add_rowset(entry.table_id.table_id, entry.rowset_id);
                         entries.push(ManifestOperation::AddRowSet(entry));
@@ -284,7 +440,11 @@ impl VersionManager {
                         entries.push(ManifestOperation::AddDV(entry));
                     }
                     EpochOp::DeleteDV(entry) => {
-                        // TODO: record delete op and apply it later
+                        dv_deletion_to_apply.push((
+                            entry.table_id.table_id,
+                            entry.rowset_id,
+                            entry.dv_id,
+                        ));
                         snapshot.delete_dv(entry.table_id.table_id, entry.rowset_id, entry.dv_id);
                         entries.push(ManifestOperation::DeleteDV(entry));
                     }
@@ -301,17 +461,48 @@ impl VersionManager {
         inner.epoch += 1;
         let epoch = inner.epoch;
         inner.status.insert(epoch, Arc::new(snapshot));
+        // truncated to microseconds, the resolution of timestamps in queries
+        let since_unix =
+            (SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)).unwrap_or_default();
+        let commit_time =
+            SystemTime::UNIX_EPOCH + Duration::from_micros(since_unix.as_micros() as u64);
+        inner.commit_times.insert(epoch, commit_time);
         inner
             .rowset_deletion_to_apply
             .insert(epoch, rowset_deletion_to_apply);
+        inner
+            .dv_deletion_to_apply
+            .insert(epoch, dv_deletion_to_apply);
+        // the memtables are flushed only when their RowSets are persisted
+        for rowset_id in flushed_memtables {
+            inner.memtables.remove(&rowset_id);
+        }
 
         Ok(epoch)
     }
 
+    /// Returns the latest epoch number.
+    pub fn latest_epoch(&self) -> u64 {
+        self.inner.lock().epoch
+    }
+
     /// Pin a snapshot of one epoch, so that all files at this epoch won't be deleted.
     pub fn pin(&self) -> Arc<Version> {
         let mut inner = self.inner.lock();
         let epoch = inner.epoch;
+        self.pin_epoch(&mut inner, epoch)
+    }
+
+    /// Pin the latest version and return a checkpoint of it, which can be written to a new
+    /// manifest.
+    pub fn pin_checkpoint(&self) -> (Arc<Version>, ManifestHeader, Vec<ManifestOperation>) {
+        let mut inner = self.inner.lock();
+        let (header, entries) = inner.checkpoint();
+        let epoch = inner.epoch;
+        (self.pin_epoch(&mut inner, epoch), header, entries)
+    }
+
+    fn pin_epoch(&self, inner: &mut VersionManagerInner, epoch: u64) -> Arc<Version> {
         *inner.ref_cnt.entry(epoch).or_default() += 1;
         Arc::new(Version {
             epoch,
@@ -321,22 +512,91 @@ impl VersionManager {
         })
     }
 
+    /// Pin a historical snapshot for time travel.
+    pub fn pin_as_of(&self, as_of: AsOf) -> StorageResult<Arc<Version>> {
+        let mut inner = self.inner.lock();
+        let epoch = match as_of {
+            AsOf::Epoch(epoch) => inner.status.contains_key(&epoch).then_some(epoch),
+            AsOf::Timestamp(time) => (inner.commit_times.iter())
+                .take_while(|(_, commit_time)| **commit_time <= time)
+                .last()
+                .map(|(epoch, _)| *epoch),
+        };
+        let epoch = epoch.ok_or_else(|| match as_of {
+            AsOf::Epoch(epoch) => TracedStorageError::not_found("epoch", epoch),
+            AsOf::Timestamp(time) => {
+                let micros = time
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default();
+                TracedStorageError::not_found("epoch at unix micros", micros.as_micros())
+            }
+        })?;
+        Ok(self.pin_epoch(&mut inner, epoch))
+    }
+
+    /// Returns the epochs readable by time travel.
+    pub fn epochs(&self) -> Vec<EpochInfo> {
+        let inner = self.inner.lock();
+        (inner.commit_times.iter())
+            .map(|(epoch, commit_time)| EpochInfo {
+                epoch: *epoch,
+                commit_time: *commit_time,
+                pinned: inner.ref_cnt.get(epoch).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+
     pub fn get_rowset(&self, table_id: u32, rowset_id: u32) -> Arc<DiskRowset> {
         let inner = self.inner.lock();
         inner.rowsets.get(&(table_id, rowset_id)).unwrap().clone()
     }
 
+    /// Returns the partition of a RowSet, or `None` if the table is not partitioned.
+    pub fn get_rowset_partition(&self, table_id: u32, rowset_id: u32) -> Option<u32> {
+        let inner = self.inner.lock();
+        inner.rowset_partitions.get(&(table_id, rowset_id)).copied()
+    }
+
+    /// Returns the latest range partitioning of a table.
+    pub fn get_partition_spec(&self, table_id: u32) -> Option<PartitionSpec> {
+        let inner = self.inner.lock();
+        inner.tables.get(&table_id)?.partition.clone()
+    }
+
     pub fn get_dv(&self, table_id: u32, dv_id: u64) -> Arc<DeleteVector> {
         let inner = self.inner.lock();
         inner.dvs.get(&(table_id, dv_id)).unwrap().clone()
     }
 
-    pub async fn find_vacuum(self: &Arc<Self>) -> StorageResult<Vec<(u32, u32)>> {
+    /// Returns the RowSets and DVs whose deletion can be applied, and removes them from the pool.
+    #[allow(clippy::type_complexity)]
+    pub async fn find_vacuum(
+        self: &Arc<Self>,
+    ) -> StorageResult<(Vec<(u32, u32)>, Vec<(u32, u32, u64)>)> {
         let mut inner = self.inner.lock();
         let min_pinned_epoch = inner.ref_cnt.keys().min().cloned();
 
+        // The oldest retained epoch is the one being the latest when the retention window starts.
+        let retention = self.storage_options.time_travel_retention;
+        let min_retained_epoch = if retention.is_zero() {
+            inner.epoch
+        } else {
+            let retention_start =
+                (SystemTime::now().checked_sub(retention)).unwrap_or(SystemTime::UNIX_EPOCH);
+            (inner.commit_times.iter())
+                .take_while(|(_, commit_time)| **commit_time <= retention_start)
+                .last()
+                .map_or(0, |(epoch, _)| *epoch)
+        };
+
         // If there is no pinned epoch, all deletions can be applied.
-        let vacuum_epoch = min_pinned_epoch.unwrap_or(inner.epoch);
+        let vacuum_epoch = min_pinned_epoch
+            .unwrap_or(inner.epoch)
+            .min(min_retained_epoch);
+
+        // Snapshots before the vacuum epoch may refer to the deleted RowSets.
+        inner.status = inner.status.split_off(&vacuum_epoch);
+        inner.commit_times = inner.commit_times.split_off(&vacuum_epoch);
 
         let can_apply = |epoch, vacuum_epoch| epoch <= vacuum_epoch;
 
@@ -350,7 +610,20 @@ impl VersionManager {
         inner
             .rowset_deletion_to_apply
             .retain(|k, _| !can_apply(*k, vacuum_epoch));
+        let mut dv_deletions = vec![];
+        for (epoch, deletion) in &inner.dv_deletion_to_apply {
+            if can_apply(*epoch, vacuum_epoch) {
+                dv_deletions.extend(deletion.iter().cloned());
+            }
+        }
+        inner
+            .dv_deletion_to_apply
+            .retain(|k, _| !can_apply(*k, vacuum_epoch));
+        for (table_id, _, dv_id) in &dv_deletions {
+            inner.dvs.remove(&(*table_id, *dv_id));
+        }
         for deletion in &deletions {
+            inner.rowset_partitions.remove(deletion);
             if let Some(rowset) = inner.rowsets.remove(deletion) {
                 match Arc::try_unwrap(rowset) {
                     Ok(rowset) => drop(rowset),
@@ -360,11 +633,11 @@ impl VersionManager {
                 warn!("duplicated deletion dectected, but we can't solve this issue for now -- see https://github.com/risinglightdb/risinglight/issues/566 for more information.");
             }
         }
-        Ok(deletions)
+        Ok((deletions, dv_deletions))
     }
 
     pub async fn do_vacuum(self: &Arc<Self>) -> StorageResult<()> {
-        let deletions = self.find_vacuum().await?;
+        let (deletions, dv_deletions) = self.find_vacuum().await?;
 
         for (table_id, rowset_id) in deletions {
             let path = self
@@ -372,11 +645,26 @@ impl VersionManager {
                 .path
                 .join(format!("{}_{}", table_id, rowset_id));
             info!("vacuum {}_{}", table_id, rowset_id);
-            if !self.storage_options.disable_all_disk_operation {
+            if let IOBackend::InMemory(map) = &self.storage_options.io_backend {
+                map.lock().retain(|file, _| !file.starts_with(&path));
+            } else if !self.storage_options.disable_all_disk_operation {
                 tokio::fs::remove_dir_all(path).await?;
             }
         }
 
+        for (table_id, rowset_id, dv_id) in dv_deletions {
+            let path = self
+                .storage_options
+                .path
+                .join(format!("dv/{}_{}_{}.dv", table_id, rowset_id, dv_id));
+            info!("vacuum DV {}_{}_{}", table_id, rowset_id, dv_id);
+            if let IOBackend::InMemory(map) = &self.storage_options.io_backend {
+                map.lock().remove(&path);
+            } else if !self.storage_options.disable_all_disk_operation {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+
         Ok(())
     }
 
@@ -385,9 +673,15 @@ impl VersionManager {
         mut stop: tokio::sync::mpsc::UnboundedReceiver<()>,
     ) -> StorageResult<()> {
         let mut vacuum_notifier = self.rx.lock().take().unwrap();
+        // epochs leaving the retention window are vacuumed periodically
+        let retention = self.storage_options.time_travel_retention;
+        let mut interval = tokio::time::interval(
+            retention.clamp(Duration::from_millis(100), Duration::from_secs(10)),
+        );
         loop {
             select! {
                 Some(_) = vacuum_notifier.recv() => self.do_vacuum().await?,
+                _ = interval.tick(), if !retention.is_zero() => self.do_vacuum().await?,
                 Some(_) = stop.recv() => break
             }
         }
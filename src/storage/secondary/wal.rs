@@ -0,0 +1,488 @@
+// Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
+
+//! Write-ahead log of the rows in memtables.
+//!
+//! Rows appended to a memtable are first written to the log, so that they can be recovered
+//! after a restart. The log is split into segments named `<Seq>.wal` in the `wal` directory.
+//! Each record in a segment is framed as follows:
+//!
+//! ```plain
+//! | length (u32) | crc32 of payload (u32) | payload |
+//! ```
+//!
+//! A payload is either a checkpoint or the rows of a committed transaction. A checkpoint only
+//! appears at the beginning of the segment written when the storage is opened, and it means
+//! that all records before it have been replayed and written again after it. The records of a
+//! memtable are skipped in replay if the memtable has been flushed into a rowset of the same id,
+//! either recorded in the manifest or below the memtable watermark of the manifest checkpoint.
+//!
+//! A segment is removed once all memtables with records in it have been flushed.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+use bytes::{Buf, BufMut};
+use rust_decimal::Decimal;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::{sync_dir, PrimitiveFixedWidthEncode, StorageResult, TracedStorageError};
+use crate::array::{ArrayBuilderImpl, DataChunk};
+use crate::catalog::TableRefId;
+use crate::types::{
+    Blob, DataType, DataValue, Date, Interval, Timestamp, TimestampTz, Vector, F64,
+};
+
+const RECORD_CHECKPOINT: u8 = 1;
+const RECORD_INSERT: u8 = 2;
+
+/// Rows appended to the memtable of a table in one transaction.
+#[derive(Clone)]
+pub struct WalRecord {
+    pub table_id: TableRefId,
+    /// The rowset id of the memtable.
+    pub rowset_id: u32,
+    pub chunk: DataChunk,
+}
+
+/// The write-ahead log shared by all tables.
+pub struct Wal {
+    /// Directory of the segments, or `None` if the log is disabled.
+    path: Option<PathBuf>,
+    enable_fsync: bool,
+    inner: Mutex<WalInner>,
+}
+
+#[derive(Default)]
+struct WalInner {
+    /// The segment being appended.
+    file: Option<File>,
+    /// Sequence number of the segment being appended.
+    seq: u64,
+    /// The rowset ids of the memtables with records in each segment.
+    segments: BTreeMap<u64, HashSet<u32>>,
+}
+
+impl Wal {
+    /// Create a log that writes nothing.
+    pub fn new_mock() -> Self {
+        Self {
+            path: None,
+            enable_fsync: false,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Create a log in the `path` directory. [`Wal::checkpoint`] must be called before appending
+    /// records.
+    pub fn new(path: PathBuf, enable_fsync: bool) -> Self {
+        Self {
+            path: Some(path),
+            enable_fsync,
+            inner: Mutex::default(),
+        }
+    }
+
+    fn segment_path(&self, seq: u64) -> PathBuf {
+        self.path.as_ref().unwrap().join(format!("{seq:08}.wal"))
+    }
+
+    /// Read all records in the log. `types` returns the column types of a table, or `None` if the
+    /// table has been dropped, in which case its records are skipped.
+    ///
+    /// A torn or corrupted record ends the replay of its segment.
+    pub async fn replay(
+        &self,
+        types: impl Fn(TableRefId) -> Option<Vec<DataType>>,
+    ) -> StorageResult<Vec<WalRecord>> {
+        let Some(path) = &self.path else {
+            return Ok(vec![]);
+        };
+        if fs::metadata(path).await.is_err() {
+            fs::create_dir(path).await?;
+        }
+
+        let mut segments = vec![];
+        let mut dir = fs::read_dir(path).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let name = entry.file_name();
+            let Some(seq) = (name.to_str())
+                .and_then(|name| name.strip_suffix(".wal"))
+                .and_then(|seq| seq.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            segments.push(seq);
+        }
+        segments.sort_unstable();
+
+        let mut records = vec![];
+        for seq in &segments {
+            let data = fs::read(self.segment_path(*seq)).await?;
+            let mut buf = &data[..];
+            while !buf.is_empty() {
+                if buf.len() < 8 {
+                    warn!("wal: torn record header in segment {seq}");
+                    break;
+                }
+                let len = buf.get_u32_le() as usize;
+                let checksum = buf.get_u32_le();
+                if buf.len() < len {
+                    warn!("wal: torn record in segment {seq}");
+                    break;
+                }
+                let (payload, rest) = buf.split_at(len);
+                buf = rest;
+                if crc32fast::hash(payload) != checksum {
+                    warn!("wal: corrupted record in segment {seq}");
+                    break;
+                }
+                match decode_record(payload, &types)? {
+                    Decoded::Checkpoint => records.clear(),
+                    Decoded::Insert(record) => records.push(record),
+                    Decoded::Skipped => {}
+                }
+            }
+        }
+
+        let mut inner = self.inner.lock().await;
+        inner.seq = segments.last().copied().unwrap_or_default();
+        inner.segments = segments
+            .into_iter()
+            .map(|seq| (seq, HashSet::new()))
+            .collect();
+        Ok(records)
+    }
+
+    /// Start a new segment with a checkpoint followed by `records`, and remove all older
+    /// segments. Called after [`Wal::replay`] with the records that are not flushed yet.
+    pub async fn checkpoint(&self, records: &[WalRecord]) -> StorageResult<()> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().await;
+        let seq = inner.seq + 1;
+
+        let mut data = vec![];
+        encode_frame(&mut data, &[RECORD_CHECKPOINT]);
+        for record in records {
+            encode_frame(&mut data, &encode_record(record));
+        }
+        // write to a temporary file first, so that a torn checkpoint is never replayed
+        let temp_path = self.path.as_ref().unwrap().join("checkpoint.tmp");
+        let mut file = File::create(&temp_path).await?;
+        file.write_all(&data).await?;
+        if self.enable_fsync {
+            file.sync_data().await?;
+        }
+        let path = self.segment_path(seq);
+        fs::rename(&temp_path, &path).await?;
+        if self.enable_fsync {
+            // persist the checkpoint before removing the segments it replaces
+            sync_dir(self.path.as_ref().unwrap()).await?;
+        }
+
+        for old in std::mem::take(&mut inner.segments).into_keys() {
+            fs::remove_file(self.segment_path(old)).await?;
+        }
+        info!("wal: {} records checkpointed", records.len());
+
+        inner.file = Some(OpenOptions::new().append(true).open(&path).await?);
+        inner.seq = seq;
+        inner
+            .segments
+            .insert(seq, records.iter().map(|record| record.rowset_id).collect());
+        Ok(())
+    }
+
+    /// Append a record and persist it.
+    pub async fn append(&self, record: &WalRecord) -> StorageResult<()> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let mut data = vec![];
+        encode_frame(&mut data, &encode_record(record));
+
+        let mut inner = self.inner.lock().await;
+        let file = inner.file.as_mut().expect("wal is not checkpointed");
+        file.write_all(&data).await?;
+        if self.enable_fsync {
+            file.sync_data().await?;
+        }
+        let seq = inner.seq;
+        inner
+            .segments
+            .entry(seq)
+            .or_default()
+            .insert(record.rowset_id);
+        Ok(())
+    }
+
+    /// Seal the current segment and append records to a new one. Called when a memtable is
+    /// frozen, so that the segments of other memtables can be removed without waiting for it.
+    pub async fn rotate(&self) -> StorageResult<()> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().await;
+        if inner
+            .segments
+            .get(&inner.seq)
+            .map_or(true, |ids| ids.is_empty())
+        {
+            return Ok(());
+        }
+        let seq = inner.seq + 1;
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(self.segment_path(seq))
+            .await?;
+        if self.enable_fsync {
+            sync_dir(self.path.as_ref().unwrap()).await?;
+        }
+        inner.file = Some(file);
+        inner.seq = seq;
+        inner.segments.insert(seq, HashSet::new());
+        Ok(())
+    }
+
+    /// Mark the records of a memtable as no longer needed, because the memtable has been flushed
+    /// or its table has been dropped. Sealed segments without needed records are removed.
+    pub async fn release(&self, rowset_id: u32) -> StorageResult<()> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().await;
+        let current = inner.seq;
+        let mut removed = vec![];
+        for (seq, ids) in &mut inner.segments {
+            ids.remove(&rowset_id);
+            if ids.is_empty() && *seq != current {
+                removed.push(*seq);
+            }
+        }
+        for seq in removed {
+            inner.segments.remove(&seq);
+            fs::remove_file(self.segment_path(seq)).await?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_frame(buf: &mut Vec<u8>, payload: &[u8]) {
+    buf.put_u32_le(payload.len() as u32);
+    buf.put_u32_le(crc32fast::hash(payload));
+    buf.put_slice(payload);
+}
+
+fn encode_record(record: &WalRecord) -> Vec<u8> {
+    let chunk = &record.chunk;
+    let mut buf = vec![RECORD_INSERT];
+    buf.put_u32_le(record.table_id.schema_id);
+    buf.put_u32_le(record.table_id.table_id);
+    buf.put_u32_le(record.rowset_id);
+    buf.put_u32_le(chunk.column_count() as u32);
+    buf.put_u32_le(chunk.cardinality() as u32);
+    for array in chunk.arrays() {
+        for value in array.iter() {
+            encode_value(&mut buf, &value);
+        }
+    }
+    buf
+}
+
+fn encode_value(buf: &mut Vec<u8>, value: &DataValue) {
+    if value.is_null() {
+        buf.put_u8(0);
+        return;
+    }
+    buf.put_u8(1);
+    match value {
+        DataValue::Null => unreachable!(),
+        DataValue::Bool(v) => v.encode(buf),
+        DataValue::Int16(v) => v.encode(buf),
+        DataValue::Int32(v) => v.encode(buf),
+        DataValue::Int64(v) => v.encode(buf),
+        DataValue::Float64(v) => v.encode(buf),
+        DataValue::Decimal(v) => v.encode(buf),
+        DataValue::Date(v) => v.encode(buf),
+        DataValue::Timestamp(v) => v.encode(buf),
+        DataValue::TimestampTz(v) => v.encode(buf),
+        DataValue::Interval(v) => v.encode(buf),
+        DataValue::String(v) => {
+            buf.put_u32_le(v.len() as u32);
+            buf.put_slice(v.as_bytes());
+        }
+        DataValue::Blob(v) => {
+            buf.put_u32_le(v.len() as u32);
+            buf.put_slice(v);
+        }
+        DataValue::Vector(v) => {
+            buf.put_u32_le(v.len() as u32);
+            for x in v.iter() {
+                x.encode(buf);
+            }
+        }
+    }
+}
+
+enum Decoded {
+    Checkpoint,
+    Insert(WalRecord),
+    /// A record of a dropped table.
+    Skipped,
+}
+
+fn decode_record(
+    mut buf: &[u8],
+    types: impl Fn(TableRefId) -> Option<Vec<DataType>>,
+) -> StorageResult<Decoded> {
+    match buf.get_u8() {
+        RECORD_CHECKPOINT => return Ok(Decoded::Checkpoint),
+        RECORD_INSERT => {}
+        kind => {
+            return Err(TracedStorageError::decode(format!(
+                "invalid wal record {kind}"
+            )))
+        }
+    }
+    let table_id = TableRefId::new(buf.get_u32_le(), buf.get_u32_le());
+    let rowset_id = buf.get_u32_le();
+    let columns = buf.get_u32_le() as usize;
+    let rows = buf.get_u32_le() as usize;
+    let Some(types) = types(table_id) else {
+        return Ok(Decoded::Skipped);
+    };
+    if types.len() != columns {
+        return Err(TracedStorageError::decode(format!(
+            "wal record of table {table_id} has {columns} columns, expected {}",
+            types.len()
+        )));
+    }
+    let mut arrays = vec![];
+    for ty in &types {
+        let mut builder = ArrayBuilderImpl::with_capacity(rows, ty);
+        for _ in 0..rows {
+            builder.push(&decode_value(&mut buf, ty)?);
+        }
+        arrays.push(builder.finish());
+    }
+    Ok(Decoded::Insert(WalRecord {
+        table_id,
+        rowset_id,
+        chunk: arrays.into_iter().collect(),
+    }))
+}
+
+fn decode_value(buf: &mut &[u8], ty: &DataType) -> StorageResult<DataValue> {
+    if buf.get_u8() == 0 {
+        return Ok(DataValue::Null);
+    }
+    Ok(match ty {
+        DataType::Null => DataValue::Null,
+        DataType::Bool => DataValue::Bool(bool::decode(buf)),
+        DataType::Int16 => DataValue::Int16(i16::decode(buf)),
+        DataType::Int32 => DataValue::Int32(i32::decode(buf)),
+        DataType::Int64 => DataValue::Int64(i64::decode(buf)),
+        DataType::Float64 => DataValue::Float64(F64::decode(buf)),
+        DataType::Decimal(_, _) => DataValue::Decimal(Decimal::decode(buf)),
+        DataType::Date => DataValue::Date(Date::decode(buf)),
+        DataType::Timestamp => DataValue::Timestamp(Timestamp::decode(buf)),
+        DataType::TimestampTz => DataValue::TimestampTz(TimestampTz::decode(buf)),
+        DataType::Interval => DataValue::Interval(Interval::decode(buf)),
+        DataType::String => {
+            let len = buf.get_u32_le() as usize;
+            let s = std::str::from_utf8(&buf[..len])
+                .map_err(|e| TracedStorageError::decode(e.to_string()))?;
+            let value = DataValue::String(s.into());
+            buf.advance(len);
+            value
+        }
+        DataType::Blob => {
+            let len = buf.get_u32_le() as usize;
+            let value = DataValue::Blob(Blob::from(&buf[..len]));
+            buf.advance(len);
+            value
+        }
+        DataType::Vector(_) => {
+            let len = buf.get_u32_le() as usize;
+            let values = (0..len).map(|_| F64::decode(buf)).collect::<Vec<_>>();
+            DataValue::Vector(Vector::new_from_ordered_f64(values))
+        }
+        DataType::Struct(_) => {
+            return Err(TracedStorageError::decode("struct is not supported in wal"))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::{ArrayImpl, I32Array, StringArray};
+
+    fn record(rowset_id: u32, values: &[i32]) -> WalRecord {
+        WalRecord {
+            table_id: TableRefId::new(1, 2),
+            rowset_id,
+            chunk: [
+                ArrayImpl::new_int32(values.iter().map(|v| Some(*v)).collect::<I32Array>()),
+                ArrayImpl::new_string(
+                    values
+                        .iter()
+                        .map(|v| (v % 2 == 0).then(|| v.to_string()))
+                        .collect::<StringArray>(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    async fn replay(wal: &Wal) -> Vec<WalRecord> {
+        wal.replay(|_| Some(vec![DataType::Int32, DataType::String]))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wal_replay() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("wal");
+
+        let wal = Wal::new(path.clone(), false);
+        assert!(replay(&wal).await.is_empty());
+        wal.checkpoint(&[]).await.unwrap();
+        wal.append(&record(1, &[1, 2])).await.unwrap();
+        wal.rotate().await.unwrap();
+        wal.append(&record(2, &[3])).await.unwrap();
+
+        // the first segment is removed once memtable 1 is flushed
+        wal.release(1).await.unwrap();
+        let wal = Wal::new(path.clone(), false);
+        let records = replay(&wal).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].rowset_id, 2);
+        assert_eq!(records[0].chunk, record(2, &[3]).chunk);
+
+        // the checkpoint replaces all older segments
+        wal.checkpoint(&records).await.unwrap();
+        wal.append(&record(3, &[4, 5])).await.unwrap();
+        let wal = Wal::new(path.clone(), false);
+        let records = replay(&wal).await;
+        assert_eq!(
+            records.iter().map(|r| r.rowset_id).collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert_eq!(records[1].chunk, record(3, &[4, 5]).chunk);
+
+        // a torn record at the end is ignored
+        let segment = wal.segment_path(wal.inner.lock().await.seq);
+        let data = fs::read(&segment).await.unwrap();
+        fs::write(&segment, &data[..data.len() - 3]).await.unwrap();
+        let wal = Wal::new(path, false);
+        assert_eq!(replay(&wal).await.len(), 1);
+    }
+}
@@ -6,10 +6,12 @@ use std::num::ParseIntError;
 use std::ops::{Add, Neg, Sub};
 use std::str::FromStr;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Interval type
-#[derive(PartialOrd, Ord, PartialEq, Eq, Debug, Copy, Clone, Default, Hash, Serialize)]
+#[derive(
+    PartialOrd, Ord, PartialEq, Eq, Debug, Copy, Clone, Default, Hash, Serialize, Deserialize,
+)]
 pub struct Interval {
     months: i32,
     days: i32,
@@ -85,6 +87,10 @@ impl Interval {
         self.months
     }
 
+    pub const fn num_millis(&self) -> i32 {
+        self.ms
+    }
+
     pub const fn is_zero(&self) -> bool {
         matches!(
             self,
@@ -88,6 +88,8 @@ impl DataType {
             (Float64, Float64 | Decimal(_, _) | String) => Some(b.clone()),
             (Decimal(_, _), Decimal(_, _) | String) => Some(b.clone()),
             (Date, Date | String) => Some(b.clone()),
+            (Timestamp, Timestamp | String) => Some(b.clone()),
+            (TimestampTz, TimestampTz | String) => Some(b.clone()),
             (Interval, Interval | String) => Some(b.clone()),
             (String, String | Blob) => Some(b.clone()),
             (Blob, Blob) => Some(b.clone()),
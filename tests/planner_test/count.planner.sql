@@ -5,9 +5,9 @@ explain select count(*) from t
 Projection
 ├── exprs:ref
 │   └── rowcount
-├── cost: 1.13
+├── cost: 1.02
 ├── rows: 1
-└── Agg { aggs: [ rowcount ], cost: 1.11, rows: 1 }
+└── StatAgg { aggs: [ rowcount ], cost: 1, rows: 1 }
     └── Scan { table: t, list: [], filter: true, cost: 0, rows: 1 }
 */
 
@@ -21,9 +21,9 @@ Projection
 │   │   └── rowcount
 │   ├── rhs: 1
 
-├── cost: 1.33
+├── cost: 1.22
 ├── rows: 1
-└── Agg { aggs: [ rowcount ], cost: 1.11, rows: 1 }
+└── StatAgg { aggs: [ rowcount ], cost: 1, rows: 1 }
     └── Scan { table: t, list: [], filter: true, cost: 0, rows: 1 }
 */
 
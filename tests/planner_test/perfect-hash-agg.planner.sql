@@ -0,0 +1,41 @@
+-- group by small integer and string domains
+explain select flag, status, count(*), sum(v) from t group by flag, status
+
+/*
+Projection
+├── exprs:
+│   ┌── flag
+│   ├── status
+│   ├── ref
+│   │   └── rowcount
+│   └── ref
+│       └── sum
+│           └── v
+├── cost: 29.204
+├── rows: 4
+└── PerfectHashAgg
+    ├── keys: [ flag, status ]
+    ├── aggs:
+    │   ┌── rowcount
+    │   └── sum
+    │       └── v
+    ├── cost: 29.004
+    ├── rows: 4
+    └── Scan { table: t, list: [ flag, status, v ], filter: true, cost: 12, rows: 4 }
+*/
+
+-- the integer range is too large
+explain select v, count(*) from t group by v
+
+/*
+Projection
+├── exprs:
+│   ┌── v
+│   └── ref
+│       └── rowcount
+├── cost: 6.3516994
+├── rows: 2
+└── HashAgg { keys: [ v ], aggs: [ rowcount ], cost: 6.2916994, rows: 2 }
+    └── Scan { table: t, list: [ v ], filter: true, cost: 2, rows: 2 }
+*/
+
@@ -0,0 +1,28 @@
+-- push runtime filter from hash join build side to probe side scan
+explain select * from fact join dim on fact.dim_id = dim.id where dim.name = 'x';
+
+/*
+HashJoin { type: inner, cond: true, lkey: [ dim_id ], rkey: [ id ], cost: 157562.14, rows: 25000 }
+├── Scan { table: fact, list: [ id, dim_id, v ], filter: true, cost: 30000, rows: 10000 }
+└── Filter { cond: = { lhs: name, rhs: 'x' }, cost: 32.1, rows: 5 }
+    └── Scan { table: dim, list: [ id, name ], filter: true, cost: 20, rows: 10, runtime_filter: [id] }
+*/
+
+-- push runtime filter from semi join to probe side scan
+explain select * from fact where dim_id in (select id from dim);
+
+/*
+HashJoin { type: semi, cond: true, lkey: [ dim_id ], rkey: [ id ], cost: 46557.49, rows: 5000 }
+├── Scan { table: fact, list: [ id, dim_id, v ], filter: true, cost: 30000, rows: 10000, runtime_filter: [dim_id] }
+└── Scan { table: dim, list: [ id ], filter: true, cost: 10, rows: 10 }
+*/
+
+-- no runtime filter on the preserved side of outer joins
+explain select * from fact right join dim on fact.dim_id = dim.id;
+
+/*
+HashJoin { type: right_outer, cond: true, lkey: [ dim_id ], rkey: [ id ], cost: 282551.3, rows: 50000 }
+├── Scan { table: fact, list: [ id, dim_id, v ], filter: true, cost: 30000, rows: 10000 }
+└── Scan { table: dim, list: [ id, name ], filter: true, cost: 20, rows: 10 }
+*/
+
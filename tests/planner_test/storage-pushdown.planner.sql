@@ -49,3 +49,96 @@ Filter { cond: and { lhs: > { lhs: a, rhs: 3 }, rhs: > { lhs: b, rhs: 1 } }, cos
 └── Scan { table: t1, list: [ a, b ], filter: > { lhs: a, rhs: 1 }, cost: 10, rows: 5 }
 */
 
+-- answer aggregations from storage statistics
+explain select count(*), min(a), max(b) from t1;
+
+/*
+Projection
+├── exprs:
+│   ┌── ref
+│   │   └── rowcount
+│   ├── ref
+│   │   └── min
+│   │       └── a
+│   └── ref
+│       └── max
+│           └── b
+├── cost: 8.04
+├── rows: 1
+└── StatAgg
+    ├── aggs:
+    │   ┌── rowcount
+    │   ├── min
+    │   │   └── a
+    │   └── max
+    │       └── b
+    ├── cost: 8
+    ├── rows: 1
+    └── Scan { table: t1, list: [ a, b ], filter: true, cost: 10, rows: 5 }
+*/
+
+-- aggregations that can not be answered from storage statistics
+explain select count(b), sum(a) from t1;
+
+/*
+Projection
+├── exprs:
+│   ┌── ref
+│   │   └── count
+│   │       └── b
+│   └── ref
+│       └── sum
+│           └── a
+├── cost: 2.03
+├── rows: 1
+└── Agg
+    ├── aggs:
+    │   ┌── count
+    │   │   └── b
+    │   └── sum
+    │       └── a
+    ├── cost: 2
+    ├── rows: 1
+    └── Scan { table: t1, list: [ a, b ], filter: true, cost: 0, rows: 0 }
+*/
+
+-- prune partitions by a range of the partition column
+explain select * from t1 where a >= 10 and a < 20 and b > 1;
+
+/*
+Filter
+├── cond: and { lhs: > { lhs: b, rhs: 1 }, rhs: and { lhs: > { lhs: 20, rhs: a }, rhs: >= { lhs: a, rhs: 10 } } }
+├── cost: 8.733334
+├── rows: 0.625
+└── Scan
+    ├── table: t1
+    ├── list: [ a, b ]
+    ├── filter: and { lhs: > { lhs: 20, rhs: a }, rhs: >= { lhs: a, rhs: 10 } }
+    ├── cost: 3.3333333
+    └── rows: 5
+*/
+
+-- prune partitions by a range of a date partition column
+explain select * from t1 where a >= date '2024-02-01' and a < date '2024-03-01';
+
+/*
+Filter
+├── cond: and { lhs: > { lhs: 2024-03-01, rhs: a }, rhs: >= { lhs: a, rhs: 2024-02-01 } }
+├── cost: 6.746667
+├── rows: 1
+└── Scan
+    ├── table: t1
+    ├── list: [ a, b ]
+    ├── filter: and { lhs: > { lhs: 2024-03-01, rhs: a }, rhs: >= { lhs: a, rhs: 2024-02-01 } }
+    ├── cost: 2.6666667
+    └── rows: 4
+*/
+
+-- prune partitions by a range of a timestamp partition column
+explain select * from t1 where a < timestamp '2024-01-01 12:00:00';
+
+/*
+Filter { cond: > { lhs: 2024-01-01 12:00:00, rhs: a }, cost: 7.63, rows: 1.5 }
+└── Scan { table: t1, list: [ a, b ], filter: > { lhs: 2024-01-01 12:00:00, rhs: a }, cost: 4, rows: 3 }
+*/
+
@@ -459,7 +459,8 @@ Projection
                             │       │       │   │           ├── list: [ r_regionkey, r_name, r_comment ]
                             │       │       │   │           ├── filter: true
                             │       │       │   │           ├── cost: 15
-                            │       │       │   │           └── rows: 5
+                            │       │       │   │           ├── rows: 5
+                            │       │       │   │           └── runtime_filter: [r_regionkey]
                             │       │       │   └── Filter
                             │       │       │       ├── cond:and
                             │       │       │       │   ├── lhs: = { lhs: p_size, rhs: 15 }
@@ -491,7 +492,8 @@ Projection
                             │       │           │   └── ps_comment
                             │       │           ├── filter: true
                             │       │           ├── cost: 4000000
-                            │       │           └── rows: 800000
+                            │       │           ├── rows: 800000
+                            │       │           └── runtime_filter: [ps_partkey]
                             │       └── Scan
                             │           ├── table: supplier
                             │           ├── list:
@@ -504,7 +506,8 @@ Projection
                             │           │   └── s_comment
                             │           ├── filter: true
                             │           ├── cost: 70000
-                            │           └── rows: 10000
+                            │           ├── rows: 10000
+                            │           └── runtime_filter: [s_nationkey, s_suppkey]
                             └── Projection { exprs: [ ps_partkey(1), ps_supplycost(1) ], cost: 9100652, rows: 800000 }
                                 └── HashJoin
                                     ├── type: inner
@@ -543,19 +546,22 @@ Projection
                                     │   │               ├── list: [ r_regionkey(1), r_name(1) ]
                                     │   │               ├── filter: true
                                     │   │               ├── cost: 10
-                                    │   │               └── rows: 5
+                                    │   │               ├── rows: 5
+                                    │   │               └── runtime_filter: [r_regionkey]
                                     │   └── Scan
                                     │       ├── table: supplier
                                     │       ├── list: [ s_suppkey(1), s_nationkey(1) ]
                                     │       ├── filter: true
                                     │       ├── cost: 20000
-                                    │       └── rows: 10000
+                                    │       ├── rows: 10000
+                                    │       └── runtime_filter: [s_nationkey]
                                     └── Scan
                                         ├── table: partsupp
                                         ├── list: [ ps_partkey(1), ps_suppkey(1), ps_supplycost(1) ]
                                         ├── filter: true
                                         ├── cost: 2400000
-                                        └── rows: 800000
+                                        ├── rows: 800000
+                                        └── runtime_filter: [ps_partkey, ps_suppkey]
 */
 
 -- tpch-q3: TPC-H Q3
@@ -643,7 +649,8 @@ Projection
                 │               ├── list: [ c_custkey, c_mktsegment ]
                 │               ├── filter: true
                 │               ├── cost: 300000
-                │               └── rows: 150000
+                │               ├── rows: 150000
+                │               └── runtime_filter: [c_custkey]
                 └── Projection { exprs: [ l_orderkey, l_extendedprice, l_discount ], cost: 37387570, rows: 3000607.5 }
                     └── Filter { cond: > { lhs: l_shipdate, rhs: 1995-03-15 }, cost: 37267544, rows: 3000607.5 }
                         └── Scan
@@ -651,7 +658,8 @@ Projection
                             ├── list: [ l_orderkey, l_extendedprice, l_discount, l_shipdate ]
                             ├── filter: true
                             ├── cost: 24004860
-                            └── rows: 6001215
+                            ├── rows: 6001215
+                            └── runtime_filter: [l_orderkey]
 */
 
 -- tpch-q4
@@ -707,7 +715,8 @@ Projection
                 │           ├── list: [ o_orderkey, o_orderdate, o_orderpriority ]
                 │           ├── filter: true
                 │           ├── cost: 4500000
-                │           └── rows: 1500000
+                │           ├── rows: 1500000
+                │           └── runtime_filter: [o_orderkey]
                 └── Projection { exprs: [ l_orderkey ], cost: 27785624, rows: 3000607.5 }
                     └── Filter { cond: > { lhs: l_receiptdate, rhs: l_commitdate }, cost: 27725612, rows: 3000607.5 }
                         └── Scan
@@ -803,10 +812,17 @@ Projection
                 │   │           ├── list: [ s_suppkey, s_nationkey ]
                 │   │           ├── filter: true
                 │   │           ├── cost: 20000
-                │   │           └── rows: 10000
+                │   │           ├── rows: 10000
+                │   │           └── runtime_filter: [s_nationkey]
                 │   └── Projection { exprs: [ r_regionkey ], cost: 16.099998, rows: 2.5 }
                 │       └── Filter { cond: = { lhs: r_name, rhs: 'AFRICA' }, cost: 16.05, rows: 2.5 }
-                │           └── Scan { table: region, list: [ r_regionkey, r_name ], filter: true, cost: 10, rows: 5 }
+                │           └── Scan
+                │               ├── table: region
+                │               ├── list: [ r_regionkey, r_name ]
+                │               ├── filter: true
+                │               ├── cost: 10
+                │               ├── rows: 5
+                │               └── runtime_filter: [r_regionkey]
                 └── Projection
                     ├── exprs: [ c_nationkey, l_suppkey, l_extendedprice, l_discount ]
                     ├── cost: 70638780
@@ -831,7 +847,8 @@ Projection
                         │       │   ├── list: [ c_custkey, c_nationkey ]
                         │       │   ├── filter: true
                         │       │   ├── cost: 300000
-                        │       │   └── rows: 150000
+                        │       │   ├── rows: 150000
+                        │       │   └── runtime_filter: [c_nationkey]
                         │       └── Projection { exprs: [ o_orderkey, o_custkey ], cost: 6416250, rows: 375000 }
                         │           └── Filter
                         │               ├── cond:and
@@ -844,13 +861,15 @@ Projection
                         │                   ├── list: [ o_orderkey, o_custkey, o_orderdate ]
                         │                   ├── filter: true
                         │                   ├── cost: 4500000
-                        │                   └── rows: 1500000
+                        │                   ├── rows: 1500000
+                        │                   └── runtime_filter: [o_custkey]
                         └── Scan
                             ├── table: lineitem
                             ├── list: [ l_orderkey, l_suppkey, l_extendedprice, l_discount ]
                             ├── filter: true
                             ├── cost: 24004860
-                            └── rows: 6001215
+                            ├── rows: 6001215
+                            └── runtime_filter: [l_suppkey, l_orderkey]
 */
 
 -- tpch-q6
@@ -1047,7 +1066,8 @@ Projection
                                     │       │       ├── list: [ c_custkey, c_nationkey ]
                                     │       │       ├── filter: true
                                     │       │       ├── cost: 300000
-                                    │       │       └── rows: 150000
+                                    │       │       ├── rows: 150000
+                                    │       │       └── runtime_filter: [c_custkey]
                                     │       └── Filter
                                     │           ├── cond:and
                                     │           │   ├── lhs: >= { lhs: l_shipdate, rhs: 1995-01-01 }
@@ -1064,20 +1084,23 @@ Projection
                                     │               │   └── l_shipdate
                                     │               ├── filter: true
                                     │               ├── cost: 30006076
-                                    │               └── rows: 6001215
+                                    │               ├── rows: 6001215
+                                    │               └── runtime_filter: [l_orderkey]
                                     └── Join { type: inner, cost: 1045050, rows: 250000 }
                                         ├── Scan
                                         │   ├── table: supplier
                                         │   ├── list: [ s_suppkey, s_nationkey ]
                                         │   ├── filter: true
                                         │   ├── cost: 20000
-                                        │   └── rows: 10000
+                                        │   ├── rows: 10000
+                                        │   └── runtime_filter: [s_nationkey, s_suppkey]
                                         └── Scan
                                             ├── table: nation
                                             ├── list: [ n_nationkey(1), n_name(1) ]
                                             ├── filter: true
                                             ├── cost: 50
-                                            └── rows: 25
+                                            ├── rows: 25
+                                            └── runtime_filter: [n_nationkey]
 */
 
 -- tpch-q8
@@ -1263,7 +1286,8 @@ Projection
                         │           │       │                       ├── list: [ r_regionkey, r_name ]
                         │           │       │                       ├── filter: true
                         │           │       │                       ├── cost: 10
-                        │           │       │                       └── rows: 5
+                        │           │       │                       ├── rows: 5
+                        │           │       │                       └── runtime_filter: [r_regionkey]
                         │           │       └── Projection
                         │           │           ├── exprs:
                         │           │           │   ┌── l_orderkey
@@ -1296,7 +1320,8 @@ Projection
                         │           │                   │   └── l_discount
                         │           │                   ├── filter: true
                         │           │                   ├── cost: 30006076
-                        │           │                   └── rows: 6001215
+                        │           │                   ├── rows: 6001215
+                        │           │                   └── runtime_filter: [l_suppkey, l_partkey]
                         │           └── Filter
                         │               ├── cond:and
                         │               │   ├── lhs: >= { lhs: 1996-12-31, rhs: o_orderdate }
@@ -1308,13 +1333,15 @@ Projection
                         │                   ├── list: [ o_orderkey, o_custkey, o_orderdate ]
                         │                   ├── filter: true
                         │                   ├── cost: 4500000
-                        │                   └── rows: 1500000
+                        │                   ├── rows: 1500000
+                        │                   └── runtime_filter: [o_orderkey]
                         └── Scan
                             ├── table: customer
                             ├── list: [ c_custkey, c_nationkey ]
                             ├── filter: true
                             ├── cost: 300000
-                            └── rows: 150000
+                            ├── rows: 150000
+                            └── runtime_filter: [c_custkey]
 */
 
 -- tpch-q9
@@ -1439,7 +1466,8 @@ Order
                             │       │   ├── list: [ s_suppkey, s_nationkey ]
                             │       │   ├── filter: true
                             │       │   ├── cost: 20000
-                            │       │   └── rows: 10000
+                            │       │   ├── rows: 10000
+                            │       │   └── runtime_filter: [s_nationkey]
                             │       └── Projection
                             │           ├── exprs:
                             │           │   ┌── l_orderkey
@@ -1496,19 +1524,22 @@ Order
                             │               │           │   └── l_discount
                             │               │           ├── filter: true
                             │               │           ├── cost: 36007290
-                            │               │           └── rows: 6001215
+                            │               │           ├── rows: 6001215
+                            │               │           └── runtime_filter: [l_suppkey, l_partkey]
                             │               └── Scan
                             │                   ├── table: partsupp
                             │                   ├── list: [ ps_partkey, ps_suppkey, ps_supplycost ]
                             │                   ├── filter: true
                             │                   ├── cost: 2400000
-                            │                   └── rows: 800000
+                            │                   ├── rows: 800000
+                            │                   └── runtime_filter: [ps_suppkey, ps_partkey]
                             └── Scan
                                 ├── table: orders
                                 ├── list: [ o_orderkey, o_orderdate ]
                                 ├── filter: true
                                 ├── cost: 3000000
-                                └── rows: 1500000
+                                ├── rows: 1500000
+                                └── runtime_filter: [o_orderkey]
 */
 
 -- tpch-q10: TPC-H Q10
@@ -1636,7 +1667,8 @@ Projection
                     │           ├── list: [ c_custkey, c_name, c_address, c_nationkey, c_phone, c_acctbal, c_comment ]
                     │           ├── filter: true
                     │           ├── cost: 1050000
-                    │           └── rows: 150000
+                    │           ├── rows: 150000
+                    │           └── runtime_filter: [c_nationkey]
                     └── Projection { exprs: [ o_orderkey, o_custkey ], cost: 6416250, rows: 375000 }
                         └── Filter
                             ├── cond:and
@@ -1649,7 +1681,8 @@ Projection
                                 ├── list: [ o_orderkey, o_custkey, o_orderdate ]
                                 ├── filter: true
                                 ├── cost: 4500000
-                                └── rows: 1500000
+                                ├── rows: 1500000
+                                └── runtime_filter: [o_orderkey, o_custkey]
 */
 
 -- tpch-q11
@@ -1748,13 +1781,15 @@ Order
             │           │           ├── list: [ s_suppkey, s_nationkey ]
             │           │           ├── filter: true
             │           │           ├── cost: 20000
-            │           │           └── rows: 10000
+            │           │           ├── rows: 10000
+            │           │           └── runtime_filter: [s_nationkey]
             │           └── Scan
             │               ├── table: partsupp
             │               ├── list: [ ps_partkey, ps_suppkey, ps_availqty, ps_supplycost ]
             │               ├── filter: true
             │               ├── cost: 3200000
-            │               └── rows: 800000
+            │               ├── rows: 800000
+            │               └── runtime_filter: [ps_suppkey]
             └── Projection
                 ├── exprs:*
                 │   ├── lhs:ref
@@ -1804,13 +1839,15 @@ Order
                             │           ├── list: [ s_suppkey(1), s_nationkey(1) ]
                             │           ├── filter: true
                             │           ├── cost: 20000
-                            │           └── rows: 10000
+                            │           ├── rows: 10000
+                            │           └── runtime_filter: [s_nationkey]
                             └── Scan
                                 ├── table: partsupp
                                 ├── list: [ ps_suppkey(1), ps_availqty(1), ps_supplycost(1) ]
                                 ├── filter: true
                                 ├── cost: 2400000
-                                └── rows: 800000
+                                ├── rows: 800000
+                                └── runtime_filter: [ps_suppkey]
 */
 
 -- tpch-q12
@@ -1920,7 +1957,8 @@ Projection
                     ├── list: [ o_orderkey, o_orderpriority ]
                     ├── filter: true
                     ├── cost: 3000000
-                    └── rows: 1500000
+                    ├── rows: 1500000
+                    └── runtime_filter: [o_orderkey]
 */
 
 -- tpch-q13
@@ -2007,7 +2045,8 @@ Projection
                                     ├── list: [ o_orderkey, o_custkey, o_comment ]
                                     ├── filter: true
                                     ├── cost: 4500000
-                                    └── rows: 1500000
+                                    ├── rows: 1500000
+                                    └── runtime_filter: [o_custkey]
 */
 
 -- tpch-q14
@@ -2079,7 +2118,8 @@ Projection
                         ├── list: [ l_partkey, l_extendedprice, l_discount, l_shipdate ]
                         ├── filter: true
                         ├── cost: 24004860
-                        └── rows: 6001215
+                        ├── rows: 6001215
+                        └── runtime_filter: [l_partkey]
 */
 
 -- tpch-q15
@@ -2150,7 +2190,8 @@ order by
             │           ├── list: [ s_suppkey, s_name, s_address, s_phone ]
             │           ├── filter: true
             │           ├── cost: 40000
-            │           └── rows: 10000
+            │           ├── rows: 10000
+            │           └── runtime_filter: [s_suppkey]
             └── Projection
                 ├── exprs:ref
                 │   └── max
@@ -2257,7 +2298,8 @@ Projection
             │           ├── list: [ ps_partkey, ps_suppkey ]
             │           ├── filter: true
             │           ├── cost: 1600000
-            │           └── rows: 800000
+            │           ├── rows: 800000
+            │           └── runtime_filter: [ps_partkey]
             └── Projection { exprs: [ s_suppkey ], cost: 32200, rows: 5000 }
                 └── Filter { cond: like { lhs: s_comment, rhs: '%Customer%Complaints%' }, cost: 32100, rows: 5000 }
                     └── Scan { table: supplier, list: [ s_suppkey, s_comment ], filter: true, cost: 20000, rows: 10000 }
@@ -2494,13 +2536,15 @@ Projection
                                 │           │   └── p_comment
                                 │           ├── filter: true
                                 │           ├── cost: 1800000
-                                │           └── rows: 200000
+                                │           ├── rows: 200000
+                                │           └── runtime_filter: [p_partkey]
                                 └── Scan
                                     ├── table: lineitem
                                     ├── list: [ l_partkey(1), l_quantity(1) ]
                                     ├── filter: true
                                     ├── cost: 12002430
-                                    └── rows: 6001215
+                                    ├── rows: 6001215
+                                    └── runtime_filter: [l_partkey]
 */
 
 -- tpch-q18
@@ -2596,19 +2640,22 @@ Projection
             │       │   │   ├── list: [ o_orderkey, o_custkey, o_totalprice, o_orderdate ]
             │       │   │   ├── filter: true
             │       │   │   ├── cost: 6000000
-            │       │   │   └── rows: 1500000
+            │       │   │   ├── rows: 1500000
+            │       │   │   └── runtime_filter: [o_orderkey]
             │       │   └── Scan
             │       │       ├── table: customer
             │       │       ├── list: [ c_custkey, c_name ]
             │       │       ├── filter: true
             │       │       ├── cost: 300000
-            │       │       └── rows: 150000
+            │       │       ├── rows: 150000
+            │       │       └── runtime_filter: [c_custkey]
             │       └── Scan
             │           ├── table: lineitem
             │           ├── list: [ l_orderkey, l_quantity ]
             │           ├── filter: true
             │           ├── cost: 12002430
-            │           └── rows: 6001215
+            │           ├── rows: 6001215
+            │           └── runtime_filter: [l_orderkey]
             └── Projection { exprs: [ l_orderkey(1) ], cost: 13050240, rows: 5 }
                 └── Filter
                     ├── cond:>
@@ -2764,7 +2811,8 @@ Projection
                                 │   └── l_shipmode
                                 ├── filter: true
                                 ├── cost: 36007290
-                                └── rows: 6001215
+                                ├── rows: 6001215
+                                └── runtime_filter: [l_partkey]
 */
 
 -- tpch-q20
@@ -2826,7 +2874,8 @@ Order { by: [ s_name ], cost: 2525379700000, rows: 5000 }
         │           ├── list: [ s_suppkey, s_name, s_address, s_nationkey ]
         │           ├── filter: true
         │           ├── cost: 40000
-        │           └── rows: 10000
+        │           ├── rows: 10000
+        │           └── runtime_filter: [s_suppkey, s_nationkey]
         └── Projection { exprs: [ ps_suppkey ], cost: 2525379700000, rows: 25000 }
             └── HashJoin
                 ├── type: semi
@@ -2908,7 +2957,8 @@ Order { by: [ s_name ], cost: 2525379700000, rows: 5000 }
                 │                                   ├── list: [ l_partkey, l_suppkey, l_quantity, l_shipdate ]
                 │                                   ├── filter: true
                 │                                   ├── cost: 24004860
-                │                                   └── rows: 6001215
+                │                                   ├── rows: 6001215
+                │                                   └── runtime_filter: [l_partkey, l_suppkey]
                 └── Projection { exprs: [ p_partkey ], cost: 644000, rows: 100000 }
                     └── Filter { cond: like { lhs: p_name, rhs: 'forest%' }, cost: 642000, rows: 100000 }
                         └── Scan { table: part, list: [ p_partkey, p_name ], filter: true, cost: 400000, rows: 200000 }
@@ -3033,7 +3083,8 @@ Projection
                 │   │       │       │           ├── list: [ s_suppkey, s_name, s_nationkey ]
                 │   │       │       │           ├── filter: true
                 │   │       │       │           ├── cost: 30000
-                │   │       │       │           └── rows: 10000
+                │   │       │       │           ├── rows: 10000
+                │   │       │       │           └── runtime_filter: [s_nationkey]
                 │   │       │       └── Projection
                 │   │       │           ├── exprs: [ l_orderkey, l_suppkey ]
                 │   │       │           ├── cost: 36817456
@@ -3047,7 +3098,8 @@ Projection
                 │   │       │                   ├── list: [ l_orderkey, l_suppkey, l_commitdate, l_receiptdate ]
                 │   │       │                   ├── filter: true
                 │   │       │                   ├── cost: 24004860
-                │   │       │                   └── rows: 6001215
+                │   │       │                   ├── rows: 6001215
+                │   │       │                   └── runtime_filter: [l_suppkey]
                 │   │       └── Projection { exprs: [ o_orderkey ], cost: 4830000, rows: 750000 }
                 │   │           └── Filter { cond: = { lhs: o_orderstatus, rhs: 'F' }, cost: 4815000, rows: 750000 }
                 │   │               └── Scan
@@ -3055,7 +3107,8 @@ Projection
                 │   │                   ├── list: [ o_orderkey, o_orderstatus ]
                 │   │                   ├── filter: true
                 │   │                   ├── cost: 3000000
-                │   │                   └── rows: 1500000
+                │   │                   ├── rows: 1500000
+                │   │                   └── runtime_filter: [o_orderkey]
                 │   └── Projection { exprs: [ l_orderkey(2), l_suppkey(2) ], cost: 36817456, rows: 3000607.5 }
                 │       └── Filter
                 │           ├── cond: > { lhs: l_receiptdate(2), rhs: l_commitdate(2) }
@@ -1,11 +1,12 @@
 // Copyright 2024 RisingLight Project Authors. Licensed under Apache-2.0.
 
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::{Error, Result};
 use risinglight::array::*;
 use risinglight::storage::SecondaryStorageOptions;
-use risinglight::Database;
+use risinglight::{Database, Session};
 use sqlplannertest::ParsedTestCase;
 
 #[tokio::main]
@@ -27,6 +28,7 @@ impl sqlplannertest::PlannerTestRunner for DatabaseWrapper {
     async fn run(&mut self, test_case: &ParsedTestCase) -> Result<String, Error> {
         if !test_case.tasks.is_empty() {
             let db = Database::new_on_disk(SecondaryStorageOptions::default_for_test()).await;
+            let db = Session::new(Arc::new(db));
             for sql in &test_case.before_sql {
                 db.run(sql).await?;
             }